@@ -1,9 +1,10 @@
 //! This module defines `open` subcommand.
 
-use std::{env, rc::Rc};
+use std::{env, error::Error, fs, path::PathBuf, rc::Rc};
 
 use crate::{
     cli::CommonOpts,
+    css::{self, Stylesheet},
     tui::{init_menu, setup_logger, BrowserView},
     util,
 };
@@ -14,6 +15,10 @@ use structopt::StructOpt;
 #[derive(StructOpt, Debug)]
 pub struct Opts {
     pub url: Option<String>,
+
+    /// A CSS file applied to all pages as a user stylesheet
+    #[structopt(long, parse(from_os_str))]
+    pub user_stylesheet: Option<PathBuf>,
 }
 
 /// `run` launches a TUI window to show the main UI.
@@ -22,6 +27,13 @@ pub fn run(common_opts: CommonOpts, opts: Opts) -> i32 {
         .url
         .and_then(|u| Some(util::normalize_fileurl_with(env::current_dir().unwrap(), u)))
         .unwrap_or("http://example.com".to_string());
+    let user_stylesheet = match opts.user_stylesheet.map(load_stylesheet).transpose() {
+        Ok(user_stylesheet) => user_stylesheet.unwrap_or(Stylesheet::new(vec![])),
+        Err(e) => {
+            eprintln!("failed to load the user stylesheet; {}", e);
+            return 1;
+        }
+    };
 
     // set up base
     let mut siv = cursive::default();
@@ -33,7 +45,7 @@ pub fn run(common_opts: CommonOpts, opts: Opts) -> i32 {
     }
 
     // prepare a window
-    let mut b = BrowserView::named(Rc::new(siv.cb_sink().clone()));
+    let mut b = BrowserView::named(Rc::new(siv.cb_sink().clone()), Rc::new(user_stylesheet));
     b.get_mut().navigate_to(start_url);
     siv.add_fullscreen_layer(b);
 
@@ -43,3 +55,9 @@ pub fn run(common_opts: CommonOpts, opts: Opts) -> i32 {
     // exit successfully after the event loop finishes
     return 0;
 }
+
+/// `load_stylesheet` reads the file at `path` and parses it as CSS.
+fn load_stylesheet(path: PathBuf) -> Result<Stylesheet, Box<dyn Error>> {
+    let raw = fs::read_to_string(path)?;
+    Ok(css::parse(raw))
+}
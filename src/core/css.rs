@@ -1,14 +1,19 @@
 //! This module includes some implementations on Cascade Style Sheets (CSS).
 
-use super::dom::{Node, NodeType};
-use combine::{
-    choice,
-    error::StreamError,
-    error::StringStreamError,
-    many, many1, optional,
-    parser::char::{self, letter, newline, space},
-    sep_by, sep_end_by, ParseError, Parser, Stream,
-};
+pub mod media;
+pub mod parser;
+pub mod supports;
+pub mod tokenizer;
+
+use self::media::MediaQueryList;
+use self::parser::ComponentValue;
+use self::supports::SupportsCondition;
+use self::tokenizer::{HashType, Token};
+use super::dom::{Document, Node, NodeId, NodeType};
+use super::html::encoding::extract_encoding_from_meta;
+use super::source::{ParseDiagnostic, SourceSpan};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::{iter::Peekable, rc::Rc, slice};
 use thiserror::Error;
 
 /// `Stylesheet` represents a single stylesheet.
@@ -16,36 +21,187 @@ use thiserror::Error;
 #[derive(Debug, PartialEq)]
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
+    /// `imports` lists the stylesheets imported with `@import`, whose rules precede `rules` in the cascade.
+    pub imports: Vec<Import>,
+    /// `parse_errors` holds the errors found while parsing the stylesheet, such as rules dropped due to invalid selectors.
+    pub parse_errors: Vec<ParseDiagnostic>,
 }
 
 impl Stylesheet {
     pub fn new(rules: Vec<Rule>) -> Self {
-        Stylesheet { rules: rules }
+        Stylesheet {
+            rules,
+            imports: vec![],
+            parse_errors: vec![],
+        }
     }
 }
 
+/// `Import` is an `@import` rule, e.g. `@import url("a.css") tty;`.
+/// https://drafts.csswg.org/css-cascade-4/#at-import
+#[derive(Debug, PartialEq)]
+pub struct Import {
+    /// `url` is the URL of the imported stylesheet, which is not resolved yet.
+    pub url: String,
+    /// `conditions` are the ones given with `supports()` and media queries; the imported stylesheet is applied only if all of them are true.
+    pub conditions: Vec<RuleCondition>,
+}
+
 /// `Rule` represents a single CSS rule.
 /// - *at-rule* such as `@font-face (...)`. it is defined at https://www.w3.org/TR/css-syntax-3/#at-rule
 /// - *qualified rule* such as `h1 { .... }`. it is defined at https://www.w3.org/TR/css-syntax-3/#qualified-rule
-#[derive(Debug, PartialEq)]
+///
+/// Only qualified rules are kept as `Rule`; the ones in conditional group rules such as `@media` are flattened with their conditions.
+#[derive(Debug)]
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    /// `conditions` are the conditions of the conditional group rules enclosing the rule, from the outermost one.
+    /// The rule is applied only if all of them are true.
+    /// They are shared by the rules in the same conditional group rule.
+    pub conditions: Vec<Rc<RuleCondition>>,
+    /// `source_span` is the location of the rule in the stylesheet, if known.
+    pub source_span: Option<SourceSpan>,
+}
+
+// NOTE: `source_span` is ignored on comparison so that rules can be compared regardless of where they are written.
+impl PartialEq for Rule {
+    fn eq(&self, other: &Self) -> bool {
+        self.selectors == other.selectors
+            && self.declarations == other.declarations
+            && self.conditions == other.conditions
+    }
 }
 
 impl Rule {
-    pub fn matches(&self, n: &Box<Node>) -> bool {
-        self.selectors.iter().any(|s| s.matches(n))
+    /// `matched_specificity` returns the highest specificity of the selectors of the rule which the node matches.
+    /// It returns `None` if the node matches none of them.
+    /// https://drafts.csswg.org/css-cascade-4/#cascade-specificity
+    pub fn matched_specificity(&self, document: &Document, id: NodeId) -> Option<Specificity> {
+        self.selectors
+            .iter()
+            .filter(|s| s.matches(document, id))
+            .map(|s| s.specificity())
+            .max()
     }
 }
 
-/// `Selector` represents a sequence of simple selectors separated by combinators.
-/// `div > p`, for instance, is a sequence two simple selectors; "div" and "p" is concatenated with combinator ">".
-///
-/// However, handling this type of sequences is too hard & boring.
-/// puppy does not support the combination of selectors. In puppy, slector is just a simple slector, not a sequence of simple selectors.
-/// TODO (enhancement): make this compliant to the standard!
-pub type Selector = SimpleSelector;
+/// `RuleCondition` is the condition of a conditional group rule.
+/// https://drafts.csswg.org/css-conditional-3/#conditional-group-rule
+#[derive(Debug, PartialEq)]
+pub enum RuleCondition {
+    /// `Media` is the condition of `@media`, which is evaluated against the device.
+    Media(MediaQueryList),
+    /// `Supports` is the condition of `@supports`, which is evaluated against the properties puppy implements.
+    Supports(SupportsCondition),
+}
+
+/// `Selector` represents a *complex selector*, i.e. a sequence of compound selectors separated by combinators.
+/// `div > p`, for instance, is a sequence of two compound selectors; "div" and "p" is concatenated with combinator ">".
+/// https://drafts.csswg.org/selectors-4/#structure
+#[derive(Debug, PartialEq)]
+pub struct Selector {
+    /// `compounds` is a list of the compound selectors from left to right.
+    /// The last one is the *subject* of the selector, which the matched node itself must match.
+    pub compounds: Vec<CompoundSelector>,
+    /// `combinators[i]` is the combinator between `compounds[i]` and `compounds[i + 1]`.
+    pub combinators: Vec<Combinator>,
+}
+
+impl Selector {
+    /// `matches` returns true if the node matches the selector.
+    /// The selector is matched from right to left; the subject first, and then the nodes around it through the combinators.
+    pub fn matches(&self, document: &Document, id: NodeId) -> bool {
+        match self.compounds.len() {
+            0 => false,
+            n => self.matches_compound(document, id, n - 1),
+        }
+    }
+
+    /// `specificity` counts the simple selectors in the selector.
+    /// https://drafts.csswg.org/selectors-4/#specificity-rules
+    pub fn specificity(&self) -> Specificity {
+        self.compounds
+            .iter()
+            .flat_map(|compound| compound.0.iter())
+            .fold(Specificity::default(), |Specificity(a, b, c), s| match s {
+                SimpleSelector::IdSelector { .. } => Specificity(a + 1, b, c),
+                SimpleSelector::ClassSelector { .. } | SimpleSelector::AttributeSelector { .. } => {
+                    Specificity(a, b + 1, c)
+                }
+                SimpleSelector::TypeSelector { .. } => Specificity(a, b, c + 1),
+                SimpleSelector::UniversalSelector => Specificity(a, b, c),
+            })
+    }
+
+    fn matches_compound(&self, document: &Document, id: NodeId, index: usize) -> bool {
+        if !self.compounds[index].matches(document.node(id)) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+        let is_element = |&id: &NodeId| matches!(document.node(id).node_type, NodeType::Element(_));
+        let mut candidates = match self.combinators[index - 1] {
+            Combinator::Descendant | Combinator::Child => document
+                .inclusive_ancestors(id)
+                .skip(1)
+                .filter(is_element)
+                .collect::<Vec<_>>(),
+            Combinator::NextSibling | Combinator::SubsequentSibling => document
+                .preceding_siblings(id)
+                .filter(is_element)
+                .collect::<Vec<_>>(),
+        };
+        if let Combinator::Child | Combinator::NextSibling = self.combinators[index - 1] {
+            candidates.truncate(1);
+        }
+        candidates
+            .into_iter()
+            .any(|candidate| self.matches_compound(document, candidate, index - 1))
+    }
+}
+
+impl From<SimpleSelector> for Selector {
+    fn from(selector: SimpleSelector) -> Self {
+        Selector {
+            compounds: vec![CompoundSelector(vec![selector])],
+            combinators: vec![],
+        }
+    }
+}
+
+/// `Specificity` is the specificity of a selector, i.e. the numbers of ID selectors, class-like selectors and type selectors in it.
+/// Specificities are compared lexicographically, so `#a` is more specific than `.b.c.d`.
+/// https://drafts.csswg.org/selectors-4/#specificity
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub struct Specificity(pub usize, pub usize, pub usize);
+
+/// `CompoundSelector` represents a sequence of simple selectors which are not separated by combinators, like `a.btn#ok`.
+/// https://drafts.csswg.org/selectors-4/#compound
+#[derive(Debug, PartialEq)]
+pub struct CompoundSelector(pub Vec<SimpleSelector>);
+
+impl CompoundSelector {
+    /// `matches` returns true if the node matches all the simple selectors.
+    pub fn matches(&self, n: &Node) -> bool {
+        self.0.iter().all(|s| s.matches(n))
+    }
+}
+
+/// `Combinator` represents a relationship between two compound selectors.
+/// https://drafts.csswg.org/selectors-4/#combinators
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Combinator {
+    /// ` `, e.g. `div p`
+    Descendant,
+    /// `>`, e.g. `ul > li`
+    Child,
+    /// `+`, e.g. `h1 + p`
+    NextSibling,
+    /// `~`, e.g. `h1 ~ p`
+    SubsequentSibling,
+}
 
 /// `SimpleSelector` represents a simple selector defined in the following standard:
 /// https://www.w3.org/TR/selectors-3/#selector-syntax
@@ -56,7 +212,6 @@ pub enum SimpleSelector {
         tag_name: String,
     },
     AttributeSelector {
-        tag_name: String,
         op: AttributeSelectorOp,
         attribute: String,
         value: String,
@@ -64,12 +219,14 @@ pub enum SimpleSelector {
     ClassSelector {
         class_name: String,
     },
-    // TODO (enhancement): support multiple attribute selectors like `a[href=bar][ping=foo]`
+    IdSelector {
+        id: String,
+    },
     // TODO (enhancement): support more attribute selectors
 }
 
 impl SimpleSelector {
-    pub fn matches(&self, n: &Box<Node>) -> bool {
+    pub fn matches(&self, n: &Node) -> bool {
         match self {
             SimpleSelector::UniversalSelector => true,
             SimpleSelector::TypeSelector { tag_name } => match n.node_type {
@@ -77,31 +234,30 @@ impl SimpleSelector {
                 _ => false,
             },
             SimpleSelector::AttributeSelector {
-                tag_name,
                 op,
                 attribute,
                 value,
             } => match n.node_type {
-                NodeType::Element(ref e) => {
-                    e.tag_name.as_str() == tag_name
-                        && match op {
-                            AttributeSelectorOp::Eq => e.attributes.get(attribute) == Some(value),
-                            AttributeSelectorOp::Contain => e
-                                .attributes
-                                .get(attribute)
-                                .map(|value| {
-                                    value
-                                        .split_ascii_whitespace()
-                                        .find(|v| v == value)
-                                        .is_some()
-                                })
-                                .unwrap_or(false),
-                        }
-                }
+                NodeType::Element(ref e) => match op {
+                    AttributeSelectorOp::Eq => e.attributes.get(attribute) == Some(value),
+                    AttributeSelectorOp::Contain => e
+                        .attributes
+                        .get(attribute)
+                        .map(|values| values.split_ascii_whitespace().any(|v| v == value))
+                        .unwrap_or(false),
+                },
                 _ => false,
             },
             SimpleSelector::ClassSelector { class_name } => match n.node_type {
-                NodeType::Element(ref e) => e.attributes.get("class") == Some(class_name),
+                NodeType::Element(ref e) => e
+                    .attributes
+                    .get("class")
+                    .map(|classes| classes.split_ascii_whitespace().any(|c| c == class_name))
+                    .unwrap_or(false),
+                _ => false,
+            },
+            SimpleSelector::IdSelector { id } => match n.node_type {
+                NodeType::Element(ref e) => e.id() == Some(id),
                 _ => false,
             },
         }
@@ -124,463 +280,1322 @@ pub enum AttributeSelectorOp {
 /// - properties, which are mostly used in "qualified rules" like `.foo {bar: piyo}` https://www.w3.org/Style/CSS/all-descriptors.en.html
 ///
 /// For simplicity, we handle two types of declarations together.
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct Declaration {
     pub name: String,
     pub value: CSSValue,
-    // TODO (enhancement): add a field for `!important`
+    /// `important` is true if the declaration is followed by `!important`.
+    /// https://drafts.csswg.org/css-cascade-4/#importance
+    pub important: bool,
+    /// `source_span` is the location of the declaration in the stylesheet, if known.
+    pub source_span: Option<SourceSpan>,
+}
+
+// NOTE: `source_span` is ignored on comparison as `Rule` does.
+impl PartialEq for Declaration {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value && self.important == other.important
+    }
 }
 
 /// `CSSValue` represents some of *component value types* defined at [CSS Values and Units Module Level 3](https://www.w3.org/TR/css-values-3/#component-types).
 #[derive(Debug, PartialEq, Clone)]
 pub enum CSSValue {
     Keyword(String),
-    Length((usize, Unit)),
+    Number(f64),
+    /// `Percentage` holds the number before `%`, e.g. `50.0` for `50%`.
+    Percentage(f64),
+    Length((f64, Unit)),
+    Color(Color),
+    /// `String` is a quoted string, e.g. `"foo"`.
+    String(String),
+    /// `Url` is the URL given with `url()`, which is not resolved yet.
+    Url(String),
+    /// `SpaceSeparatedList` is a sequence of values separated by whitespaces, e.g. `1px solid red`.
+    SpaceSeparatedList(Vec<CSSValue>),
+    /// `CommaSeparatedList` is a sequence of values separated by commas, e.g. `"Foo", serif`.
+    CommaSeparatedList(Vec<CSSValue>),
 }
 
-/// `Unit` describes *a relative length unit* defined at [CSS Values and Units Module Level 3](https://www.w3.org/TR/css-values-3/#lengths)
-#[derive(Debug, PartialEq, Clone)]
+impl CSSValue {
+    /// `to_color` returns the color the value represents.
+    /// Named colors are parsed as keywords since they can't be distinguished from the other keywords without their properties.
+    /// https://www.w3.org/TR/css-color-4/#color-syntax
+    pub fn to_color(&self) -> Option<Color> {
+        match self {
+            CSSValue::Color(color) => Some(*color),
+            CSSValue::Keyword(name) => Color::from_name(name.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// `Unit` describes *a length unit* defined at [CSS Values and Units Module Level 3](https://www.w3.org/TR/css-values-3/#lengths)
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Unit {
     Em,
-    // TODO (enhancement): add more units here from the definition.
+    Rem,
+    Ch,
+    Vw,
+    Vh,
+    /// `Px` is an absolute length unit, in which lengths are resolved as computed values.
+    /// https://www.w3.org/TR/css-values-3/#absolute-lengths
+    Px,
+}
+
+impl Unit {
+    /// `from_name` returns the unit named `name` in ASCII case-insensitive manner.
+    pub fn from_name(name: &str) -> Option<Unit> {
+        match name.to_ascii_lowercase().as_str() {
+            "em" => Some(Unit::Em),
+            "rem" => Some(Unit::Rem),
+            "ch" => Some(Unit::Ch),
+            "vw" => Some(Unit::Vw),
+            "vh" => Some(Unit::Vh),
+            "px" => Some(Unit::Px),
+            _ => None,
+        }
+    }
+}
+
+/// `Color` is an sRGB color with an alpha channel.
+/// https://www.w3.org/TR/css-color-4/#color-type
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    /// `a` is the alpha channel from 0.0 (transparent) to 1.0 (opaque).
+    pub a: f64,
+}
+
+/// `NAMED_COLORS` is the list of the named colors puppy knows.
+/// https://www.w3.org/TR/css-color-4/#named-colors
+const NAMED_COLORS: &[(&str, (u8, u8, u8))] = &[
+    ("black", (0, 0, 0)),
+    ("silver", (192, 192, 192)),
+    ("gray", (128, 128, 128)),
+    ("grey", (128, 128, 128)),
+    ("white", (255, 255, 255)),
+    ("maroon", (128, 0, 0)),
+    ("red", (255, 0, 0)),
+    ("purple", (128, 0, 128)),
+    ("fuchsia", (255, 0, 255)),
+    ("magenta", (255, 0, 255)),
+    ("green", (0, 128, 0)),
+    ("lime", (0, 255, 0)),
+    ("olive", (128, 128, 0)),
+    ("yellow", (255, 255, 0)),
+    ("navy", (0, 0, 128)),
+    ("blue", (0, 0, 255)),
+    ("teal", (0, 128, 128)),
+    ("aqua", (0, 255, 255)),
+    ("cyan", (0, 255, 255)),
+    ("orange", (255, 165, 0)),
+    // TODO (enhancement): add the other named colors
+];
+
+impl Color {
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 1.0 }
+    }
+
+    /// `from_name` returns the named color `name`, which is ASCII case-insensitive.
+    pub fn from_name(name: &str) -> Option<Color> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Some(Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0.0,
+            });
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, (r, g, b))| Color::rgb(*r, *g, *b))
+    }
+
+    /// `from_hex` parses the digits of a hex color, such as `fff` or `ff000080`.
+    /// https://www.w3.org/TR/css-color-4/#hex-notation
+    pub fn from_hex(digits: &str) -> Option<Color> {
+        let values = match digits.len() {
+            3 | 4 => digits
+                .chars()
+                .map(|c| c.to_digit(16).map(|d| (d * 17) as u8))
+                .collect::<Option<Vec<_>>>()?,
+            6 | 8 => (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<_>>>()?,
+            _ => return None,
+        };
+        Some(Color {
+            r: values[0],
+            g: values[1],
+            b: values[2],
+            a: values.get(3).map(|&a| a as f64 / 255.0).unwrap_or(1.0),
+        })
+    }
+
+    /// `from_function` computes the color given with a color function, such as `rgb(255, 0, 0)` or `hsl(120 100% 50% / 0.5)`.
+    /// https://www.w3.org/TR/css-color-4/#rgb-functions
+    /// https://www.w3.org/TR/css-color-4/#the-hsl-notation
+    pub fn from_function(name: &str, arguments: &[CSSValue]) -> Option<Color> {
+        if arguments.len() != 3 && arguments.len() != 4 {
+            return None;
+        }
+        let alpha = match arguments.get(3) {
+            None => 1.0,
+            Some(CSSValue::Number(a)) => a.clamp(0.0, 1.0),
+            Some(CSSValue::Percentage(a)) => (a / 100.0).clamp(0.0, 1.0),
+            Some(_) => return None,
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "rgb" | "rgba" => {
+                let channel = |value: &CSSValue| match value {
+                    CSSValue::Number(n) => Some(n.round().clamp(0.0, 255.0) as u8),
+                    CSSValue::Percentage(p) => Some((p * 2.55).round().clamp(0.0, 255.0) as u8),
+                    _ => None,
+                };
+                Some(Color {
+                    r: channel(&arguments[0])?,
+                    g: channel(&arguments[1])?,
+                    b: channel(&arguments[2])?,
+                    a: alpha,
+                })
+            }
+            "hsl" | "hsla" => match arguments {
+                [CSSValue::Number(h), CSSValue::Percentage(s), CSSValue::Percentage(l), ..] => {
+                    let (r, g, b) = hsl_to_rgb(*h, s / 100.0, l / 100.0);
+                    Some(Color { r, g, b, a: alpha })
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// `hsl_to_rgb` converts a color in HSL into RGB.
+/// https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> (u8, u8, u8) {
+    let saturation = saturation.clamp(0.0, 1.0);
+    let lightness = lightness.clamp(0.0, 1.0);
+    let f = |n: f64| {
+        let k = (n + hue.rem_euclid(360.0) / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        let value = lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+        (value * 255.0).round() as u8
+    };
+    (f(0.0), f(8.0), f(4.0))
 }
 
 /// `CSSParseError` describes an error occured during CSS parsing.
 #[derive(Error, Debug, PartialEq)]
 pub enum CSSParseError {
-    #[error("failed to parse; {0}")]
-    InvalidResourceError(StringStreamError),
+    #[error("invalid selector; {0}")]
+    InvalidSelector(&'static str),
+    #[error("invalid value; {0}")]
+    InvalidValue(&'static str),
 }
 
 // [NOTE] Specification on CSS parsing https://www.w3.org/TR/css-syntax-3/#parsing-overview
 //
-// The specification defines parsing algorithm of HTML, which takes input stream as argument and emits DOM.
+// The specification defines parsing algorithm of CSS, which takes input stream as argument and emits a stylesheet.
 // It consists of the following two stages:
 // 1. tokenization stage
-// 2. tree construction stage
+// 2. parsing stage
 // The first one, tokenization stage, generates tokens from input stream.
-// The latter one, tree construction stage, constructs a DOM while handling scripts inside <script> tags.
-
-// This functions parses `response` as CSS in non-standard manner.
-pub fn parse(raw: String) -> Result<Stylesheet, CSSParseError> {
-    rules()
-        .parse(raw.as_str())
-        .map(|(rules, _)| Stylesheet::new(rules))
-        .map_err(|e| CSSParseError::InvalidResourceError(e))
-}
-
-fn whitespaces<Input>() -> impl Parser<Input, Output = String>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    many::<String, _, _>(space().or(newline()))
-}
-
-fn rules<Input>() -> impl Parser<Input, Output = Vec<Rule>>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    (whitespaces(), many(rule().skip(whitespaces()))).map(|(_, rules)| rules)
-}
-
-fn rule<Input>() -> impl Parser<Input, Output = Rule>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    (
-        selectors().skip(whitespaces()),
-        char::char('{').skip(whitespaces()),
-        declarations().skip(whitespaces()),
-        char::char('}'),
-    )
-        .map(|(selectors, _, declarations, _)| Rule {
-            selectors: selectors,
-            declarations,
+// The latter one, parsing stage, groups the tokens into rules and declarations, and drops invalid ones.
+//
+// puppy implements the two stages in `tokenizer` and `parser` modules respectively.
+// The preludes of rules and the values of declarations are then parsed from the component values by the functions below,
+// as the preludes of `@media` and `@supports` are by `media` and `supports` modules.
+
+/// This functions decodes `data` fetched as a stylesheet.
+/// The encoding is given by the BOM, the `charset` parameter of `content_type`, or `@charset` at the beginning, in this order; UTF-8 is used otherwise.
+/// https://drafts.csswg.org/css-syntax-3/#input-byte-stream
+pub fn decode(data: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(|value| extract_encoding_from_meta(value.to_ascii_lowercase().as_bytes()))
+        .or_else(|| {
+            // NOTE: `@charset` is recognized only if it's written exactly in this form.
+            let label = data.strip_prefix(b"@charset \"")?;
+            let len = label.windows(2).position(|w| w == b"\";")?;
+            Encoding::for_label(&label[..len]).map(|encoding| {
+                if encoding == UTF_16BE || encoding == UTF_16LE {
+                    UTF_8
+                } else {
+                    encoding
+                }
+            })
         })
+        .unwrap_or(UTF_8);
+    // NOTE: `decode` prefers the encoding given by the BOM if any, and removes the BOM.
+    encoding.decode(data).0.into_owned()
+}
+
+/// This functions parses `raw` as CSS.
+/// Since the parsing algorithm recovers from errors, it always produces a `Stylesheet`; the errors are kept in `Stylesheet::parse_errors`.
+pub fn parse(raw: String) -> Stylesheet {
+    let mut parser = parser::Parser::new(raw.as_str());
+    let rules = parser.parse_rules();
+    Stylesheet {
+        rules,
+        imports: parser.take_imports(),
+        parse_errors: parser.take_errors(),
+    }
+}
+
+/// This functions parses `raw` as a list of declarations, such as the value of `style` attributes.
+/// Invalid declarations are dropped as `parse` does.
+/// https://drafts.csswg.org/css-style-attr/#syntax
+pub fn parse_declarations(raw: &str) -> Vec<Declaration> {
+    parser::Parser::new(raw).parse_declarations()
+}
+
+/// This functions parses `raw` as a list of selectors separated by commas, such as the argument of `querySelector`.
+/// https://drafts.csswg.org/selectors-4/#parse-a-selector
+pub fn parse_selectors(raw: &str) -> Result<Vec<Selector>, CSSParseError> {
+    parse_selector_list(&parser::Parser::new(raw).parse_component_values())
 }
 
-fn selectors<Input>() -> impl Parser<Input, Output = Vec<Selector>>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    sep_by(
-        selector().skip(whitespaces()),
-        char::char(',').skip(whitespaces()),
-    )
-}
-
-fn selector<Input>() -> impl Parser<Input, Output = Selector>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    simple_selector()
-}
-
-fn simple_selector<Input>() -> impl Parser<Input, Output = SimpleSelector>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    let universal_selector = char::char('*').map(|_| SimpleSelector::UniversalSelector);
-    let class_selector =
-        (char::char('.'), many1(letter())).map(|(_, class_name)| SimpleSelector::ClassSelector {
-            class_name: class_name,
-        });
-    let type_or_attribute_selector = (
-        many1(letter()).skip(whitespaces()),
-        optional((
-            char::char('[').skip(whitespaces()),
-            many1(letter()),
-            choice((char::string("="), char::string("~="))),
-            many1(letter()),
-            char::char(']'),
-        )),
-    )
-        .and_then(|(tag_name, opts)| match opts {
-            Some((_, attribute, op, value, _)) => {
-                let op = match op {
-                    "=" => AttributeSelectorOp::Eq,
-                    "~=" => AttributeSelectorOp::Contain,
+/// `parse_selector_list` parses component values as a list of selectors separated by commas.
+/// https://drafts.csswg.org/selectors-4/#typedef-selector-list
+fn parse_selector_list(values: &[ComponentValue]) -> Result<Vec<Selector>, CSSParseError> {
+    values
+        .split(|v| *v == ComponentValue::Token(Token::Comma))
+        .map(parse_selector)
+        .collect()
+}
+
+/// `ComponentValues` is a cursor on component values, which the parsers of selectors consume from the front.
+type ComponentValues<'a> = Peekable<slice::Iter<'a, ComponentValue>>;
+
+/// `parse_selector` parses component values as a complex selector, e.g. `div > p.a`.
+/// https://drafts.csswg.org/selectors-4/#typedef-complex-selector
+fn parse_selector(values: &[ComponentValue]) -> Result<Selector, CSSParseError> {
+    let mut values = values.iter().peekable();
+    skip_whitespaces(&mut values);
+    let mut selector = Selector {
+        compounds: vec![parse_compound_selector(&mut values)?],
+        combinators: vec![],
+    };
+    loop {
+        let has_whitespace = skip_whitespaces(&mut values);
+        let combinator = match values.peek() {
+            Some(ComponentValue::Token(Token::Delim('>'))) => Some(Combinator::Child),
+            Some(ComponentValue::Token(Token::Delim('+'))) => Some(Combinator::NextSibling),
+            Some(ComponentValue::Token(Token::Delim('~'))) => Some(Combinator::SubsequentSibling),
+            Some(_) => None,
+            None => return Ok(selector),
+        };
+        let combinator = match combinator {
+            Some(combinator) => {
+                values.next();
+                skip_whitespaces(&mut values);
+                combinator
+            }
+            // NOTE: whitespaces are a descendant combinator only if another compound selector follows them.
+            None if has_whitespace => Combinator::Descendant,
+            None => {
+                return Err(CSSParseError::InvalidSelector(
+                    "unexpected value after a compound selector",
+                ))
+            }
+        };
+        selector.combinators.push(combinator);
+        selector
+            .compounds
+            .push(parse_compound_selector(&mut values)?);
+    }
+}
+
+/// `parse_compound_selector` parses simple selectors which are not separated by whitespaces nor combinators.
+/// Only the first one can be a type selector or a universal selector.
+/// https://drafts.csswg.org/selectors-4/#typedef-compound-selector
+fn parse_compound_selector(
+    values: &mut ComponentValues,
+) -> Result<CompoundSelector, CSSParseError> {
+    let mut selectors = vec![];
+    match values.peek() {
+        Some(ComponentValue::Token(Token::Delim('*'))) => {
+            selectors.push(SimpleSelector::UniversalSelector);
+            values.next();
+        }
+        Some(ComponentValue::Token(Token::Ident(tag_name))) => {
+            // NOTE: puppy handles HTML documents only, whose elements have lowercase names.
+            selectors.push(SimpleSelector::TypeSelector {
+                tag_name: tag_name.to_ascii_lowercase(),
+            });
+            values.next();
+        }
+        _ => {}
+    }
+    loop {
+        let selector = match values.peek() {
+            Some(ComponentValue::Token(Token::Delim('.'))) => {
+                values.next();
+                match values.next() {
+                    Some(ComponentValue::Token(Token::Ident(class_name))) => {
+                        SimpleSelector::ClassSelector {
+                            class_name: class_name.clone(),
+                        }
+                    }
                     _ => {
-                        return Err(<Input::Error as combine::error::ParseError<
-                            char,
-                            Input::Range,
-                            Input::Position,
-                        >>::StreamError::message_static_message(
-                            "invalid attribute selector op",
+                        return Err(CSSParseError::InvalidSelector(
+                            "`.` must be followed by a class name",
                         ))
                     }
-                };
-                Ok(SimpleSelector::AttributeSelector {
-                    tag_name: tag_name,
-                    attribute: attribute,
-                    op: op,
-                    value: value,
-                })
+                }
+            }
+            // NOTE: an ID selector requires a hash token whose name is an identifier, so `#123` is invalid.
+            Some(ComponentValue::Token(Token::Hash(id, HashType::Id))) => {
+                let selector = SimpleSelector::IdSelector { id: id.clone() };
+                values.next();
+                selector
+            }
+            Some(ComponentValue::SimpleBlock('[', block)) => {
+                let selector = parse_attribute_selector(block)?;
+                values.next();
+                selector
             }
-            None => Ok(SimpleSelector::TypeSelector { tag_name: tag_name }),
-        });
-
-    choice((
-        universal_selector,
-        class_selector,
-        type_or_attribute_selector,
-    ))
-}
-
-fn declarations<Input>() -> impl Parser<Input, Output = Vec<Declaration>>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    sep_end_by(
-        declaration().skip(whitespaces()),
-        char::char(';').skip(whitespaces()),
-    )
-}
-
-fn declaration<Input>() -> impl Parser<Input, Output = Declaration>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    (
-        many1(letter()).skip(whitespaces()),
-        char::char(':').skip(whitespaces()),
-        css_value(),
-    )
-        .map(|(k, _, v)| Declaration { name: k, value: v })
-}
-
-fn css_value<Input>() -> impl Parser<Input, Output = CSSValue>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    let keyword = many1(letter()).map(|s| CSSValue::Keyword(s));
-    let length = (
-        many1(char::digit()).map(|s: String| s.parse::<usize>().unwrap()),
-        char::string("em"),
-    )
-        .map(|(num, _unit)| CSSValue::Length((num, Unit::Em)));
-    choice((keyword, length))
+            _ => break,
+        };
+        selectors.push(selector);
+    }
+    if selectors.is_empty() {
+        return Err(CSSParseError::InvalidSelector("a selector is missing"));
+    }
+    Ok(CompoundSelector(selectors))
+}
+
+/// `parse_attribute_selector` parses the content of an attribute selector, e.g. `rel="next"` of `[rel="next"]`.
+/// https://drafts.csswg.org/selectors-4/#attribute-selectors
+fn parse_attribute_selector(values: &[ComponentValue]) -> Result<SimpleSelector, CSSParseError> {
+    // `~=` is a sequence of two delimiters, which can't be separated by whitespaces
+    let has_include_match = values.windows(2).any(|pair| {
+        pair == [
+            ComponentValue::Token(Token::Delim('~')),
+            ComponentValue::Token(Token::Delim('=')),
+        ]
+    });
+    let values = values
+        .iter()
+        .filter(|v| !v.is_whitespace())
+        .collect::<Vec<_>>();
+    let (attribute, op, value) = match values.as_slice() {
+        [ComponentValue::Token(Token::Ident(attribute)), ComponentValue::Token(Token::Delim('=')), value] => {
+            (attribute, AttributeSelectorOp::Eq, value)
+        }
+        [ComponentValue::Token(Token::Ident(attribute)), ComponentValue::Token(Token::Delim('~')), ComponentValue::Token(Token::Delim('=')), value]
+            if has_include_match =>
+        {
+            (attribute, AttributeSelectorOp::Contain, value)
+        }
+        _ => return Err(CSSParseError::InvalidSelector("invalid attribute selector")),
+    };
+    let value = match value {
+        ComponentValue::Token(Token::Ident(value))
+        | ComponentValue::Token(Token::String(value)) => value.clone(),
+        _ => {
+            return Err(CSSParseError::InvalidSelector(
+                "the value of an attribute selector must be an identifier or a string",
+            ))
+        }
+    };
+    Ok(SimpleSelector::AttributeSelector {
+        attribute: attribute.to_ascii_lowercase(),
+        op,
+        value,
+    })
+}
+
+/// `skip_whitespaces` consumes whitespaces, and returns true if there is any.
+fn skip_whitespaces(values: &mut ComponentValues) -> bool {
+    let mut skipped = false;
+    while values.peek().map(|v| v.is_whitespace()).unwrap_or(false) {
+        values.next();
+        skipped = true;
+    }
+    skipped
+}
+
+/// `parse_declaration_value` parses component values as the value of a declaration,
+/// which is a comma-separated list of space-separated lists of component values.
+/// A list with only one item is simplified into the item.
+fn parse_declaration_value(values: &[ComponentValue]) -> Result<CSSValue, CSSParseError> {
+    let mut lists = values
+        .split(|v| *v == ComponentValue::Token(Token::Comma))
+        .map(parse_space_separated_values)
+        .collect::<Result<Vec<_>, _>>()?;
+    if lists.len() == 1 {
+        return Ok(lists.remove(0));
+    }
+    Ok(CSSValue::CommaSeparatedList(lists))
+}
+
+fn parse_space_separated_values(values: &[ComponentValue]) -> Result<CSSValue, CSSParseError> {
+    let mut items = values
+        .iter()
+        .filter(|v| !v.is_whitespace())
+        .map(parse_component_value)
+        .collect::<Result<Vec<_>, _>>()?;
+    match items.len() {
+        0 => Err(CSSParseError::InvalidValue("a value is missing")),
+        1 => Ok(items.remove(0)),
+        _ => Ok(CSSValue::SpaceSeparatedList(items)),
+    }
+}
+
+/// `parse_component_value` parses a single value, such as a keyword, a length, a color, a string or a URL.
+/// https://www.w3.org/TR/css-values-3/#component-types
+fn parse_component_value(value: &ComponentValue) -> Result<CSSValue, CSSParseError> {
+    match value {
+        ComponentValue::Token(Token::Ident(keyword)) => Ok(CSSValue::Keyword(keyword.clone())),
+        ComponentValue::Token(Token::String(value)) => Ok(CSSValue::String(value.clone())),
+        ComponentValue::Token(Token::Hash(digits, _)) => Color::from_hex(digits)
+            .map(CSSValue::Color)
+            .ok_or(CSSParseError::InvalidValue("invalid hex color")),
+        // https://www.w3.org/TR/css-values-3/#urls
+        ComponentValue::Token(Token::Url(url)) => Ok(CSSValue::Url(url.clone())),
+        ComponentValue::Function(name, arguments) if name.eq_ignore_ascii_case("url") => {
+            match arguments
+                .iter()
+                .filter(|v| !v.is_whitespace())
+                .collect::<Vec<_>>()
+                .as_slice()
+            {
+                [ComponentValue::Token(Token::String(url))] => Ok(CSSValue::Url(url.clone())),
+                _ => Err(CSSParseError::InvalidValue("invalid URL")),
+            }
+        }
+        ComponentValue::Function(name, arguments) => {
+            let arguments = parse_function_arguments(arguments)?;
+            Color::from_function(name.as_str(), arguments.as_slice())
+                .map(CSSValue::Color)
+                .ok_or(CSSParseError::InvalidValue("invalid color function"))
+        }
+        value => parse_numeric_value(value),
+    }
+}
+
+/// `parse_function_arguments` parses the arguments of functions like `rgb()`,
+/// which are numeric values separated by a comma, a slash, or whitespaces.
+fn parse_function_arguments(values: &[ComponentValue]) -> Result<Vec<CSSValue>, CSSParseError> {
+    let mut arguments = vec![];
+    let mut after_separator = false;
+    for value in values.iter().filter(|v| !v.is_whitespace()) {
+        match value {
+            ComponentValue::Token(Token::Comma) | ComponentValue::Token(Token::Delim('/')) => {
+                if arguments.is_empty() || after_separator {
+                    return Err(CSSParseError::InvalidValue("an argument is missing"));
+                }
+                after_separator = true;
+            }
+            value => {
+                arguments.push(parse_numeric_value(value)?);
+                after_separator = false;
+            }
+        }
+    }
+    if arguments.is_empty() || after_separator {
+        return Err(CSSParseError::InvalidValue("an argument is missing"));
+    }
+    Ok(arguments)
+}
+
+/// `parse_numeric_value` parses a number, a percentage, or a length.
+/// https://www.w3.org/TR/css-values-3/#numeric-types
+fn parse_numeric_value(value: &ComponentValue) -> Result<CSSValue, CSSParseError> {
+    match value {
+        ComponentValue::Token(Token::Number(number)) => Ok(CSSValue::Number(*number)),
+        ComponentValue::Token(Token::Percentage(number)) => Ok(CSSValue::Percentage(*number)),
+        ComponentValue::Token(Token::Dimension(number, unit)) => Unit::from_name(unit)
+            .map(|unit| CSSValue::Length((*number, unit)))
+            .ok_or(CSSParseError::InvalidValue("unknown unit")),
+        _ => Err(CSSParseError::InvalidValue("unexpected value")),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::source::SourcePosition;
 
     #[test]
     fn test_stylesheet() {
         assert_eq!(
-            parse("test [foo=bar] { aa: bb; cc: 1em } rule { ee: dd;  }".to_string()),
-            Ok(Stylesheet::new(vec![
+            parse("test[foo=bar] { aa: bb; cc: 1em } rule { ee: dd;  }".to_string()),
+            Stylesheet::new(vec![
                 Rule {
-                    selectors: vec![SimpleSelector::AttributeSelector {
-                        tag_name: "test".to_string(),
-                        attribute: "foo".to_string(),
-                        op: AttributeSelectorOp::Eq,
-                        value: "bar".to_string()
+                    selectors: vec![Selector {
+                        compounds: vec![CompoundSelector(vec![
+                            SimpleSelector::TypeSelector {
+                                tag_name: "test".to_string(),
+                            },
+                            SimpleSelector::AttributeSelector {
+                                attribute: "foo".to_string(),
+                                op: AttributeSelectorOp::Eq,
+                                value: "bar".to_string()
+                            }
+                        ])],
+                        combinators: vec![],
                     }],
                     declarations: vec![
                         Declaration {
                             name: "aa".to_string(),
-                            value: CSSValue::Keyword("bb".to_string())
+                            value: CSSValue::Keyword("bb".to_string()),
+                            important: false,
+                            source_span: None,
                         },
                         Declaration {
                             name: "cc".to_string(),
-                            value: CSSValue::Length((1, Unit::Em)),
+                            value: CSSValue::Length((1.0, Unit::Em)),
+                            important: false,
+                            source_span: None,
                         }
-                    ]
+                    ],
+                    conditions: vec![],
+                    source_span: None,
                 },
                 Rule {
-                    selectors: vec![SimpleSelector::TypeSelector {
+                    selectors: vec![Selector::from(SimpleSelector::TypeSelector {
                         tag_name: "rule".to_string(),
-                    }],
+                    })],
                     declarations: vec![Declaration {
                         name: "ee".to_string(),
-                        value: CSSValue::Keyword("dd".to_string())
-                    }]
+                        value: CSSValue::Keyword("dd".to_string()),
+                        important: false,
+                        source_span: None,
+                    }],
+                    conditions: vec![],
+                    source_span: None,
                 },
-            ]))
+            ])
         );
     }
 
     #[test]
-    fn test_rule() {
-        assert_eq!(
-            rule().parse("test [foo=bar] {}"),
-            Ok((
-                Rule {
-                    selectors: vec![SimpleSelector::AttributeSelector {
-                        tag_name: "test".to_string(),
-                        attribute: "foo".to_string(),
-                        op: AttributeSelectorOp::Eq,
-                        value: "bar".to_string()
-                    }],
-                    declarations: vec![]
-                },
-                ""
+    fn test_stylesheet_with_source_spans() {
+        let stylesheet = parse("a { b: c }\n  d {\n    e: 1em;\n  }".to_string());
+        let span = |start: (usize, usize), end: (usize, usize)| {
+            Some(SourceSpan::new(
+                SourcePosition::new(start.0, start.1),
+                SourcePosition::new(end.0, end.1),
             ))
+        };
+        assert_eq!(stylesheet.rules[0].source_span, span((1, 1), (1, 11)));
+        assert_eq!(
+            stylesheet.rules[0].declarations[0].source_span,
+            span((1, 5), (1, 9))
+        );
+        assert_eq!(stylesheet.rules[1].source_span, span((2, 3), (4, 4)));
+        assert_eq!(
+            stylesheet.rules[1].declarations[0].source_span,
+            span((3, 5), (3, 11))
         );
 
+        let stylesheet = parse("a { b: c }\nd { e: ; }".to_string());
+        assert_eq!(stylesheet.rules.len(), 2);
+        assert_eq!(stylesheet.rules[1].declarations, vec![]);
         assert_eq!(
-            rule().parse("test [foo=bar], testtest[piyo~=guoo] {}"),
-            Ok((
+            stylesheet.parse_errors,
+            vec![ParseDiagnostic::new(
+                "`e` has no value; the declaration is dropped".to_string(),
+                SourcePosition::new(2, 5)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_stylesheet_with_errors() {
+        let stylesheet = parse(
+            "/* comment */ p { font-size: 10px; -webkit-foo: 1; color: ; }\n\
+             a!b { color: red }\n\
+             .x\\:y { _z: 'a\\'b'; width: 1foo; margin: 0 }"
+                .to_string(),
+        );
+        assert_eq!(
+            stylesheet.rules,
+            vec![
                 Rule {
-                    selectors: vec![
-                        SimpleSelector::AttributeSelector {
-                            tag_name: "test".to_string(),
-                            attribute: "foo".to_string(),
-                            op: AttributeSelectorOp::Eq,
-                            value: "bar".to_string()
+                    selectors: parse_selectors("p").unwrap(),
+                    declarations: vec![
+                        Declaration {
+                            name: "font-size".to_string(),
+                            value: CSSValue::Length((10.0, Unit::Px)),
+                            important: false,
+                            source_span: None,
+                        },
+                        Declaration {
+                            name: "-webkit-foo".to_string(),
+                            value: CSSValue::Number(1.0),
+                            important: false,
+                            source_span: None,
                         },
-                        SimpleSelector::AttributeSelector {
-                            tag_name: "testtest".to_string(),
-                            attribute: "piyo".to_string(),
-                            op: AttributeSelectorOp::Contain,
-                            value: "guoo".to_string()
-                        }
                     ],
-                    declarations: vec![]
+                    conditions: vec![],
+                    source_span: None,
                 },
-                ""
-            ))
-        );
-
-        assert_eq!(
-            rule().parse("test [foo=bar] { aa: bb; cc: 1em }"),
-            Ok((
                 Rule {
-                    selectors: vec![SimpleSelector::AttributeSelector {
-                        tag_name: "test".to_string(),
-                        attribute: "foo".to_string(),
-                        op: AttributeSelectorOp::Eq,
-                        value: "bar".to_string()
-                    }],
+                    selectors: vec![Selector::from(SimpleSelector::ClassSelector {
+                        class_name: "x:y".to_string(),
+                    })],
                     declarations: vec![
                         Declaration {
-                            name: "aa".to_string(),
-                            value: CSSValue::Keyword("bb".to_string())
+                            name: "_z".to_string(),
+                            value: CSSValue::String("a'b".to_string()),
+                            important: false,
+                            source_span: None,
                         },
                         Declaration {
-                            name: "cc".to_string(),
-                            value: CSSValue::Length((1, Unit::Em)),
-                        }
-                    ]
+                            name: "margin".to_string(),
+                            value: CSSValue::Number(0.0),
+                            important: false,
+                            source_span: None,
+                        },
+                    ],
+                    conditions: vec![],
+                    source_span: None,
                 },
-                ""
-            ))
+            ]
+        );
+        assert_eq!(
+            stylesheet
+                .parse_errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "1:52: `color` has no value; the declaration is dropped",
+                "2:1: invalid selector `a!b`; the rule is dropped",
+                "3:21: invalid value `1foo` for `width`; the declaration is dropped",
+            ]
         );
     }
 
     #[test]
-    fn test_selectors() {
+    fn test_decode() {
+        // "é" in windows-1252 and UTF-8
         assert_eq!(
-            selectors().parse("test [aa=bb], piyo[cc~=dd] {"),
-            Ok((
-                vec![
-                    SimpleSelector::AttributeSelector {
-                        tag_name: "test".to_string(),
-                        attribute: "aa".to_string(),
-                        op: AttributeSelectorOp::Eq,
-                        value: "bb".to_string()
+            decode(b"p { content: '\xe9' }", None),
+            "p { content: '\u{fffd}' }"
+        );
+        assert_eq!(
+            decode(b"@charset \"latin1\"; p { content: '\xe9' }", None),
+            "@charset \"latin1\"; p { content: '\u{e9}' }"
+        );
+        assert_eq!(
+            decode(
+                b"p { content: '\xe9' }",
+                Some("text/css; charset=windows-1252")
+            ),
+            "p { content: '\u{e9}' }"
+        );
+        // BOM wins over the others, and `@charset` of UTF-16 means UTF-8
+        assert_eq!(
+            decode(
+                b"\xef\xbb\xbfp { content: '\xc3\xa9' }",
+                Some("text/css; charset=windows-1252")
+            ),
+            "p { content: '\u{e9}' }"
+        );
+        assert_eq!(
+            decode(b"@charset \"utf-16le\"; p { content: '\xc3\xa9' }", None),
+            "@charset \"utf-16le\"; p { content: '\u{e9}' }"
+        );
+        // `@charset` must be written exactly
+        assert_eq!(
+            decode(b"@charset 'latin1'; p { content: '\xe9' }", None),
+            "@charset 'latin1'; p { content: '\u{fffd}' }"
+        );
+    }
+
+    #[test]
+    fn test_parse_selectors() {
+        assert_eq!(
+            parse_selectors(" p, .a "),
+            Ok(vec![
+                Selector::from(SimpleSelector::TypeSelector {
+                    tag_name: "p".to_string(),
+                }),
+                Selector::from(SimpleSelector::ClassSelector {
+                    class_name: "a".to_string(),
+                }),
+            ])
+        );
+        assert_eq!(
+            parse_selectors("p/* comment */.\\31 a[title=\"a\\\"b\"]"),
+            Ok(vec![Selector {
+                compounds: vec![CompoundSelector(vec![
+                    SimpleSelector::TypeSelector {
+                        tag_name: "p".to_string(),
+                    },
+                    SimpleSelector::ClassSelector {
+                        class_name: "1a".to_string(),
                     },
                     SimpleSelector::AttributeSelector {
-                        tag_name: "piyo".to_string(),
-                        attribute: "cc".to_string(),
-                        op: AttributeSelectorOp::Contain,
-                        value: "dd".to_string()
+                        attribute: "title".to_string(),
+                        op: AttributeSelectorOp::Eq,
+                        value: "a\"b".to_string()
                     }
-                ],
-                "{"
-            ))
+                ])],
+                combinators: vec![],
+            }])
+        );
+        // an ID starting with an escaped digit is valid, but an unescaped digit isn't
+        assert_eq!(
+            parse_selectors("#\\31 23"),
+            Ok(vec![Selector::from(SimpleSelector::IdSelector {
+                id: "123".to_string(),
+            })])
         );
+        assert!(parse_selectors("#123").is_err());
+        assert!(parse_selectors("").is_err());
+        assert!(parse_selectors("/* comment */").is_err());
+        assert!(parse_selectors("p {").is_err());
+        assert!(parse_selectors("p,").is_err());
     }
 
     #[test]
-    fn test_declarations() {
+    fn test_rule() {
+        let rule = |input: &str| parse(input.to_string()).rules.pop();
         assert_eq!(
-            declarations().parse("foo: bar; piyo: guoo }"),
-            Ok((
-                vec![
-                    Declaration {
-                        name: "foo".to_string(),
-                        value: CSSValue::Keyword("bar".to_string())
+            rule("test[foo=bar] {}"),
+            Some(Rule {
+                selectors: vec![Selector {
+                    compounds: vec![CompoundSelector(vec![
+                        SimpleSelector::TypeSelector {
+                            tag_name: "test".to_string(),
+                        },
+                        SimpleSelector::AttributeSelector {
+                            attribute: "foo".to_string(),
+                            op: AttributeSelectorOp::Eq,
+                            value: "bar".to_string()
+                        }
+                    ])],
+                    combinators: vec![],
+                }],
+                declarations: vec![],
+                conditions: vec![],
+                source_span: None,
+            })
+        );
+
+        assert_eq!(
+            rule("test[foo=bar], testtest[piyo~=guoo] {}"),
+            Some(Rule {
+                selectors: vec![
+                    Selector {
+                        compounds: vec![CompoundSelector(vec![
+                            SimpleSelector::TypeSelector {
+                                tag_name: "test".to_string(),
+                            },
+                            SimpleSelector::AttributeSelector {
+                                attribute: "foo".to_string(),
+                                op: AttributeSelectorOp::Eq,
+                                value: "bar".to_string()
+                            }
+                        ])],
+                        combinators: vec![],
                     },
-                    Declaration {
-                        name: "piyo".to_string(),
-                        value: CSSValue::Keyword("guoo".to_string())
+                    Selector {
+                        compounds: vec![CompoundSelector(vec![
+                            SimpleSelector::TypeSelector {
+                                tag_name: "testtest".to_string(),
+                            },
+                            SimpleSelector::AttributeSelector {
+                                attribute: "piyo".to_string(),
+                                op: AttributeSelectorOp::Contain,
+                                value: "guoo".to_string()
+                            }
+                        ])],
+                        combinators: vec![],
                     }
                 ],
-                "}"
-            ))
+                declarations: vec![],
+                conditions: vec![],
+                source_span: None,
+            })
         );
 
         assert_eq!(
-            declarations().parse("foo: bar; piyo: 1em; }"),
-            Ok((
-                vec![
+            rule("test[foo=bar] { aa: bb; cc: 1em }"),
+            Some(Rule {
+                selectors: vec![Selector {
+                    compounds: vec![CompoundSelector(vec![
+                        SimpleSelector::TypeSelector {
+                            tag_name: "test".to_string(),
+                        },
+                        SimpleSelector::AttributeSelector {
+                            attribute: "foo".to_string(),
+                            op: AttributeSelectorOp::Eq,
+                            value: "bar".to_string()
+                        }
+                    ])],
+                    combinators: vec![],
+                }],
+                declarations: vec![
                     Declaration {
-                        name: "foo".to_string(),
-                        value: CSSValue::Keyword("bar".to_string())
+                        name: "aa".to_string(),
+                        value: CSSValue::Keyword("bb".to_string()),
+                        important: false,
+                        source_span: None,
                     },
                     Declaration {
-                        name: "piyo".to_string(),
-                        value: CSSValue::Length((1, Unit::Em))
+                        name: "cc".to_string(),
+                        value: CSSValue::Length((1.0, Unit::Em)),
+                        important: false,
+                        source_span: None,
                     }
                 ],
-                "}"
-            ))
+                conditions: vec![],
+                source_span: None,
+            })
         );
     }
 
     #[test]
-    fn test_selector() {
+    fn test_selectors() {
         assert_eq!(
-            selector().parse("test [foo=bar], "),
-            Ok((
-                SimpleSelector::AttributeSelector {
-                    tag_name: "test".to_string(),
-                    attribute: "foo".to_string(),
-                    op: AttributeSelectorOp::Eq,
-                    value: "bar".to_string()
+            parse_selectors("test[aa=bb], piyo[cc~=dd]"),
+            Ok(vec![
+                Selector {
+                    compounds: vec![CompoundSelector(vec![
+                        SimpleSelector::TypeSelector {
+                            tag_name: "test".to_string(),
+                        },
+                        SimpleSelector::AttributeSelector {
+                            attribute: "aa".to_string(),
+                            op: AttributeSelectorOp::Eq,
+                            value: "bb".to_string()
+                        }
+                    ])],
+                    combinators: vec![],
                 },
-                ", "
-            ))
+                Selector {
+                    compounds: vec![CompoundSelector(vec![
+                        SimpleSelector::TypeSelector {
+                            tag_name: "piyo".to_string(),
+                        },
+                        SimpleSelector::AttributeSelector {
+                            attribute: "cc".to_string(),
+                            op: AttributeSelectorOp::Contain,
+                            value: "dd".to_string()
+                        }
+                    ])],
+                    combinators: vec![],
+                }
+            ])
         );
     }
 
     #[test]
-    fn test_simple_selector() {
+    fn test_declarations() {
+        let declarations = |input: &str| parser::Parser::new(input).parse_declarations();
         assert_eq!(
-            simple_selector().parse("* {"),
-            Ok((SimpleSelector::UniversalSelector, " {"))
+            declarations("foo: bar; piyo: guoo "),
+            vec![
+                Declaration {
+                    name: "foo".to_string(),
+                    value: CSSValue::Keyword("bar".to_string()),
+                    important: false,
+                    source_span: None,
+                },
+                Declaration {
+                    name: "piyo".to_string(),
+                    value: CSSValue::Keyword("guoo".to_string()),
+                    important: false,
+                    source_span: None,
+                }
+            ]
         );
 
         assert_eq!(
-            simple_selector().parse("test{"),
-            Ok((
-                SimpleSelector::TypeSelector {
-                    tag_name: "test".to_string(),
+            declarations("foo: bar; piyo: 1em; "),
+            vec![
+                Declaration {
+                    name: "foo".to_string(),
+                    value: CSSValue::Keyword("bar".to_string()),
+                    important: false,
+                    source_span: None,
                 },
-                "{"
-            ))
+                Declaration {
+                    name: "piyo".to_string(),
+                    value: CSSValue::Length((1.0, Unit::Em)),
+                    important: false,
+                    source_span: None,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selector() {
+        assert_eq!(
+            parse_selectors("div  p > a.b"),
+            Ok(vec![Selector {
+                compounds: vec![
+                    CompoundSelector(vec![SimpleSelector::TypeSelector {
+                        tag_name: "div".to_string(),
+                    }]),
+                    CompoundSelector(vec![SimpleSelector::TypeSelector {
+                        tag_name: "p".to_string(),
+                    }]),
+                    CompoundSelector(vec![
+                        SimpleSelector::TypeSelector {
+                            tag_name: "a".to_string(),
+                        },
+                        SimpleSelector::ClassSelector {
+                            class_name: "b".to_string(),
+                        }
+                    ]),
+                ],
+                combinators: vec![Combinator::Descendant, Combinator::Child],
+            }])
+        );
+
+        assert_eq!(
+            parse_selectors("h1+p~#x "),
+            Ok(vec![Selector {
+                compounds: vec![
+                    CompoundSelector(vec![SimpleSelector::TypeSelector {
+                        tag_name: "h1".to_string(),
+                    }]),
+                    CompoundSelector(vec![SimpleSelector::TypeSelector {
+                        tag_name: "p".to_string(),
+                    }]),
+                    CompoundSelector(vec![SimpleSelector::IdSelector {
+                        id: "x".to_string(),
+                    }]),
+                ],
+                combinators: vec![Combinator::NextSibling, Combinator::SubsequentSibling],
+            }])
+        );
+
+        assert!(parse_selectors("> p").is_err());
+        assert!(parse_selectors("p >").is_err());
+        assert!(parse_selectors("p > > q").is_err());
+    }
+
+    #[test]
+    fn test_compound_selector() {
+        let compounds =
+            |input: &str| parse_selectors(input).map(|mut selectors| selectors.remove(0).compounds);
+        assert_eq!(
+            compounds("* "),
+            Ok(vec![CompoundSelector(vec![
+                SimpleSelector::UniversalSelector
+            ])])
+        );
+
+        assert_eq!(
+            compounds("DIV"),
+            Ok(vec![CompoundSelector(vec![SimpleSelector::TypeSelector {
+                tag_name: "div".to_string(),
+            }])])
         );
 
         assert_eq!(
-            simple_selector().parse("test [foo=bar] "),
-            Ok((
+            compounds("a.btn#ok[ rel = 'next' ] "),
+            Ok(vec![CompoundSelector(vec![
+                SimpleSelector::TypeSelector {
+                    tag_name: "a".to_string(),
+                },
+                SimpleSelector::ClassSelector {
+                    class_name: "btn".to_string(),
+                },
+                SimpleSelector::IdSelector {
+                    id: "ok".to_string(),
+                },
                 SimpleSelector::AttributeSelector {
-                    tag_name: "test".to_string(),
-                    attribute: "foo".to_string(),
+                    attribute: "rel".to_string(),
                     op: AttributeSelectorOp::Eq,
-                    value: "bar".to_string()
-                },
-                " "
-            ))
+                    value: "next".to_string()
+                }
+            ])])
         );
 
+        assert!(compounds("{").is_err());
+        assert!(compounds("a*").is_err());
+        assert!(compounds("a. b").is_err());
+    }
+
+    #[test]
+    fn test_simple_selector() {
         assert_eq!(
-            simple_selector().parse("test[foo~=bar]{"),
-            Ok((
-                SimpleSelector::AttributeSelector {
-                    tag_name: "test".to_string(),
-                    attribute: "foo".to_string(),
-                    op: AttributeSelectorOp::Contain,
-                    value: "bar".to_string()
-                },
-                "{"
-            ))
+            parse_selectors(".foo-bar_1"),
+            Ok(vec![Selector::from(SimpleSelector::ClassSelector {
+                class_name: "foo-bar_1".to_string(),
+            })])
         );
+
+        assert_eq!(
+            parse_selectors("[FOO~=\"bar baz\"]"),
+            Ok(vec![Selector::from(SimpleSelector::AttributeSelector {
+                attribute: "foo".to_string(),
+                op: AttributeSelectorOp::Contain,
+                value: "bar baz".to_string()
+            })])
+        );
+
+        assert!(parse_selectors("[foo]").is_err());
+        assert!(parse_selectors("[foo ~ = bar]").is_err());
+        assert!(parse_selectors("[foo=1]").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        use crate::core::dom::{AttrMap, Element, NodeTree};
+
+        fn element(
+            tag_name: &str,
+            attributes: &[(&str, &str)],
+            children: Vec<NodeTree>,
+        ) -> NodeTree {
+            let attributes = attributes
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<AttrMap>();
+            Element::new(tag_name.to_string(), attributes, children)
+        }
+
+        // <div><ul class="menu"><li id="a"></li><li id="b" class="x y"></li></ul><h1></h1><p></p><p></p></div>
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let div = document.append_tree(
+            root,
+            element(
+                "div",
+                &[],
+                vec![
+                    element(
+                        "ul",
+                        &[("class", "menu")],
+                        vec![
+                            element("li", &[("id", "a")], vec![]),
+                            element("li", &[("id", "b"), ("class", "x y")], vec![]),
+                        ],
+                    ),
+                    element("h1", &[], vec![]),
+                    element("p", &[], vec![]),
+                    element("p", &[], vec![]),
+                ],
+            ),
+        );
+        let children = document.children(div).collect::<Vec<_>>();
+        let (ul, h1, p1, p2) = (children[0], children[1], children[2], children[3]);
+        let items = document.children(ul).collect::<Vec<_>>();
+        let (a, b) = (items[0], items[1]);
+
+        let matches = |selector: &str, id: NodeId| {
+            parse_selectors(selector).unwrap()[0].matches(&document, id)
+        };
+        assert!(matches("div li", a));
+        assert!(matches("div > ul > li", a));
+        assert!(!matches("div > li", a));
+        assert!(matches("ul.menu li#b.x", b));
+        assert!(matches("li[class~=y]", b));
+        assert!(!matches("li[class~=\"x y\"]", b));
+        assert!(matches("li + li", b));
+        assert!(!matches("li + li", a));
+        assert!(matches("h1 + p", p1));
+        assert!(!matches("h1 + p", p2));
+        assert!(matches("h1 ~ p", p2));
+        assert!(matches("ul ~ p", p2));
+        assert!(!matches("p ~ h1", h1));
+        assert!(matches("div ul + h1", h1));
+        assert!(!matches("span li", a));
     }
 
     #[test]
     fn test_declaration() {
+        let declaration = |input: &str| parser::Parser::new(input).parse_declarations().pop();
         assert_eq!(
-            declaration().parse("keykey:1em"),
-            Ok((
-                Declaration {
-                    name: "keykey".to_string(),
-                    value: CSSValue::Length((1, Unit::Em))
-                },
-                ""
-            ))
+            declaration("keykey:1em"),
+            Some(Declaration {
+                name: "keykey".to_string(),
+                value: CSSValue::Length((1.0, Unit::Em)),
+                important: false,
+                source_span: None,
+            })
         );
 
         assert_eq!(
-            declaration().parse("keyabc : 3em "),
-            Ok((
-                Declaration {
-                    name: "keyabc".to_string(),
-                    value: CSSValue::Length((3, Unit::Em))
-                },
-                " "
-            ))
+            declaration("keyabc : 3em "),
+            Some(Declaration {
+                name: "keyabc".to_string(),
+                value: CSSValue::Length((3.0, Unit::Em)),
+                important: false,
+                source_span: None,
+            })
         );
 
         assert_eq!(
-            declaration().parse("keyhello : piyo "),
-            Ok((
-                Declaration {
-                    name: "keyhello".to_string(),
-                    value: CSSValue::Keyword("piyo".to_string()),
-                },
-                " "
-            ))
+            declaration("keyhello : piyo "),
+            Some(Declaration {
+                name: "keyhello".to_string(),
+                value: CSSValue::Keyword("piyo".to_string()),
+                important: false,
+                source_span: None,
+            })
         );
 
-        assert!(declaration().parse("aaaaa").is_err())
+        assert_eq!(
+            declaration("key: none ! IMPORTANT;"),
+            Some(Declaration {
+                name: "key".to_string(),
+                value: CSSValue::Keyword("none".to_string()),
+                important: true,
+                source_span: None,
+            })
+        );
+
+        assert_eq!(declaration("aaaaa"), None);
+    }
+
+    #[test]
+    fn test_specificity() {
+        let specificity = |selector: &str| parse_selectors(selector).unwrap()[0].specificity();
+        assert_eq!(specificity("*"), Specificity(0, 0, 0));
+        assert_eq!(specificity("li"), Specificity(0, 0, 1));
+        assert_eq!(specificity("ul li"), Specificity(0, 0, 2));
+        assert_eq!(specificity("ul > li.a[b=c]"), Specificity(0, 2, 2));
+        assert_eq!(specificity("#x *"), Specificity(1, 0, 0));
+        assert!(specificity("#x") > specificity("a.b.c.d"));
+        assert!(specificity(".b") > specificity("html body div p"));
+    }
+
+    fn parse_value(input: &str) -> Result<CSSValue, CSSParseError> {
+        parse_declaration_value(&parser::Parser::new(input).parse_component_values())
     }
 
     #[test]
     fn test_css_value() {
-        let expected = css_value().parse("1em");
-        assert_eq!(expected, Ok((CSSValue::Length((1, Unit::Em)), "")))
+        let parse = parse_value;
+        assert_eq!(parse("1em"), Ok(CSSValue::Length((1.0, Unit::Em))));
+        assert_eq!(parse("0"), Ok(CSSValue::Number(0.0)));
+        assert_eq!(parse("-.5"), Ok(CSSValue::Number(-0.5)));
+        assert_eq!(parse("1.5em"), Ok(CSSValue::Length((1.5, Unit::Em))));
+        assert_eq!(parse("10PX"), Ok(CSSValue::Length((10.0, Unit::Px))));
+        assert_eq!(parse("2rem"), Ok(CSSValue::Length((2.0, Unit::Rem))));
+        assert_eq!(parse("3ch"), Ok(CSSValue::Length((3.0, Unit::Ch))));
+        assert_eq!(parse("50vw"), Ok(CSSValue::Length((50.0, Unit::Vw))));
+        assert_eq!(parse("25vh"), Ok(CSSValue::Length((25.0, Unit::Vh))));
+        assert_eq!(parse("50%"), Ok(CSSValue::Percentage(50.0)));
+        assert!(parse("10foo").is_err());
+
+        assert_eq!(
+            parse("inline-block"),
+            Ok(CSSValue::Keyword("inline-block".to_string()))
+        );
+        assert_eq!(parse("\"a b\""), Ok(CSSValue::String("a b".to_string())));
+        assert_eq!(parse("'c'"), Ok(CSSValue::String("c".to_string())));
+        assert_eq!(
+            parse("url(a/b.png)"),
+            Ok(CSSValue::Url("a/b.png".to_string()))
+        );
+        assert_eq!(
+            parse("URL( \"c d.png\" )"),
+            Ok(CSSValue::Url("c d.png".to_string()))
+        );
+
+        assert_eq!(
+            parse("1px solid red"),
+            Ok(CSSValue::SpaceSeparatedList(vec![
+                CSSValue::Length((1.0, Unit::Px)),
+                CSSValue::Keyword("solid".to_string()),
+                CSSValue::Keyword("red".to_string()),
+            ]))
+        );
+        assert_eq!(
+            parse("\"Foo Bar\", serif"),
+            Ok(CSSValue::CommaSeparatedList(vec![
+                CSSValue::String("Foo Bar".to_string()),
+                CSSValue::Keyword("serif".to_string()),
+            ]))
+        );
+        assert_eq!(
+            parse("a b,c"),
+            Ok(CSSValue::CommaSeparatedList(vec![
+                CSSValue::SpaceSeparatedList(vec![
+                    CSSValue::Keyword("a".to_string()),
+                    CSSValue::Keyword("b".to_string()),
+                ]),
+                CSSValue::Keyword("c".to_string()),
+            ]))
+        );
+        assert!(parse("a,").is_err());
+        assert!(parse("a, , b").is_err());
+        assert!(parse("{a}").is_err());
+    }
+
+    #[test]
+    fn test_color() {
+        let parse = parse_value;
+        assert_eq!(
+            parse("#fff"),
+            Ok(CSSValue::Color(Color::rgb(255, 255, 255)))
+        );
+        assert_eq!(
+            parse("#FF000080"),
+            Ok(CSSValue::Color(Color {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 128.0 / 255.0,
+            }))
+        );
+        assert!(parse("#ff").is_err());
+        assert_eq!(
+            parse("rgb(255, 128, 0)"),
+            Ok(CSSValue::Color(Color::rgb(255, 128, 0)))
+        );
+        assert_eq!(
+            parse("rgba(100%, 0%, 0%, 0.5)"),
+            Ok(CSSValue::Color(Color {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 0.5,
+            }))
+        );
+        assert_eq!(
+            parse("rgb(0 0 255 / 50%)"),
+            Ok(CSSValue::Color(Color {
+                r: 0,
+                g: 0,
+                b: 255,
+                a: 0.5,
+            }))
+        );
+        assert_eq!(
+            parse("hsl(120, 100%, 50%)"),
+            Ok(CSSValue::Color(Color::rgb(0, 255, 0)))
+        );
+        assert_eq!(
+            parse("hsl(0 0% 50%)"),
+            Ok(CSSValue::Color(Color::rgb(128, 128, 128)))
+        );
+        assert!(parse("rgb(1, 2)").is_err());
+        assert!(parse("rgb(1,, 2, 3)").is_err());
+        assert!(parse("rgb(1, 2, 3,)").is_err());
+        assert!(parse("rgb(1, a, 3)").is_err());
+
+        assert_eq!(
+            CSSValue::Keyword("Red".to_string()).to_color(),
+            Some(Color::rgb(255, 0, 0))
+        );
+        assert_eq!(
+            CSSValue::Keyword("transparent".to_string())
+                .to_color()
+                .map(|c| c.a),
+            Some(0.0)
+        );
+        assert_eq!(CSSValue::Keyword("block".to_string()).to_color(), None);
+        assert_eq!(CSSValue::Number(1.0).to_color(), None);
     }
 }
@@ -0,0 +1,608 @@
+//! This module includes an implementation of media queries, which are the conditions of `@media` and `@import` rules.
+//!
+//! Media queries are defined at https://drafts.csswg.org/mediaqueries-4/.
+//! They are evaluated against `Device`, which describes the terminal puppy runs in.
+
+use super::parser::ComponentValue;
+use super::tokenizer::Token;
+use super::{parse_declaration_value, CSSValue, Unit};
+
+/// `CELL_WIDTH` and `CELL_HEIGHT` are the size of a cell of the terminal in pixels.
+/// A cell is as wide as `1ch` and as tall as `1em` of the initial font size.
+pub const CELL_WIDTH: f64 = INITIAL_FONT_SIZE / 2.0;
+pub const CELL_HEIGHT: f64 = INITIAL_FONT_SIZE;
+
+/// `INITIAL_FONT_SIZE` is the font size which relative lengths in media queries are resolved with, i.e. the one of `font-size: medium`.
+/// https://drafts.csswg.org/mediaqueries-4/#units
+const INITIAL_FONT_SIZE: f64 = 16.0;
+
+/// `COLOR_BITS` is the number of bits per color component puppy assumes the terminal has.
+const COLOR_BITS: f64 = 8.0;
+
+/// `Device` describes the environment documents are rendered in, which media queries are evaluated against.
+/// https://drafts.csswg.org/mediaqueries-4/#media
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Device {
+    /// `width` and `height` are the size of the viewport in cells.
+    pub width: usize,
+    pub height: usize,
+    /// `color_scheme` is the color scheme the user prefers.
+    pub color_scheme: ColorScheme,
+}
+
+/// `ColorScheme` is the value of `prefers-color-scheme` media feature.
+/// https://drafts.csswg.org/mediaqueries-5/#prefers-color-scheme
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+/// The default device is a terminal of the classic size, 80x24.
+impl Default for Device {
+    fn default() -> Self {
+        Device {
+            width: 80,
+            height: 24,
+            color_scheme: ColorScheme::Light,
+        }
+    }
+}
+
+impl Device {
+    /// `feature` returns the value of the media feature named `name`, or `None` if puppy doesn't know the feature.
+    /// Lengths are returned in pixels.
+    fn feature(&self, name: &str) -> Option<CSSValue> {
+        let width = self.width as f64 * CELL_WIDTH;
+        let height = self.height as f64 * CELL_HEIGHT;
+        match name {
+            "width" => Some(CSSValue::Length((width, Unit::Px))),
+            "height" => Some(CSSValue::Length((height, Unit::Px))),
+            "orientation" if height >= width => Some(CSSValue::Keyword("portrait".to_string())),
+            "orientation" => Some(CSSValue::Keyword("landscape".to_string())),
+            "prefers-color-scheme" => Some(CSSValue::Keyword(
+                match self.color_scheme {
+                    ColorScheme::Light => "light",
+                    ColorScheme::Dark => "dark",
+                }
+                .to_string(),
+            )),
+            "color" => Some(CSSValue::Number(COLOR_BITS)),
+            "monochrome" => Some(CSSValue::Number(0.0)),
+            // NOTE: terminals are grid devices, which show texts on a fixed grid of cells.
+            // https://drafts.csswg.org/mediaqueries-4/#grid
+            "grid" => Some(CSSValue::Number(1.0)),
+            _ => None,
+        }
+    }
+
+    /// `length_in_px` converts a length in a media query into pixels.
+    /// https://drafts.csswg.org/mediaqueries-4/#units
+    fn length_in_px(&self, (size, unit): (f64, Unit)) -> f64 {
+        match unit {
+            Unit::Px => size,
+            Unit::Em | Unit::Rem => size * INITIAL_FONT_SIZE,
+            Unit::Ch => size * CELL_WIDTH,
+            Unit::Vw => size * self.width as f64 * CELL_WIDTH / 100.0,
+            Unit::Vh => size * self.height as f64 * CELL_HEIGHT / 100.0,
+        }
+    }
+}
+
+/// `MediaQueryList` is a list of media queries separated by commas, e.g. `screen, (max-width: 80ch)`.
+/// It matches if any of the queries matches; an empty list matches any device.
+/// https://drafts.csswg.org/mediaqueries-4/#mq-list
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MediaQueryList(pub Vec<MediaQuery>);
+
+/// `MediaQuery` is a single media query, e.g. `not tty and (color)`.
+/// https://drafts.csswg.org/mediaqueries-4/#mq-syntax
+#[derive(Debug, PartialEq, Clone)]
+pub struct MediaQuery {
+    /// `negated` is true if the query starts with `not`, which negates the whole query.
+    pub negated: bool,
+    pub media_type: MediaType,
+    pub condition: Option<MediaCondition>,
+}
+
+/// `MediaType` is a broad category of devices.
+/// https://drafts.csswg.org/mediaqueries-4/#media-types
+#[derive(Debug, PartialEq, Clone)]
+pub enum MediaType {
+    All,
+    /// `Tty` is the media type of terminals, which puppy is.
+    Tty,
+    /// `Other` is any other media type such as `screen` and `print`, which never matches.
+    Other(String),
+}
+
+/// `MediaCondition` is a combination of media features with `not`, `and` and `or`.
+/// https://drafts.csswg.org/mediaqueries-4/#media-conditions
+#[derive(Debug, PartialEq, Clone)]
+pub enum MediaCondition {
+    Feature(MediaFeature),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+}
+
+/// `MediaFeature` tests a single feature of the device.
+/// https://drafts.csswg.org/mediaqueries-4/#mq-features
+#[derive(Debug, PartialEq, Clone)]
+pub enum MediaFeature {
+    /// `Boolean` is a feature without value, e.g. `(color)`, which matches if the value of the feature isn't zero.
+    Boolean(String),
+    /// `Range` compares the value of a feature with a value, e.g. `(width >= 80ch)`.
+    /// Features with a value such as `(min-width: 80ch)` and `(orientation: portrait)` are represented as comparisons as well.
+    Range(String, Comparison, CSSValue),
+}
+
+/// `Comparison` is an operator in a media feature of range form.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Comparison {
+    /// `flip` returns the operator which gives the same result with the operands swapped, e.g. `<` for `>`.
+    fn flip(self) -> Self {
+        match self {
+            Comparison::Lt => Comparison::Gt,
+            Comparison::Le => Comparison::Ge,
+            Comparison::Eq => Comparison::Eq,
+            Comparison::Ge => Comparison::Le,
+            Comparison::Gt => Comparison::Lt,
+        }
+    }
+
+    fn compare(self, left: f64, right: f64) -> bool {
+        match self {
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Eq => left == right,
+            Comparison::Ge => left >= right,
+            Comparison::Gt => left > right,
+        }
+    }
+}
+
+impl MediaQueryList {
+    /// `matches` returns true if the list is empty or any of the queries matches `device`.
+    pub fn matches(&self, device: &Device) -> bool {
+        self.0.is_empty() || self.0.iter().any(|query| query.matches(device))
+    }
+}
+
+impl MediaQuery {
+    /// `not_all` returns the query which never matches.
+    /// Invalid media queries are replaced with it. https://drafts.csswg.org/mediaqueries-4/#error-handling
+    pub fn not_all() -> Self {
+        MediaQuery {
+            negated: true,
+            media_type: MediaType::All,
+            condition: None,
+        }
+    }
+
+    pub fn matches(&self, device: &Device) -> bool {
+        let media_type = match self.media_type {
+            MediaType::All | MediaType::Tty => true,
+            MediaType::Other(_) => false,
+        };
+        let condition = self
+            .condition
+            .as_ref()
+            .map(|c| c.matches(device))
+            .unwrap_or(true);
+        (media_type && condition) != self.negated
+    }
+}
+
+impl MediaCondition {
+    pub fn matches(&self, device: &Device) -> bool {
+        match self {
+            MediaCondition::Feature(feature) => feature.matches(device),
+            MediaCondition::Not(condition) => !condition.matches(device),
+            MediaCondition::And(conditions) => conditions.iter().all(|c| c.matches(device)),
+            MediaCondition::Or(conditions) => conditions.iter().any(|c| c.matches(device)),
+        }
+    }
+}
+
+impl MediaFeature {
+    pub fn matches(&self, device: &Device) -> bool {
+        match self {
+            MediaFeature::Boolean(name) => match device.feature(name) {
+                Some(CSSValue::Length((size, _))) | Some(CSSValue::Number(size)) => size != 0.0,
+                Some(_) => true,
+                None => false,
+            },
+            MediaFeature::Range(name, comparison, value) => match (device.feature(name), value) {
+                (Some(CSSValue::Length((actual, _))), CSSValue::Length(length)) => {
+                    comparison.compare(actual, device.length_in_px(*length))
+                }
+                (Some(CSSValue::Number(actual)), CSSValue::Number(number)) => {
+                    comparison.compare(actual, *number)
+                }
+                (Some(CSSValue::Keyword(actual)), CSSValue::Keyword(keyword)) => {
+                    *comparison == Comparison::Eq && actual.eq_ignore_ascii_case(keyword)
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+/// `parse_media_query_list` parses component values as a list of media queries.
+/// Each invalid query is replaced with `not all`, so that the others still work.
+/// https://drafts.csswg.org/mediaqueries-4/#typedef-media-query-list
+pub fn parse_media_query_list(values: &[ComponentValue]) -> MediaQueryList {
+    let values = values
+        .iter()
+        .filter(|v| !v.is_whitespace())
+        .cloned()
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return MediaQueryList::default();
+    }
+    MediaQueryList(
+        values
+            .split(|v| *v == ComponentValue::Token(Token::Comma))
+            .map(|query| parse_media_query(query).unwrap_or_else(MediaQuery::not_all))
+            .collect(),
+    )
+}
+
+/// `parse_media_query` parses component values without whitespaces as a media query.
+/// https://drafts.csswg.org/mediaqueries-4/#typedef-media-query
+fn parse_media_query(values: &[ComponentValue]) -> Option<MediaQuery> {
+    // a query may be a media condition only, e.g. `(color)` or `not (color)`
+    let is_condition = match values {
+        [ComponentValue::Token(Token::Ident(_)), ComponentValue::Token(Token::Ident(_)), ..] => {
+            false
+        }
+        [ComponentValue::Token(Token::Ident(keyword)), ..] => keyword.eq_ignore_ascii_case("not"),
+        _ => true,
+    };
+    if is_condition {
+        return Some(MediaQuery {
+            negated: false,
+            media_type: MediaType::All,
+            condition: Some(parse_media_condition(values, true)?),
+        });
+    }
+
+    let (negated, rest) = match values {
+        [ComponentValue::Token(Token::Ident(keyword)), ComponentValue::Token(Token::Ident(_)), ..]
+            if keyword.eq_ignore_ascii_case("not") || keyword.eq_ignore_ascii_case("only") =>
+        {
+            (keyword.eq_ignore_ascii_case("not"), &values[1..])
+        }
+        _ => (false, values),
+    };
+    let media_type = match &rest[0] {
+        ComponentValue::Token(Token::Ident(name)) => match name.to_ascii_lowercase().as_str() {
+            "not" | "only" | "and" | "or" | "layer" => return None,
+            "all" => MediaType::All,
+            "tty" => MediaType::Tty,
+            name => MediaType::Other(name.to_string()),
+        },
+        _ => return None,
+    };
+    let condition = match &rest[1..] {
+        [] => None,
+        [ComponentValue::Token(Token::Ident(keyword)), condition @ ..]
+            if keyword.eq_ignore_ascii_case("and") =>
+        {
+            Some(parse_media_condition(condition, false)?)
+        }
+        _ => return None,
+    };
+    Some(MediaQuery {
+        negated,
+        media_type,
+        condition,
+    })
+}
+
+/// `parse_media_condition` parses component values without whitespaces as a media condition.
+/// `or` is allowed only if `allow_or` is true, since it can't follow a media type.
+/// https://drafts.csswg.org/mediaqueries-4/#typedef-media-condition
+fn parse_media_condition(values: &[ComponentValue], allow_or: bool) -> Option<MediaCondition> {
+    match values {
+        [ComponentValue::Token(Token::Ident(keyword)), value]
+            if keyword.eq_ignore_ascii_case("not") =>
+        {
+            return Some(MediaCondition::Not(Box::new(parse_media_in_parens(value)?)));
+        }
+        [] => return None,
+        _ => {}
+    }
+
+    let mut conditions = vec![parse_media_in_parens(&values[0])?];
+    let mut operator = None;
+    for pair in values[1..].chunks(2) {
+        let keyword = match pair {
+            [ComponentValue::Token(Token::Ident(keyword)), _] => keyword.to_ascii_lowercase(),
+            _ => return None,
+        };
+        match keyword.as_str() {
+            "and" => {}
+            "or" if allow_or => {}
+            _ => return None,
+        }
+        if operator.get_or_insert_with(|| keyword.clone()) != &keyword {
+            return None;
+        }
+        conditions.push(parse_media_in_parens(&pair[1])?);
+    }
+    Some(match operator.as_deref() {
+        None => conditions.remove(0),
+        Some("and") => MediaCondition::And(conditions),
+        _ => MediaCondition::Or(conditions),
+    })
+}
+
+/// `parse_media_in_parens` parses a block enclosed with `()`, which is a media condition or a media feature.
+/// https://drafts.csswg.org/mediaqueries-4/#typedef-media-in-parens
+fn parse_media_in_parens(value: &ComponentValue) -> Option<MediaCondition> {
+    let values = match value {
+        ComponentValue::SimpleBlock('(', values) => values
+            .iter()
+            .filter(|v| !v.is_whitespace())
+            .cloned()
+            .collect::<Vec<_>>(),
+        _ => return None,
+    };
+    parse_media_condition(&values, true).or_else(|| parse_media_feature(&values))
+}
+
+/// `parse_media_feature` parses the content of a media feature, e.g. `min-width: 80ch` or `40ch < width <= 80ch`.
+/// Unknown features are invalid, and so are values of the wrong type; e.g. `min-` and `max-` prefixes are invalid for keywords.
+/// https://drafts.csswg.org/mediaqueries-4/#typedef-media-feature
+fn parse_media_feature(values: &[ComponentValue]) -> Option<MediaCondition> {
+    // boolean context, e.g. `color`
+    if let [ComponentValue::Token(Token::Ident(name))] = values {
+        let name = name.to_ascii_lowercase();
+        Device::default().feature(name.as_str())?;
+        return Some(MediaCondition::Feature(MediaFeature::Boolean(name)));
+    }
+
+    // plain form, e.g. `min-width: 80ch`
+    if let [ComponentValue::Token(Token::Ident(name)), ComponentValue::Token(Token::Colon), value @ ..] =
+        values
+    {
+        let name = name.to_ascii_lowercase();
+        let (name, comparison) = if let Some(name) = name.strip_prefix("min-") {
+            (name, Comparison::Ge)
+        } else if let Some(name) = name.strip_prefix("max-") {
+            (name, Comparison::Le)
+        } else {
+            (name.as_str(), Comparison::Eq)
+        };
+        return to_range_feature(name, comparison, value).map(MediaCondition::Feature);
+    }
+
+    // range form, e.g. `width >= 80ch` or `40ch < width <= 80ch`
+    let mut operands = vec![];
+    let mut comparisons = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i < values.len() {
+        let delim = match &values[i] {
+            ComponentValue::Token(Token::Delim(c)) if "<>=".contains(*c) => *c,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        operands.push(&values[start..i]);
+        let or_equal =
+            delim != '=' && values.get(i + 1) == Some(&ComponentValue::Token(Token::Delim('=')));
+        comparisons.push(match (delim, or_equal) {
+            ('<', false) => Comparison::Lt,
+            ('<', true) => Comparison::Le,
+            ('>', false) => Comparison::Gt,
+            ('>', true) => Comparison::Ge,
+            _ => Comparison::Eq,
+        });
+        i += if or_equal { 2 } else { 1 };
+        start = i;
+    }
+    operands.push(&values[start..]);
+
+    let feature_name = |values: &[ComponentValue]| match values {
+        [ComponentValue::Token(Token::Ident(name))] => Some(name.to_ascii_lowercase()),
+        _ => None,
+    };
+    match (operands.as_slice(), comparisons.as_slice()) {
+        ([left, right], [comparison]) => match (feature_name(left), feature_name(right)) {
+            (Some(name), _) => to_range_feature(name.as_str(), *comparison, right),
+            (None, Some(name)) => to_range_feature(name.as_str(), comparison.flip(), left),
+            (None, None) => None,
+        }
+        .map(MediaCondition::Feature),
+        ([left, name, right], [first, second]) => {
+            // both operators must point to the same direction, e.g. `a < width <= b`
+            let is_less = |c: &Comparison| matches!(c, Comparison::Lt | Comparison::Le);
+            let is_greater = |c: &Comparison| matches!(c, Comparison::Gt | Comparison::Ge);
+            if !(is_less(first) && is_less(second) || is_greater(first) && is_greater(second)) {
+                return None;
+            }
+            let name = feature_name(name)?;
+            Some(MediaCondition::And(vec![
+                MediaCondition::Feature(to_range_feature(name.as_str(), first.flip(), left)?),
+                MediaCondition::Feature(to_range_feature(name.as_str(), *second, right)?),
+            ]))
+        }
+        _ => None,
+    }
+}
+
+/// `to_range_feature` makes a media feature which compares the feature named `name` with `values`.
+/// It returns `None` if the feature is unknown, or the value has a different type from the feature.
+fn to_range_feature(
+    name: &str,
+    comparison: Comparison,
+    values: &[ComponentValue],
+) -> Option<MediaFeature> {
+    let value = match (
+        Device::default().feature(name)?,
+        parse_declaration_value(values).ok()?,
+    ) {
+        // `0` is a valid length without unit
+        (CSSValue::Length(_), CSSValue::Number(0.0)) => CSSValue::Length((0.0, Unit::Px)),
+        (CSSValue::Length(_), value @ CSSValue::Length(_)) => value,
+        (CSSValue::Number(_), value @ CSSValue::Number(_)) => value,
+        (CSSValue::Keyword(_), value @ CSSValue::Keyword(_)) if comparison == Comparison::Eq => {
+            value
+        }
+        _ => return None,
+    };
+    Some(MediaFeature::Range(name.to_string(), comparison, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::css::parser::Parser;
+
+    fn parse(input: &str) -> MediaQueryList {
+        parse_media_query_list(&Parser::new(input).parse_component_values())
+    }
+
+    fn range(name: &str, comparison: Comparison, value: CSSValue) -> MediaCondition {
+        MediaCondition::Feature(MediaFeature::Range(name.to_string(), comparison, value))
+    }
+
+    #[test]
+    fn test_parse_media_query_list() {
+        assert_eq!(parse(""), MediaQueryList(vec![]));
+        assert_eq!(
+            parse("screen, not tty and (min-width: 40ch) and (color)"),
+            MediaQueryList(vec![
+                MediaQuery {
+                    negated: false,
+                    media_type: MediaType::Other("screen".to_string()),
+                    condition: None,
+                },
+                MediaQuery {
+                    negated: true,
+                    media_type: MediaType::Tty,
+                    condition: Some(MediaCondition::And(vec![
+                        range("width", Comparison::Ge, CSSValue::Length((40.0, Unit::Ch))),
+                        MediaCondition::Feature(MediaFeature::Boolean("color".to_string())),
+                    ])),
+                },
+            ])
+        );
+        assert_eq!(
+            parse("(prefers-color-scheme: DARK) or (not (grid))"),
+            MediaQueryList(vec![MediaQuery {
+                negated: false,
+                media_type: MediaType::All,
+                condition: Some(MediaCondition::Or(vec![
+                    range(
+                        "prefers-color-scheme",
+                        Comparison::Eq,
+                        CSSValue::Keyword("DARK".to_string())
+                    ),
+                    MediaCondition::Not(Box::new(MediaCondition::Feature(MediaFeature::Boolean(
+                        "grid".to_string()
+                    )))),
+                ])),
+            }])
+        );
+        assert_eq!(
+            parse("(400px < width <= 80ch), (0 = height)"),
+            MediaQueryList(vec![
+                MediaQuery {
+                    negated: false,
+                    media_type: MediaType::All,
+                    condition: Some(MediaCondition::And(vec![
+                        range("width", Comparison::Gt, CSSValue::Length((400.0, Unit::Px))),
+                        range("width", Comparison::Le, CSSValue::Length((80.0, Unit::Ch))),
+                    ])),
+                },
+                MediaQuery {
+                    negated: false,
+                    media_type: MediaType::All,
+                    condition: Some(range(
+                        "height",
+                        Comparison::Eq,
+                        CSSValue::Length((0.0, Unit::Px))
+                    )),
+                },
+            ])
+        );
+
+        // invalid queries are replaced with `not all`
+        for input in &[
+            "(unknown)",
+            "(width: red)",
+            "(min-orientation: portrait)",
+            "(width < 1px > 2px)",
+            "tty and (color) or (grid)",
+            "(color) and (grid) or (width)",
+            "and",
+            "tty (color)",
+        ] {
+            assert_eq!(
+                parse(input),
+                MediaQueryList(vec![MediaQuery::not_all()]),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(
+            parse("(unknown), tty").0[1],
+            MediaQuery {
+                negated: false,
+                media_type: MediaType::Tty,
+                condition: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let device = Device {
+            width: 100,
+            height: 30,
+            color_scheme: ColorScheme::Dark,
+        };
+        let matches = |input: &str| parse(input).matches(&device);
+
+        // media types
+        assert!(matches(""));
+        assert!(matches("all"));
+        assert!(matches("only tty"));
+        assert!(!matches("screen"));
+        assert!(matches("print, tty"));
+        assert!(matches("not print"));
+        assert!(!matches("not tty"));
+        // the viewport is 800px wide and 480px tall
+        assert!(matches("(width: 800px)"));
+        assert!(matches("(min-width: 100ch) and (max-width: 50em)"));
+        assert!(!matches("(min-width: 101ch)"));
+        assert!(matches("(30em = height)"));
+        assert!(!matches("(400px < width < 100vh)"));
+        assert!(matches("(400px < width <= 100vw)"));
+        assert!(matches("(orientation: landscape)"));
+        assert!(!matches("(orientation: portrait)"));
+        assert!(matches("(width) and (height)"));
+        // the other features
+        assert!(matches("(prefers-color-scheme: dark)"));
+        assert!(!matches("(prefers-color-scheme: light)"));
+        assert!(matches("(color) and (min-color: 8) and (grid)"));
+        assert!(!matches("(monochrome)"));
+        assert!(matches("not all and (monochrome)"));
+        // invalid queries never match
+        assert!(!matches("(hover: hover)"));
+        assert!(!matches("not (hover: hover)"));
+    }
+}
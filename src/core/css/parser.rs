@@ -0,0 +1,786 @@
+//! This module includes an implementation of the parsing stage of CSS parsing.
+//!
+//! The parser groups tokens into rules and declarations as defined at https://www.w3.org/TR/css-syntax-3/#parsing.
+//! It recovers from errors as the standard requires; an invalid rule or declaration is dropped and recorded as a parse error, and the others are kept.
+
+use super::media::parse_media_query_list;
+use super::supports::parse_supports_condition;
+use super::tokenizer::{tokenize, Token};
+use super::{
+    parse_declaration_value, parse_selector_list, Declaration, Import, Rule, RuleCondition,
+};
+use crate::source::{ParseDiagnostic, SourcePosition, SourceSpan};
+use log::trace;
+use std::{fmt, rc::Rc};
+
+/// `ComponentValue` is a token, or a group of tokens enclosed with brackets.
+/// https://www.w3.org/TR/css-syntax-3/#component-value
+#[derive(Debug, PartialEq, Clone)]
+pub enum ComponentValue {
+    Token(Token),
+    /// `Function` is a function token and the values until the corresponding `)`, e.g. `rgb(0, 0, 0)`.
+    Function(String, Vec<ComponentValue>),
+    /// `SimpleBlock` is the values enclosed with `{}`, `[]` or `()`. It holds the opening bracket and the values.
+    SimpleBlock(char, Vec<ComponentValue>),
+}
+
+impl ComponentValue {
+    pub fn is_whitespace(&self) -> bool {
+        *self == ComponentValue::Token(Token::Whitespace)
+    }
+}
+
+impl fmt::Display for ComponentValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComponentValue::Token(token) => write!(f, "{}", token),
+            ComponentValue::Function(name, values) => write!(
+                f,
+                "{}{})",
+                Token::Function(name.clone()),
+                serialize_all(values)
+            ),
+            ComponentValue::SimpleBlock(open, values) => {
+                let close = match open {
+                    '{' => '}',
+                    '[' => ']',
+                    _ => ')',
+                };
+                write!(f, "{}{}{}", open, serialize_all(values), close)
+            }
+        }
+    }
+}
+
+/// `serialize` converts component values into CSS text without leading and trailing whitespaces, e.g. to show them in diagnostics.
+/// Comments are dropped and escapes are normalized.
+pub fn serialize(values: &[ComponentValue]) -> String {
+    let start = values
+        .iter()
+        .position(|v| !v.is_whitespace())
+        .unwrap_or(values.len());
+    let end = values
+        .iter()
+        .rposition(|v| !v.is_whitespace())
+        .map(|i| i + 1)
+        .unwrap_or(start);
+    serialize_all(&values[start..end])
+}
+
+fn serialize_all(values: &[ComponentValue]) -> String {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+/// `Parser` converts a sequence of tokens into rules and declarations.
+pub struct Parser {
+    tokens: Vec<(Token, SourceSpan)>,
+    pos: usize,
+    errors: Vec<ParseDiagnostic>,
+    /// `conditions` are the conditions of the conditional group rules being parsed, from the outermost one.
+    conditions: Vec<Rc<RuleCondition>>,
+    imports: Vec<Import>,
+    /// `imports_allowed` is true until a rule other than `@charset` and `@import` appears.
+    imports_allowed: bool,
+}
+
+impl Parser {
+    pub fn new(input: &str) -> Self {
+        let (tokens, errors) = tokenize(input);
+        Parser {
+            tokens,
+            pos: 0,
+            errors,
+            conditions: vec![],
+            imports: vec![],
+            imports_allowed: true,
+        }
+    }
+
+    /// `take_errors` returns parse errors found so far, including the ones found in tokenization.
+    pub fn take_errors(&mut self) -> Vec<ParseDiagnostic> {
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|e| e.position);
+        errors
+    }
+
+    /// `take_imports` returns `@import` rules found so far.
+    pub fn take_imports(&mut self) -> Vec<Import> {
+        std::mem::take(&mut self.imports)
+    }
+
+    /// `parse_rules` parses the input as the rules of a stylesheet.
+    /// `@import` rules are kept apart from the rules, and can be taken with `take_imports`.
+    /// https://www.w3.org/TR/css-syntax-3/#parse-stylesheet
+    pub fn parse_rules(&mut self) -> Vec<Rule> {
+        self.consume_rules(false)
+    }
+
+    /// `parse_declarations` parses the input as a list of declarations, such as the value of `style` attributes.
+    /// https://www.w3.org/TR/css-syntax-3/#parse-list-of-declarations
+    pub fn parse_declarations(&mut self) -> Vec<Declaration> {
+        self.consume_declarations(false)
+    }
+
+    /// `parse_component_values` parses the whole input as component values.
+    /// https://www.w3.org/TR/css-syntax-3/#parse-list-of-component-values
+    pub fn parse_component_values(&mut self) -> Vec<ComponentValue> {
+        let mut values = vec![];
+        while *self.peek() != Token::EOF {
+            values.push(self.consume_component_value());
+        }
+        values
+    }
+
+    /// `consume_rules` consumes rules until the end of input, or `}` if `nested` is true.
+    /// The rules in conditional group rules are flattened into the result.
+    /// https://www.w3.org/TR/css-syntax-3/#consume-list-of-rules
+    fn consume_rules(&mut self, nested: bool) -> Vec<Rule> {
+        let mut rules = vec![];
+        loop {
+            match self.peek() {
+                Token::Whitespace | Token::CDO | Token::CDC => self.pos += 1,
+                Token::EOF => {
+                    if nested {
+                        let position = self.peek_span().start;
+                        self.error(
+                            "unexpected end of stylesheet in a block".to_string(),
+                            position,
+                        );
+                    }
+                    return rules;
+                }
+                Token::CloseCurly if nested => {
+                    self.pos += 1;
+                    return rules;
+                }
+                Token::AtKeyword(_) => rules.extend(self.consume_at_rule(nested)),
+                _ => {
+                    if let Some(rule) = self.consume_qualified_rule(nested) {
+                        self.imports_allowed = false;
+                        rules.push(rule);
+                    }
+                }
+            }
+        }
+    }
+
+    /// `consume_at_rule` consumes an at-rule in a list of rules, and returns the rules in it.
+    /// https://www.w3.org/TR/css-syntax-3/#consume-at-rule
+    /// - `@charset` is ignored, since the encoding is decided before parsing.
+    /// - `@import` is kept in `self.imports`.
+    /// - `@media` and `@supports` give their conditions to the rules in them.
+    /// - The other at-rules are dropped.
+    fn consume_at_rule(&mut self, nested: bool) -> Vec<Rule> {
+        let (name, span, prelude) = self.consume_at_rule_prelude(nested);
+        let has_block = *self.peek() == Token::OpenCurly;
+        match name.to_ascii_lowercase().as_str() {
+            "charset" if !has_block => {}
+            "import" if !has_block => self.consume_import(&prelude, span),
+            "media" if has_block => {
+                let condition = RuleCondition::Media(parse_media_query_list(&prelude));
+                return self.consume_conditional_rules(condition);
+            }
+            "supports" if has_block => match parse_supports_condition(&prelude) {
+                Some(condition) => {
+                    return self.consume_conditional_rules(RuleCondition::Supports(condition))
+                }
+                None => {
+                    self.consume_component_value();
+                    self.error(
+                        format!(
+                            "invalid condition `{}` for @supports; the rule is dropped",
+                            serialize(&prelude)
+                        ),
+                        span.start,
+                    );
+                }
+            },
+            _ => self.drop_at_rule(name, span),
+        }
+        vec![]
+    }
+
+    /// `consume_at_rule_prelude` consumes the name and the prelude of an at-rule.
+    /// The block of the at-rule is left unconsumed, while `;` which ends the at-rule is consumed.
+    fn consume_at_rule_prelude(
+        &mut self,
+        nested: bool,
+    ) -> (String, SourceSpan, Vec<ComponentValue>) {
+        let (name, span) = match self.consume() {
+            (Token::AtKeyword(name), span) => (name, span),
+            (token, _) => unreachable!("an at-rule starts with {:?}", token),
+        };
+        let mut prelude = vec![];
+        loop {
+            match self.peek() {
+                Token::Semicolon => {
+                    self.pos += 1;
+                    break;
+                }
+                Token::EOF | Token::OpenCurly => break,
+                Token::CloseCurly if nested => break,
+                _ => prelude.push(self.consume_component_value()),
+            }
+        }
+        (name, span, prelude)
+    }
+
+    /// `drop_at_rule` consumes the rest of an at-rule puppy doesn't support, and records it as a parse error.
+    fn drop_at_rule(&mut self, name: String, span: SourceSpan) {
+        if *self.peek() == Token::OpenCurly {
+            self.consume_component_value();
+        }
+        self.error(
+            format!("@{} is not supported; the rule is dropped", name),
+            span.start,
+        );
+    }
+
+    /// `consume_import` parses the prelude of `@import`, e.g. `url("a.css") supports(display: block) tty`.
+    /// https://drafts.csswg.org/css-cascade-4/#at-import
+    fn consume_import(&mut self, prelude: &[ComponentValue], span: SourceSpan) {
+        if !self.imports_allowed {
+            self.error(
+                "@import must precede all the other rules; the rule is dropped".to_string(),
+                span.start,
+            );
+            return;
+        }
+        let mut values = prelude.iter().skip_while(|v| v.is_whitespace());
+        let url = match values.next() {
+            Some(ComponentValue::Token(Token::String(url)))
+            | Some(ComponentValue::Token(Token::Url(url))) => url.clone(),
+            Some(ComponentValue::Function(name, arguments)) if name.eq_ignore_ascii_case("url") => {
+                match arguments
+                    .iter()
+                    .filter(|v| !v.is_whitespace())
+                    .collect::<Vec<_>>()
+                    .as_slice()
+                {
+                    [ComponentValue::Token(Token::String(url))] => url.clone(),
+                    _ => {
+                        self.error(
+                            "invalid URL for @import; the rule is dropped".to_string(),
+                            span.start,
+                        );
+                        return;
+                    }
+                }
+            }
+            _ => {
+                self.error(
+                    "@import must have a URL; the rule is dropped".to_string(),
+                    span.start,
+                );
+                return;
+            }
+        };
+
+        let mut values = values.skip_while(|v| v.is_whitespace()).peekable();
+        let mut conditions = vec![];
+        if let Some(ComponentValue::Function(name, arguments)) = values.peek() {
+            if name.eq_ignore_ascii_case("supports") {
+                // `supports()` takes a declaration as well as a condition
+                let condition = parse_supports_condition(arguments).or_else(|| {
+                    parse_supports_condition(&[ComponentValue::SimpleBlock('(', arguments.clone())])
+                });
+                match condition {
+                    Some(condition) => conditions.push(RuleCondition::Supports(condition)),
+                    None => {
+                        self.error(
+                            "invalid condition in @import; the rule is dropped".to_string(),
+                            span.start,
+                        );
+                        return;
+                    }
+                }
+                values.next();
+            }
+        }
+        let media = values.cloned().collect::<Vec<_>>();
+        conditions.push(RuleCondition::Media(parse_media_query_list(&media)));
+        self.imports.push(Import { url, conditions });
+    }
+
+    /// `consume_conditional_rules` consumes the block of a conditional group rule, whose condition is `condition`.
+    /// https://drafts.csswg.org/css-conditional-3/#conditional-group-rule
+    fn consume_conditional_rules(&mut self, condition: RuleCondition) -> Vec<Rule> {
+        // NOTE: `@import` can't follow conditional group rules, nor appear in them.
+        self.imports_allowed = false;
+        self.pos += 1;
+        self.conditions.push(Rc::new(condition));
+        let rules = self.consume_rules(true);
+        self.conditions.pop();
+        rules
+    }
+
+    /// `consume_qualified_rule` consumes a qualified rule.
+    /// It returns `None` if the rule is invalid, e.g. when its selectors can't be parsed.
+    /// `}` ends the rule without any block if `nested` is true, since it closes the enclosing block.
+    /// https://www.w3.org/TR/css-syntax-3/#consume-qualified-rule
+    fn consume_qualified_rule(&mut self, nested: bool) -> Option<Rule> {
+        let start = self.peek_span().start;
+        let mut prelude = vec![];
+        loop {
+            match self.peek() {
+                Token::EOF => {
+                    self.error(
+                        "unexpected end of stylesheet before `{`; the rule is dropped".to_string(),
+                        start,
+                    );
+                    return None;
+                }
+                Token::CloseCurly if nested => {
+                    self.error(
+                        "unexpected `}` before `{`; the rule is dropped".to_string(),
+                        start,
+                    );
+                    return None;
+                }
+                Token::OpenCurly => break,
+                _ => prelude.push(self.consume_component_value()),
+            }
+        }
+
+        let selectors = match parse_selector_list(&prelude) {
+            Ok(selectors) => selectors,
+            Err(_) => {
+                self.error(
+                    format!(
+                        "invalid selector `{}`; the rule is dropped",
+                        serialize(&prelude)
+                    ),
+                    start,
+                );
+                self.consume_component_value();
+                return None;
+            }
+        };
+        self.pos += 1;
+        let declarations = self.consume_declarations(true);
+        Some(Rule {
+            selectors,
+            declarations,
+            conditions: self.conditions.clone(),
+            source_span: Some(SourceSpan::new(start, self.last_span().end)),
+        })
+    }
+
+    /// `consume_declarations` consumes declarations until the end of input, or `}` if `nested` is true.
+    /// https://www.w3.org/TR/css-syntax-3/#consume-list-of-declarations
+    fn consume_declarations(&mut self, nested: bool) -> Vec<Declaration> {
+        let mut declarations = vec![];
+        loop {
+            match self.peek() {
+                Token::Whitespace | Token::Semicolon => self.pos += 1,
+                Token::EOF => {
+                    if nested {
+                        let position = self.peek_span().start;
+                        self.error(
+                            "unexpected end of stylesheet in a block".to_string(),
+                            position,
+                        );
+                    }
+                    return declarations;
+                }
+                Token::CloseCurly if nested => {
+                    self.pos += 1;
+                    return declarations;
+                }
+                Token::AtKeyword(_) => {
+                    let (name, span, _) = self.consume_at_rule_prelude(nested);
+                    self.drop_at_rule(name, span);
+                }
+                Token::Ident(_) => {
+                    if let Some(declaration) = self.consume_declaration(nested) {
+                        declarations.push(declaration);
+                    }
+                }
+                _ => {
+                    let start = self.peek_span().start;
+                    self.consume_declaration_values(nested);
+                    self.error(
+                        "a declaration must start with a property name; the declaration is dropped"
+                            .to_string(),
+                        start,
+                    );
+                }
+            }
+        }
+    }
+
+    /// `consume_declaration` consumes a declaration starting with its property name.
+    /// It returns `None` if the declaration is invalid, e.g. when its value can't be parsed.
+    /// https://www.w3.org/TR/css-syntax-3/#consume-declaration
+    fn consume_declaration(&mut self, nested: bool) -> Option<Declaration> {
+        let (name, start) = match self.consume() {
+            (Token::Ident(name), span) => (name, span.start),
+            (token, _) => unreachable!("a declaration starts with {:?}", token),
+        };
+        let mut values = self.consume_declaration_values(nested);
+
+        // the property name must be followed by `:`
+        let colon = values.iter().position(|(v, _)| !v.is_whitespace());
+        match colon.map(|i| &values[i].0) {
+            Some(ComponentValue::Token(Token::Colon)) => {
+                values.drain(..=colon.unwrap());
+            }
+            _ => {
+                self.error(
+                    format!("expected `:` after `{}`; the declaration is dropped", name),
+                    start,
+                );
+                return None;
+            }
+        }
+
+        // the value may end with `!important`
+        trim_whitespaces(&mut values);
+        let end = values.last().map(|(_, span)| span.end).unwrap_or_default();
+        let important = ends_with_important(&values);
+        if important {
+            let bang = values
+                .iter()
+                .rposition(|(v, _)| *v == ComponentValue::Token(Token::Delim('!')))
+                .unwrap();
+            values.truncate(bang);
+            trim_whitespaces(&mut values);
+        }
+
+        let values = values.into_iter().map(|(v, _)| v).collect::<Vec<_>>();
+        if values.is_empty() {
+            self.error(
+                format!("`{}` has no value; the declaration is dropped", name),
+                start,
+            );
+            return None;
+        }
+        match parse_declaration_value(&values) {
+            Ok(value) => Some(Declaration {
+                name,
+                value,
+                important,
+                source_span: Some(SourceSpan::new(start, end)),
+            }),
+            Err(_) => {
+                self.error(
+                    format!(
+                        "invalid value `{}` for `{}`; the declaration is dropped",
+                        serialize(&values),
+                        name
+                    ),
+                    start,
+                );
+                None
+            }
+        }
+    }
+
+    /// `consume_declaration_values` consumes component values with their locations until `;` or the end of the declaration list.
+    /// The terminator is left unconsumed.
+    fn consume_declaration_values(&mut self, nested: bool) -> Vec<(ComponentValue, SourceSpan)> {
+        let mut values = vec![];
+        loop {
+            match self.peek() {
+                Token::Semicolon | Token::EOF => return values,
+                Token::CloseCurly if nested => return values,
+                _ => {
+                    let start = self.peek_span().start;
+                    let value = self.consume_component_value();
+                    values.push((value, SourceSpan::new(start, self.last_span().end)));
+                }
+            }
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-component-value
+    fn consume_component_value(&mut self) -> ComponentValue {
+        match self.consume().0 {
+            Token::OpenCurly => {
+                ComponentValue::SimpleBlock('{', self.consume_block(Token::CloseCurly))
+            }
+            Token::OpenSquare => {
+                ComponentValue::SimpleBlock('[', self.consume_block(Token::CloseSquare))
+            }
+            Token::OpenParen => {
+                ComponentValue::SimpleBlock('(', self.consume_block(Token::CloseParen))
+            }
+            Token::Function(name) => {
+                ComponentValue::Function(name, self.consume_block(Token::CloseParen))
+            }
+            token => ComponentValue::Token(token),
+        }
+    }
+
+    /// `consume_block` consumes component values until `ending`, which closes the block.
+    /// https://www.w3.org/TR/css-syntax-3/#consume-simple-block
+    fn consume_block(&mut self, ending: Token) -> Vec<ComponentValue> {
+        let mut values = vec![];
+        loop {
+            if *self.peek() == ending {
+                self.pos += 1;
+                return values;
+            }
+            if *self.peek() == Token::EOF {
+                let position = self.peek_span().start;
+                self.error(
+                    "unexpected end of stylesheet in a block".to_string(),
+                    position,
+                );
+                return values;
+            }
+            values.push(self.consume_component_value());
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn peek_span(&self) -> SourceSpan {
+        self.tokens[self.pos].1
+    }
+
+    /// `last_span` returns the location of the token consumed last.
+    fn last_span(&self) -> SourceSpan {
+        self.tokens[self.pos.saturating_sub(1)].1
+    }
+
+    /// `consume` returns the next token and moves on, except at the end of input.
+    fn consume(&mut self) -> (Token, SourceSpan) {
+        let token = self.tokens[self.pos].clone();
+        if token.0 != Token::EOF {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&mut self, message: String, position: SourcePosition) {
+        trace!("parse error in CSS parsing; {}", message);
+        self.errors.push(ParseDiagnostic::new(message, position));
+    }
+}
+
+/// `ends_with_important` returns true if the values end with `!important`, whose keyword is ASCII case-insensitive.
+/// https://www.w3.org/TR/css-syntax-3/#consume-declaration
+fn ends_with_important(values: &[(ComponentValue, SourceSpan)]) -> bool {
+    let mut values = values.iter().rev().filter(|(v, _)| !v.is_whitespace());
+    match (values.next(), values.next()) {
+        (
+            Some((ComponentValue::Token(Token::Ident(keyword)), _)),
+            Some((ComponentValue::Token(Token::Delim('!')), _)),
+        ) => keyword.eq_ignore_ascii_case("important"),
+        _ => false,
+    }
+}
+
+fn trim_whitespaces(values: &mut Vec<(ComponentValue, SourceSpan)>) {
+    while values
+        .last()
+        .map(|(v, _)| v.is_whitespace())
+        .unwrap_or(false)
+    {
+        values.pop();
+    }
+    let leading = values.iter().take_while(|(v, _)| v.is_whitespace()).count();
+    values.drain(..leading);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::css::{
+        media::MediaQueryList, parse_selectors, CSSValue, Selector, SimpleSelector, Unit,
+    };
+
+    fn declaration(name: &str, value: CSSValue, important: bool) -> Declaration {
+        Declaration {
+            name: name.to_string(),
+            value,
+            important,
+            source_span: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let mut parser = Parser::new("test[foo=bar], p {} rule { ee: dd;  }");
+        assert_eq!(
+            parser.parse_rules(),
+            vec![
+                Rule {
+                    selectors: parse_selectors("test[foo=bar], p").unwrap(),
+                    declarations: vec![],
+                    conditions: vec![],
+                    source_span: None,
+                },
+                Rule {
+                    selectors: vec![Selector::from(SimpleSelector::TypeSelector {
+                        tag_name: "rule".to_string(),
+                    })],
+                    declarations: vec![declaration(
+                        "ee",
+                        CSSValue::Keyword("dd".to_string()),
+                        false
+                    )],
+                    conditions: vec![],
+                    source_span: None,
+                },
+            ]
+        );
+        assert_eq!(parser.take_errors(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_rules_with_errors() {
+        let mut parser =
+            Parser::new("@font-face { src: url(a.woff) }\np! { a: b }\n<!-- q { c: d } -->\nr { e: f; } s { g: h");
+        let rules = parser.parse_rules();
+        assert_eq!(
+            rules
+                .iter()
+                .map(|rule| rule.declarations[0].name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["c", "e", "g"]
+        );
+        assert_eq!(
+            parser
+                .take_errors()
+                .into_iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "1:1: @font-face is not supported; the rule is dropped",
+                "2:1: invalid selector `p!`; the rule is dropped",
+                "4:21: unexpected end of stylesheet in a block",
+            ]
+        );
+
+        let mut parser = Parser::new("a { b: c } d");
+        assert_eq!(parser.parse_rules().len(), 1);
+        assert_eq!(
+            parser.take_errors(),
+            vec![ParseDiagnostic::new(
+                "unexpected end of stylesheet before `{`; the rule is dropped".to_string(),
+                SourcePosition::new(1, 12)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_at_rules() {
+        let mut parser = Parser::new(
+            r#"@charset "utf-8";
+@import "a.css";
+@IMPORT url(b.css) supports(display: block) tty, (color);
+@import url( "c.css" ) ;
+@media tty { p { a: b } @supports (c: d) { q { e: f } } }
+@media { r { g: h } @font-face { src: url(a.woff) } s }
+t { i: j }
+@import "d.css";
+@supports (k: l) or { u { m: n } }"#,
+        );
+        let rules = parser.parse_rules();
+        let media = Rc::new(RuleCondition::Media(parse_media_query_list(
+            &Parser::new("tty").parse_component_values(),
+        )));
+        let supports = Rc::new(RuleCondition::Supports(
+            parse_supports_condition(&Parser::new("(c: d)").parse_component_values()).unwrap(),
+        ));
+        assert_eq!(
+            rules
+                .iter()
+                .map(|rule| (rule.declarations[0].name.as_str(), rule.conditions.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", vec![media.clone()]),
+                ("e", vec![media, supports]),
+                (
+                    "g",
+                    vec![Rc::new(RuleCondition::Media(MediaQueryList::default()))]
+                ),
+                ("i", vec![]),
+            ]
+        );
+        assert_eq!(
+            parser
+                .take_imports()
+                .into_iter()
+                .map(|import| (import.url, import.conditions.len()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a.css".to_string(), 1),
+                ("b.css".to_string(), 2),
+                ("c.css".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            parser
+                .take_errors()
+                .into_iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "6:21: @font-face is not supported; the rule is dropped",
+                "6:53: unexpected `}` before `{`; the rule is dropped",
+                "8:1: @import must precede all the other rules; the rule is dropped",
+                "9:1: invalid condition `(k: l) or` for @supports; the rule is dropped",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_declarations() {
+        let mut parser = Parser::new(
+            "font-size: 10px; -webkit-box-flex: 1;--x:1; /* note */ color: red !IMPORTANT ;",
+        );
+        assert_eq!(
+            parser.parse_declarations(),
+            vec![
+                declaration("font-size", CSSValue::Length((10.0, Unit::Px)), false),
+                declaration("-webkit-box-flex", CSSValue::Number(1.0), false),
+                declaration("--x", CSSValue::Number(1.0), false),
+                declaration("color", CSSValue::Keyword("red".to_string()), true),
+            ]
+        );
+        assert_eq!(parser.take_errors(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_declarations_with_errors() {
+        let mut parser =
+            Parser::new("a: 1foo; b c; d: ; e: \"x\ny; 12: 3; f: {g: h}; @page {} i: j");
+        assert_eq!(
+            parser.parse_declarations(),
+            vec![declaration("i", CSSValue::Keyword("j".to_string()), false)]
+        );
+        assert_eq!(
+            parser
+                .take_errors()
+                .into_iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "1:1: invalid value `1foo` for `a`; the declaration is dropped",
+                "1:10: expected `:` after `b`; the declaration is dropped",
+                "1:15: `d` has no value; the declaration is dropped",
+                "1:20: invalid value `\" y` for `e`; the declaration is dropped",
+                "1:25: newline-in-string",
+                "2:4: a declaration must start with a property name; the declaration is dropped",
+                "2:11: invalid value `{g: h}` for `f`; the declaration is dropped",
+                "2:22: @page is not supported; the rule is dropped",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let serialize_input = |input: &str| serialize(&Parser::new(input).parse_component_values());
+        assert_eq!(serialize_input("  a  b  "), "a b");
+        assert_eq!(
+            serialize_input("rgb( 1 ,/**/2 ) [x='y'] {}"),
+            "rgb( 1 ,2 ) [x=\"y\"] {}"
+        );
+        assert_eq!(serialize_input(""), "");
+    }
+}
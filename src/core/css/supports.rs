@@ -0,0 +1,197 @@
+//! This module includes an implementation of the conditions of `@supports` rules.
+//!
+//! The conditions are defined at https://drafts.csswg.org/css-conditional-3/#at-supports.
+//! Whether puppy supports a declaration depends on the properties it implements, so the evaluation is left to the caller.
+
+use super::parser::ComponentValue;
+use super::tokenizer::Token;
+use super::{parse_declaration_value, parse_selector_list, Declaration, Selector};
+
+/// `SupportsCondition` is a combination of supports features with `not`, `and` and `or`.
+/// https://drafts.csswg.org/css-conditional-3/#typedef-supports-condition
+#[derive(Debug, PartialEq)]
+pub enum SupportsCondition {
+    Not(Box<SupportsCondition>),
+    And(Vec<SupportsCondition>),
+    Or(Vec<SupportsCondition>),
+    /// `Declaration` is true if puppy supports the declaration, e.g. `(display: block)`.
+    Declaration(Declaration),
+    /// `Selector` is a selector given with `selector()`, which is true since puppy could parse it.
+    /// https://drafts.csswg.org/css-conditional-4/#at-supports-ext
+    Selector(Vec<Selector>),
+    /// `Unsupported` is a condition which is false whatever puppy supports,
+    /// such as an unknown function or a declaration whose value can't be parsed.
+    Unsupported,
+}
+
+impl SupportsCondition {
+    /// `matches` evaluates the condition; `is_supported` decides whether a declaration is supported.
+    pub fn matches<F>(&self, is_supported: &F) -> bool
+    where
+        F: Fn(&Declaration) -> bool,
+    {
+        match self {
+            SupportsCondition::Not(condition) => !condition.matches(is_supported),
+            SupportsCondition::And(conditions) => {
+                conditions.iter().all(|c| c.matches(is_supported))
+            }
+            SupportsCondition::Or(conditions) => conditions.iter().any(|c| c.matches(is_supported)),
+            SupportsCondition::Declaration(declaration) => is_supported(declaration),
+            SupportsCondition::Selector(_) => true,
+            SupportsCondition::Unsupported => false,
+        }
+    }
+}
+
+/// `parse_supports_condition` parses component values as the condition of `@supports`.
+/// It returns `None` if the values don't follow the grammar, which makes the whole rule invalid.
+pub fn parse_supports_condition(values: &[ComponentValue]) -> Option<SupportsCondition> {
+    let values = values
+        .iter()
+        .filter(|v| !v.is_whitespace())
+        .collect::<Vec<_>>();
+    match values.as_slice() {
+        [ComponentValue::Token(Token::Ident(keyword)), value]
+            if keyword.eq_ignore_ascii_case("not") =>
+        {
+            return Some(SupportsCondition::Not(Box::new(parse_supports_in_parens(
+                value,
+            )?)));
+        }
+        [] => return None,
+        _ => {}
+    }
+
+    let mut conditions = vec![parse_supports_in_parens(values[0])?];
+    let mut operator = None;
+    for pair in values[1..].chunks(2) {
+        let keyword = match pair {
+            [ComponentValue::Token(Token::Ident(keyword)), _] => keyword.to_ascii_lowercase(),
+            _ => return None,
+        };
+        if keyword != "and" && keyword != "or" {
+            return None;
+        }
+        if operator.get_or_insert_with(|| keyword.clone()) != &keyword {
+            return None;
+        }
+        conditions.push(parse_supports_in_parens(pair[1])?);
+    }
+    Some(match operator.as_deref() {
+        None => conditions.remove(0),
+        Some("and") => SupportsCondition::And(conditions),
+        _ => SupportsCondition::Or(conditions),
+    })
+}
+
+/// `parse_supports_in_parens` parses a condition in `()`, a declaration in `()`, or a function.
+/// https://drafts.csswg.org/css-conditional-3/#typedef-supports-in-parens
+fn parse_supports_in_parens(value: &ComponentValue) -> Option<SupportsCondition> {
+    match value {
+        ComponentValue::SimpleBlock('(', values) => parse_supports_condition(values)
+            .or_else(|| parse_supports_declaration(values))
+            .or(Some(SupportsCondition::Unsupported)),
+        ComponentValue::Function(name, values) if name.eq_ignore_ascii_case("selector") => {
+            Some(match parse_selector_list(values) {
+                Ok(selectors) => SupportsCondition::Selector(selectors),
+                Err(_) => SupportsCondition::Unsupported,
+            })
+        }
+        ComponentValue::Function(_, _) => Some(SupportsCondition::Unsupported),
+        _ => None,
+    }
+}
+
+/// `parse_supports_declaration` parses the content of `(property: value)`.
+/// https://drafts.csswg.org/css-conditional-3/#typedef-supports-decl
+fn parse_supports_declaration(values: &[ComponentValue]) -> Option<SupportsCondition> {
+    let start = values.iter().position(|v| !v.is_whitespace())?;
+    let name = match &values[start] {
+        ComponentValue::Token(Token::Ident(name)) => name.clone(),
+        _ => return None,
+    };
+    let colon = start
+        + 1
+        + values[start + 1..]
+            .iter()
+            .position(|v| !v.is_whitespace())?;
+    if values[colon] != ComponentValue::Token(Token::Colon) {
+        return None;
+    }
+    Some(match parse_declaration_value(&values[colon + 1..]) {
+        Ok(value) => SupportsCondition::Declaration(Declaration {
+            name,
+            value,
+            important: false,
+            source_span: None,
+        }),
+        Err(_) => SupportsCondition::Unsupported,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::css::{parse_selectors, parser::Parser, CSSValue};
+
+    fn parse(input: &str) -> Option<SupportsCondition> {
+        parse_supports_condition(&Parser::new(input).parse_component_values())
+    }
+
+    fn declaration(name: &str, value: &str) -> SupportsCondition {
+        SupportsCondition::Declaration(Declaration {
+            name: name.to_string(),
+            value: CSSValue::Keyword(value.to_string()),
+            important: false,
+            source_span: None,
+        })
+    }
+
+    #[test]
+    fn test_parse_supports_condition() {
+        assert_eq!(
+            parse("(display: block)"),
+            Some(declaration("display", "block"))
+        );
+        assert_eq!(
+            parse("not ((a: b) or (c: d))"),
+            Some(SupportsCondition::Not(Box::new(SupportsCondition::Or(
+                vec![declaration("a", "b"), declaration("c", "d")]
+            ))))
+        );
+        assert_eq!(
+            parse("(a: b) AND selector(p > q) and (c: 1foo) and foo(x)"),
+            Some(SupportsCondition::And(vec![
+                declaration("a", "b"),
+                SupportsCondition::Selector(parse_selectors("p > q").unwrap()),
+                SupportsCondition::Unsupported,
+                SupportsCondition::Unsupported,
+            ]))
+        );
+        assert_eq!(
+            parse("(unknown syntax)"),
+            Some(SupportsCondition::Unsupported)
+        );
+
+        // invalid conditions
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("a: b"), None);
+        assert_eq!(parse("(a: b) and (c: d) or (e: f)"), None);
+        assert_eq!(parse("not (a: b) and (c: d)"), None);
+        assert_eq!(parse("(a: b) (c: d)"), None);
+    }
+
+    #[test]
+    fn test_matches() {
+        let is_supported = |d: &Declaration| d.name == "display";
+        let matches = |input: &str| parse(input).unwrap().matches(&is_supported);
+        assert!(matches("(display: block)"));
+        assert!(!matches("(float: left)"));
+        assert!(matches("not (float: left)"));
+        assert!(matches("(display: block) and selector(a)"));
+        assert!(!matches("(display: block) and (float: left)"));
+        assert!(matches("(float: left) or ((display: block))"));
+        assert!(!matches("(display: 1foo)"));
+        assert!(!matches("foo(display: block)"));
+    }
+}
@@ -0,0 +1,812 @@
+//! This module includes an implementation of the tokenization stage of CSS parsing.
+//!
+//! The tokenizer follows the algorithm defined at https://www.w3.org/TR/css-syntax-3/#tokenization.
+//! It never fails; malformed input is turned into tokens such as `BadString` and `BadUrl`, and the problems are recorded as parse errors.
+
+use crate::source::{ParseDiagnostic, SourcePosition, SourceSpan};
+use log::trace;
+use std::fmt;
+
+/// `Token` is an output of the tokenizer, which is defined at https://www.w3.org/TR/css-syntax-3/#tokenization.
+/// Comments are consumed by the tokenizer and never appear as tokens.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    Ident(String),
+    /// `Function` is an identifier followed by `(`, e.g. `rgb(`. It holds the name without `(`.
+    Function(String),
+    /// `AtKeyword` is `@` followed by an identifier, e.g. `@media`. It holds the name without `@`.
+    AtKeyword(String),
+    /// `Hash` is `#` followed by name characters, e.g. `#fff` or `#main`. It holds the name without `#`.
+    Hash(String, HashType),
+    String(String),
+    BadString,
+    /// `Url` is an unquoted URL, e.g. `url(a.png)`. It holds the URL without `url(` and `)`.
+    Url(String),
+    BadUrl,
+    Delim(char),
+    Number(f64),
+    /// `Percentage` holds the number before `%`.
+    Percentage(f64),
+    /// `Dimension` is a number followed by a unit, e.g. `10px`.
+    Dimension(f64, String),
+    Whitespace,
+    /// `CDO` is `<!--`.
+    CDO,
+    /// `CDC` is `-->`.
+    CDC,
+    Colon,
+    Semicolon,
+    Comma,
+    OpenSquare,
+    CloseSquare,
+    OpenParen,
+    CloseParen,
+    OpenCurly,
+    CloseCurly,
+    EOF,
+}
+
+/// `HashType` tells whether the name of a hash token is an identifier, which is required for ID selectors.
+/// https://www.w3.org/TR/css-syntax-3/#hash-token-diagram
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HashType {
+    Id,
+    Unrestricted,
+}
+
+/// `Token` is displayed as CSS text which represents the same token.
+/// Names and strings are escaped as needed, and numbers are written in their shortest form.
+/// https://drafts.csswg.org/cssom/#serialize-an-identifier
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "{}", escape_identifier(name)),
+            Token::Function(name) => write!(f, "{}(", escape_identifier(name)),
+            Token::AtKeyword(name) => write!(f, "@{}", escape_identifier(name)),
+            Token::Hash(name, HashType::Id) => write!(f, "#{}", escape_identifier(name)),
+            Token::Hash(name, HashType::Unrestricted) => write!(f, "#{}", escape_name(name)),
+            Token::String(value) => write!(f, "{}", escape_string(value)),
+            Token::BadString => write!(f, "\""),
+            Token::Url(url) => write!(f, "url({})", escape_string(url)),
+            Token::BadUrl => write!(f, "url("),
+            Token::Delim(c) => write!(f, "{}", c),
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Percentage(value) => write!(f, "{}%", value),
+            Token::Dimension(value, unit) => write!(f, "{}{}", value, escape_identifier(unit)),
+            Token::Whitespace => write!(f, " "),
+            Token::CDO => write!(f, "<!--"),
+            Token::CDC => write!(f, "-->"),
+            Token::Colon => write!(f, ":"),
+            Token::Semicolon => write!(f, ";"),
+            Token::Comma => write!(f, ","),
+            Token::OpenSquare => write!(f, "["),
+            Token::CloseSquare => write!(f, "]"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::OpenCurly => write!(f, "{{"),
+            Token::CloseCurly => write!(f, "}}"),
+            Token::EOF => Ok(()),
+        }
+    }
+}
+
+/// `escape_identifier` escapes `name` so that it is tokenized as an identifier.
+/// https://drafts.csswg.org/cssom/#serialize-an-identifier
+fn escape_identifier(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    if chars == ['-'] {
+        return "\\-".to_string();
+    }
+    let mut escaped = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let starts_with_digit = c.is_ascii_digit() && (i == 0 || (i == 1 && chars[0] == '-'));
+        if starts_with_digit {
+            escaped.push_str(&format!("\\{:x} ", c as u32));
+        } else {
+            escaped.push_str(&escape_name_char(c));
+        }
+    }
+    escaped
+}
+
+/// `escape_name` escapes `name` so that it is tokenized as a sequence of name characters, e.g. the name of a hash token.
+fn escape_name(name: &str) -> String {
+    name.chars().map(escape_name_char).collect()
+}
+
+fn escape_name_char(c: char) -> String {
+    match c {
+        '\0' => '\u{FFFD}'.to_string(),
+        '\u{1}'..='\u{1F}' | '\u{7F}' => format!("\\{:x} ", c as u32),
+        c if is_name_code_point(c) => c.to_string(),
+        c => format!("\\{}", c),
+    }
+}
+
+/// `escape_string` quotes `value` with `"`.
+/// https://drafts.csswg.org/cssom/#serialize-a-string
+fn escape_string(value: &str) -> String {
+    let mut escaped = "\"".to_string();
+    for c in value.chars() {
+        match c {
+            '\0' => escaped.push('\u{FFFD}'),
+            '\u{1}'..='\u{1F}' | '\u{7F}' => escaped.push_str(&format!("\\{:x} ", c as u32)),
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// `tokenize` converts the whole `input` into tokens with their locations.
+/// The last token is always `Token::EOF`.
+pub fn tokenize(input: &str) -> (Vec<(Token, SourceSpan)>, Vec<ParseDiagnostic>) {
+    let mut tokenizer = Tokenizer::new(input);
+    let mut tokens = vec![];
+    loop {
+        let token = tokenizer.next_token();
+        let is_eof = token.0 == Token::EOF;
+        tokens.push(token);
+        if is_eof {
+            break;
+        }
+    }
+    (tokens, tokenizer.take_errors())
+}
+
+/// `Tokenizer` converts characters into a sequence of `Token`.
+pub struct Tokenizer {
+    input: Vec<char>,
+    // `positions[i]` is the location of `input[i]`, and the last one is the location of EOF.
+    positions: Vec<SourcePosition>,
+    pos: usize,
+    errors: Vec<ParseDiagnostic>,
+}
+
+impl Tokenizer {
+    /// `new` creates a tokenizer for `input`.
+    /// Newlines and NULL characters are normalized as the standard requires: https://www.w3.org/TR/css-syntax-3/#input-preprocessing
+    pub fn new(input: &str) -> Self {
+        let mut chars = vec![];
+        let mut raw = input.chars().peekable();
+        while let Some(c) = raw.next() {
+            match c {
+                '\r' => {
+                    if raw.peek() == Some(&'\n') {
+                        raw.next();
+                    }
+                    chars.push('\n');
+                }
+                '\u{C}' => chars.push('\n'),
+                '\0' => chars.push('\u{FFFD}'),
+                c => chars.push(c),
+            }
+        }
+
+        let mut positions = Vec::with_capacity(chars.len() + 1);
+        let mut position = SourcePosition::default();
+        for &c in &chars {
+            positions.push(position);
+            position = if c == '\n' {
+                SourcePosition::new(position.line + 1, 1)
+            } else {
+                SourcePosition::new(position.line, position.column + 1)
+            };
+        }
+        positions.push(position);
+
+        Tokenizer {
+            input: chars,
+            positions,
+            pos: 0,
+            errors: vec![],
+        }
+    }
+
+    /// `take_errors` returns parse errors found so far.
+    pub fn take_errors(&mut self) -> Vec<ParseDiagnostic> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// `next_token` consumes a token and returns it with its location.
+    /// https://www.w3.org/TR/css-syntax-3/#consume-token
+    pub fn next_token(&mut self) -> (Token, SourceSpan) {
+        self.consume_comments();
+        let start = self.positions[self.pos];
+        let token = self.consume_token();
+        (token, SourceSpan::new(start, self.positions[self.pos]))
+    }
+
+    fn consume_token(&mut self) -> Token {
+        let c = match self.consume() {
+            Some(c) => c,
+            None => return Token::EOF,
+        };
+        match c {
+            c if is_whitespace(c) => {
+                while self.peek(0).map(is_whitespace).unwrap_or(false) {
+                    self.pos += 1;
+                }
+                Token::Whitespace
+            }
+            '"' | '\'' => self.consume_string(c),
+            '#' => {
+                if self.peek(0).map(is_name_code_point).unwrap_or(false)
+                    || self.starts_with_valid_escape(0)
+                {
+                    let hash_type = if self.starts_with_identifier(0) {
+                        HashType::Id
+                    } else {
+                        HashType::Unrestricted
+                    };
+                    Token::Hash(self.consume_name(), hash_type)
+                } else {
+                    Token::Delim(c)
+                }
+            }
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '+' | '.' => {
+                self.pos -= 1;
+                if self.starts_with_number(0) {
+                    self.consume_numeric()
+                } else {
+                    self.pos += 1;
+                    Token::Delim(c)
+                }
+            }
+            ',' => Token::Comma,
+            '-' => {
+                self.pos -= 1;
+                if self.starts_with_number(0) {
+                    self.consume_numeric()
+                } else if self.peek(1) == Some('-') && self.peek(2) == Some('>') {
+                    self.pos += 3;
+                    Token::CDC
+                } else if self.starts_with_identifier(0) {
+                    self.consume_ident_like()
+                } else {
+                    self.pos += 1;
+                    Token::Delim(c)
+                }
+            }
+            ':' => Token::Colon,
+            ';' => Token::Semicolon,
+            '<' => {
+                if self.peek(0) == Some('!')
+                    && self.peek(1) == Some('-')
+                    && self.peek(2) == Some('-')
+                {
+                    self.pos += 3;
+                    Token::CDO
+                } else {
+                    Token::Delim(c)
+                }
+            }
+            '@' => {
+                if self.starts_with_identifier(0) {
+                    Token::AtKeyword(self.consume_name())
+                } else {
+                    Token::Delim(c)
+                }
+            }
+            '[' => Token::OpenSquare,
+            '\\' => {
+                self.pos -= 1;
+                if self.starts_with_valid_escape(0) {
+                    self.consume_ident_like()
+                } else {
+                    self.pos += 1;
+                    self.error("invalid-escape");
+                    Token::Delim(c)
+                }
+            }
+            ']' => Token::CloseSquare,
+            '{' => Token::OpenCurly,
+            '}' => Token::CloseCurly,
+            c if c.is_ascii_digit() => {
+                self.pos -= 1;
+                self.consume_numeric()
+            }
+            c if is_name_start_code_point(c) => {
+                self.pos -= 1;
+                self.consume_ident_like()
+            }
+            c => Token::Delim(c),
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-comments
+    fn consume_comments(&mut self) {
+        while self.peek(0) == Some('/') && self.peek(1) == Some('*') {
+            self.pos += 2;
+            loop {
+                match self.consume() {
+                    Some('*') if self.peek(0) == Some('/') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.error("eof-in-comment");
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-numeric-token
+    fn consume_numeric(&mut self) -> Token {
+        let value = self.consume_number();
+        if self.starts_with_identifier(0) {
+            Token::Dimension(value, self.consume_name())
+        } else if self.peek(0) == Some('%') {
+            self.pos += 1;
+            Token::Percentage(value)
+        } else {
+            Token::Number(value)
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-number
+    fn consume_number(&mut self) -> f64 {
+        let mut repr = String::new();
+        if let Some(c @ '+') | Some(c @ '-') = self.peek(0) {
+            repr.push(c);
+            self.pos += 1;
+        }
+        self.consume_digits(&mut repr);
+        if self.peek(0) == Some('.') && self.peek(1).map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            repr.push('.');
+            self.pos += 1;
+            self.consume_digits(&mut repr);
+        }
+        if let Some('e') | Some('E') = self.peek(0) {
+            let digit_at = match self.peek(1) {
+                Some('+') | Some('-') => 2,
+                _ => 1,
+            };
+            if self
+                .peek(digit_at)
+                .map(|c| c.is_ascii_digit())
+                .unwrap_or(false)
+            {
+                for _ in 0..digit_at {
+                    repr.push(self.consume().unwrap());
+                }
+                self.consume_digits(&mut repr);
+            }
+        }
+        repr.parse::<f64>().unwrap_or_default()
+    }
+
+    fn consume_digits(&mut self, repr: &mut String) {
+        while let Some(c) = self.peek(0).filter(|c| c.is_ascii_digit()) {
+            repr.push(c);
+            self.pos += 1;
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-ident-like-token
+    fn consume_ident_like(&mut self) -> Token {
+        let name = self.consume_name();
+        if name.eq_ignore_ascii_case("url") && self.peek(0) == Some('(') {
+            self.pos += 1;
+            // NOTE: `url(` followed by a quoted string is a function token, whose argument is a string token.
+            let mut offset = 0;
+            while self.peek(offset).map(is_whitespace).unwrap_or(false) {
+                offset += 1;
+            }
+            match self.peek(offset) {
+                Some('"') | Some('\'') => {
+                    self.pos += offset.saturating_sub(1);
+                    Token::Function(name)
+                }
+                _ => self.consume_url(),
+            }
+        } else if self.peek(0) == Some('(') {
+            self.pos += 1;
+            Token::Function(name)
+        } else {
+            Token::Ident(name)
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-string-token
+    fn consume_string(&mut self, ending: char) -> Token {
+        let mut value = String::new();
+        loop {
+            match self.consume() {
+                Some(c) if c == ending => return Token::String(value),
+                None => {
+                    self.error("eof-in-string");
+                    return Token::String(value);
+                }
+                Some('\n') => {
+                    self.error("newline-in-string");
+                    self.pos -= 1;
+                    return Token::BadString;
+                }
+                Some('\\') => match self.peek(0) {
+                    None => {}
+                    Some('\n') => self.pos += 1,
+                    Some(_) => value.push(self.consume_escape()),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-url-token
+    fn consume_url(&mut self) -> Token {
+        let mut url = String::new();
+        self.consume_whitespaces();
+        loop {
+            match self.consume() {
+                Some(')') => return Token::Url(url),
+                None => {
+                    self.error("eof-in-url");
+                    return Token::Url(url);
+                }
+                Some(c) if is_whitespace(c) => {
+                    self.consume_whitespaces();
+                    match self.consume() {
+                        Some(')') => return Token::Url(url),
+                        None => {
+                            self.error("eof-in-url");
+                            return Token::Url(url);
+                        }
+                        Some(_) => {
+                            self.error("unexpected-character-in-url");
+                            self.consume_bad_url_remnants();
+                            return Token::BadUrl;
+                        }
+                    }
+                }
+                Some(c) if c == '"' || c == '\'' || c == '(' || is_non_printable(c) => {
+                    self.error("unexpected-character-in-url");
+                    self.consume_bad_url_remnants();
+                    return Token::BadUrl;
+                }
+                Some('\\') => {
+                    self.pos -= 1;
+                    if self.starts_with_valid_escape(0) {
+                        self.pos += 1;
+                        url.push(self.consume_escape());
+                    } else {
+                        self.pos += 1;
+                        self.error("invalid-escape");
+                        self.consume_bad_url_remnants();
+                        return Token::BadUrl;
+                    }
+                }
+                Some(c) => url.push(c),
+            }
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-remnants-of-bad-url
+    fn consume_bad_url_remnants(&mut self) {
+        loop {
+            match self.consume() {
+                None | Some(')') => return,
+                Some('\\') if self.peek(0).map(|c| c != '\n').unwrap_or(false) => {
+                    self.consume_escape();
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// `consume_escape` consumes an escaped code point just after `\`.
+    /// https://www.w3.org/TR/css-syntax-3/#consume-escaped-code-point
+    fn consume_escape(&mut self) -> char {
+        match self.consume() {
+            Some(c) if c.is_ascii_hexdigit() => {
+                let mut digits = c.to_string();
+                while digits.len() < 6 {
+                    match self.peek(0).filter(|c| c.is_ascii_hexdigit()) {
+                        Some(c) => {
+                            digits.push(c);
+                            self.pos += 1;
+                        }
+                        None => break,
+                    }
+                }
+                if self.peek(0).map(is_whitespace).unwrap_or(false) {
+                    self.pos += 1;
+                }
+                match u32::from_str_radix(digits.as_str(), 16) {
+                    Ok(0) | Err(_) => '\u{FFFD}',
+                    Ok(code) => std::char::from_u32(code).unwrap_or('\u{FFFD}'),
+                }
+            }
+            Some(c) => c,
+            None => {
+                self.error("eof-in-escape");
+                '\u{FFFD}'
+            }
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-name
+    fn consume_name(&mut self) -> String {
+        let mut name = String::new();
+        loop {
+            match self.peek(0) {
+                Some(c) if is_name_code_point(c) => {
+                    name.push(c);
+                    self.pos += 1;
+                }
+                Some('\\') if self.starts_with_valid_escape(0) => {
+                    self.pos += 1;
+                    name.push(self.consume_escape());
+                }
+                _ => return name,
+            }
+        }
+    }
+
+    fn consume_whitespaces(&mut self) {
+        while self.peek(0).map(is_whitespace).unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#starts-with-a-valid-escape
+    fn starts_with_valid_escape(&self, offset: usize) -> bool {
+        self.peek(offset) == Some('\\') && self.peek(offset + 1) != Some('\n')
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#would-start-an-identifier
+    fn starts_with_identifier(&self, offset: usize) -> bool {
+        match self.peek(offset) {
+            Some('-') => match self.peek(offset + 1) {
+                Some('-') => true,
+                Some(c) if is_name_start_code_point(c) => true,
+                _ => self.starts_with_valid_escape(offset + 1),
+            },
+            Some('\\') => self.starts_with_valid_escape(offset),
+            Some(c) => is_name_start_code_point(c),
+            None => false,
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#starts-with-a-number
+    fn starts_with_number(&self, offset: usize) -> bool {
+        let is_digit = |offset: usize| {
+            self.peek(offset)
+                .map(|c| c.is_ascii_digit())
+                .unwrap_or(false)
+        };
+        match self.peek(offset) {
+            Some('+') | Some('-') => {
+                is_digit(offset + 1) || (self.peek(offset + 1) == Some('.') && is_digit(offset + 2))
+            }
+            Some('.') => is_digit(offset + 1),
+            _ => is_digit(offset),
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.input.get(self.pos + offset).copied()
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        let c = self.peek(0);
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn error(&mut self, message: &'static str) {
+        trace!("parse error in tokenization; {}", message);
+        let position = self.positions[self.pos.saturating_sub(1)];
+        self.errors
+            .push(ParseDiagnostic::new(message.to_string(), position));
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == '\n' || c == '\t' || c == ' '
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#name-start-code-point
+fn is_name_start_code_point(c: char) -> bool {
+    c.is_ascii_alphabetic() || !c.is_ascii() || c == '_'
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#name-code-point
+fn is_name_code_point(c: char) -> bool {
+    is_name_start_code_point(c) || c.is_ascii_digit() || c == '-'
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#non-printable-code-point
+fn is_non_printable(c: char) -> bool {
+    matches!(c, '\u{0}'..='\u{8}' | '\u{B}' | '\u{E}'..='\u{1F}' | '\u{7F}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<Token> {
+        tokenize(input)
+            .0
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokens("p.a > #b{font-size:1.5em}"),
+            vec![
+                Token::Ident("p".to_string()),
+                Token::Delim('.'),
+                Token::Ident("a".to_string()),
+                Token::Whitespace,
+                Token::Delim('>'),
+                Token::Whitespace,
+                Token::Hash("b".to_string(), HashType::Id),
+                Token::OpenCurly,
+                Token::Ident("font-size".to_string()),
+                Token::Colon,
+                Token::Dimension(1.5, "em".to_string()),
+                Token::CloseCurly,
+                Token::EOF,
+            ]
+        );
+        assert_eq!(
+            tokens("-webkit-box --x -1 +.5% 1e3 <!-- -->"),
+            vec![
+                Token::Ident("-webkit-box".to_string()),
+                Token::Whitespace,
+                Token::Ident("--x".to_string()),
+                Token::Whitespace,
+                Token::Number(-1.0),
+                Token::Whitespace,
+                Token::Percentage(0.5),
+                Token::Whitespace,
+                Token::Number(1000.0),
+                Token::Whitespace,
+                Token::CDO,
+                Token::Whitespace,
+                Token::CDC,
+                Token::EOF,
+            ]
+        );
+        assert_eq!(
+            tokens("@media rgb(0)url( a.png )url('b')"),
+            vec![
+                Token::AtKeyword("media".to_string()),
+                Token::Whitespace,
+                Token::Function("rgb".to_string()),
+                Token::Number(0.0),
+                Token::CloseParen,
+                Token::Url("a.png".to_string()),
+                Token::Function("url".to_string()),
+                Token::String("b".to_string()),
+                Token::CloseParen,
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_comments_and_escapes() {
+        assert_eq!(
+            tokens("a/* b */c/* d"),
+            vec![
+                Token::Ident("a".to_string()),
+                Token::Ident("c".to_string()),
+                Token::EOF,
+            ]
+        );
+        assert_eq!(
+            tokens(
+                r#".a\:b \31 0 "x\"y\
+z" '\41'"#
+            ),
+            vec![
+                Token::Delim('.'),
+                Token::Ident("a:b".to_string()),
+                Token::Whitespace,
+                Token::Ident("10".to_string()),
+                Token::Whitespace,
+                Token::String("x\"yz".to_string()),
+                Token::Whitespace,
+                Token::String("A".to_string()),
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_hashes() {
+        assert_eq!(
+            tokens(r"#a #\31 23 #123 #-1"),
+            vec![
+                Token::Hash("a".to_string(), HashType::Id),
+                Token::Whitespace,
+                Token::Hash("123".to_string(), HashType::Id),
+                Token::Whitespace,
+                Token::Hash("123".to_string(), HashType::Unrestricted),
+                Token::Whitespace,
+                Token::Hash("-1".to_string(), HashType::Unrestricted),
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_bad_tokens() {
+        let (tokens, errors) = tokenize("\"a\nb url(a b) c");
+        assert_eq!(
+            tokens
+                .into_iter()
+                .map(|(token, _)| token)
+                .collect::<Vec<_>>(),
+            vec![
+                Token::BadString,
+                Token::Whitespace,
+                Token::Ident("b".to_string()),
+                Token::Whitespace,
+                Token::BadUrl,
+                Token::Whitespace,
+                Token::Ident("c".to_string()),
+                Token::EOF,
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                ParseDiagnostic::new("newline-in-string".to_string(), SourcePosition::new(1, 3)),
+                ParseDiagnostic::new(
+                    "unexpected-character-in-url".to_string(),
+                    SourcePosition::new(2, 9)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let (tokens, _) = tokenize("a {\r\n  b: c }");
+        let span = |start: (usize, usize), end: (usize, usize)| {
+            SourceSpan::new(
+                SourcePosition::new(start.0, start.1),
+                SourcePosition::new(end.0, end.1),
+            )
+        };
+        assert_eq!(tokens[0].1, span((1, 1), (1, 2)));
+        assert_eq!(tokens[3].1, span((1, 4), (2, 3)));
+        assert_eq!(
+            tokens[4],
+            (Token::Ident("b".to_string()), span((2, 3), (2, 4)))
+        );
+        assert_eq!(tokens.last().unwrap().1, span((2, 9), (2, 9)));
+    }
+
+    #[test]
+    fn test_display() {
+        let serialize = |input: &str| {
+            tokens(input)
+                .iter()
+                .map(|token| token.to_string())
+                .collect::<String>()
+        };
+        assert_eq!(serialize("a  /**/b"), "a b");
+        assert_eq!(serialize(r".\31 a\:b"), r".\31 a\:b");
+        assert_eq!(serialize("'it\\'s' url(a\\)b)"), r#""it's" url("a)b")"#);
+        assert_eq!(serialize("#0f0 +1.50px 10%"), "#0f0 1.5px 10%");
+        assert_eq!(serialize(r"#\31 23 #123 #-a"), r"#\31 23 #123 #-a");
+    }
+}
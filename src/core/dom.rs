@@ -6,8 +6,23 @@ pub use self::chardata::*;
 pub mod document;
 pub use self::document::*;
 
+pub mod document_type;
+pub use self::document_type::*;
+
+pub mod exception;
+pub use self::exception::*;
+
 pub mod element;
 pub use self::element::*;
 
+pub mod metadata;
+pub use self::metadata::*;
+
+pub mod mutation;
+pub use self::mutation::*;
+
 pub mod node;
 pub use self::node::*;
+
+pub mod traversal;
+pub use self::traversal::*;
@@ -1,21 +1,210 @@
 //! This module defines some interfaces related to `CharacterData`.
 
-use super::{Node, NodeType};
+use super::{DOMException, NodeTree, NodeType};
 
 /// `CharacterData` is an abstract interface defined at [DOM Standard](https://dom.spec.whatwg.org/#interface-characterdata).
-pub trait CharacterData {}
+///
+/// Offsets and counts are in UTF-16 code units as the standard says.
+/// NOTE: `String` can't hold a lone surrogate, so a surrogate pair split by an operation is replaced with U+FFFD.
+pub trait CharacterData {
+    /// https://dom.spec.whatwg.org/#dom-characterdata-data
+    fn data(&self) -> &str;
+
+    fn set_data(&mut self, data: String);
+
+    /// https://dom.spec.whatwg.org/#dom-characterdata-length
+    fn length(&self) -> usize {
+        self.data().encode_utf16().count()
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-cd-substring
+    fn substring_data(&self, offset: usize, count: usize) -> Result<String, DOMException> {
+        let units = self.data().encode_utf16().collect::<Vec<_>>();
+        if offset > units.len() {
+            return Err(DOMException::IndexSizeError(
+                "the offset is greater than the length",
+            ));
+        }
+        let end = units.len().min(offset.saturating_add(count));
+        Ok(String::from_utf16_lossy(&units[offset..end]))
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-characterdata-appenddata
+    fn append_data(&mut self, data: &str) {
+        let mut new_data = self.data().to_string();
+        new_data.push_str(data);
+        self.set_data(new_data);
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-characterdata-insertdata
+    fn insert_data(&mut self, offset: usize, data: &str) -> Result<(), DOMException> {
+        self.replace_data(offset, 0, data)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-characterdata-deletedata
+    fn delete_data(&mut self, offset: usize, count: usize) -> Result<(), DOMException> {
+        self.replace_data(offset, count, "")
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-cd-replace
+    fn replace_data(
+        &mut self,
+        offset: usize,
+        count: usize,
+        data: &str,
+    ) -> Result<(), DOMException> {
+        let units = self.data().encode_utf16().collect::<Vec<_>>();
+        if offset > units.len() {
+            return Err(DOMException::IndexSizeError(
+                "the offset is greater than the length",
+            ));
+        }
+        let end = units.len().min(offset.saturating_add(count));
+        let new_units = units[..offset]
+            .iter()
+            .cloned()
+            .chain(data.encode_utf16())
+            .chain(units[end..].iter().cloned())
+            .collect::<Vec<_>>();
+        self.set_data(String::from_utf16_lossy(&new_units));
+        Ok(())
+    }
+}
 
 /// `Text` is a kind of `Node`.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Text {
     pub data: String,
 }
 impl Text {
-    pub fn new(text: String) -> Box<Node> {
-        Box::new(Node {
+    pub fn new(text: String) -> NodeTree {
+        NodeTree {
             node_type: NodeType::Text(Text { data: text }),
             children: vec![],
-        })
+        }
+    }
+}
+impl CharacterData for Text {
+    fn data(&self) -> &str {
+        self.data.as_str()
+    }
+
+    fn set_data(&mut self, data: String) {
+        self.data = data;
+    }
+}
+
+/// `CDATASection` is a kind of `Text`, which is defined at [DOM Standard](https://dom.spec.whatwg.org/#interface-cdatasection).
+/// The HTML parser never creates it, since puppy handles foreign content as HTML.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CDATASection {
+    pub data: String,
+}
+impl CDATASection {
+    pub fn new(data: String) -> NodeTree {
+        NodeTree {
+            node_type: NodeType::CDATASection(CDATASection { data }),
+            children: vec![],
+        }
+    }
+}
+impl CharacterData for CDATASection {
+    fn data(&self) -> &str {
+        self.data.as_str()
+    }
+
+    fn set_data(&mut self, data: String) {
+        self.data = data;
+    }
+}
+
+/// `Comment` is a kind of `Node`, which is defined at [DOM Standard](https://dom.spec.whatwg.org/#interface-comment).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    pub data: String,
+}
+impl Comment {
+    pub fn new(data: String) -> NodeTree {
+        NodeTree {
+            node_type: NodeType::Comment(Comment { data: data }),
+            children: vec![],
+        }
+    }
+}
+impl CharacterData for Comment {
+    fn data(&self) -> &str {
+        self.data.as_str()
+    }
+
+    fn set_data(&mut self, data: String) {
+        self.data = data;
+    }
+}
+
+/// `ProcessingInstruction` is a kind of `Node`, which is defined at [DOM Standard](https://dom.spec.whatwg.org/#interface-processinginstruction).
+/// The HTML parser never creates it; `<?xml ...>` in HTML is parsed as a comment.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProcessingInstruction {
+    pub target: String,
+    pub data: String,
+}
+impl ProcessingInstruction {
+    pub fn new(target: String, data: String) -> NodeTree {
+        NodeTree {
+            node_type: NodeType::ProcessingInstruction(ProcessingInstruction { target, data }),
+            children: vec![],
+        }
+    }
+}
+impl CharacterData for ProcessingInstruction {
+    fn data(&self) -> &str {
+        self.data.as_str()
+    }
+
+    fn set_data(&mut self, data: String) {
+        self.data = data;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_character_data() {
+        let mut text = Text {
+            data: "hello".to_string(),
+        };
+        assert_eq!(text.length(), 5);
+        text.append_data(" world");
+        assert_eq!(text.data(), "hello world");
+        assert_eq!(text.insert_data(5, ","), Ok(()));
+        assert_eq!(text.data(), "hello, world");
+        assert_eq!(text.delete_data(5, 100), Ok(()));
+        assert_eq!(text.data(), "hello");
+        assert_eq!(text.replace_data(0, 1, "J"), Ok(()));
+        assert_eq!(text.data(), "Jello");
+        assert_eq!(text.substring_data(1, 3), Ok("ell".to_string()));
+        assert_eq!(text.substring_data(5, 1), Ok("".to_string()));
+        assert_eq!(
+            text.substring_data(6, 1),
+            Err(DOMException::IndexSizeError(
+                "the offset is greater than the length"
+            ))
+        );
+        assert!(text.insert_data(6, "!").is_err());
+    }
+
+    #[test]
+    fn test_character_data_in_utf16() {
+        // U+1F600 is a surrogate pair in UTF-16.
+        let mut comment = Comment {
+            data: "a\u{3042}\u{1F600}b".to_string(),
+        };
+        assert_eq!(comment.length(), 5);
+        assert_eq!(comment.substring_data(2, 2), Ok("\u{1F600}".to_string()));
+        assert_eq!(comment.substring_data(3, 2), Ok("\u{FFFD}b".to_string()));
+        assert_eq!(comment.delete_data(1, 1), Ok(()));
+        assert_eq!(comment.data(), "a\u{1F600}b");
     }
 }
-impl CharacterData for Text {}
@@ -1,49 +1,298 @@
 //! This module defines some interfaces related to `Document` interface.
 
+use crate::core::css::{parse_selectors, CSSParseError, Selector};
 use crate::core::dom::NodeType;
+use crate::core::html::{
+    parse_fragment,
+    serializer::{serialize, serialize_children, SerializationOptions},
+};
+use crate::source::ParseDiagnostic;
+use chrono::{DateTime, FixedOffset};
+use std::error::Error;
 
-use super::Node;
+use super::{
+    is_valid_attribute_name, AttrMap, CharacterData, DOMException, DocumentReadyState, Element,
+    MutationObserver, MutationRecord, MutationRecordType, Node, NodeId, NodeTree, Text,
+};
 
 /// `Document` is a kind of `Node`. Here is a list of major WebIDL definition related to the interface:
 /// - https://dom.spec.whatwg.org/#interface-document
 /// - https://html.spec.whatwg.org/multipage/dom.html#the-document-object
 ///
-/// In the standard, `Document` interface inherits `Node` objects,
-/// indicating that it's one of the options to include the following `Document` struct in `super::NodeType`.
-/// However, if you do so, our implementation will be more complex. Thie is because the behaviour of `Document` and `Element` is much different!
-#[derive(Debug, PartialEq)]
+/// `Document` owns all the nodes in it, including nodes removed from the tree, and each node is identified by `NodeId`.
+/// The document itself is the root node whose `node_type` is `NodeType::Document`, and this struct holds the other properties of the document.
+#[derive(Debug)]
 pub struct Document {
     pub url: String,
     pub document_uri: String,
-    pub document_element: Box<Node>,
+    /// `parse_errors` is a list of errors the HTML parser recovered from while parsing this document.
+    pub parse_errors: Vec<ParseDiagnostic>,
+
+    /// `character_set` is the name of the encoding the document was decoded with.
+    /// https://dom.spec.whatwg.org/#concept-document-encoding
+    pub character_set: String,
+    /// `content_type` is the MIME type of the document, such as `text/html`.
+    /// https://dom.spec.whatwg.org/#concept-document-content-type
+    pub content_type: String,
+    /// `referrer` is the URL of the document which navigated to this document, or the empty string if there's no such document.
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-referrer
+    pub referrer: String,
+    /// `last_modified` is the value of `Last-Modified` header sent with the document, if any.
+    pub last_modified: Option<DateTime<FixedOffset>>,
+    pub ready_state: DocumentReadyState,
+
+    // NOTE: nodes are never freed until the document is dropped, so that a `NodeId` never points at another node.
+    // TODO (enhancement): free nodes which are neither in the tree nor referenced from scripts.
+    nodes: Vec<Node>,
+    pub(super) mutation_observers: Vec<MutationObserver>,
+}
+
+// NOTE: two documents are equal if they have the same URLs and trees; `parse_errors` and nodes out of the tree are ignored.
+impl PartialEq for Document {
+    fn eq(&self, other: &Self) -> bool {
+        self.url == other.url
+            && self.document_uri == other.document_uri
+            && self.to_tree(self.root()) == other.to_tree(other.root())
+    }
 }
 
 impl Document {
-    pub fn new(url: String, document_uri: String, document_element: Box<Node>) -> Document {
+    /// `new` creates a document which has no child.
+    pub fn new(url: String, document_uri: String) -> Document {
         Document {
-            url: url,
-            document_uri: document_uri,
-            document_element: document_element,
+            url,
+            document_uri,
+            parse_errors: vec![],
+
+            character_set: "UTF-8".to_string(),
+            content_type: "text/html".to_string(),
+            referrer: "".to_string(),
+            last_modified: None,
+            ready_state: DocumentReadyState::Complete,
+
+            nodes: vec![Node::new(NodeType::Document)],
+            mutation_observers: vec![],
         }
     }
 
-    pub fn collect_tag_inners(&self, tag_name: &str) -> Vec<String> {
-        fn intl(node: &Box<Node>, tag_name: &str) -> Vec<String> {
-            if let NodeType::Element(ref element) = node.node_type {
-                if element.tag_name.as_str() == tag_name {
-                    return vec![node.inner_text()];
-                }
+    /// `root` returns the ID of the document node itself.
+    pub fn root(&self) -> NodeId {
+        NodeId::from_index(0)
+    }
+
+    /// `node` returns the node with the given ID.
+    /// It panics if the ID is from another document; use `get` if the ID may be invalid.
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.index()]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.index()]
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.index())
+    }
+
+    /// `create_node` adds a new node to the document. The node is not inserted into the tree.
+    pub fn create_node(&mut self, node_type: NodeType) -> NodeId {
+        self.nodes.push(Node::new(node_type));
+        NodeId::from_index(self.nodes.len() - 1)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-document-documentelement
+    pub fn document_element(&self) -> Option<NodeId> {
+        self.children(self.root())
+            .find(|&id| matches!(self.node(id).node_type, NodeType::Element(_)))
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-document-doctype
+    pub fn doctype(&self) -> Option<NodeId> {
+        self.children(self.root())
+            .find(|&id| matches!(self.node(id).node_type, NodeType::DocumentType(_)))
+    }
+
+    /// `append` inserts `child` as the last child of `parent`.
+    pub fn append(&mut self, parent: NodeId, child: NodeId) {
+        self.insert(parent, child, None);
+    }
+
+    /// `insert` inserts `child` into `parent` just before `reference`, or as the last child if `reference` is `None`.
+    /// `child` is removed from its current parent first.
+    ///
+    /// NOTE: this doesn't validate the insertion; the caller must make sure the tree stays valid.
+    pub fn insert(&mut self, parent: NodeId, child: NodeId, reference: Option<NodeId>) {
+        self.detach(child);
+
+        let previous = match reference {
+            Some(reference) => self.node(reference).previous_sibling,
+            None => self.node(parent).last_child,
+        };
+        {
+            let child = self.node_mut(child);
+            child.parent = Some(parent);
+            child.previous_sibling = previous;
+            child.next_sibling = reference;
+        }
+        match previous {
+            Some(previous) => self.node_mut(previous).next_sibling = Some(child),
+            None => self.node_mut(parent).first_child = Some(child),
+        }
+        match reference {
+            Some(reference) => self.node_mut(reference).previous_sibling = Some(child),
+            None => self.node_mut(parent).last_child = Some(child),
+        }
+    }
+
+    /// `detach` removes the node from its parent. The node and its descendants are kept in the document.
+    pub fn detach(&mut self, id: NodeId) {
+        let parent = match self.node_mut(id).parent.take() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let previous = self.node_mut(id).previous_sibling.take();
+        let next = self.node_mut(id).next_sibling.take();
+        match previous {
+            Some(previous) => self.node_mut(previous).next_sibling = next,
+            None => self.node_mut(parent).first_child = next,
+        }
+        match next {
+            Some(next) => self.node_mut(next).previous_sibling = previous,
+            None => self.node_mut(parent).last_child = previous,
+        }
+    }
+
+    /// `append_tree` adds the nodes in `tree` to the document, and appends them to `parent`.
+    /// It returns the ID of the root of `tree`.
+    pub fn append_tree(&mut self, parent: NodeId, tree: NodeTree) -> NodeId {
+        self.insert_tree(parent, tree, None)
+    }
+
+    /// `insert_tree` adds the nodes in `tree` to the document, and inserts them into `parent` just before `reference`.
+    /// It returns the ID of the root of `tree`.
+    pub fn insert_tree(
+        &mut self,
+        parent: NodeId,
+        tree: NodeTree,
+        reference: Option<NodeId>,
+    ) -> NodeId {
+        let id = self.create_node(tree.node_type);
+        self.insert(parent, id, reference);
+        for child in tree.children {
+            self.append_tree(id, child);
+        }
+        id
+    }
+
+    /// `to_tree` returns a copy of the subtree rooted at `id`.
+    pub fn to_tree(&self, id: NodeId) -> NodeTree {
+        NodeTree {
+            node_type: self.node(id).node_type.clone(),
+            children: self.children(id).map(|child| self.to_tree(child)).collect(),
+        }
+    }
+
+    pub fn inner_text(&self, id: NodeId) -> String {
+        self.descendants(id)
+            .filter_map(|descendant| match self.node(descendant).node_type {
+                NodeType::Text(ref t) => Some(t.data.as_str()),
+                NodeType::CDATASection(ref c) => Some(c.data.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// `inner_html` returns the markup of the children of the node.
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-innerhtml
+    pub fn inner_html(&self, id: NodeId) -> String {
+        serialize_children(self, id, SerializationOptions::default())
+    }
+
+    /// `outer_html` returns the markup of the node itself.
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+    pub fn outer_html(&self, id: NodeId) -> String {
+        serialize(self, id, SerializationOptions::default())
+    }
+
+    /// `to_pretty_string` returns the markup of the node with each child on its own indented line.
+    pub fn to_pretty_string(&self, id: NodeId) -> String {
+        serialize(self, id, SerializationOptions { pretty: true })
+    }
+
+    /// `set_inner_html` replaces the children of the element with nodes parsed from `html` in the context of the element.
+    pub fn set_inner_html(&mut self, id: NodeId, html: String) -> Result<(), Box<dyn Error>> {
+        let trees = match self.node(id).node_type {
+            NodeType::Element(ref e) => parse_fragment(html.as_str(), e),
+            _ => return Err("innerHTML can be set only to elements".into()),
+        };
+        self.replace_all(id, trees);
+        Ok(())
+    }
+
+    /// `set_outer_html` replaces the node with nodes parsed from `html` in the context of its parent.
+    /// Nothing happens if the node has no parent, and the node can't be replaced if the parent is the document.
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+    pub fn set_outer_html(&mut self, id: NodeId, html: String) -> Result<(), DOMException> {
+        let parent = match self.node(id).parent {
+            Some(parent) => parent,
+            None => return Ok(()),
+        };
+        let trees = match self.node(parent).node_type {
+            NodeType::Document => {
+                return Err(DOMException::NoModificationAllowedError(
+                    "the node is a child of the document",
+                ))
+            }
+            NodeType::Element(ref e) => parse_fragment(html.as_str(), e),
+            // NOTE: the parent is a document fragment here, which is regarded as `body` element.
+            _ => {
+                let body = Element {
+                    tag_name: "body".to_string(),
+                    attributes: AttrMap::new(),
+                };
+                parse_fragment(html.as_str(), &body)
             }
+        };
 
-            node.children
-                .iter()
-                .map(|child| intl(child, tag_name))
-                .collect::<Vec<Vec<String>>>()
-                .into_iter()
-                .flatten()
-                .collect()
+        let previous_sibling = self.node(id).previous_sibling;
+        let next_sibling = self.node(id).next_sibling;
+        self.detach(id);
+        let added_nodes = trees
+            .into_iter()
+            .map(|tree| self.insert_tree(parent, tree, next_sibling))
+            .collect();
+        self.queue_tree_mutation_record(
+            parent,
+            added_nodes,
+            vec![id],
+            previous_sibling,
+            next_sibling,
+        );
+        Ok(())
+    }
+
+    /// `replace_all` replaces the children of the node with the nodes in `trees`, and queues a mutation record.
+    /// https://dom.spec.whatwg.org/#concept-node-replace-all
+    pub(super) fn replace_all(&mut self, id: NodeId, trees: Vec<NodeTree>) {
+        let removed_nodes = self.children(id).collect::<Vec<_>>();
+        for &child in &removed_nodes {
+            self.detach(child);
         }
-        intl(&self.document_element, tag_name)
+        let added_nodes = trees
+            .into_iter()
+            .map(|tree| self.append_tree(id, tree))
+            .collect();
+        self.queue_tree_mutation_record(id, added_nodes, removed_nodes, None, None);
+    }
+
+    pub fn collect_tag_inners(&self, tag_name: &str) -> Vec<String> {
+        self.descendants(self.root())
+            .filter(|&id| match self.node(id).node_type {
+                NodeType::Element(ref element) => element.tag_name.as_str() == tag_name,
+                _ => false,
+            })
+            .map(|id| self.inner_text(id))
+            .collect()
     }
 
     pub fn get_script_inners(&self) -> Vec<String> {
@@ -55,24 +304,1098 @@ impl Document {
     }
 }
 
+/// Implementations for mutation algorithms defined at https://dom.spec.whatwg.org/#mutation-algorithms.
+/// Unlike `insert` and `detach`, they validate the operation and never break the tree.
+impl Document {
+    /// `append_child` inserts `node` as the last child of `parent`, and returns `node`.
+    /// https://dom.spec.whatwg.org/#dom-node-appendchild
+    pub fn append_child(&mut self, parent: NodeId, node: NodeId) -> Result<NodeId, DOMException> {
+        self.pre_insert(parent, node, None)
+    }
+
+    /// `insert_before` inserts `node` into `parent` just before `child`, or as the last child if `child` is `None`, and returns `node`.
+    /// https://dom.spec.whatwg.org/#dom-node-insertbefore
+    pub fn insert_before(
+        &mut self,
+        parent: NodeId,
+        node: NodeId,
+        child: Option<NodeId>,
+    ) -> Result<NodeId, DOMException> {
+        self.pre_insert(parent, node, child)
+    }
+
+    /// `remove_child` removes `child` from `parent`, and returns `child`.
+    /// https://dom.spec.whatwg.org/#dom-node-removechild
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId) -> Result<NodeId, DOMException> {
+        if self.node(child).parent != Some(parent) {
+            return Err(DOMException::NotFoundError(
+                "the node to be removed is not a child of this node",
+            ));
+        }
+        self.remove_node(child);
+        Ok(child)
+    }
+
+    /// `replace_child` replaces `child` of `parent` with `node`, and returns `child`.
+    /// https://dom.spec.whatwg.org/#concept-node-replace
+    pub fn replace_child(
+        &mut self,
+        parent: NodeId,
+        node: NodeId,
+        child: NodeId,
+    ) -> Result<NodeId, DOMException> {
+        self.ensure_validity(parent, node, Some(child), true)?;
+
+        let mut reference = self.node(child).next_sibling;
+        if reference == Some(node) {
+            reference = self.node(node).next_sibling;
+        }
+        let previous_sibling = self.node(child).previous_sibling;
+        self.detach(child);
+        let added_nodes = self.insert_node(parent, node, reference);
+        // NOTE: `node` may have been the previous sibling, which is removed from the parent by `insert_node`.
+        let previous_sibling = match previous_sibling {
+            Some(previous) if previous == node => self.node(node).previous_sibling,
+            previous => previous,
+        };
+        self.queue_tree_mutation_record(
+            parent,
+            added_nodes,
+            vec![child],
+            previous_sibling,
+            reference,
+        );
+        Ok(child)
+    }
+
+    /// `clone_node` returns a copy of the node, which is not inserted into the tree.
+    /// The descendants are copied as well if `deep` is true.
+    /// https://dom.spec.whatwg.org/#concept-node-clone
+    pub fn clone_node(&mut self, id: NodeId, deep: bool) -> Result<NodeId, DOMException> {
+        // NOTE: a clone of the document would be another document, which puppy can't create from here.
+        if let NodeType::Document = self.node(id).node_type {
+            return Err(DOMException::NotSupportedError(
+                "documents can not be cloned",
+            ));
+        }
+
+        let clone = self.create_node(self.node(id).node_type.clone());
+        if deep {
+            for child in self.children(id).collect::<Vec<_>>() {
+                let child = self.clone_node(child, true)?;
+                self.append(clone, child);
+            }
+        }
+        Ok(clone)
+    }
+
+    /// `split_text` splits the text node at `offset` in UTF-16 code units, and returns a new text node with the latter part.
+    /// The new node is inserted just after the node if the node has a parent.
+    /// https://dom.spec.whatwg.org/#concept-text-split
+    pub fn split_text(&mut self, id: NodeId, offset: usize) -> Result<NodeId, DOMException> {
+        let node_type = &self.node(id).node_type;
+        if !node_type.is_text() {
+            return Err(DOMException::NotSupportedError(
+                "only text nodes can be split",
+            ));
+        }
+        let new_data = node_type
+            .character_data()
+            .unwrap()
+            .substring_data(offset, usize::MAX)?;
+        self.modify_character_data(id, |data| data.delete_data(offset, usize::MAX))?;
+
+        let new_node = self.create_node(NodeType::Text(Text { data: new_data }));
+        if let Some(parent) = self.node(id).parent {
+            let reference = self.node(id).next_sibling;
+            let added_nodes = self.insert_node(parent, new_node, reference);
+            self.queue_tree_mutation_record(parent, added_nodes, vec![], Some(id), reference);
+        }
+        Ok(new_node)
+    }
+
+    /// `normalize` removes empty text nodes, and concatenates adjacent text nodes into the first one, in the descendants of the node.
+    /// https://dom.spec.whatwg.org/#dom-node-normalize
+    pub fn normalize(&mut self, id: NodeId) {
+        let mut next = self.node(id).first_child;
+        while let Some(current) = next {
+            next = self.node(current).next_sibling;
+            let mut data = match self.node(current).node_type {
+                NodeType::Text(ref t) => t.data.clone(),
+                _ => {
+                    self.normalize(current);
+                    continue;
+                }
+            };
+
+            let length = data.len();
+            while let Some(sibling) = next {
+                match self.node(sibling).node_type {
+                    NodeType::Text(ref t) => data.push_str(t.data.as_str()),
+                    _ => break,
+                }
+                next = self.node(sibling).next_sibling;
+                self.remove_node(sibling);
+            }
+
+            if data.is_empty() {
+                self.remove_node(current);
+            } else if data.len() != length {
+                self.modify_character_data(current, |c| {
+                    c.set_data(data);
+                    Ok(())
+                })
+                .unwrap();
+            }
+        }
+    }
+
+    /// `is_inclusive_ancestor` returns true if `ancestor` is `id` itself or one of its ancestors.
+    /// https://dom.spec.whatwg.org/#concept-tree-inclusive-ancestor
+    pub fn is_inclusive_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        self.inclusive_ancestors(id).any(|node| node == ancestor)
+    }
+
+    /// `get_attribute` returns the value of the attribute of the element.
+    /// It returns `None` if the node is not an element or doesn't have the attribute.
+    /// https://dom.spec.whatwg.org/#dom-element-getattribute
+    pub fn get_attribute(&self, id: NodeId, name: &str) -> Option<String> {
+        match self.node(id).node_type {
+            NodeType::Element(ref e) => e.attributes.get(name).cloned(),
+            _ => None,
+        }
+    }
+
+    /// `set_attribute` sets the attribute of the element, and queues a mutation record.
+    /// https://dom.spec.whatwg.org/#concept-element-attributes-change
+    pub fn set_attribute(
+        &mut self,
+        id: NodeId,
+        name: &str,
+        value: String,
+    ) -> Result<(), DOMException> {
+        if !is_valid_attribute_name(name) {
+            return Err(DOMException::InvalidCharacterError(
+                "the name is not a valid attribute name",
+            ));
+        }
+        let old_value = match self.node_mut(id).node_type {
+            NodeType::Element(ref mut e) => e.attributes.insert(name.to_string(), value),
+            _ => {
+                return Err(DOMException::NotSupportedError(
+                    "only elements can have attributes",
+                ))
+            }
+        };
+        self.queue_attribute_mutation_record(id, name, old_value);
+        Ok(())
+    }
+
+    /// `remove_attribute` removes the attribute of the element if it exists, and queues a mutation record.
+    /// https://dom.spec.whatwg.org/#concept-element-attributes-remove
+    pub fn remove_attribute(&mut self, id: NodeId, name: &str) -> Result<(), DOMException> {
+        let old_value = match self.node_mut(id).node_type {
+            NodeType::Element(ref mut e) => e.attributes.remove(name),
+            _ => {
+                return Err(DOMException::NotSupportedError(
+                    "only elements can have attributes",
+                ))
+            }
+        };
+        if old_value.is_some() {
+            self.queue_attribute_mutation_record(id, name, old_value);
+        }
+        Ok(())
+    }
+
+    /// `toggle_attribute` removes the attribute of the element if it exists, or adds it with the empty value otherwise.
+    /// `force` makes the attribute exist if it's `Some(true)`, and makes it absent if it's `Some(false)`.
+    /// It returns whether the attribute exists after toggling.
+    /// https://dom.spec.whatwg.org/#dom-element-toggleattribute
+    pub fn toggle_attribute(
+        &mut self,
+        id: NodeId,
+        name: &str,
+        force: Option<bool>,
+    ) -> Result<bool, DOMException> {
+        if !is_valid_attribute_name(name) {
+            return Err(DOMException::InvalidCharacterError(
+                "the name is not a valid attribute name",
+            ));
+        }
+        let exists = self.get_attribute(id, name).is_some();
+        match (exists, force) {
+            (false, None) | (false, Some(true)) => {
+                self.set_attribute(id, name, "".to_string())?;
+                Ok(true)
+            }
+            (true, None) | (true, Some(false)) => {
+                self.remove_attribute(id, name)?;
+                Ok(false)
+            }
+            (exists, Some(_)) => Ok(exists),
+        }
+    }
+
+    fn queue_attribute_mutation_record(
+        &mut self,
+        id: NodeId,
+        name: &str,
+        old_value: Option<String>,
+    ) {
+        self.queue_mutation_record(
+            MutationRecord {
+                record_type: MutationRecordType::Attributes,
+                target: id,
+                added_nodes: vec![],
+                removed_nodes: vec![],
+                previous_sibling: None,
+                next_sibling: None,
+                attribute_name: Some(name.to_string()),
+                old_value: None,
+            },
+            old_value,
+        );
+    }
+
+    /// `modify_character_data` applies `f` to the data of the node, and queues a mutation record if `f` succeeds.
+    /// It returns `NotSupportedError` if the node is not `CharacterData`.
+    /// https://dom.spec.whatwg.org/#concept-cd-replace
+    pub fn modify_character_data<F>(&mut self, id: NodeId, f: F) -> Result<(), DOMException>
+    where
+        F: FnOnce(&mut dyn CharacterData) -> Result<(), DOMException>,
+    {
+        let data = match self.node_mut(id).node_type.character_data_mut() {
+            Some(data) => data,
+            None => {
+                return Err(DOMException::NotSupportedError(
+                    "the node has no character data",
+                ))
+            }
+        };
+        let old_value = data.data().to_string();
+        f(data)?;
+        self.queue_mutation_record(
+            MutationRecord {
+                record_type: MutationRecordType::CharacterData,
+                target: id,
+                added_nodes: vec![],
+                removed_nodes: vec![],
+                previous_sibling: None,
+                next_sibling: None,
+                attribute_name: None,
+                old_value: None,
+            },
+            Some(old_value),
+        );
+        Ok(())
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-node-pre-insert
+    fn pre_insert(
+        &mut self,
+        parent: NodeId,
+        node: NodeId,
+        child: Option<NodeId>,
+    ) -> Result<NodeId, DOMException> {
+        self.ensure_validity(parent, node, child, false)?;
+
+        let reference = if child == Some(node) {
+            self.node(node).next_sibling
+        } else {
+            child
+        };
+        let previous_sibling = match reference {
+            Some(reference) => self.node(reference).previous_sibling,
+            None => self.node(parent).last_child,
+        };
+        let added_nodes = self.insert_node(parent, node, reference);
+        // NOTE: `node` may have been the previous sibling, which is removed from the parent by `insert_node`.
+        let previous_sibling = match previous_sibling {
+            Some(previous) if previous == node => self.node(node).previous_sibling,
+            previous => previous,
+        };
+        self.queue_tree_mutation_record(parent, added_nodes, vec![], previous_sibling, reference);
+        Ok(node)
+    }
+
+    /// `insert_node` inserts `node` into `parent` before `reference`, and returns the inserted nodes.
+    /// If `node` is a `DocumentFragment`, its children are moved instead, and the fragment becomes empty.
+    /// If `node` has a parent, it's removed from the parent first.
+    /// The caller must queue a mutation record for `parent`.
+    /// https://dom.spec.whatwg.org/#concept-node-insert
+    fn insert_node(
+        &mut self,
+        parent: NodeId,
+        node: NodeId,
+        reference: Option<NodeId>,
+    ) -> Vec<NodeId> {
+        if let NodeType::DocumentFragment = self.node(node).node_type {
+            let children = self.children(node).collect::<Vec<_>>();
+            for &child in &children {
+                self.insert(parent, child, reference);
+            }
+            self.queue_tree_mutation_record(node, vec![], children.clone(), None, None);
+            children
+        } else {
+            if self.node(node).parent.is_some() {
+                self.remove_node(node);
+            }
+            self.insert(parent, node, reference);
+            vec![node]
+        }
+    }
+
+    /// `remove_node` removes the node from its parent, and queues a mutation record.
+    /// https://dom.spec.whatwg.org/#concept-node-remove
+    fn remove_node(&mut self, id: NodeId) {
+        let node = self.node(id);
+        let (parent, previous_sibling, next_sibling) =
+            match (node.parent, node.previous_sibling, node.next_sibling) {
+                (Some(parent), previous, next) => (parent, previous, next),
+                _ => return,
+            };
+        self.detach(id);
+        self.queue_tree_mutation_record(parent, vec![], vec![id], previous_sibling, next_sibling);
+    }
+
+    /// `ensure_validity` checks if `node` can be inserted into `parent` before `child`.
+    /// If `replacing` is true, it checks if `child` can be replaced with `node` instead.
+    /// https://dom.spec.whatwg.org/#concept-node-ensure-pre-insertion-validity
+    fn ensure_validity(
+        &self,
+        parent: NodeId,
+        node: NodeId,
+        child: Option<NodeId>,
+        replacing: bool,
+    ) -> Result<(), DOMException> {
+        let parent_is_document = match self.node(parent).node_type {
+            NodeType::Document => true,
+            NodeType::DocumentFragment | NodeType::Element(_) => false,
+            _ => {
+                return Err(DOMException::HierarchyRequestError(
+                    "only documents, document fragments and elements can have children",
+                ))
+            }
+        };
+        if self.is_inclusive_ancestor(node, parent) {
+            return Err(DOMException::HierarchyRequestError(
+                "the new child contains the parent",
+            ));
+        }
+        if let Some(child) = child {
+            if self.node(child).parent != Some(parent) {
+                return Err(DOMException::NotFoundError(
+                    "the reference node is not a child of this node",
+                ));
+            }
+        }
+        match self.node(node).node_type {
+            NodeType::Document => {
+                return Err(DOMException::HierarchyRequestError(
+                    "documents can not be inserted",
+                ))
+            }
+            NodeType::Text(_) | NodeType::CDATASection(_) if parent_is_document => {
+                return Err(DOMException::HierarchyRequestError(
+                    "text can not be inserted into documents",
+                ))
+            }
+            NodeType::DocumentType(_) if !parent_is_document => {
+                return Err(DOMException::HierarchyRequestError(
+                    "doctypes can be inserted only into documents",
+                ))
+            }
+            _ => {}
+        }
+        if !parent_is_document {
+            return Ok(());
+        }
+
+        // a document can have only one element and one doctype, and the doctype must come first.
+        let is_element = |id: &NodeId| matches!(self.node(*id).node_type, NodeType::Element(_));
+        let is_doctype =
+            |id: &NodeId| matches!(self.node(*id).node_type, NodeType::DocumentType(_));
+        // when `child` is replaced, it doesn't conflict with `node`.
+        let mut others = self
+            .children(parent)
+            .filter(|&id| !(replacing && Some(id) == child));
+        let inserts_element = match self.node(node).node_type {
+            NodeType::Element(_) => true,
+            NodeType::DocumentFragment => {
+                let elements = self.children(node).filter(is_element).count();
+                if elements > 1
+                    || self
+                        .children(node)
+                        .any(|c| self.node(c).node_type.is_text())
+                {
+                    return Err(DOMException::HierarchyRequestError(
+                        "a document can have neither more than one element nor text",
+                    ));
+                }
+                elements == 1
+            }
+            _ => false,
+        };
+        let invalid = if inserts_element {
+            others.any(|id| is_element(&id))
+                || (!replacing && child.as_ref().map(is_doctype).unwrap_or(false))
+                || child
+                    .map(|c| self.following_siblings(c).any(|id| is_doctype(&id)))
+                    .unwrap_or(false)
+        } else if let NodeType::DocumentType(_) = self.node(node).node_type {
+            let element_precedes = match child {
+                Some(c) => self.preceding_siblings(c).any(|id| is_element(&id)),
+                None => self.children(parent).any(|id| is_element(&id)),
+            };
+            others.any(|id| is_doctype(&id)) || element_precedes
+        } else {
+            false
+        };
+        if invalid {
+            return Err(DOMException::HierarchyRequestError(
+                "a document can have only one doctype followed by one element",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Implementations for finding elements, defined at https://dom.spec.whatwg.org/#interface-parentnode and so on.
+/// `selectors` are parsed with `css::parse_selectors`, and an invalid one is reported as `CSSParseError`.
+impl Document {
+    /// `query_selector` returns the first descendant element of the node which matches `selectors`.
+    /// https://dom.spec.whatwg.org/#dom-parentnode-queryselector
+    pub fn query_selector(
+        &self,
+        id: NodeId,
+        selectors: &str,
+    ) -> Result<Option<NodeId>, CSSParseError> {
+        let selectors = parse_selectors(selectors)?;
+        Ok(self
+            .descendants(id)
+            .find(|&descendant| self.matches_any(descendant, &selectors)))
+    }
+
+    /// `query_selector_all` returns all the descendant elements of the node which match `selectors`.
+    /// https://dom.spec.whatwg.org/#dom-parentnode-queryselectorall
+    pub fn query_selector_all(
+        &self,
+        id: NodeId,
+        selectors: &str,
+    ) -> Result<Vec<NodeId>, CSSParseError> {
+        let selectors = parse_selectors(selectors)?;
+        Ok(self
+            .descendants(id)
+            .filter(|&descendant| self.matches_any(descendant, &selectors))
+            .collect())
+    }
+
+    /// `matches` returns true if the node is an element which matches `selectors`.
+    /// https://dom.spec.whatwg.org/#dom-element-matches
+    pub fn matches(&self, id: NodeId, selectors: &str) -> Result<bool, CSSParseError> {
+        let selectors = parse_selectors(selectors)?;
+        Ok(self.matches_any(id, &selectors))
+    }
+
+    /// `closest` returns the nearest inclusive ancestor element of the node which matches `selectors`.
+    /// https://dom.spec.whatwg.org/#dom-element-closest
+    pub fn closest(&self, id: NodeId, selectors: &str) -> Result<Option<NodeId>, CSSParseError> {
+        let selectors = parse_selectors(selectors)?;
+        Ok(self
+            .inclusive_ancestors(id)
+            .find(|&node| self.matches_any(node, &selectors)))
+    }
+
+    /// `get_elements_by_tag_name` returns the descendant elements of the node whose tag name is `qualified_name`.
+    /// `*` matches any element.
+    /// https://dom.spec.whatwg.org/#concept-getelementsbytagname
+    pub fn get_elements_by_tag_name(&self, id: NodeId, qualified_name: &str) -> Vec<NodeId> {
+        // NOTE: puppy handles HTML documents only, whose elements have lowercase names.
+        let qualified_name = qualified_name.to_ascii_lowercase();
+        self.descendants(id)
+            .filter(|&descendant| match self.node(descendant).node_type {
+                NodeType::Element(ref e) => qualified_name == "*" || e.tag_name == qualified_name,
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// `get_elements_by_class_name` returns the descendant elements of the node which have all the classes in `class_names`.
+    /// `class_names` is a list of classes separated by whitespaces.
+    /// https://dom.spec.whatwg.org/#concept-getelementsbyclassname
+    pub fn get_elements_by_class_name(&self, id: NodeId, class_names: &str) -> Vec<NodeId> {
+        let class_names = class_names.split_ascii_whitespace().collect::<Vec<_>>();
+        if class_names.is_empty() {
+            return vec![];
+        }
+        self.descendants(id)
+            .filter(|&descendant| match self.node(descendant).node_type {
+                NodeType::Element(ref e) => {
+                    let classes = e
+                        .attributes
+                        .get("class")
+                        .map(|classes| classes.split_ascii_whitespace().collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    class_names.iter().all(|name| classes.contains(name))
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
+    fn matches_any(&self, id: NodeId, selectors: &[Selector]) -> bool {
+        let node = self.node(id);
+        matches!(node.node_type, NodeType::Element(_))
+            && selectors.iter().any(|selector| selector.matches(self, id))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::dom::{AttrMap, Document, Element};
+    use crate::dom::{
+        AttrMap, Comment, DOMException, Document, DocumentType, Element, NodeId, NodeType, Text,
+    };
+
+    fn document() -> Document {
+        let url = "http://example.com";
+        Document::new(url.to_string(), url.to_string())
+    }
 
     #[test]
     fn test_valid_new() {
         let url = "http://example.com";
-        let document = Document::new(
-            url.to_string(),
-            url.to_string(),
-            Element::new("p".to_string(), AttrMap::new(), vec![]),
-        );
-
+        let mut document = Document::new(url.to_string(), url.to_string());
         assert_eq!(document.url, url.to_string());
         assert_eq!(document.document_uri, url.to_string());
+        assert_eq!(document.document_element(), None);
+
+        let root = document.root();
+        document.append_tree(root, Comment::new("".to_string()));
+        let p = document.append_tree(root, Element::new("p".to_string(), AttrMap::new(), vec![]));
+        assert_eq!(document.document_element(), Some(p));
         assert_eq!(
-            document.document_element,
+            document.to_tree(p),
             Element::new("p".to_string(), AttrMap::new(), vec![])
         );
     }
+
+    #[test]
+    fn test_links() {
+        let mut document = document();
+        let root = document.root();
+        let ul = document.append_tree(root, Element::new("ul".to_string(), AttrMap::new(), vec![]));
+        let a = document.create_node(NodeType::Text(Text {
+            data: "a".to_string(),
+        }));
+        let b = document.create_node(NodeType::Text(Text {
+            data: "b".to_string(),
+        }));
+        let c = document.create_node(NodeType::Text(Text {
+            data: "c".to_string(),
+        }));
+
+        document.append(ul, c);
+        document.insert(ul, a, Some(c));
+        document.insert(ul, b, Some(c));
+        assert_eq!(document.children(ul).collect::<Vec<_>>(), vec![a, b, c]);
+        assert_eq!(document.node(b).parent(), Some(ul));
+        assert_eq!(document.node(b).previous_sibling(), Some(a));
+        assert_eq!(document.node(b).next_sibling(), Some(c));
+        assert_eq!(document.node(ul).first_child(), Some(a));
+        assert_eq!(document.node(ul).last_child(), Some(c));
+
+        // moving a node detaches it from the current position.
+        document.append(ul, a);
+        assert_eq!(document.children(ul).collect::<Vec<_>>(), vec![b, c, a]);
+        assert_eq!(document.node(b).previous_sibling(), None);
+
+        // detached nodes are still available.
+        document.detach(c);
+        assert_eq!(document.children(ul).collect::<Vec<_>>(), vec![b, a]);
+        assert_eq!(document.node(b).next_sibling(), Some(a));
+        assert_eq!(document.node(c).parent(), None);
+        assert_eq!(document.inner_text(c), "");
+        assert_eq!(document.inner_text(ul), "ba");
+    }
+
+    #[test]
+    fn test_inner_text() {
+        let mut document = document();
+        let root = document.root();
+        let div = document.append_tree(
+            root,
+            Element::new(
+                "div".to_string(),
+                AttrMap::new(),
+                vec![
+                    Text::new("hello world".to_string()),
+                    Element::new(
+                        "p".to_string(),
+                        AttrMap::new(),
+                        vec![
+                            Element::new(
+                                "p".to_string(),
+                                AttrMap::new(),
+                                vec![Text::new("1".to_string())],
+                            ),
+                            Element::new("p".to_string(), AttrMap::new(), vec![]),
+                            Element::new(
+                                "p".to_string(),
+                                AttrMap::new(),
+                                vec![Text::new("3".to_string())],
+                            ),
+                        ],
+                    ),
+                ],
+            ),
+        );
+        assert_eq!(document.inner_text(div), "hello world13".to_string());
+    }
+
+    #[test]
+    fn test_set_inner_html() {
+        let mut document = document();
+        let root = document.root();
+        let tr = document.append_tree(root, Element::new("tr".to_string(), AttrMap::new(), vec![]));
+        let text = document.append_tree(tr, Text::new("text".to_string()));
+        assert!(document
+            .set_inner_html(tr, "<td>1</td><td>2</td>".to_string())
+            .is_ok());
+        assert_eq!(document.inner_html(tr), "<td>1</td><td>2</td>".to_string());
+        assert_eq!(document.node(text).parent(), None);
+
+        assert!(document
+            .set_inner_html(text, "<p>1</p>".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn test_set_outer_html() {
+        let mut document = document();
+        let root = document.root();
+        let tr = document.append_tree(
+            root,
+            Element::new(
+                "tr".to_string(),
+                AttrMap::new(),
+                vec![
+                    Element::new("th".to_string(), AttrMap::new(), vec![]),
+                    Element::new("td".to_string(), AttrMap::new(), vec![]),
+                    Element::new("td".to_string(), AttrMap::new(), vec![]),
+                ],
+            ),
+        );
+        let td = document.children(tr).nth(1).unwrap();
+        assert_eq!(
+            document.set_outer_html(td, "<td>1</td><td>2</td>".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            document.inner_html(tr),
+            "<th></th><td>1</td><td>2</td><td></td>".to_string()
+        );
+        assert_eq!(document.node(td).parent(), None);
+
+        // a detached node is left as is, while a child of the document can't be replaced
+        assert_eq!(document.set_outer_html(td, "<p></p>".to_string()), Ok(()));
+        assert_eq!(
+            document.set_outer_html(tr, "<p></p>".to_string()),
+            Err(DOMException::NoModificationAllowedError(
+                "the node is a child of the document"
+            ))
+        );
+        assert_eq!(document.children(root).collect::<Vec<_>>(), vec![tr]);
+    }
+
+    #[test]
+    fn test_outer_html() {
+        let mut document = document();
+        let root = document.root();
+        let ul = document.append_tree(
+            root,
+            Element::new(
+                "ul".to_string(),
+                AttrMap::new(),
+                vec![
+                    Element::new(
+                        "li".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new("1".to_string())],
+                    ),
+                    Element::new("li".to_string(), AttrMap::new(), vec![]),
+                ],
+            ),
+        );
+        assert_eq!(document.outer_html(ul), "<ul><li>1</li><li></li></ul>");
+        assert_eq!(
+            document.to_pretty_string(ul),
+            "<ul>\n  <li>1</li>\n  <li></li>\n</ul>"
+        );
+    }
+
+    fn text(document: &mut Document, data: &str) -> NodeId {
+        document.create_node(NodeType::Text(Text {
+            data: data.to_string(),
+        }))
+    }
+
+    #[test]
+    fn test_insert_child() {
+        let mut document = document();
+        let root = document.root();
+        let html = document.append_tree(
+            root,
+            Element::new("html".to_string(), AttrMap::new(), vec![]),
+        );
+        let a = text(&mut document, "a");
+        let b = text(&mut document, "b");
+
+        assert_eq!(document.append_child(html, b), Ok(b));
+        assert_eq!(document.insert_before(html, a, Some(b)), Ok(a));
+        assert_eq!(document.children(html).collect::<Vec<_>>(), vec![a, b]);
+        // inserting a node before itself keeps it in place.
+        assert_eq!(document.insert_before(html, b, Some(b)), Ok(b));
+        assert_eq!(document.children(html).collect::<Vec<_>>(), vec![a, b]);
+
+        assert_eq!(
+            document.append_child(a, b),
+            Err(DOMException::HierarchyRequestError(
+                "only documents, document fragments and elements can have children"
+            ))
+        );
+        assert_eq!(
+            document.append_child(html, html),
+            Err(DOMException::HierarchyRequestError(
+                "the new child contains the parent"
+            ))
+        );
+        assert_eq!(
+            document.append_child(html, root),
+            Err(DOMException::HierarchyRequestError(
+                "the new child contains the parent"
+            ))
+        );
+        let c = text(&mut document, "c");
+        assert_eq!(
+            document.insert_before(html, c, Some(c)),
+            Err(DOMException::NotFoundError(
+                "the reference node is not a child of this node"
+            ))
+        );
+        assert!(document.append_child(root, c).is_err());
+    }
+
+    #[test]
+    fn test_insert_child_into_document() {
+        let mut document = document();
+        let root = document.root();
+        let html = document.create_node(NodeType::Element(Element {
+            tag_name: "html".to_string(),
+            attributes: AttrMap::new(),
+        }));
+        let doctype = document.create_node(NodeType::DocumentType(DocumentType {
+            name: "html".to_string(),
+            public_id: "".to_string(),
+            system_id: "".to_string(),
+        }));
+        let comment = document.append_tree(root, Comment::new("".to_string()));
+
+        assert!(document.append_child(root, html).is_ok());
+        // a doctype must come before the element.
+        assert!(document.append_child(root, doctype).is_err());
+        assert!(document.insert_before(root, doctype, Some(html)).is_ok());
+        assert_eq!(
+            document.children(root).collect::<Vec<_>>(),
+            vec![comment, doctype, html]
+        );
+
+        let body = document.create_node(NodeType::Element(Element {
+            tag_name: "body".to_string(),
+            attributes: AttrMap::new(),
+        }));
+        assert!(document.append_child(root, body).is_err());
+        assert!(document.insert_before(root, body, Some(comment)).is_err());
+        // the element to be replaced doesn't count.
+        assert_eq!(document.replace_child(root, body, html), Ok(html));
+        assert_eq!(document.document_element(), Some(body));
+        assert!(document.replace_child(root, body, doctype).is_err());
+    }
+
+    #[test]
+    fn test_insert_document_fragment() {
+        let mut document = document();
+        let root = document.root();
+        let p = document.append_tree(
+            root,
+            Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![Text::new("c".to_string())],
+            ),
+        );
+        let fragment = document.create_node(NodeType::DocumentFragment);
+        let a = text(&mut document, "a");
+        let b = text(&mut document, "b");
+        assert!(document.append_child(fragment, a).is_ok());
+        assert!(document.append_child(fragment, b).is_ok());
+
+        // the children of the fragment are moved instead of the fragment itself.
+        let c = document.node(p).first_child();
+        assert_eq!(document.insert_before(p, fragment, c), Ok(fragment));
+        assert_eq!(document.inner_text(p), "abc");
+        assert_eq!(document.node(fragment).first_child(), None);
+        assert_eq!(document.node(a).parent(), Some(p));
+
+        // a fragment inserted into a document must have at most one element and no text.
+        let mut document = self::document();
+        let root = document.root();
+        let fragment = document.create_node(NodeType::DocumentFragment);
+        let t = text(&mut document, "t");
+        document.append(fragment, t);
+        assert!(document.append_child(root, fragment).is_err());
+        document.detach(t);
+        let html = document.append_tree(
+            fragment,
+            Element::new("html".to_string(), AttrMap::new(), vec![]),
+        );
+        assert!(document.append_child(root, fragment).is_ok());
+        assert_eq!(document.document_element(), Some(html));
+    }
+
+    #[test]
+    fn test_split_text() {
+        let mut document = document();
+        let root = document.root();
+        let p = document.append_tree(
+            root,
+            Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![
+                    Text::new("hello world".to_string()),
+                    Comment::new("".to_string()),
+                ],
+            ),
+        );
+        let hello = document.node(p).first_child().unwrap();
+        let world = document.split_text(hello, 6).unwrap();
+        assert_eq!(
+            document.node(hello).node_type,
+            NodeType::Text(Text {
+                data: "hello ".to_string()
+            })
+        );
+        assert_eq!(document.outer_html(p), "<p>hello world<!----></p>");
+        assert_eq!(document.node(hello).next_sibling(), Some(world));
+        assert_eq!(
+            document.node(world).node_type,
+            NodeType::Text(Text {
+                data: "world".to_string()
+            })
+        );
+        assert!(document.split_text(world, 6).is_err());
+        let comment = document.node(p).last_child().unwrap();
+        assert!(document.split_text(comment, 0).is_err());
+    }
+
+    #[test]
+    fn test_remove_and_replace_child() {
+        let mut document = document();
+        let root = document.root();
+        let p = document.append_tree(root, Element::new("p".to_string(), AttrMap::new(), vec![]));
+        let a = text(&mut document, "a");
+        let b = text(&mut document, "b");
+        let c = text(&mut document, "c");
+        document.append(p, a);
+        document.append(p, b);
+
+        assert_eq!(document.replace_child(p, c, a), Ok(a));
+        assert_eq!(document.children(p).collect::<Vec<_>>(), vec![c, b]);
+        assert_eq!(document.node(a).parent(), None);
+        // replacing a node with its next sibling moves the sibling.
+        assert_eq!(document.replace_child(p, b, c), Ok(c));
+        assert_eq!(document.children(p).collect::<Vec<_>>(), vec![b]);
+
+        assert_eq!(document.remove_child(p, b), Ok(b));
+        assert_eq!(document.node(p).first_child(), None);
+        assert_eq!(
+            document.remove_child(p, b),
+            Err(DOMException::NotFoundError(
+                "the node to be removed is not a child of this node"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_clone_node() {
+        let mut document = document();
+        let root = document.root();
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "x".to_string());
+        let tree = Element::new(
+            "ul".to_string(),
+            attributes,
+            vec![Element::new(
+                "li".to_string(),
+                AttrMap::new(),
+                vec![Text::new("1".to_string())],
+            )],
+        );
+        let ul = document.append_tree(root, tree);
+
+        let deep = document.clone_node(ul, true).unwrap();
+        assert_eq!(document.node(deep).parent(), None);
+        assert_eq!(document.to_tree(deep), document.to_tree(ul));
+        let shallow = document.clone_node(ul, false).unwrap();
+        assert_eq!(document.outer_html(shallow), "<ul id=\"x\"></ul>");
+        assert!(document.clone_node(root, true).is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut document = document();
+        let root = document.root();
+        let div = document.append_tree(
+            root,
+            Element::new(
+                "div".to_string(),
+                AttrMap::new(),
+                vec![
+                    Text::new("".to_string()),
+                    Text::new("a".to_string()),
+                    Text::new("".to_string()),
+                    Text::new("b".to_string()),
+                    Element::new(
+                        "p".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new("c".to_string()), Text::new("d".to_string())],
+                    ),
+                    Text::new("".to_string()),
+                ],
+            ),
+        );
+        document.normalize(div);
+        assert_eq!(
+            document.to_tree(div),
+            Element::new(
+                "div".to_string(),
+                AttrMap::new(),
+                vec![
+                    Text::new("ab".to_string()),
+                    Element::new(
+                        "p".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new("cd".to_string())],
+                    ),
+                ],
+            )
+        );
+    }
+
+    /// `parse_into` parses `html` as a fragment in `body`, and returns a document with the result in `body` element.
+    fn parse_into(html: &str) -> (Document, NodeId) {
+        let mut document = document();
+        let root = document.root();
+        let body = document.append_tree(
+            root,
+            Element::new("body".to_string(), AttrMap::new(), vec![]),
+        );
+        document.set_inner_html(body, html.to_string()).unwrap();
+        (document, body)
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut document = document();
+        let root = document.root();
+        let p = document.append_tree(root, Element::new("p".to_string(), AttrMap::new(), vec![]));
+
+        assert_eq!(document.set_attribute(p, "id", "a".to_string()), Ok(()));
+        assert_eq!(document.get_attribute(p, "id"), Some("a".to_string()));
+        assert_eq!(document.toggle_attribute(p, "hidden", None), Ok(true));
+        assert_eq!(document.toggle_attribute(p, "hidden", Some(true)), Ok(true));
+        assert_eq!(document.toggle_attribute(p, "hidden", None), Ok(false));
+        assert_eq!(
+            document.toggle_attribute(p, "hidden", Some(false)),
+            Ok(false)
+        );
+        assert_eq!(document.get_attribute(p, "hidden"), None);
+        assert_eq!(document.remove_attribute(p, "id"), Ok(()));
+        assert_eq!(document.get_attribute(p, "id"), None);
+
+        assert_eq!(
+            document.set_attribute(p, "a b", "".to_string()),
+            Err(DOMException::InvalidCharacterError(
+                "the name is not a valid attribute name"
+            ))
+        );
+        assert!(document.toggle_attribute(root, "id", None).is_err());
+    }
+
+    #[test]
+    fn test_descendants() {
+        let (document, body) = parse_into("<p>a<b>b</b></p><p><i>c</i></p>d");
+        let tags = document
+            .descendants(body)
+            .map(|id| match document.node(id).node_type {
+                NodeType::Element(ref e) => e.tag_name.clone(),
+                NodeType::Text(ref t) => t.data.clone(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tags, vec!["p", "a", "b", "b", "p", "i", "c", "d"]);
+
+        // descendants of a node don't include its siblings.
+        let p = document.node(body).first_child().unwrap();
+        assert_eq!(document.descendants(p).count(), 3);
+        let text = document.node(p).first_child().unwrap();
+        assert_eq!(document.descendants(text).count(), 0);
+    }
+
+    #[test]
+    fn test_query_selector() {
+        let (document, body) =
+            parse_into("<div class=\"a b\"><p id=x>1</p><p class=b>2</p></div><p>3</p>");
+        let texts = |ids: Vec<NodeId>| {
+            ids.into_iter()
+                .map(|id| document.inner_text(id))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            texts(document.query_selector_all(body, "p").unwrap()),
+            vec!["1", "2", "3"]
+        );
+        assert_eq!(
+            texts(document.query_selector_all(body, ".b").unwrap()),
+            vec!["12", "2"]
+        );
+        let div = document.query_selector(body, "div").unwrap().unwrap();
+        assert_eq!(
+            texts(document.query_selector_all(div, "p").unwrap()),
+            vec!["1", "2"]
+        );
+        assert_eq!(document.query_selector(div, "div"), Ok(None));
+        assert!(document.query_selector(body, "p {").is_err());
+
+        let p = document.query_selector(body, "p").unwrap().unwrap();
+        assert_eq!(document.matches(p, "div, p"), Ok(true));
+        assert_eq!(document.matches(p, ".a"), Ok(false));
+        assert_eq!(document.closest(p, ".a"), Ok(Some(div)));
+        assert_eq!(document.closest(p, "p"), Ok(Some(p)));
+        assert_eq!(document.closest(p, "span"), Ok(None));
+    }
+
+    #[test]
+    fn test_get_elements_by() {
+        let (document, body) =
+            parse_into("<div class=\"a b\"><P class=\"b  a c\">1</P><p class=b>2</p></div>");
+        assert_eq!(document.get_elements_by_tag_name(body, "P").len(), 2);
+        assert_eq!(document.get_elements_by_tag_name(body, "*").len(), 3);
+        assert_eq!(document.get_elements_by_class_name(body, " b ").len(), 3);
+        assert_eq!(document.get_elements_by_class_name(body, "a b").len(), 2);
+        assert_eq!(document.get_elements_by_class_name(body, "c\ta").len(), 1);
+        assert_eq!(document.get_elements_by_class_name(body, " ").len(), 0);
+    }
 }
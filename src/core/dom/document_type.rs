@@ -0,0 +1,24 @@
+//! This module defines some interfaces related to `DocumentType` interface.
+
+use super::{NodeTree, NodeType};
+
+/// `DocumentType` is a kind of `Node` defined at [DOM Standard](https://dom.spec.whatwg.org/#interface-documenttype).
+#[derive(Debug, PartialEq, Clone)]
+pub struct DocumentType {
+    pub name: String,
+    pub public_id: String,
+    pub system_id: String,
+}
+
+impl DocumentType {
+    pub fn new(name: String, public_id: String, system_id: String) -> NodeTree {
+        NodeTree {
+            node_type: NodeType::DocumentType(DocumentType {
+                name: name,
+                public_id: public_id,
+                system_id: system_id,
+            }),
+            children: vec![],
+        }
+    }
+}
@@ -1,38 +1,199 @@
 //! This module defines some interfaces related to `Element` interface.
 
-use super::node::{Node, NodeType};
+use super::node::{NodeTree, NodeType};
 use std::collections::HashMap;
 
 pub type AttrMap = HashMap<String, String>;
 
 /// `Element` is a kind of `Node` defined at [DOM Standard](https://dom.spec.whatwg.org/#interface-element).
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Element {
     pub tag_name: String,
     pub attributes: AttrMap,
 }
 
 impl Element {
-    pub fn new(name: String, attributes: AttrMap, children: Vec<Box<Node>>) -> Box<Node> {
-        Box::new(Node {
+    pub fn new(name: String, attributes: AttrMap, children: Vec<NodeTree>) -> NodeTree {
+        NodeTree {
             node_type: NodeType::Element(Element {
                 tag_name: name,
                 attributes: attributes,
             }),
             children,
-        })
+        }
     }
 
     pub fn id(&self) -> Option<&String> {
         self.attributes.get("id")
     }
 
-    pub fn attributes(&self) -> Vec<(String, String)> {
-        self.attributes
-            .iter()
-            .clone()
-            .into_iter()
-            .map(|(k, v)| (k.to_string(), v.to_string()))
-            .collect()
+    /// `attribute_names` returns the names of the attributes.
+    /// NOTE: attributes are not ordered in `AttrMap`, so the names are sorted to keep the order stable.
+    pub fn attribute_names(&self) -> Vec<String> {
+        let mut names = self.attributes.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// `class_list` returns the classes of the element, i.e. the ordered set parsed from its `class` attribute.
+    /// https://dom.spec.whatwg.org/#dom-element-classlist
+    pub fn class_list(&self) -> Vec<String> {
+        parse_ordered_set(
+            self.attributes
+                .get("class")
+                .map(String::as_str)
+                .unwrap_or(""),
+        )
+    }
+}
+
+/// `parse_ordered_set` splits `input` by ASCII whitespaces, and removes duplicate tokens.
+/// https://dom.spec.whatwg.org/#concept-ordered-set-parser
+pub fn parse_ordered_set(input: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = vec![];
+    for token in input.split_ascii_whitespace() {
+        if !tokens.iter().any(|t| t == token) {
+            tokens.push(token.to_string());
+        }
+    }
+    tokens
+}
+
+/// `is_valid_attribute_name` checks whether `name` can be used as the name of an attribute.
+/// https://dom.spec.whatwg.org/#valid-attribute-local-name
+pub fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_ascii_whitespace() || matches!(c, '\0' | '/' | '>' | '='))
+}
+
+/// `is_valid_name` checks whether `name` matches the `Name` production of XML,
+/// which is required for the name of an element created by `createElement` and the target of a processing instruction.
+/// https://www.w3.org/TR/xml/#NT-Name
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if is_name_start_char(c) => chars.all(is_name_char),
+        _ => false,
+    }
+}
+
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z'
+        | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}')
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}')
+}
+
+/// `dataset_attribute_name` converts the name of a property of `dataset` to the name of the corresponding `data-*` attribute.
+/// It returns `None` if the name contains `-` followed by an ASCII lower alpha, which makes `SyntaxError`.
+/// https://html.spec.whatwg.org/multipage/dom.html#dom-domstringmap-setitem
+pub fn dataset_attribute_name(property: &str) -> Option<String> {
+    let mut chars = property.chars().peekable();
+    let mut name = "data-".to_string();
+    while let Some(c) = chars.next() {
+        if c == '-' && matches!(chars.peek(), Some(c) if c.is_ascii_lowercase()) {
+            return None;
+        }
+        if c.is_ascii_uppercase() {
+            name.push('-');
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    Some(name)
+}
+
+/// `dataset_property_name` converts the name of a `data-*` attribute to the name of the corresponding property of `dataset`.
+/// It returns `None` if the attribute is not exposed to `dataset`.
+/// https://html.spec.whatwg.org/multipage/dom.html#concept-domstringmap-pairs
+pub fn dataset_property_name(attribute: &str) -> Option<String> {
+    let name = attribute.strip_prefix("data-")?;
+    if name.chars().any(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    let mut chars = name.chars().peekable();
+    let mut property = String::new();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(next) if c == '-' && next.is_ascii_lowercase() => {
+                property.push(next.to_ascii_uppercase());
+                chars.next();
+            }
+            _ => property.push(c),
+        }
+    }
+    Some(property)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class_list() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("class".to_string(), " a b\ta  c ".to_string());
+        let e = Element {
+            tag_name: "p".to_string(),
+            attributes,
+        };
+        assert_eq!(e.class_list(), vec!["a", "b", "c"]);
+        assert_eq!(e.attribute_names(), vec!["class"]);
+    }
+
+    #[test]
+    fn test_attribute_name() {
+        assert!(is_valid_attribute_name("data-x"));
+        assert!(!is_valid_attribute_name(""));
+        assert!(!is_valid_attribute_name("a b"));
+        assert!(!is_valid_attribute_name("a=b"));
+    }
+
+    #[test]
+    fn test_name() {
+        assert!(is_valid_name("p"));
+        assert!(is_valid_name("my-element"));
+        assert!(is_valid_name("xml-stylesheet"));
+        assert!(is_valid_name("\u{130}"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("1p"));
+        assert!(!is_valid_name("-p"));
+        assert!(!is_valid_name("img src=x onerror=alert(1)"));
+        assert!(!is_valid_name("<p>"));
+    }
+
+    #[test]
+    fn test_dataset_names() {
+        assert_eq!(
+            dataset_attribute_name("fooBarBaz"),
+            Some("data-foo-bar-baz".to_string())
+        );
+        assert_eq!(
+            dataset_attribute_name("foo-1"),
+            Some("data-foo-1".to_string())
+        );
+        assert_eq!(dataset_attribute_name("foo-bar"), None);
+
+        assert_eq!(
+            dataset_property_name("data-foo-bar-baz"),
+            Some("fooBarBaz".to_string())
+        );
+        assert_eq!(
+            dataset_property_name("data-foo-1"),
+            Some("foo-1".to_string())
+        );
+        assert_eq!(dataset_property_name("data-Foo"), None);
+        assert_eq!(dataset_property_name("id"), None);
     }
 }
@@ -0,0 +1,56 @@
+//! This module defines `DOMException`, an error thrown by DOM operations.
+
+use thiserror::Error;
+
+/// `DOMException` is an error defined at https://webidl.spec.whatwg.org/#idl-DOMException.
+/// Each variant corresponds to an error name listed at https://webidl.spec.whatwg.org/#idl-DOMException-error-names.
+#[derive(Error, Debug, PartialEq)]
+pub enum DOMException {
+    /// The index is not in the allowed range.
+    #[error("IndexSizeError: {0}")]
+    IndexSizeError(&'static str),
+
+    /// The operation would yield an incorrect node tree.
+    #[error("HierarchyRequestError: {0}")]
+    HierarchyRequestError(&'static str),
+
+    /// The object is in an invalid state.
+    #[error("InvalidStateError: {0}")]
+    InvalidStateError(&'static str),
+
+    /// The string contains invalid characters.
+    #[error("InvalidCharacterError: {0}")]
+    InvalidCharacterError(&'static str),
+
+    /// The object can not be modified.
+    #[error("NoModificationAllowedError: {0}")]
+    NoModificationAllowedError(&'static str),
+
+    /// The object can not be found here.
+    #[error("NotFoundError: {0}")]
+    NotFoundError(&'static str),
+
+    /// The string did not match the expected pattern.
+    #[error("SyntaxError: {0}")]
+    SyntaxError(&'static str),
+
+    /// The operation is not supported.
+    #[error("NotSupportedError: {0}")]
+    NotSupportedError(&'static str),
+}
+
+impl DOMException {
+    /// `name` returns the error name, which is exposed to scripts as `DOMException.prototype.name`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DOMException::IndexSizeError(_) => "IndexSizeError",
+            DOMException::HierarchyRequestError(_) => "HierarchyRequestError",
+            DOMException::InvalidStateError(_) => "InvalidStateError",
+            DOMException::InvalidCharacterError(_) => "InvalidCharacterError",
+            DOMException::NoModificationAllowedError(_) => "NoModificationAllowedError",
+            DOMException::NotFoundError(_) => "NotFoundError",
+            DOMException::SyntaxError(_) => "SyntaxError",
+            DOMException::NotSupportedError(_) => "NotSupportedError",
+        }
+    }
+}
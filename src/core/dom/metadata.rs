@@ -0,0 +1,396 @@
+//! This module defines the metadata of documents, such as the title and the base URL.
+//! Most of them are defined at [HTML Standard](https://html.spec.whatwg.org/multipage/dom.html#the-document-object).
+
+use chrono::{DateTime, FixedOffset, Local};
+
+use super::{AttrMap, DOMException, Document, Element, MutationRecord, NodeId, NodeType, Text};
+use crate::core::url::Url;
+
+/// `DocumentReadyState` is the *current document readiness* of a document.
+/// A document is `Loading` while it's parsed, `Interactive` after it's parsed, and `Complete` after it's loaded.
+/// https://html.spec.whatwg.org/multipage/dom.html#current-document-readiness
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DocumentReadyState {
+    Loading,
+    Interactive,
+    Complete,
+}
+
+impl DocumentReadyState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DocumentReadyState::Loading => "loading",
+            DocumentReadyState::Interactive => "interactive",
+            DocumentReadyState::Complete => "complete",
+        }
+    }
+}
+
+/// `StyleSource` is where a stylesheet of a document comes from.
+/// https://html.spec.whatwg.org/multipage/semantics.html#styling
+#[derive(Debug, PartialEq, Clone)]
+pub enum StyleSource {
+    /// `Inline` is the text of a `style` element.
+    Inline(String),
+    /// `External` is the URL given with a `link` element whose `rel` contains `stylesheet`.
+    External(Url),
+}
+
+/// Implementations for the metadata of documents.
+impl Document {
+    /// `head` returns the first `head` element which is a child of the document element.
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-head
+    pub fn head(&self) -> Option<NodeId> {
+        let html = self.html_element()?;
+        self.children(html)
+            .find(|&id| self.is_element_named(id, &["head"]))
+    }
+
+    /// `body` returns the first `body` or `frameset` element which is a child of the document element.
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-body
+    pub fn body(&self) -> Option<NodeId> {
+        let html = self.html_element()?;
+        self.children(html)
+            .find(|&id| self.is_element_named(id, &["body", "frameset"]))
+    }
+
+    /// `title` returns the text of the first `title` element in the document, whose whitespaces are stripped and collapsed.
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-title
+    pub fn title(&self) -> String {
+        match self.title_element() {
+            Some(title) => self
+                .inner_text(title)
+                .split_ascii_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => "".to_string(),
+        }
+    }
+
+    /// `set_title` replaces the text of the first `title` element with `value`.
+    /// A new `title` element is appended to `head` if no `title` element exists, and nothing happens if `head` doesn't exist either.
+    /// https://html.spec.whatwg.org/multipage/dom.html#document.title
+    pub fn set_title(&mut self, value: String) -> Result<(), DOMException> {
+        let title = match (self.title_element(), self.head()) {
+            (Some(title), _) => title,
+            (None, Some(head)) => {
+                let title = self.create_node(NodeType::Element(Element {
+                    tag_name: "title".to_string(),
+                    attributes: AttrMap::new(),
+                }));
+                self.append_child(head, title)?
+            }
+            (None, None) => return Ok(()),
+        };
+        let trees = if value.is_empty() {
+            vec![]
+        } else {
+            vec![Text::new(value)]
+        };
+        self.replace_all(title, trees);
+        Ok(())
+    }
+
+    /// `base_url` returns the URL which relative URLs in the document are resolved against.
+    /// It's the `href` of the first `base` element with the attribute, resolved against the URL of the document.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#document-base-url
+    pub fn base_url(&self) -> Option<Url> {
+        let url = Url::parse(self.url.as_str()).ok()?;
+        let href = self
+            .descendants(self.root())
+            .find_map(|id| match self.node(id).node_type {
+                NodeType::Element(ref e) if e.tag_name == "base" => e.attributes.get("href"),
+                _ => None,
+            });
+        // https://html.spec.whatwg.org/multipage/semantics.html#set-the-frozen-base-url
+        match href {
+            Some(href) => Some(url.join(href.as_str()).unwrap_or(url)),
+            None => Some(url),
+        }
+    }
+
+    /// `resolve_url` parses `input` as a URL relative to the base URL of the document.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#parse-a-url
+    pub fn resolve_url(&self, input: &str) -> Option<Url> {
+        match self.base_url() {
+            Some(base) => base.join(input).ok(),
+            None => Url::parse(input).ok(),
+        }
+    }
+
+    /// `get_style_sources` returns the stylesheets of the document in tree order, which is the order they are applied in.
+    /// The `href` of a `link` element is resolved against the base URL of the document; links to alternative stylesheets and invalid URLs are ignored.
+    /// https://html.spec.whatwg.org/multipage/links.html#link-type-stylesheet
+    pub fn get_style_sources(&self) -> Vec<StyleSource> {
+        self.descendants(self.root())
+            .filter_map(|id| match self.node(id).node_type {
+                NodeType::Element(ref e) if e.tag_name == "style" => {
+                    Some(StyleSource::Inline(self.inner_text(id)))
+                }
+                NodeType::Element(ref e) if e.tag_name == "link" && is_stylesheet_link(e) => {
+                    match e.attributes.get("href") {
+                        Some(href) if !href.is_empty() => {
+                            self.resolve_url(href.as_str()).map(StyleSource::External)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// `affects_style_sources` returns true if the mutation may add stylesheets to the document,
+    /// that is, it touched a `link`, `style` or `base` element, or inserted one.
+    /// Removing one never adds stylesheets, so removed nodes are not checked.
+    pub fn affects_style_sources(&self, record: &MutationRecord) -> bool {
+        let is_style_source = |id| self.is_element_named(id, &["link", "style", "base"]);
+        self.inclusive_ancestors(record.target).any(is_style_source)
+            || record
+                .added_nodes
+                .iter()
+                .any(|&id| is_style_source(id) || self.descendants(id).any(is_style_source))
+    }
+
+    /// `last_modified_string` returns the date and time when the document was last modified, formatted in the local time zone.
+    /// The current date and time is returned if it's unknown.
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-lastmodified
+    pub fn last_modified_string(&self) -> String {
+        let local = match self.last_modified {
+            Some(last_modified) => last_modified.with_timezone(&Local),
+            None => Local::now(),
+        };
+        local.format("%m/%d/%Y %H:%M:%S").to_string()
+    }
+
+    fn html_element(&self) -> Option<NodeId> {
+        self.document_element()
+            .filter(|&id| self.is_element_named(id, &["html"]))
+    }
+
+    fn title_element(&self) -> Option<NodeId> {
+        self.descendants(self.root())
+            .find(|&id| self.is_element_named(id, &["title"]))
+    }
+
+    fn is_element_named(&self, id: NodeId, tag_names: &[&str]) -> bool {
+        match self.node(id).node_type {
+            NodeType::Element(ref e) => tag_names.contains(&e.tag_name.as_str()),
+            _ => false,
+        }
+    }
+}
+
+/// `is_stylesheet_link` returns true if the `rel` of the `link` element has `stylesheet` keyword and doesn't have `alternate` keyword.
+/// Keywords are compared in ASCII case-insensitive manner.
+fn is_stylesheet_link(element: &Element) -> bool {
+    let has = |keyword: &str| {
+        element
+            .attributes
+            .get("rel")
+            .map(|rel| {
+                rel.split_ascii_whitespace()
+                    .any(|k| k.eq_ignore_ascii_case(keyword))
+            })
+            .unwrap_or(false)
+    };
+    has("stylesheet") && !has("alternate")
+}
+
+/// `parse_http_date` parses the value of HTTP headers like `Last-Modified`.
+/// https://httpwg.org/specs/rfc7231.html#http.date
+pub fn parse_http_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc2822(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dom::{MutationObserverInit, NodeTree};
+
+    fn document_with(trees: Vec<NodeTree>) -> Document {
+        let url = "http://example.com/a/b.html";
+        let mut document = Document::new(url.to_string(), url.to_string());
+        let root = document.root();
+        for tree in trees {
+            document.append_tree(root, tree);
+        }
+        document
+    }
+
+    fn element(tag_name: &str, children: Vec<NodeTree>) -> NodeTree {
+        Element::new(tag_name.to_string(), AttrMap::new(), children)
+    }
+
+    #[test]
+    fn test_head_and_body() {
+        let document = document_with(vec![element(
+            "html",
+            vec![element("head", vec![]), element("body", vec![])],
+        )]);
+        let html = document.document_element().unwrap();
+        let children = document.children(html).collect::<Vec<_>>();
+        assert_eq!(document.head(), Some(children[0]));
+        assert_eq!(document.body(), Some(children[1]));
+
+        let document = document_with(vec![element("svg", vec![element("head", vec![])])]);
+        assert_eq!(document.head(), None);
+        assert_eq!(document.body(), None);
+    }
+
+    #[test]
+    fn test_title() {
+        let mut document = document_with(vec![element(
+            "html",
+            vec![element(
+                "head",
+                vec![element(
+                    "title",
+                    vec![Text::new("  hello \n world ".to_string())],
+                )],
+            )],
+        )]);
+        assert_eq!(document.title(), "hello world");
+        assert_eq!(document.set_title("bye".to_string()), Ok(()));
+        assert_eq!(document.title(), "bye");
+        let head = document.head().unwrap();
+        assert_eq!(document.inner_html(head), "<title>bye</title>");
+
+        let mut document = document_with(vec![element("html", vec![element("head", vec![])])]);
+        assert_eq!(document.title(), "");
+        assert_eq!(document.set_title("new".to_string()), Ok(()));
+        let head = document.head().unwrap();
+        assert_eq!(document.inner_html(head), "<title>new</title>");
+
+        let mut document = document_with(vec![]);
+        assert_eq!(document.set_title("none".to_string()), Ok(()));
+        assert_eq!(document.title(), "");
+    }
+
+    #[test]
+    fn test_base_url() {
+        let document = document_with(vec![]);
+        assert_eq!(
+            document.resolve_url("c.html").map(|u| u.to_string()),
+            Some("http://example.com/a/c.html".to_string())
+        );
+
+        let mut attributes = AttrMap::new();
+        attributes.insert("href".to_string(), "/x/".to_string());
+        let document = document_with(vec![
+            element("base", vec![]),
+            Element::new("base".to_string(), attributes, vec![]),
+        ]);
+        assert_eq!(
+            document.base_url().map(|u| u.to_string()),
+            Some("http://example.com/x/".to_string())
+        );
+        assert_eq!(
+            document.resolve_url("c.html").map(|u| u.to_string()),
+            Some("http://example.com/x/c.html".to_string())
+        );
+    }
+
+    #[test]
+    fn test_style_sources() {
+        let link = |rel: &str, href: &str| {
+            let mut attributes = AttrMap::new();
+            attributes.insert("rel".to_string(), rel.to_string());
+            attributes.insert("href".to_string(), href.to_string());
+            Element::new("link".to_string(), attributes, vec![])
+        };
+        let document = document_with(vec![element(
+            "html",
+            vec![
+                element(
+                    "head",
+                    vec![
+                        link("StyleSheet", "a.css"),
+                        element("style", vec![Text::new("p { color: red }".to_string())]),
+                        link("alternate stylesheet", "b.css"),
+                        link("icon", "c.png"),
+                        link("stylesheet", ""),
+                    ],
+                ),
+                element("body", vec![link("preload stylesheet", "/d.css")]),
+            ],
+        )]);
+        assert_eq!(
+            document.get_style_sources(),
+            vec![
+                StyleSource::External(Url::parse("http://example.com/a/a.css").unwrap()),
+                StyleSource::Inline("p { color: red }".to_string()),
+                StyleSource::External(Url::parse("http://example.com/d.css").unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_affects_style_sources() {
+        let mut document = document_with(vec![element(
+            "html",
+            vec![
+                element("style", vec![Text::new("".to_string())]),
+                element("div", vec![]),
+            ],
+        )]);
+        let html = document.document_element().unwrap();
+        let children = document.children(html).collect::<Vec<_>>();
+        let (style, div) = (children[0], children[1]);
+        let text = document.children(style).next().unwrap();
+        let observer = document.create_mutation_observer();
+        let options = MutationObserverInit {
+            child_list: true,
+            attributes: Some(true),
+            character_data: Some(true),
+            subtree: true,
+            ..Default::default()
+        };
+        document.observe(observer, html, options).unwrap();
+        let affects = |document: &mut Document| {
+            let records = document.take_records(observer);
+            records.iter().any(|r| document.affects_style_sources(r))
+        };
+
+        // mutations unrelated to stylesheets
+        document.set_attribute(div, "id", "x".to_string()).unwrap();
+        let p = document.append_tree(div, element("p", vec![]));
+        assert!(!affects(&mut document));
+        document.remove_child(html, style).unwrap();
+        assert!(!affects(&mut document));
+
+        // mutations of the contents of a `style` element
+        document.append_child(html, style).unwrap();
+        assert!(affects(&mut document));
+        document
+            .modify_character_data(text, |data| {
+                data.append_data("p {}");
+                Ok(())
+            })
+            .unwrap();
+        assert!(affects(&mut document));
+
+        // insertion of a subtree with a `link` element
+        let section = document.create_node(NodeType::Element(Element {
+            tag_name: "section".to_string(),
+            attributes: AttrMap::new(),
+        }));
+        document.append_tree(section, element("link", vec![]));
+        document.append_child(p, section).unwrap();
+        assert!(affects(&mut document));
+    }
+
+    #[test]
+    fn test_last_modified() {
+        let mut document = document_with(vec![]);
+        let last_modified = parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert!(last_modified.is_some());
+        document.last_modified = last_modified;
+        let expected = last_modified.unwrap().with_timezone(&Local);
+        assert_eq!(
+            document.last_modified_string(),
+            expected.format("%m/%d/%Y %H:%M:%S").to_string()
+        );
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,431 @@
+//! This module defines mutation observers and records, which are defined at [DOM Standard](https://dom.spec.whatwg.org/#mutation-observers).
+
+use thiserror::Error;
+
+use super::{Document, NodeId};
+
+/// `MutationObserverId` identifies a mutation observer registered to a `Document`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct MutationObserverId(usize);
+
+impl MutationObserverId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// `MutationObserverInit` tells which mutations an observer is interested in.
+/// `attributes` and `character_data` are implied by the related options if they're `None`.
+/// https://dom.spec.whatwg.org/#dictdef-mutationobserverinit
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MutationObserverInit {
+    pub child_list: bool,
+    pub attributes: Option<bool>,
+    pub character_data: Option<bool>,
+    pub subtree: bool,
+    pub attribute_old_value: Option<bool>,
+    pub character_data_old_value: Option<bool>,
+    pub attribute_filter: Option<Vec<String>>,
+}
+
+/// `InvalidObserverInit` is an error for options `MutationObserver.observe` rejects with `TypeError`.
+#[derive(Error, Debug, PartialEq)]
+#[error("invalid options for MutationObserver: {0}")]
+pub struct InvalidObserverInit(&'static str);
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MutationRecordType {
+    ChildList,
+    Attributes,
+    CharacterData,
+}
+
+impl MutationRecordType {
+    /// `as_str` returns the value of `MutationRecord.type` exposed to scripts.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MutationRecordType::ChildList => "childList",
+            MutationRecordType::Attributes => "attributes",
+            MutationRecordType::CharacterData => "characterData",
+        }
+    }
+}
+
+/// `MutationRecord` describes a mutation of the tree.
+/// https://dom.spec.whatwg.org/#interface-mutationrecord
+#[derive(Debug, PartialEq, Clone)]
+pub struct MutationRecord {
+    pub record_type: MutationRecordType,
+    pub target: NodeId,
+    pub added_nodes: Vec<NodeId>,
+    pub removed_nodes: Vec<NodeId>,
+    pub previous_sibling: Option<NodeId>,
+    pub next_sibling: Option<NodeId>,
+    pub attribute_name: Option<String>,
+    pub old_value: Option<String>,
+}
+
+/// `MutationObserver` holds the nodes an observer observes, and the records queued for it.
+///
+/// NOTE: transient registered observers, which follow removed nodes until the next notification, are not supported.
+#[derive(Debug, Default)]
+pub struct MutationObserver {
+    registrations: Vec<(NodeId, MutationObserverInit)>,
+    records: Vec<MutationRecord>,
+}
+
+/// Implementations for mutation observers.
+/// Mutations made through the mutation algorithms, e.g. `append_child`, are queued to interested observers as `MutationRecord`.
+/// Unchecked operations like `insert` and `detach` are not recorded, which the HTML parser uses.
+impl Document {
+    /// `create_mutation_observer` registers a new mutation observer, which observes no node yet.
+    pub fn create_mutation_observer(&mut self) -> MutationObserverId {
+        self.mutation_observers.push(MutationObserver::default());
+        MutationObserverId(self.mutation_observers.len() - 1)
+    }
+
+    /// `observe` starts observing mutations of `target` with `options`.
+    /// If the observer already observes `target`, the options are replaced.
+    /// https://dom.spec.whatwg.org/#dom-mutationobserver-observe
+    pub fn observe(
+        &mut self,
+        observer: MutationObserverId,
+        target: NodeId,
+        mut options: MutationObserverInit,
+    ) -> Result<(), InvalidObserverInit> {
+        if options.attributes.is_none()
+            && (options.attribute_old_value.is_some() || options.attribute_filter.is_some())
+        {
+            options.attributes = Some(true);
+        }
+        if options.character_data.is_none() && options.character_data_old_value.is_some() {
+            options.character_data = Some(true);
+        }
+        let attributes = options.attributes.unwrap_or(false);
+        let character_data = options.character_data.unwrap_or(false);
+        if !options.child_list && !attributes && !character_data {
+            return Err(InvalidObserverInit(
+                "one of childList, attributes and characterData must be true",
+            ));
+        }
+        if !attributes
+            && (options.attribute_old_value == Some(true) || options.attribute_filter.is_some())
+        {
+            return Err(InvalidObserverInit(
+                "attributeOldValue and attributeFilter require attributes",
+            ));
+        }
+        if !character_data && options.character_data_old_value == Some(true) {
+            return Err(InvalidObserverInit(
+                "characterDataOldValue requires characterData",
+            ));
+        }
+
+        let registrations = match self.mutation_observers.get_mut(observer.index()) {
+            Some(observer) => &mut observer.registrations,
+            None => return Ok(()),
+        };
+        match registrations.iter_mut().find(|(node, _)| *node == target) {
+            Some(registration) => registration.1 = options,
+            None => registrations.push((target, options)),
+        }
+        Ok(())
+    }
+
+    /// `disconnect` stops observing any node, and discards the queued records.
+    /// https://dom.spec.whatwg.org/#dom-mutationobserver-disconnect
+    pub fn disconnect(&mut self, observer: MutationObserverId) {
+        if let Some(observer) = self.mutation_observers.get_mut(observer.index()) {
+            observer.registrations.clear();
+            observer.records.clear();
+        }
+    }
+
+    /// `take_records` returns the records queued for the observer, and empties the queue.
+    /// https://dom.spec.whatwg.org/#dom-mutationobserver-takerecords
+    pub fn take_records(&mut self, observer: MutationObserverId) -> Vec<MutationRecord> {
+        match self.mutation_observers.get_mut(observer.index()) {
+            Some(observer) => std::mem::take(&mut observer.records),
+            None => vec![],
+        }
+    }
+
+    /// `has_mutation_records` returns true if any observer has queued records.
+    pub fn has_mutation_records(&self) -> bool {
+        self.mutation_observers
+            .iter()
+            .any(|observer| !observer.records.is_empty())
+    }
+
+    /// `queue_mutation_record` queues `record` to each observer interested in it.
+    /// `old_value` is set to the record only for observers which want it.
+    /// https://dom.spec.whatwg.org/#queue-a-mutation-record
+    pub(super) fn queue_mutation_record(
+        &mut self,
+        record: MutationRecord,
+        old_value: Option<String>,
+    ) {
+        if self.mutation_observers.is_empty() {
+            return;
+        }
+        let ancestors = self.inclusive_ancestors(record.target).collect::<Vec<_>>();
+        let interested = self
+            .mutation_observers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, observer)| {
+                let options = observer
+                    .registrations
+                    .iter()
+                    .filter(|(node, options)| {
+                        ancestors.contains(node) && (*node == record.target || options.subtree)
+                    })
+                    .map(|(_, options)| options)
+                    .filter(|options| match record.record_type {
+                        MutationRecordType::ChildList => options.child_list,
+                        MutationRecordType::Attributes => {
+                            options.attributes.unwrap_or(false)
+                                && match options.attribute_filter {
+                                    Some(ref filter) => record
+                                        .attribute_name
+                                        .iter()
+                                        .any(|name| filter.contains(name)),
+                                    None => true,
+                                }
+                        }
+                        MutationRecordType::CharacterData => {
+                            options.character_data.unwrap_or(false)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if options.is_empty() {
+                    return None;
+                }
+                let wants_old_value = options.iter().any(|options| match record.record_type {
+                    MutationRecordType::Attributes => options.attribute_old_value == Some(true),
+                    MutationRecordType::CharacterData => {
+                        options.character_data_old_value == Some(true)
+                    }
+                    MutationRecordType::ChildList => false,
+                });
+                Some((i, wants_old_value))
+            })
+            .collect::<Vec<_>>();
+
+        for (i, wants_old_value) in interested {
+            let mut record = record.clone();
+            if wants_old_value {
+                record.old_value = old_value.clone();
+            }
+            self.mutation_observers[i].records.push(record);
+        }
+    }
+
+    /// `queue_tree_mutation_record` queues a record of `childList` type, unless no node is added or removed.
+    /// https://dom.spec.whatwg.org/#queue-a-tree-mutation-record
+    pub(super) fn queue_tree_mutation_record(
+        &mut self,
+        target: NodeId,
+        added_nodes: Vec<NodeId>,
+        removed_nodes: Vec<NodeId>,
+        previous_sibling: Option<NodeId>,
+        next_sibling: Option<NodeId>,
+    ) {
+        if added_nodes.is_empty() && removed_nodes.is_empty() {
+            return;
+        }
+        self.queue_mutation_record(
+            MutationRecord {
+                record_type: MutationRecordType::ChildList,
+                target,
+                added_nodes,
+                removed_nodes,
+                previous_sibling,
+                next_sibling,
+                attribute_name: None,
+                old_value: None,
+            },
+            None,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dom::{AttrMap, Element, NodeType, Text};
+
+    fn document() -> (Document, NodeId, NodeId) {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let div = document.append_tree(
+            root,
+            Element::new(
+                "div".to_string(),
+                AttrMap::new(),
+                vec![Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("hello".to_string())],
+                )],
+            ),
+        );
+        let p = document.children(div).next().unwrap();
+        (document, div, p)
+    }
+
+    #[test]
+    fn test_observe() {
+        let (mut document, div, _) = document();
+        let observer = document.create_mutation_observer();
+        assert!(document
+            .observe(observer, div, MutationObserverInit::default())
+            .is_err());
+        assert!(document
+            .observe(
+                observer,
+                div,
+                MutationObserverInit {
+                    attributes: Some(false),
+                    attribute_old_value: Some(true),
+                    ..Default::default()
+                }
+            )
+            .is_err());
+        assert_eq!(
+            document.observe(
+                observer,
+                div,
+                MutationObserverInit {
+                    character_data_old_value: Some(true),
+                    ..Default::default()
+                }
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_child_list_records() {
+        let (mut document, div, p) = document();
+        let observer = document.create_mutation_observer();
+        let options = MutationObserverInit {
+            child_list: true,
+            ..Default::default()
+        };
+        document.observe(observer, div, options).unwrap();
+
+        let b = document.create_node(NodeType::Element(Element {
+            tag_name: "b".to_string(),
+            attributes: AttrMap::new(),
+        }));
+        document.append_child(div, b).unwrap();
+        // mutations of descendants are not observed without `subtree`.
+        let text = document.children(p).next().unwrap();
+        document.remove_child(p, text).unwrap();
+        document.insert_before(div, text, Some(p)).unwrap();
+        assert!(document.has_mutation_records());
+
+        let records = document.take_records(observer);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].record_type, MutationRecordType::ChildList);
+        assert_eq!(records[0].added_nodes, vec![b]);
+        assert_eq!(records[0].previous_sibling, Some(p));
+        assert_eq!(records[0].next_sibling, None);
+        assert_eq!(records[1].added_nodes, vec![text]);
+        assert_eq!(records[1].next_sibling, Some(p));
+        assert!(!document.has_mutation_records());
+
+        document.replace_child(div, p, b).unwrap();
+        let records = document.take_records(observer);
+        assert_eq!(records.len(), 2);
+        // `p` is removed from its parent first.
+        assert_eq!(records[0].removed_nodes, vec![p]);
+        assert_eq!(records[1].added_nodes, vec![p]);
+        assert_eq!(records[1].removed_nodes, vec![b]);
+
+        document.disconnect(observer);
+        document.remove_child(div, p).unwrap();
+        assert!(document.take_records(observer).is_empty());
+    }
+
+    #[test]
+    fn test_attribute_records() {
+        let (mut document, div, p) = document();
+        let observer = document.create_mutation_observer();
+        let options = MutationObserverInit {
+            attribute_filter: Some(vec!["id".to_string()]),
+            attribute_old_value: Some(true),
+            subtree: true,
+            ..Default::default()
+        };
+        document.observe(observer, div, options).unwrap();
+
+        document.set_attribute(p, "id", "a".to_string()).unwrap();
+        document.set_attribute(p, "class", "b".to_string()).unwrap();
+        document.set_attribute(p, "id", "c".to_string()).unwrap();
+        document.remove_attribute(p, "id").unwrap();
+        document.remove_attribute(p, "id").unwrap();
+        let records = document.take_records(observer);
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (
+                    r.target,
+                    r.attribute_name.as_deref(),
+                    r.old_value.as_deref()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (p, Some("id"), None),
+                (p, Some("id"), Some("a")),
+                (p, Some("id"), Some("c")),
+            ]
+        );
+        assert_eq!(records[0].record_type, MutationRecordType::Attributes);
+    }
+
+    #[test]
+    fn test_character_data_records() {
+        let (mut document, div, p) = document();
+        let text = document.children(p).next().unwrap();
+        let observer = document.create_mutation_observer();
+        let options = MutationObserverInit {
+            character_data: Some(true),
+            character_data_old_value: Some(true),
+            subtree: true,
+            ..Default::default()
+        };
+        document.observe(observer, div, options).unwrap();
+        let other = document.create_mutation_observer();
+        let options = MutationObserverInit {
+            character_data: Some(true),
+            subtree: true,
+            ..Default::default()
+        };
+        document.observe(other, div, options).unwrap();
+
+        document
+            .modify_character_data(text, |c| c.replace_data(0, 1, "j"))
+            .unwrap();
+        assert!(document
+            .modify_character_data(text, |c| c.insert_data(100, "!"))
+            .is_err());
+        let records = document.take_records(observer);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].target, text);
+        assert_eq!(records[0].old_value, Some("hello".to_string()));
+        let records = document.take_records(other);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].old_value, None);
+        assert_eq!(
+            document
+                .node(text)
+                .node_type
+                .character_data()
+                .unwrap()
+                .data(),
+            "jello"
+        );
+    }
+}
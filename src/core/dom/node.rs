@@ -1,128 +1,125 @@
-use super::super::html::parse_without_normalziation;
-use std::error::Error;
+use super::CharacterData;
+use crate::source::SourceSpan;
+
+/// `NodeId` identifies a node in a `Document`.
+/// IDs are indices of nodes stored in the document, so an ID stays valid as long as the document lives, even after the node is removed from the tree.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn from_index(index: usize) -> Self {
+        NodeId(index)
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
 
 // `Node` interface
 // definition: https://dom.spec.whatwg.org/#interface-node
-#[derive(Debug, PartialEq)]
+//
+// Nodes are owned by `Document`, and linked to each other with `NodeId`.
+// The links can be changed only through `Document` so that they are always consistent.
+#[derive(Debug)]
 pub struct Node {
     pub node_type: NodeType,
-    pub children: Vec<Box<Node>>,
+    /// `source_span` is the location in the source where the node came from, if the node was created by the HTML parser.
+    /// For elements, it points at the start tag.
+    pub source_span: Option<SourceSpan>,
+
+    pub(super) parent: Option<NodeId>,
+    pub(super) first_child: Option<NodeId>,
+    pub(super) last_child: Option<NodeId>,
+    pub(super) previous_sibling: Option<NodeId>,
+    pub(super) next_sibling: Option<NodeId>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum NodeType {
+    Document,
+    DocumentFragment,
     Element(super::element::Element),
     Text(super::chardata::Text),
+    CDATASection(super::chardata::CDATASection),
+    Comment(super::chardata::Comment),
+    ProcessingInstruction(super::chardata::ProcessingInstruction),
+    DocumentType(super::document_type::DocumentType),
 }
 
-impl Node {
-    pub fn inner_text(&self) -> String {
-        self.children
-            .iter()
-            .clone()
-            .into_iter()
-            .map(|node| match &node.node_type {
-                NodeType::Text(t) => t.data.clone(),
-                _ => node.inner_text(),
-            })
-            .collect::<Vec<_>>()
-            .join("")
+impl NodeType {
+    /// `character_data` returns the node as `CharacterData` if it's a text, comment, or processing instruction.
+    pub fn character_data(&self) -> Option<&dyn CharacterData> {
+        match self {
+            NodeType::Text(t) => Some(t),
+            NodeType::CDATASection(c) => Some(c),
+            NodeType::Comment(c) => Some(c),
+            NodeType::ProcessingInstruction(p) => Some(p),
+            _ => None,
+        }
     }
 
-    pub fn inner_html(&self) -> String {
-        self.children
-            .iter()
-            .clone()
-            .into_iter()
-            .map(|node| node.to_string())
-            .collect::<Vec<_>>()
-            .join("")
+    pub fn character_data_mut(&mut self) -> Option<&mut dyn CharacterData> {
+        match self {
+            NodeType::Text(t) => Some(t),
+            NodeType::CDATASection(c) => Some(c),
+            NodeType::Comment(c) => Some(c),
+            NodeType::ProcessingInstruction(p) => Some(p),
+            _ => None,
+        }
     }
 
-    pub fn set_inner_html(&mut self, html: String) -> Result<(), Box<dyn Error>> {
-        let node = parse_without_normalziation(html.as_bytes().into())?;
-        self.children = node;
-        Ok(())
+    /// `is_text` returns true if the node is a `Text` node, including `CDATASection`.
+    /// https://dom.spec.whatwg.org/#interface-text
+    pub fn is_text(&self) -> bool {
+        matches!(self, NodeType::Text(_) | NodeType::CDATASection(_))
     }
 }
 
-impl ToString for Node {
-    fn to_string(&self) -> String {
-        match self.node_type {
-            NodeType::Element(ref e) => {
-                let attrs = e
-                    .attributes
-                    .iter()
-                    .clone()
-                    .into_iter()
-                    .map(|(k, v)| {
-                        // TODO (security): do this securely! This might causes mXSS.
-                        format!("{}=\"{}\"", k, v)
-                    })
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                let children = self
-                    .children
-                    .iter()
-                    .clone()
-                    .into_iter()
-                    .map(|node| node.to_string())
-                    .collect::<Vec<_>>()
-                    .join("");
-                if attrs != "" {
-                    format!("<{} {}>{}</{}>", e.tag_name, attrs, children, e.tag_name)
-                } else {
-                    format!("<{}>{}</{}>", e.tag_name, children, e.tag_name)
-                }
-            }
-            NodeType::Text(ref t) => t.data.clone(),
+impl Node {
+    pub fn new(node_type: NodeType) -> Self {
+        Node {
+            node_type,
+            source_span: None,
+
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        core::dom::Text,
-        dom::{AttrMap, Element},
-    };
-
-    #[test]
-    fn test_inner_text() {
-        {
-            let node = Element::new(
-                "p".to_string(),
-                AttrMap::new(),
-                vec![Text::new("hello world".to_string())],
-            );
-            assert_eq!(node.inner_text(), "hello world".to_string());
-        }
-        {
-            let node = Element::new(
-                "div".to_string(),
-                AttrMap::new(),
-                vec![
-                    Text::new("hello world".to_string()),
-                    Element::new(
-                        "p".to_string(),
-                        AttrMap::new(),
-                        vec![
-                            Element::new(
-                                "p".to_string(),
-                                AttrMap::new(),
-                                vec![Text::new("1".to_string())],
-                            ),
-                            Element::new("p".to_string(), AttrMap::new(), vec![]),
-                            Element::new(
-                                "p".to_string(),
-                                AttrMap::new(),
-                                vec![Text::new("3".to_string())],
-                            ),
-                        ],
-                    ),
-                ],
-            );
-            assert_eq!(node.inner_text(), "hello world13".to_string());
-        }
+    /// https://dom.spec.whatwg.org/#dom-node-parentnode
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-firstchild
+    pub fn first_child(&self) -> Option<NodeId> {
+        self.first_child
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-lastchild
+    pub fn last_child(&self) -> Option<NodeId> {
+        self.last_child
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-previoussibling
+    pub fn previous_sibling(&self) -> Option<NodeId> {
+        self.previous_sibling
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-node-nextsibling
+    pub fn next_sibling(&self) -> Option<NodeId> {
+        self.next_sibling
     }
 }
+
+/// `NodeTree` is a tree of nodes which doesn't belong to any document.
+/// It's handy to build a tree at once with `Document::append_tree`, and to compare trees with `Document::to_tree`.
+#[derive(Debug, PartialEq)]
+pub struct NodeTree {
+    pub node_type: NodeType,
+    pub children: Vec<NodeTree>,
+}
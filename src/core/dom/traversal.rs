@@ -0,0 +1,724 @@
+//! This module defines interfaces and iterators to traverse the node tree.
+//! The interfaces are defined at [DOM Standard](https://dom.spec.whatwg.org/#traversal).
+
+use std::cell::RefCell;
+
+use super::{Document, NodeId, NodeType};
+
+/// `Children` is an iterator over the children of a node.
+pub struct Children<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = self.document.node(current).next_sibling;
+        Some(current)
+    }
+}
+
+/// `Descendants` is an iterator over the descendants of a node in tree order.
+/// https://dom.spec.whatwg.org/#concept-tree-order
+pub struct Descendants<'a> {
+    document: &'a Document,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = self.document.following(current, self.root);
+        Some(current)
+    }
+}
+
+/// `Ancestors` is an iterator over the inclusive ancestors of a node, from the node itself up to the root.
+pub struct Ancestors<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = self.document.node(current).parent;
+        Some(current)
+    }
+}
+
+/// `Siblings` is an iterator over the following or preceding siblings of a node, from the nearest one.
+pub struct Siblings<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+    forward: bool,
+}
+
+impl<'a> Iterator for Siblings<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        let node = self.document.node(current);
+        self.next = if self.forward {
+            node.next_sibling
+        } else {
+            node.previous_sibling
+        };
+        Some(current)
+    }
+}
+
+/// Implementations for iterators over the tree.
+impl Document {
+    pub fn children(&self, id: NodeId) -> Children<'_> {
+        Children {
+            document: self,
+            next: self.node(id).first_child,
+        }
+    }
+
+    /// `descendants` returns an iterator over the descendants of the node, not including the node itself.
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_> {
+        Descendants {
+            document: self,
+            root: id,
+            next: self.node(id).first_child,
+        }
+    }
+
+    /// `inclusive_ancestors` returns an iterator over the node itself and its ancestors.
+    /// https://dom.spec.whatwg.org/#concept-tree-inclusive-ancestor
+    pub fn inclusive_ancestors(&self, id: NodeId) -> Ancestors<'_> {
+        Ancestors {
+            document: self,
+            next: Some(id),
+        }
+    }
+
+    /// `following_siblings` returns an iterator over the siblings after the node in tree order.
+    pub fn following_siblings(&self, id: NodeId) -> Siblings<'_> {
+        Siblings {
+            document: self,
+            next: self.node(id).next_sibling,
+            forward: true,
+        }
+    }
+
+    /// `preceding_siblings` returns an iterator over the siblings before the node, from the nearest one.
+    pub fn preceding_siblings(&self, id: NodeId) -> Siblings<'_> {
+        Siblings {
+            document: self,
+            next: self.node(id).previous_sibling,
+            forward: false,
+        }
+    }
+
+    /// `following` returns the node just after the node in tree order within the inclusive descendants of `root`.
+    /// https://dom.spec.whatwg.org/#concept-tree-following
+    pub fn following(&self, id: NodeId, root: NodeId) -> Option<NodeId> {
+        if let Some(child) = self.node(id).first_child {
+            return Some(child);
+        }
+        // go up until a node which has the next sibling is found.
+        self.inclusive_ancestors(id)
+            .take_while(|&ancestor| ancestor != root)
+            .find_map(|ancestor| self.node(ancestor).next_sibling)
+    }
+
+    /// `preceding` returns the node just before the node in tree order within the inclusive descendants of `root`.
+    /// https://dom.spec.whatwg.org/#concept-tree-preceding
+    pub fn preceding(&self, id: NodeId, root: NodeId) -> Option<NodeId> {
+        if id == root {
+            return None;
+        }
+        match self.node(id).previous_sibling {
+            // the last inclusive descendant of the previous sibling
+            Some(mut node) => {
+                while let Some(child) = self.node(node).last_child {
+                    node = child;
+                }
+                Some(node)
+            }
+            None => self.node(id).parent,
+        }
+    }
+}
+
+/// `WhatToShow` is a bitmask which tells the types of nodes `TreeWalker` and `NodeIterator` show.
+/// https://dom.spec.whatwg.org/#interface-nodefilter
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WhatToShow(pub u32);
+
+impl WhatToShow {
+    pub const ALL: WhatToShow = WhatToShow(0xFFFF_FFFF);
+    pub const ELEMENT: WhatToShow = WhatToShow(0x1);
+    pub const ATTRIBUTE: WhatToShow = WhatToShow(0x2);
+    pub const TEXT: WhatToShow = WhatToShow(0x4);
+    pub const CDATA_SECTION: WhatToShow = WhatToShow(0x8);
+    pub const PROCESSING_INSTRUCTION: WhatToShow = WhatToShow(0x40);
+    pub const COMMENT: WhatToShow = WhatToShow(0x80);
+    pub const DOCUMENT: WhatToShow = WhatToShow(0x100);
+    pub const DOCUMENT_TYPE: WhatToShow = WhatToShow(0x200);
+    pub const DOCUMENT_FRAGMENT: WhatToShow = WhatToShow(0x400);
+
+    /// `shows` returns true if nodes of `node_type` are included in the mask.
+    pub fn shows(self, node_type: &NodeType) -> bool {
+        let bit = match node_type {
+            NodeType::Element(_) => WhatToShow::ELEMENT,
+            NodeType::Text(_) => WhatToShow::TEXT,
+            NodeType::CDATASection(_) => WhatToShow::CDATA_SECTION,
+            NodeType::ProcessingInstruction(_) => WhatToShow::PROCESSING_INSTRUCTION,
+            NodeType::Comment(_) => WhatToShow::COMMENT,
+            NodeType::Document => WhatToShow::DOCUMENT,
+            NodeType::DocumentType(_) => WhatToShow::DOCUMENT_TYPE,
+            NodeType::DocumentFragment => WhatToShow::DOCUMENT_FRAGMENT,
+        };
+        self.0 & bit.0 != 0
+    }
+}
+
+/// `FilterResult` is a value returned by node filters.
+/// https://dom.spec.whatwg.org/#interface-nodefilter
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FilterResult {
+    Accept,
+    Reject,
+    Skip,
+}
+
+/// `NodeFilter` decides whether `TreeWalker` and `NodeIterator` show the node or not.
+/// An error returned by the filter aborts the traversal, e.g. an exception thrown by a filter in scripts.
+pub type NodeFilter<'a, E> = dyn FnMut(NodeId) -> Result<FilterResult, E> + 'a;
+
+/// `filter_node` runs the steps at https://dom.spec.whatwg.org/#concept-node-filter.
+///
+/// NOTE: the document is borrowed only while it's read, so that `filter` can modify the document.
+fn filter_node<E>(
+    document: &RefCell<Document>,
+    what_to_show: WhatToShow,
+    id: NodeId,
+    filter: &mut NodeFilter<'_, E>,
+) -> Result<FilterResult, E> {
+    if !what_to_show.shows(&document.borrow().node(id).node_type) {
+        return Ok(FilterResult::Skip);
+    }
+    filter(id)
+}
+
+/// `TreeWalker` walks around the subtree rooted at `root`, showing the nodes which pass the filter.
+/// https://dom.spec.whatwg.org/#interface-treewalker
+///
+/// Each method takes the document in `RefCell` because the filter may modify the tree while walking.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TreeWalker {
+    pub root: NodeId,
+    pub what_to_show: WhatToShow,
+    pub current_node: NodeId,
+}
+
+impl TreeWalker {
+    pub fn new(root: NodeId, what_to_show: WhatToShow) -> TreeWalker {
+        TreeWalker {
+            root,
+            what_to_show,
+            current_node: root,
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-parentnode
+    pub fn parent_node<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        let mut node = self.current_node;
+        while node != self.root {
+            node = match document.borrow().node(node).parent {
+                Some(parent) => parent,
+                None => return Ok(None),
+            };
+            if filter_node(document, self.what_to_show, node, filter)? == FilterResult::Accept {
+                self.current_node = node;
+                return Ok(Some(node));
+            }
+        }
+        Ok(None)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-firstchild
+    pub fn first_child<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        self.traverse_children(document, filter, true)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-lastchild
+    pub fn last_child<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        self.traverse_children(document, filter, false)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-previoussibling
+    pub fn previous_sibling<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        self.traverse_siblings(document, filter, false)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-nextsibling
+    pub fn next_sibling<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        self.traverse_siblings(document, filter, true)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-previousnode
+    pub fn previous_node<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        let mut node = self.current_node;
+        while node != self.root {
+            let mut sibling = document.borrow().node(node).previous_sibling;
+            while let Some(s) = sibling {
+                node = s;
+                let mut result = filter_node(document, self.what_to_show, node, filter)?;
+                while result != FilterResult::Reject {
+                    node = match document.borrow().node(node).last_child {
+                        Some(child) => child,
+                        None => break,
+                    };
+                    result = filter_node(document, self.what_to_show, node, filter)?;
+                }
+                if result == FilterResult::Accept {
+                    self.current_node = node;
+                    return Ok(Some(node));
+                }
+                sibling = document.borrow().node(node).previous_sibling;
+            }
+            if node == self.root {
+                return Ok(None);
+            }
+            node = match document.borrow().node(node).parent {
+                Some(parent) => parent,
+                None => return Ok(None),
+            };
+            if filter_node(document, self.what_to_show, node, filter)? == FilterResult::Accept {
+                self.current_node = node;
+                return Ok(Some(node));
+            }
+        }
+        Ok(None)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-nextnode
+    pub fn next_node<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        let mut node = self.current_node;
+        let mut result = FilterResult::Accept;
+        loop {
+            while result != FilterResult::Reject {
+                node = match document.borrow().node(node).first_child {
+                    Some(child) => child,
+                    None => break,
+                };
+                result = filter_node(document, self.what_to_show, node, filter)?;
+                if result == FilterResult::Accept {
+                    self.current_node = node;
+                    return Ok(Some(node));
+                }
+            }
+
+            // go up until a node which has the next sibling is found.
+            let mut temporary = Some(node);
+            let mut sibling = None;
+            while let Some(t) = temporary {
+                if t == self.root {
+                    return Ok(None);
+                }
+                let (next_sibling, parent) = {
+                    let document = document.borrow();
+                    let node = document.node(t);
+                    (node.next_sibling, node.parent)
+                };
+                sibling = next_sibling;
+                if sibling.is_some() {
+                    break;
+                }
+                temporary = parent;
+            }
+            // NOTE: the standard doesn't care about this case, but the current node may have been moved out of the root.
+            node = match sibling {
+                Some(sibling) => sibling,
+                None => return Ok(None),
+            };
+
+            result = filter_node(document, self.what_to_show, node, filter)?;
+            if result == FilterResult::Accept {
+                self.current_node = node;
+                return Ok(Some(node));
+            }
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-traverse-children
+    fn traverse_children<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+        first: bool,
+    ) -> Result<Option<NodeId>, E> {
+        let child_of = |id: NodeId| {
+            let document = document.borrow();
+            let node = document.node(id);
+            if first {
+                node.first_child
+            } else {
+                node.last_child
+            }
+        };
+        let sibling_of = |id: NodeId| {
+            let document = document.borrow();
+            let node = document.node(id);
+            if first {
+                node.next_sibling
+            } else {
+                node.previous_sibling
+            }
+        };
+
+        let mut next = child_of(self.current_node);
+        'outer: while let Some(mut node) = next {
+            let result = filter_node(document, self.what_to_show, node, filter)?;
+            if result == FilterResult::Accept {
+                self.current_node = node;
+                return Ok(Some(node));
+            }
+            if result == FilterResult::Skip {
+                if let Some(child) = child_of(node) {
+                    next = Some(child);
+                    continue;
+                }
+            }
+            loop {
+                if let Some(sibling) = sibling_of(node) {
+                    next = Some(sibling);
+                    continue 'outer;
+                }
+                match document.borrow().node(node).parent {
+                    Some(parent) if parent != self.root && parent != self.current_node => {
+                        node = parent
+                    }
+                    _ => return Ok(None),
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-traverse-siblings
+    fn traverse_siblings<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+        next: bool,
+    ) -> Result<Option<NodeId>, E> {
+        let sibling_of = |id: NodeId| {
+            let document = document.borrow();
+            let node = document.node(id);
+            if next {
+                node.next_sibling
+            } else {
+                node.previous_sibling
+            }
+        };
+        let child_of = |id: NodeId| {
+            let document = document.borrow();
+            let node = document.node(id);
+            if next {
+                node.first_child
+            } else {
+                node.last_child
+            }
+        };
+
+        let mut node = self.current_node;
+        if node == self.root {
+            return Ok(None);
+        }
+        loop {
+            let mut sibling = sibling_of(node);
+            while let Some(s) = sibling {
+                node = s;
+                let result = filter_node(document, self.what_to_show, node, filter)?;
+                if result == FilterResult::Accept {
+                    self.current_node = node;
+                    return Ok(Some(node));
+                }
+                sibling = child_of(node);
+                if result == FilterResult::Reject || sibling.is_none() {
+                    sibling = sibling_of(node);
+                }
+            }
+            node = match document.borrow().node(node).parent {
+                Some(parent) if parent != self.root => parent,
+                _ => return Ok(None),
+            };
+            if filter_node(document, self.what_to_show, node, filter)? == FilterResult::Accept {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// `NodeIterator` iterates over the inclusive descendants of `root` in tree order, showing the nodes which pass the filter.
+/// https://dom.spec.whatwg.org/#interface-nodeiterator
+///
+/// TODO (enhancement): run the pre-removing steps (https://dom.spec.whatwg.org/#nodeiterator-pre-removing-steps) when a node is removed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NodeIterator {
+    pub root: NodeId,
+    pub what_to_show: WhatToShow,
+    pub reference_node: NodeId,
+    pub pointer_before_reference_node: bool,
+}
+
+impl NodeIterator {
+    pub fn new(root: NodeId, what_to_show: WhatToShow) -> NodeIterator {
+        NodeIterator {
+            root,
+            what_to_show,
+            reference_node: root,
+            pointer_before_reference_node: true,
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-nodeiterator-nextnode
+    pub fn next_node<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        self.traverse(document, filter, true)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-nodeiterator-previousnode
+    pub fn previous_node<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+    ) -> Result<Option<NodeId>, E> {
+        self.traverse(document, filter, false)
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-nodeiterator-traverse
+    fn traverse<E>(
+        &mut self,
+        document: &RefCell<Document>,
+        filter: &mut NodeFilter<'_, E>,
+        next: bool,
+    ) -> Result<Option<NodeId>, E> {
+        let mut node = self.reference_node;
+        let mut before_node = self.pointer_before_reference_node;
+        loop {
+            if next == before_node {
+                before_node = !next;
+            } else {
+                let document = document.borrow();
+                let moved = if next {
+                    document.following(node, self.root)
+                } else {
+                    document.preceding(node, self.root)
+                };
+                node = match moved {
+                    Some(moved) => moved,
+                    None => return Ok(None),
+                };
+            }
+            if filter_node(document, self.what_to_show, node, filter)? == FilterResult::Accept {
+                break;
+            }
+        }
+        self.reference_node = node;
+        self.pointer_before_reference_node = before_node;
+        Ok(Some(node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dom::{AttrMap, Comment, Element, Text};
+    use std::convert::Infallible;
+
+    /// `document` returns a document with `<div><p>a<!--b--></p><p><i>c</i></p></div>` and the IDs of the nodes in tree order.
+    fn document() -> (RefCell<Document>, Vec<NodeId>) {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let element =
+            |tag_name: &str, children| Element::new(tag_name.to_string(), AttrMap::new(), children);
+        let div = document.append_tree(
+            root,
+            element(
+                "div",
+                vec![
+                    element(
+                        "p",
+                        vec![Text::new("a".to_string()), Comment::new("b".to_string())],
+                    ),
+                    element("p", vec![element("i", vec![Text::new("c".to_string())])]),
+                ],
+            ),
+        );
+        let ids = document.descendants(div).collect();
+        (RefCell::new(document), ids)
+    }
+
+    fn accept_all(_: NodeId) -> Result<FilterResult, Infallible> {
+        Ok(FilterResult::Accept)
+    }
+
+    #[test]
+    fn test_iterators() {
+        let (document, ids) = document();
+        let document = document.into_inner();
+        let (p1, a, b, p2, i, c) = (ids[0], ids[1], ids[2], ids[3], ids[4], ids[5]);
+        let div = document.document_element().unwrap();
+
+        assert_eq!(
+            document.inclusive_ancestors(c).collect::<Vec<_>>(),
+            vec![c, i, p2, div, document.root()]
+        );
+        assert_eq!(document.following_siblings(a).collect::<Vec<_>>(), vec![b]);
+        assert_eq!(
+            document.preceding_siblings(p2).collect::<Vec<_>>(),
+            vec![p1]
+        );
+        assert_eq!(document.following(b, div), Some(p2));
+        assert_eq!(document.following(c, div), None);
+        assert_eq!(document.preceding(p2, div), Some(b));
+        assert_eq!(document.preceding(p1, div), Some(div));
+        assert_eq!(document.preceding(div, div), None);
+    }
+
+    #[test]
+    fn test_what_to_show() {
+        let show = WhatToShow(WhatToShow::ELEMENT.0 | WhatToShow::COMMENT.0);
+        assert!(show.shows(&NodeType::Comment(Comment {
+            data: "".to_string()
+        })));
+        assert!(!show.shows(&NodeType::Document));
+        assert!(WhatToShow::ALL.shows(&NodeType::DocumentFragment));
+    }
+
+    #[test]
+    fn test_tree_walker() {
+        let (document, ids) = document();
+        let (p1, a, b, p2, i, c) = (ids[0], ids[1], ids[2], ids[3], ids[4], ids[5]);
+        let div = document.borrow().document_element().unwrap();
+
+        let mut walker = TreeWalker::new(div, WhatToShow::ALL);
+        let mut nodes = vec![];
+        while let Ok(Some(node)) = walker.next_node(&document, &mut accept_all) {
+            nodes.push(node);
+        }
+        assert_eq!(nodes, vec![p1, a, b, p2, i, c]);
+        nodes.clear();
+        while let Ok(Some(node)) = walker.previous_node(&document, &mut accept_all) {
+            nodes.push(node);
+        }
+        assert_eq!(nodes, vec![i, p2, b, a, p1, div]);
+
+        // skipped nodes are not shown, but their children are.
+        let mut walker = TreeWalker::new(div, WhatToShow::TEXT);
+        assert_eq!(walker.first_child(&document, &mut accept_all), Ok(Some(a)));
+        assert_eq!(walker.next_sibling(&document, &mut accept_all), Ok(Some(c)));
+        assert_eq!(walker.parent_node(&document, &mut accept_all), Ok(None));
+        assert_eq!(
+            walker.previous_sibling(&document, &mut accept_all),
+            Ok(Some(a))
+        );
+
+        // rejected nodes are not shown, and neither are their children.
+        let mut walker = TreeWalker::new(div, WhatToShow::ALL);
+        let mut reject_p2 = |id: NodeId| -> Result<_, Infallible> {
+            Ok(if id == p2 {
+                FilterResult::Reject
+            } else {
+                FilterResult::Accept
+            })
+        };
+        assert_eq!(walker.last_child(&document, &mut reject_p2), Ok(Some(p1)));
+        walker.current_node = b;
+        assert_eq!(walker.next_node(&document, &mut reject_p2), Ok(None));
+        walker.current_node = c;
+        assert_eq!(walker.parent_node(&document, &mut reject_p2), Ok(Some(i)));
+        assert_eq!(walker.parent_node(&document, &mut reject_p2), Ok(Some(div)));
+
+        // an error from the filter aborts the walk.
+        let mut walker = TreeWalker::new(div, WhatToShow::ALL);
+        assert_eq!(
+            walker.next_node(&document, &mut |_| Err("error")),
+            Err("error")
+        );
+        assert_eq!(walker.current_node, div);
+    }
+
+    #[test]
+    fn test_node_iterator() {
+        let (document, ids) = document();
+        let (p1, a, _, p2, i, c) = (ids[0], ids[1], ids[2], ids[3], ids[4], ids[5]);
+        let div = document.borrow().document_element().unwrap();
+
+        let mut iterator = NodeIterator::new(div, WhatToShow::ELEMENT);
+        let mut nodes = vec![];
+        while let Ok(Some(node)) = iterator.next_node(&document, &mut accept_all) {
+            nodes.push(node);
+        }
+        assert_eq!(nodes, vec![div, p1, p2, i]);
+        assert_eq!(iterator.reference_node, i);
+        assert!(!iterator.pointer_before_reference_node);
+
+        // the filter may modify the document.
+        assert_eq!(
+            iterator.previous_node(&document, &mut |id| {
+                if id == i {
+                    document.borrow_mut().detach(c);
+                }
+                Ok::<_, Infallible>(FilterResult::Accept)
+            }),
+            Ok(Some(i))
+        );
+        assert_eq!(document.borrow().children(i).next(), None);
+        assert_eq!(
+            iterator.previous_node(&document, &mut accept_all),
+            Ok(Some(p2))
+        );
+
+        let mut iterator = NodeIterator::new(div, WhatToShow::TEXT);
+        assert_eq!(iterator.next_node(&document, &mut accept_all), Ok(Some(a)));
+        assert_eq!(iterator.next_node(&document, &mut accept_all), Ok(None));
+    }
+}
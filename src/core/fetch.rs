@@ -2,15 +2,30 @@
 
 use crate::url::{ParseError, Url};
 use log::{error, info};
-use num_derive::{self, FromPrimitive};
 use reqwest;
 use std::fs;
 use std::{collections::HashMap, fmt, str::FromStr};
 use thiserror::Error;
 
-#[derive(Debug, PartialEq, FromPrimitive)]
-pub enum HTTPStatus {
-    OK = 200,
+/// `HTTPStatus` is the status code of a response.
+/// https://fetch.spec.whatwg.org/#concept-response-status
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HTTPStatus(pub u16);
+
+impl HTTPStatus {
+    pub const OK: HTTPStatus = HTTPStatus(200);
+
+    /// `is_ok` returns true if the status is in the range 200 to 299, which is called an *ok status*.
+    /// https://fetch.spec.whatwg.org/#ok-status
+    pub fn is_ok(&self) -> bool {
+        (200..=299).contains(&self.0)
+    }
+}
+
+impl fmt::Display for HTTPStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -77,6 +92,21 @@ pub struct Response {
     pub data: Vec<u8>,
 }
 
+impl Response {
+    /// `content_type` returns the value of `Content-Type` header, if any.
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("content-type")
+    }
+
+    /// `header` returns the value of the header with the name, which is compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum FetchError {
     #[error("failed to fetch because of something")]
@@ -124,12 +154,25 @@ pub fn fetch(request: Request) -> Result<Response, FetchError> {
                         "[http(s):] remote resource at {} is requested.",
                         u.to_string()
                     );
-                    match reqwest::blocking::get(u.to_string()).and_then(|resp| resp.bytes()) {
-                        Ok(content) => Ok(Response {
+                    match reqwest::blocking::get(u.to_string()).and_then(|resp| {
+                        let status = HTTPStatus(resp.status().as_u16());
+                        let headers = resp
+                            .headers()
+                            .iter()
+                            .filter_map(|(name, value)| {
+                                value
+                                    .to_str()
+                                    .ok()
+                                    .map(|value| (name.to_string(), value.to_string()))
+                            })
+                            .collect::<HeaderMap>();
+                        resp.bytes().map(|content| (status, headers, content))
+                    }) {
+                        Ok((status, headers, content)) => Ok(Response {
                             url: u,
-                            status: HTTPStatus::OK,
+                            status,
                             rtype: ResponseType::Basic,
-                            headers: HeaderMap::new(),
+                            headers: headers,
                             data: content.to_vec(),
                         }),
                         Err(_e) => Err(FetchError::NetworkError { response: None }),
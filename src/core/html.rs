@@ -1,27 +1,35 @@
 //! This module includes some implementations on HTML.
 
-use crate::dom::{AttrMap, Document, Element, Node, Text};
-use crate::fetch::Response;
-#[allow(unused_imports)]
-use combine::EasyParser;
-use combine::{
-    attempt,
-    error::{StreamError, StringStreamError},
-    many,
-    parser::char::{newline, space},
-};
-use combine::{between, many1, parser, sep_by, Parser, Stream};
-use combine::{choice, error::ParseError};
-use combine::{
-    parser::char::{char, letter},
-    satisfy,
+pub mod encoding;
+mod entities;
+pub mod parser;
+pub mod serializer;
+pub mod tokenizer;
+pub mod tree_builder;
+
+use self::{
+    parser::{Parser, ParserStatus},
+    tokenizer::Tokenizer,
+    tree_builder::TreeBuilder,
 };
+use crate::dom::{AttrMap, Document, Element, NodeTree};
+use crate::fetch::Response;
+use std::string::FromUtf8Error;
 use thiserror::Error;
 
+/// `VOID_ELEMENTS` is a list of elements which never have any contents, defined at https://html.spec.whatwg.org/multipage/syntax.html#void-elements.
+pub const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
 #[derive(Error, Debug, PartialEq)]
 pub enum HTMLParseError {
-    #[error("failed to parse; {0}")]
-    InvalidResourceError(StringStreamError),
+    #[error("failed to decode the resource; {0}")]
+    DecodeError(FromUtf8Error),
+
+    #[error("failed to decode the resource; {0} is not supported")]
+    UnsupportedEncodingError(&'static str),
 }
 
 // [NOTE] Specification on HTML parsing: https://html.spec.whatwg.org/multipage/parsing.html#parsing
@@ -33,155 +41,45 @@ pub enum HTMLParseError {
 // The first one, tokenization stage, generates tokens from input stream.
 // The latter one, tree construction stage, constructs a DOM while handling scripts inside <script> tags.
 //
-// This implementation omits details of those two stages for simplicity.
-// Please check the following if you'd like to know about the parsing process more deeply:
+// puppy implements the two stages in `tokenizer` and `tree_builder` modules respectively.
+// Those are not complete; please check the following if you'd like to know about the parsing process more deeply:
 // - html5ever crate by Serve project https://github.com/servo/html5ever
 // - HTMLDocumentParser, HTMLTokenizer, HTMLTreeBuilder of Chromium (src/third_party/blink/renderer/core/html/parser/*)
 
-/// This functions parses `response` as HTML in non-standard manner.
+/// This functions parses `response` as HTML.
+/// Since the parsing algorithm recovers from any kind of malformed markup, it always produces a `Document` unless the resource cannot be decoded.
+/// Scripts in the document are not executed; use `parser::Parser` to run them while parsing.
 pub fn parse(response: Response) -> Result<Document, HTMLParseError> {
-    // NOTE: Here we assume the resource is HTML and encoded by UTF-8.
-    // We should determine character encoding as follows:
-    // https://html.spec.whatwg.org/multipage/parsing.html#the-input-byte-streama
-    let nodes = parse_without_normalziation(response.data);
-    match nodes {
-        Ok(nodes) => {
-            let document_element = if nodes.len() == 1 {
-                nodes.into_iter().nth(0).unwrap()
-            } else {
-                Element::new("html".to_string(), AttrMap::new(), nodes)
-            };
-            Ok(Document::new(
-                response.url.to_string(),
-                response.url.to_string(),
-                document_element,
-            ))
-        }
-        Err(e) => Err(e),
-    }
-}
-
-pub fn parse_without_normalziation(data: Vec<u8>) -> Result<Vec<Box<Node>>, HTMLParseError> {
-    // NOTE: Here we assume the resource is HTML and encoded by UTF-8.
-    // We should determine character encoding as follows:
-    // https://html.spec.whatwg.org/multipage/parsing.html#the-input-byte-streama
-    let body = String::from_utf8(data).unwrap();
-
-    nodes()
-        .parse(&body as &str)
-        .map(|(nodes, _)| nodes)
-        .map_err(|e| HTMLParseError::InvalidResourceError(e))
-}
-
-// `nodes_` (and `nodes`) tries to parse input as Element or Text.
-fn nodes_<Input>() -> impl Parser<Input, Output = Vec<Box<Node>>>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    attempt(many(choice((attempt(element()), attempt(text())))))
-}
-
-/// `text` consumes input until `<` comes.
-fn text<Input>() -> impl Parser<Input, Output = Box<Node>>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    many1(satisfy(|c: char| c != '<')).map(|t| Text::new(t))
+    let mut parser = Parser::from_response(&response);
+    parser.feed(&response.data)?;
+    parser.close()?;
+    while let ParserStatus::Script(_) = parser.run() {}
+    Ok(parser.finish())
 }
 
-/// `element` consumes `<tag_name attr_name="attr_value" ...>(children)</tag_name>`.
-fn element<Input>() -> impl Parser<Input, Output = Box<Node>>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    (open_tag(), nodes(), close_tag()).and_then(
-        |((open_tag_name, attributes), children, close_tag_name)| {
-            if open_tag_name == close_tag_name {
-                Ok(Element::new(open_tag_name, attributes, children))
-            } else {
-                Err(<Input::Error as combine::error::ParseError<
-                    char,
-                    Input::Range,
-                    Input::Position,
-                >>::StreamError::message_static_message(
-                    "tag name of open tag and close tag mismatched",
-                ))
-            }
-        },
-    )
-}
-
-/// `open_tag` consumes `<tag_name attr_name="attr_value" ...>`.
-fn open_tag<Input>() -> impl Parser<Input, Output = (String, AttrMap)>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    let open_tag_name = many1::<String, _, _>(letter());
-    let open_tag_content = (
-        open_tag_name,
-        many::<String, _, _>(space().or(newline())),
-        attributes(),
-    )
-        .map(|v: (String, _, AttrMap)| (v.0, v.2));
-    between(char('<'), char('>'), open_tag_content)
-}
-
-/// close_tag consumes `</tag_name>`.
-fn close_tag<Input>() -> impl Parser<Input, Output = String>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    let close_tag_name = many1::<String, _, _>(letter());
-    let close_tag_content = (char('/'), close_tag_name).map(|v| v.1);
-    between(char('<'), char('>'), close_tag_content)
+/// This functions parses `data` as a fragment of HTML in `body` element.
+/// `data` must be encoded in UTF-8.
+pub fn parse_without_normalziation(data: Vec<u8>) -> Result<Vec<NodeTree>, HTMLParseError> {
+    let body = String::from_utf8(data).map_err(HTMLParseError::DecodeError)?;
+    let context = Element {
+        tag_name: "body".to_string(),
+        attributes: AttrMap::new(),
+    };
+    Ok(parse_fragment(body.as_str(), &context))
 }
 
-/// `attribute` consumes `name="value"`.
-fn attribute<Input>() -> impl Parser<Input, Output = (String, String)>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    let attribute_name = many1::<String, _, _>(letter());
-    let attribute_inner_value = many1::<String, _, _>(satisfy(|c: char| c != '"')).map(|x|x.replace("&quot;", "\""));
-    let attribute_value = between(char('"'), char('"'), attribute_inner_value);
-    (
-        attribute_name,
-        many::<String, _, _>(space().or(newline())),
-        char('='),
-        many::<String, _, _>(space().or(newline())),
-        attribute_value,
-    )
-        .map(|v| (v.0, v.4))
-}
+/// This functions parses `data` as a fragment of HTML in `context` element, as `innerHTML` setter does.
+/// The context decides how `data` is parsed; `<td>` is kept in `<tr>`, and any markup in `<textarea>` is parsed as text, for instance.
+/// See https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-parsing-algorithm for the details.
+pub fn parse_fragment(data: &str, context: &Element) -> Vec<NodeTree> {
+    let mut tokenizer = Tokenizer::new();
+    tokenizer.feed(data);
+    tokenizer.close();
 
-/// `attributes` consumes `name1="value1" name2="value2" ... name="value"`
-fn attributes<Input>() -> impl Parser<Input, Output = AttrMap>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    sep_by::<Vec<(String, String)>, _, _, _>(
-        attribute(),
-        many::<String, _, _>(space().or(newline())),
-    )
-    .map(|attrs: Vec<(String, String)>| {
-        let m: AttrMap = attrs.into_iter().collect();
-        m
-    })
-}
+    let mut tree_builder = TreeBuilder::for_fragment(tokenizer, context);
+    tree_builder.run();
 
-parser! {
-    fn nodes[Input]()(Input) -> Vec<Box<Node>>
-    where [Input: Stream<Token = char>]
-    {
-        nodes_()
-    }
+    tree_builder.into_fragment()
 }
 
 #[cfg(test)]
@@ -190,214 +88,419 @@ mod tests {
 
     use super::*;
     use crate::fetch::{HeaderMap, Response};
+    use crate::source::{SourcePosition, SourceSpan};
     use crate::{
-        dom::{AttrMap, Document, Element, Text},
+        dom::{AttrMap, Comment, Document, DocumentType, Element, Text},
         fetch::{HTTPStatus, ResponseType},
     };
 
+    fn response(data: &str) -> Response {
+        Response {
+            url: Url::parse("http://example.com").unwrap(),
+            status: HTTPStatus::OK,
+            rtype: ResponseType::Basic,
+            headers: HeaderMap::new(),
+            data: data.as_bytes().to_vec(),
+        }
+    }
+
+    /// `document_with` returns a document at http://example.com/ whose children are `trees`.
+    fn document_with(trees: Vec<NodeTree>) -> Document {
+        let url = Url::parse("http://example.com").unwrap().to_string();
+        let mut document = Document::new(url.clone(), url);
+        let root = document.root();
+        for tree in trees {
+            document.append_tree(root, tree);
+        }
+        document
+    }
+
+    fn document(body: Vec<NodeTree>) -> Document {
+        document_with(vec![Element::new(
+            "html".to_string(),
+            AttrMap::new(),
+            vec![
+                Element::new("head".to_string(), AttrMap::new(), vec![]),
+                Element::new("body".to_string(), AttrMap::new(), body),
+            ],
+        )])
+    }
+
+    fn element(tag_name: &str, children: Vec<NodeTree>) -> NodeTree {
+        Element::new(tag_name.to_string(), AttrMap::new(), children)
+    }
+
+    fn text(data: &str) -> NodeTree {
+        Text::new(data.to_string())
+    }
+
     // parsing tests of attributes
     #[test]
-    fn test_parse_attribute() {
+    fn test_parse_attributes() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "test".to_string());
+        attributes.insert("class".to_string(), "sample".to_string());
+        assert_eq!(
+            parse_without_normalziation("<p id=\"test\" class=\"sample\"></p>".into()),
+            Ok(vec![Element::new("p".to_string(), attributes, vec![])])
+        );
+    }
+
+    #[test]
+    fn test_parse_relaxed_attributes() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("data-id".to_string(), "x".to_string());
+        attributes.insert("aria-label".to_string(), "".to_string());
+        attributes.insert("class".to_string(), "a b".to_string());
+        attributes.insert("width".to_string(), "10".to_string());
+        attributes.insert("disabled".to_string(), "".to_string());
+        attributes.insert("value".to_string(), "".to_string());
         assert_eq!(
-            attribute().easy_parse("test=\"foobar\""),
-            Ok((("test".to_string(), "foobar".to_string()), ""))
+            parse_without_normalziation(
+                "<input data-id=\"x\" aria-label class='a b' width=10 disabled value=\"\">".into()
+            ),
+            Ok(vec![Element::new("input".to_string(), attributes, vec![])])
         );
+    }
 
+    #[test]
+    fn test_parse_duplicate_attributes() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "first".to_string());
+        attributes.insert("class".to_string(), "a".to_string());
         assert_eq!(
-            attribute().easy_parse("test = \"foobar\""),
-            Ok((("test".to_string(), "foobar".to_string()), ""))
+            parse_without_normalziation("<p id=first class=a ID=second class=\"b\"></p>".into()),
+            Ok(vec![Element::new("p".to_string(), attributes, vec![])])
         );
+    }
 
+    // parsing tests of documents
+    #[test]
+    fn test_parse_single_without_nest() {
         assert_eq!(
-            attribute().easy_parse("test = \"&quot;&quot;\""),
-            Ok((("test".to_string(), "\"\"".to_string()), ""))
+            parse(response("<p>Hello World</p>")),
+            Ok(document(vec![element("p", vec![text("Hello World")])]))
         )
     }
 
     #[test]
-    fn test_parse_attributes() {
-        let mut expected_map = AttrMap::new();
-        expected_map.insert("test".to_string(), "foobar".to_string());
+    fn test_parse_two_without_nest() {
         assert_eq!(
-            attributes().easy_parse("test=\"foobar\""),
-            Ok((expected_map, ""))
-        );
+            parse(response("<p>Hello World (1)</p><p>Hello World (2)</p>")),
+            Ok(document(vec![
+                element("p", vec![text("Hello World (1)")]),
+                element("p", vec![text("Hello World (2)")]),
+            ]))
+        )
+    }
 
-        assert_eq!(attributes().easy_parse(""), Ok((AttrMap::new(), "")))
+    #[test]
+    fn test_parse_with_nest() {
+        assert_eq!(
+            parse(response("<div><p>nested (1)</p><p>nested (2)</p></div>")),
+            Ok(document(vec![element(
+                "div",
+                vec![
+                    element("p", vec![text("nested (1)")]),
+                    element("p", vec![text("nested (2)")]),
+                ]
+            )]))
+        )
     }
 
-    // parsing tests of open tags
     #[test]
-    fn test_parse_open_tag_without_attributes() {}
+    fn test_parse_with_explicit_head_and_body() {
+        assert_eq!(
+            parse(response(
+                "<html><head><title>hi</title></head><body>Hello</body></html>"
+            )),
+            Ok(document_with(vec![element(
+                "html",
+                vec![
+                    element("head", vec![element("title", vec![text("hi")])]),
+                    element("body", vec![text("Hello")]),
+                ]
+            )]))
+        )
+    }
 
     #[test]
-    fn test_parse_open_tag() {
-        {
-            assert_eq!(
-                open_tag().easy_parse("<p>aaaa"),
-                Ok((("p".to_string(), AttrMap::new()), "aaaa"))
-            );
-        }
-        {
-            let mut attributes = AttrMap::new();
-            attributes.insert("id".to_string(), "test".to_string());
-            assert_eq!(
-                open_tag().easy_parse("<p id=\"test\">"),
-                Ok((("p".to_string(), attributes), ""))
-            )
-        }
+    fn test_parse_with_implied_end_tags() {
+        assert_eq!(
+            parse(response("<p>one<p>two<ul><li>a<li>b</ul>")),
+            Ok(document(vec![
+                element("p", vec![text("one")]),
+                element("p", vec![text("two")]),
+                element(
+                    "ul",
+                    vec![
+                        element("li", vec![text("a")]),
+                        element("li", vec![text("b")])
+                    ]
+                ),
+            ]))
+        )
+    }
 
-        {
-            let result = open_tag().easy_parse("<p id=\"test\" class=\"sample\">");
-            let mut attributes = AttrMap::new();
-            attributes.insert("id".to_string(), "test".to_string());
-            attributes.insert("class".to_string(), "sample".to_string());
-            assert_eq!(result, Ok((("p".to_string(), attributes), "")));
-        }
+    #[test]
+    fn test_parse_with_mismatched_end_tags() {
+        assert_eq!(
+            parse(response("<div><p>Hello World</div></span>")),
+            Ok(document(vec![element(
+                "div",
+                vec![element("p", vec![text("Hello World")])]
+            )]))
+        );
+        assert_eq!(parse(response("a < b")), Ok(document(vec![text("a < b")])));
+    }
 
-        {
-            assert!(open_tag().easy_parse("<p id>").is_err());
-        }
+    #[test]
+    fn test_parse_with_misnested_formatting_elements() {
+        assert_eq!(
+            parse(response("<b>1<i>2</b>3</i>")),
+            Ok(document(vec![
+                element("b", vec![text("1"), element("i", vec![text("2")])]),
+                element("i", vec![text("3")]),
+            ]))
+        );
+        assert_eq!(
+            parse(response("<b>1<p>2</b>3</p>")),
+            Ok(document(vec![
+                element("b", vec![text("1")]),
+                element("p", vec![element("b", vec![text("2")]), text("3")]),
+            ]))
+        );
     }
 
-    // parsing tests of close tags
     #[test]
-    fn test_parse_close_tag() {
-        let result = close_tag().easy_parse("</p>");
-        assert_eq!(result, Ok(("p".to_string(), "")))
+    fn test_parse_with_tables() {
+        assert_eq!(
+            parse(response("<table><tr><td>1</td></tr>x</table>")),
+            Ok(document(vec![
+                text("x"),
+                element(
+                    "table",
+                    vec![element(
+                        "tbody",
+                        vec![element("tr", vec![element("td", vec![text("1")])])]
+                    )]
+                ),
+            ]))
+        );
     }
 
-    // parsing tests of an element
     #[test]
-    fn test_parse_element() {
+    fn test_parse_void_elements() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("type".to_string(), "text".to_string());
         assert_eq!(
-            element().easy_parse("<p></p>"),
-            Ok((Element::new("p".to_string(), AttrMap::new(), vec![]), ""))
+            parse(response(
+                "<p>a<br>b<img/><input type=\"text\"></input>c</p>"
+            )),
+            Ok(document(vec![element(
+                "p",
+                vec![
+                    text("a"),
+                    element("br", vec![]),
+                    text("b"),
+                    element("img", vec![]),
+                    Element::new("input".to_string(), attributes, vec![]),
+                    text("c"),
+                ]
+            )]))
+        );
+        assert_eq!(
+            parse(response("<div/>x")),
+            Ok(document(vec![element("div", vec![text("x")])]))
         );
+    }
 
+    #[test]
+    fn test_parse_raw_text_elements() {
+        let script = "if (a < b && c > d) { x.innerHTML = \"</div><p>\"; } // <!-- </script";
+        let document = parse(response(&format!(
+            "<script>{}</script><style>div > p {{ color: red; }}</style>",
+            script
+        )))
+        .unwrap();
+        assert_eq!(document.get_script_inners(), vec![script.to_string()]);
         assert_eq!(
-            element().easy_parse("<p>Hello World</p>"),
-            Ok((
-                Element::new(
-                    "p".to_string(),
-                    AttrMap::new(),
-                    vec![Text::new("Hello World".to_string())]
-                ),
-                ""
-            ))
+            document.get_style_inners(),
+            vec!["div > p { color: red; }".to_string()]
         );
 
-        assert!(element().easy_parse("<p>Hello World</div>").is_err());
+        assert_eq!(
+            parse_without_normalziation(
+                "<textarea><b>&lt;bold&gt;</b></textarea><xmp>&amp;</xmp>".into()
+            ),
+            Ok(vec![
+                element("textarea", vec![text("<b><bold></b>")]),
+                element("xmp", vec![text("&amp;")]),
+            ])
+        );
+        assert_eq!(
+            parse(response("<title>a &amp; <b>b</b></title><p>x</p>")),
+            Ok(document_with(vec![element(
+                "html",
+                vec![
+                    element("head", vec![element("title", vec![text("a & <b>b</b>")])]),
+                    element("body", vec![element("p", vec![text("x")])]),
+                ]
+            )]))
+        );
+        assert_eq!(
+            parse_without_normalziation("<plaintext></plaintext><p>".into()),
+            Ok(vec![element("plaintext", vec![text("</plaintext><p>")])])
+        );
     }
 
-    // parsing tests of a tag
     #[test]
-    fn test_parse_text() {
-        {
-            assert_eq!(
-                text().easy_parse("Hello World"),
-                Ok((Text::new("Hello World".to_string()), ""))
-            );
-        }
-        {
-            assert_eq!(
-                text().easy_parse("Hello World<"),
-                Ok((Text::new("Hello World".to_string()), "<"))
-            );
-        }
+    fn test_parse_with_encodings() {
+        let mut shift_jis = response("");
+        shift_jis.data = b"<p>\x82\xA0</p>".to_vec();
+        shift_jis.headers.insert(
+            "Content-Type".to_string(),
+            "text/html; charset=Shift_JIS".to_string(),
+        );
+        assert_eq!(
+            parse(shift_jis),
+            Ok(document(vec![element("p", vec![text("\u{3042}")])]))
+        );
+
+        let mut shift_jis_with_meta = response("");
+        shift_jis_with_meta.data = b"<meta charset=shift_jis><p>\x82\xA0</p>".to_vec();
+        let parsed = parse(shift_jis_with_meta).unwrap();
+        assert_eq!(
+            parsed.inner_text(parsed.document_element().unwrap()),
+            "\u{3042}".to_string()
+        );
+
+        let mut latin1 = response("");
+        latin1.data = b"<p>caf\xE9</p>".to_vec();
+        assert_eq!(
+            parse(latin1),
+            Ok(document(vec![element("p", vec![text("caf\u{E9}")])]))
+        );
+
+        let mut unsupported = response("");
+        unsupported.data = b"<meta charset=iso-2022-kr><p>x</p>".to_vec();
+        assert_eq!(
+            parse(unsupported),
+            Err(HTMLParseError::UnsupportedEncodingError("replacement"))
+        );
     }
 
-    // parsing tests of documents
     #[test]
-    fn test_parse_single_without_nest() {
-        let url = "http://example.com";
-        let s = Response {
-            url: Url::parse(url).unwrap(),
-            status: HTTPStatus::OK,
-            rtype: ResponseType::Basic,
-            headers: HeaderMap::new(),
-            data: "<p>Hello World</p>".as_bytes().to_vec(),
+    fn test_parse_with_source_spans() {
+        let document = parse(response("<!DOCTYPE html>\n<p id=x>hello\n<b>world</b></p>")).unwrap();
+        let span = |start: (usize, usize), end: (usize, usize)| {
+            Some(SourceSpan::new(
+                SourcePosition::new(start.0, start.1),
+                SourcePosition::new(end.0, end.1),
+            ))
         };
-        let got = parse(s);
-        let expected = Ok(Document::new(
-            Url::parse(url).unwrap().to_string(),
-            Url::parse(url).unwrap().to_string(),
-            Element::new(
-                "p".to_string(),
-                AttrMap::new(),
-                vec![Text::new("Hello World".to_string())],
-            ),
-        ));
-        assert_eq!(got, expected)
+
+        let html = document.document_element().unwrap();
+        assert_eq!(document.node(html).source_span, None);
+        let body = document.node(html).last_child().unwrap();
+        let p = document.node(body).first_child().unwrap();
+        assert_eq!(document.node(p).source_span, span((2, 1), (2, 9)));
+        let children = document.children(p).collect::<Vec<_>>();
+        assert_eq!(document.node(children[0]).source_span, span((2, 9), (3, 1)));
+        assert_eq!(document.node(children[1]).source_span, span((3, 1), (3, 4)));
+        let doctype = document.doctype().unwrap();
+        assert_eq!(document.node(doctype).source_span, span((1, 1), (1, 16)));
     }
 
     #[test]
-    fn test_parse_two_without_nest() {
-        let url = "http://example.com";
-        let s = Response {
-            url: Url::parse(url).unwrap(),
-            status: HTTPStatus::OK,
-            rtype: ResponseType::Basic,
-            headers: HeaderMap::new(),
-            data: "<p>Hello World (1)</p><p>Hello World (2)</p>"
-                .as_bytes()
-                .to_vec(),
-        };
-        let expected = Ok(Document::new(
-            Url::parse(url).unwrap().to_string(),
-            Url::parse(url).unwrap().to_string(),
-            Element::new(
-                "html".to_string(),
-                AttrMap::new(),
+    fn test_parse_with_errors() {
+        let document = parse(response("<p>\n<b>&unknown;</p>\n<p a=1 a=2>")).unwrap();
+        let errors = document
+            .parse_errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.position.line, e.position.column))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                ("missing-doctype", 1, 1),
+                ("unknown-named-character-reference", 2, 4),
+                ("unexpected-end-tag", 2, 13),
+                ("duplicate-attribute", 3, 11),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_comments_and_doctype() {
+        // comments before the document element are kept as children of the document.
+        let expected = document_with(vec![
+            DocumentType::new("html".to_string(), "".to_string(), "".to_string()),
+            Comment::new(" before ".to_string()),
+            element(
+                "html",
                 vec![
-                    Element::new(
-                        "p".to_string(),
-                        AttrMap::new(),
-                        vec![Text::new("Hello World (1)".to_string())],
-                    ),
-                    Element::new(
-                        "p".to_string(),
-                        AttrMap::new(),
-                        vec![Text::new("Hello World (2)".to_string())],
+                    element("head", vec![]),
+                    element(
+                        "body",
+                        vec![element(
+                            "p",
+                            vec![Comment::new(" inside ".to_string()), text("a")],
+                        )],
                     ),
                 ],
             ),
-        ));
-        assert_eq!(parse(s), expected)
+            Comment::new(" after ".to_string()),
+        ]);
+        assert_eq!(
+            parse(response(
+                "<!DOCTYPE html><!-- before --><p><!-- inside -->a</p></body></html><!-- after -->"
+            )),
+            Ok(expected)
+        );
     }
 
     #[test]
-    fn test_parse_with_nest() {
-        let url = "http://example.com";
-        let s = Response {
-            url: Url::parse(url).unwrap(),
-            status: HTTPStatus::OK,
-            rtype: ResponseType::Basic,
-            headers: HeaderMap::new(),
-            data: "<div><p>nested (1)</p><p>nested (2)</p></div>"
-                .as_bytes()
-                .to_vec(),
+    fn test_parse_without_normalziation() {
+        assert_eq!(
+            parse_without_normalziation("<p>1</p>2".into()),
+            Ok(vec![element("p", vec![text("1")]), text("2")])
+        );
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        let context = |tag_name: &str| Element {
+            tag_name: tag_name.to_string(),
+            attributes: AttrMap::new(),
         };
-        let expected = Ok(Document::new(
-            Url::parse(url).unwrap().to_string(),
-            Url::parse(url).unwrap().to_string(),
-            Element::new(
-                "div".to_string(),
-                AttrMap::new(),
-                vec![
-                    Element::new(
-                        "p".to_string(),
-                        AttrMap::new(),
-                        vec![Text::new("nested (1)".to_string())],
-                    ),
-                    Element::new(
-                        "p".to_string(),
-                        AttrMap::new(),
-                        vec![Text::new("nested (2)".to_string())],
-                    ),
-                ],
-            ),
-        ));
-        assert_eq!(parse(s), expected)
+
+        assert_eq!(
+            parse_fragment("<td>1</td><td>2", &context("tr")),
+            vec![
+                element("td", vec![text("1")]),
+                element("td", vec![text("2")])
+            ]
+        );
+        // without the context, table cells outside of tables are ignored.
+        assert_eq!(
+            parse_fragment("<td>1</td><td>2", &context("div")),
+            vec![text("12")]
+        );
+        assert_eq!(
+            parse_fragment("<b>&amp;</b></textarea>", &context("textarea")),
+            vec![text("<b>&</b></textarea>")]
+        );
+        assert_eq!(
+            parse_fragment("if (a < b) { '&amp;' }", &context("script")),
+            vec![text("if (a < b) { '&amp;' }")]
+        );
+        assert_eq!(
+            parse_fragment("<option>1<option>2", &context("select")),
+            vec![
+                element("option", vec![text("1")]),
+                element("option", vec![text("2")])
+            ]
+        );
     }
 }
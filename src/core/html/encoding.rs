@@ -0,0 +1,270 @@
+//! This module includes an implementation of the encoding sniffing algorithm of HTML.
+//! The algorithm is defined at https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding.
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+
+/// `PRESCAN_LENGTH` is the number of bytes the prescan looks at.
+pub const PRESCAN_LENGTH: usize = 1024;
+
+/// `sniff` determines the character encoding of `data`.
+/// `content_type` is the value of `Content-Type` header sent with the resource, if any.
+pub fn sniff(data: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(data) {
+        return encoding;
+    }
+
+    if let Some(encoding) = content_type
+        .and_then(|value| extract_encoding_from_meta(value.to_ascii_lowercase().as_bytes()))
+    {
+        return encoding;
+    }
+
+    if let Some(encoding) = prescan(data) {
+        return encoding;
+    }
+
+    // NOTE: the standard allows implementations to guess the encoding here.
+    // puppy prefers UTF-8 whenever the resource is valid as UTF-8, and falls back to windows-1252 otherwise.
+    // `data` may be the first part of the resource, so a sequence cut off at the end is allowed.
+    match std::str::from_utf8(data) {
+        Ok(_) => UTF_8,
+        Err(e) if e.error_len().is_none() => UTF_8,
+        Err(_) => WINDOWS_1252,
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+fn prescan(data: &[u8]) -> Option<&'static Encoding> {
+    let data = &data[..std::cmp::min(data.len(), PRESCAN_LENGTH)];
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let rest = &data[pos..];
+        if rest.starts_with(b"<!--") {
+            // skip to the end of the comment; `<!-->` is a complete comment here.
+            pos = (pos + 4..data.len())
+                .find(|&i| data[i] == b'>' && data[i - 1] == b'-' && data[i - 2] == b'-')?
+                + 1;
+        } else if starts_with_ignore_ascii_case(rest, b"<meta")
+            && matches!(rest.get(5), Some(&b) if is_whitespace(b) || b == b'/')
+        {
+            pos += 6;
+            if let Some(encoding) = prescan_meta(data, &mut pos)? {
+                return Some(encoding);
+            }
+        } else if rest.len() >= 2
+            && rest[0] == b'<'
+            && (rest[1].is_ascii_alphabetic()
+                || (rest[1] == b'/' && matches!(rest.get(2), Some(b) if b.is_ascii_alphabetic())))
+        {
+            pos = (pos..data.len()).find(|&i| is_whitespace(data[i]) || data[i] == b'>')?;
+            while get_attribute(data, &mut pos)?.is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            pos = (pos..data.len()).find(|&i| data[i] == b'>')? + 1;
+        } else {
+            pos += 1;
+        }
+    }
+
+    None
+}
+
+/// `prescan_meta` processes attributes of a `<meta>` element in the prescan.
+/// It returns `None` if the data ends in the middle of the element.
+fn prescan_meta(data: &[u8], pos: &mut usize) -> Option<Option<&'static Encoding>> {
+    let mut attribute_names = vec![];
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+
+    while let Some((name, value)) = get_attribute(data, pos)? {
+        if attribute_names.contains(&name) {
+            continue;
+        }
+        match name.as_slice() {
+            b"http-equiv" if value == b"content-type" => got_pragma = true,
+            b"content" if charset.is_none() => {
+                if let Some(encoding) = extract_encoding_from_meta(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+            b"charset" => {
+                charset = Encoding::for_label(&value);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        attribute_names.push(name);
+    }
+
+    match (need_pragma, charset) {
+        (None, _) | (_, None) => Some(None),
+        (Some(true), _) if !got_pragma => Some(None),
+        (_, Some(encoding)) if encoding == UTF_16BE || encoding == UTF_16LE => Some(Some(UTF_8)),
+        (_, Some(encoding)) if encoding == X_USER_DEFINED => Some(Some(WINDOWS_1252)),
+        (_, Some(encoding)) => Some(Some(encoding)),
+    }
+}
+
+/// `get_attribute` reads an attribute at `pos` and returns its lowercased name and value.
+/// It returns `Some(None)` if there's no more attribute, and `None` if the data ends in the middle of the attribute.
+/// https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+fn get_attribute(data: &[u8], pos: &mut usize) -> Option<Option<(Vec<u8>, Vec<u8>)>> {
+    while is_whitespace(*data.get(*pos)?) || data[*pos] == b'/' {
+        *pos += 1;
+    }
+    if data[*pos] == b'>' {
+        return Some(None);
+    }
+
+    let mut name = vec![];
+    let mut value = vec![];
+
+    // attribute name
+    loop {
+        match *data.get(*pos)? {
+            b'=' if !name.is_empty() => {
+                *pos += 1;
+                break;
+            }
+            b if is_whitespace(b) => {
+                while is_whitespace(*data.get(*pos)?) {
+                    *pos += 1;
+                }
+                if data[*pos] != b'=' {
+                    return Some(Some((name, value)));
+                }
+                *pos += 1;
+                break;
+            }
+            b'/' | b'>' => return Some(Some((name, value))),
+            b => name.push(b.to_ascii_lowercase()),
+        }
+        *pos += 1;
+    }
+
+    // attribute value
+    while is_whitespace(*data.get(*pos)?) {
+        *pos += 1;
+    }
+    match data[*pos] {
+        quote @ b'"' | quote @ b'\'' => loop {
+            *pos += 1;
+            match *data.get(*pos)? {
+                b if b == quote => {
+                    *pos += 1;
+                    return Some(Some((name, value)));
+                }
+                b => value.push(b.to_ascii_lowercase()),
+            }
+        },
+        b'>' => Some(Some((name, value))),
+        _ => loop {
+            match *data.get(*pos)? {
+                b if is_whitespace(b) || b == b'>' => return Some(Some((name, value))),
+                b => value.push(b.to_ascii_lowercase()),
+            }
+            *pos += 1;
+        },
+    }
+}
+
+/// `extract_encoding_from_meta` finds `charset=...` in `value`, which is expected to be lowercased.
+/// It's also used for the value of `Content-Type` header.
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+pub fn extract_encoding_from_meta(value: &[u8]) -> Option<&'static Encoding> {
+    let mut pos = 0;
+    loop {
+        pos += value[pos..]
+            .windows(b"charset".len())
+            .position(|w| w == b"charset")?
+            + b"charset".len();
+        while matches!(value.get(pos), Some(&b) if is_whitespace(b)) {
+            pos += 1;
+        }
+        if value.get(pos) == Some(&b'=') {
+            pos += 1;
+            break;
+        }
+    }
+    while matches!(value.get(pos), Some(&b) if is_whitespace(b)) {
+        pos += 1;
+    }
+
+    let label = match value.get(pos)? {
+        quote @ b'"' | quote @ b'\'' => {
+            let len = value[pos + 1..].iter().position(|b| b == quote)?;
+            &value[pos + 1..pos + 1 + len]
+        }
+        _ => {
+            let len = value[pos..]
+                .iter()
+                .position(|&b| is_whitespace(b) || b == b';')
+                .unwrap_or(value.len() - pos);
+            &value[pos..pos + len]
+        }
+    };
+    Encoding::for_label(label)
+}
+
+fn starts_with_ignore_ascii_case(data: &[u8], prefix: &[u8]) -> bool {
+    data.len() >= prefix.len() && data[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::SHIFT_JIS;
+
+    #[test]
+    fn test_sniff() {
+        // BOM
+        assert_eq!(
+            sniff(b"\xFF\xFEa\x00", Some("text/html; charset=shift_jis")),
+            UTF_16LE
+        );
+        // Content-Type header
+        assert_eq!(
+            sniff(
+                b"<meta charset=utf-8>",
+                Some("text/html; Charset=\"Shift_JIS\"")
+            ),
+            SHIFT_JIS
+        );
+        assert_eq!(sniff(b"<meta charset=utf-8>", Some("text/html")), UTF_8);
+        // fallback
+        assert_eq!(sniff(b"<p>\xE3\x81\x82</p>", None), UTF_8);
+        assert_eq!(sniff(b"<p>caf\xE9</p>", None), WINDOWS_1252);
+        assert_eq!(sniff(b"<p>\xE3\x81", None), UTF_8);
+    }
+
+    #[test]
+    fn test_prescan() {
+        assert_eq!(prescan(b"<meta charset=\"Shift_JIS\">"), Some(SHIFT_JIS));
+        assert_eq!(prescan(b"<META CHARSET=sjis>"), Some(SHIFT_JIS));
+        assert_eq!(
+            prescan(b"<meta http-equiv=Content-Type content='text/html; charset=shift_jis'>"),
+            Some(SHIFT_JIS)
+        );
+        // `content` is used only with `http-equiv`.
+        assert_eq!(
+            prescan(b"<meta content='text/html; charset=shift_jis'>"),
+            None
+        );
+        // comments and attributes of other elements are skipped.
+        assert_eq!(
+            prescan(
+                b"<!-- <meta charset=utf-8> --><p title='<meta charset=utf-8>'><meta charset=sjis>"
+            ),
+            Some(SHIFT_JIS)
+        );
+        assert_eq!(prescan(b"<meta charset=utf-16le>"), Some(UTF_8));
+        assert_eq!(prescan(b"<meta charset=unknown>"), None);
+        assert_eq!(prescan(b"<p>no meta</p>"), None);
+    }
+}
@@ -0,0 +1,423 @@
+//! This module includes an incremental HTML parser, which can be fed a document chunk by chunk and paused to run scripts.
+//!
+//! The parser pauses whenever the end tag of `<script>` is processed, so that the script can be executed against the document parsed so far.
+//! See https://html.spec.whatwg.org/multipage/parsing.html#overview-of-the-parsing-model for the details.
+
+use super::{
+    encoding::{self, PRESCAN_LENGTH},
+    tokenizer::Tokenizer,
+    tree_builder::TreeBuilder,
+    HTMLParseError,
+};
+use crate::dom::{parse_http_date, Document, DocumentReadyState};
+use crate::fetch::Response;
+use chrono::{DateTime, FixedOffset};
+use encoding_rs::{CoderResult, Decoder, REPLACEMENT, UTF_8};
+
+/// `ParserStatus` describes why `Parser::run` returned.
+#[derive(Debug, PartialEq)]
+pub enum ParserStatus {
+    /// The parser consumed all the input given so far.
+    NeedsInput,
+    /// The parser found a script to be executed. Its text is included.
+    Script(String),
+    /// The parser reached the end of the input.
+    Finished,
+}
+
+/// `Parser` parses a document incrementally.
+///
+/// The caller feeds the resource with `feed` and `close`, and calls `run` until it returns `ParserStatus::Finished`.
+/// When `run` returns `ParserStatus::Script`, the caller can take the document parsed so far with `take_document`,
+/// execute the script, and give the document back with `restore_document` before calling `run` again.
+pub struct Parser {
+    url: String,
+    content_type: Option<String>,
+    last_modified: Option<DateTime<FixedOffset>>,
+    referrer: String,
+
+    // `undecoded` holds bytes received before the encoding is determined.
+    undecoded: Vec<u8>,
+    decoder: Option<Decoder>,
+
+    tree_builder: TreeBuilder,
+}
+
+impl Parser {
+    /// `new` creates a parser for a resource at `url`.
+    /// `content_type` is the value of `Content-Type` header sent with the resource, if any.
+    pub fn new(url: String, content_type: Option<&str>) -> Self {
+        Parser {
+            url,
+            content_type: content_type.map(|value| value.to_string()),
+            last_modified: None,
+            referrer: "".to_string(),
+
+            undecoded: vec![],
+            decoder: None,
+
+            tree_builder: TreeBuilder::new(Tokenizer::new()),
+        }
+    }
+
+    /// `from_response` creates a parser for `response`, whose headers give the metadata of the document.
+    pub fn from_response(response: &Response) -> Self {
+        let mut parser = Parser::new(response.url.to_string(), response.content_type());
+        parser.last_modified = response.header("last-modified").and_then(parse_http_date);
+        parser
+    }
+
+    /// `set_referrer` sets the URL of the document which navigated to the parsed document.
+    pub fn set_referrer(&mut self, referrer: String) {
+        self.referrer = referrer;
+    }
+
+    /// `feed` appends `data` to the input.
+    /// The encoding is determined once enough bytes are received for the prescan, or the input is closed.
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), HTMLParseError> {
+        if self.decoder.is_none() {
+            self.undecoded.extend_from_slice(data);
+            if self.undecoded.len() >= PRESCAN_LENGTH {
+                self.start_decoding(false)?;
+            }
+            return Ok(());
+        }
+        self.decode(data, false);
+        Ok(())
+    }
+
+    /// `close` marks the end of the input.
+    pub fn close(&mut self) -> Result<(), HTMLParseError> {
+        if self.decoder.is_none() {
+            self.start_decoding(true)?;
+        } else {
+            self.decode(&[], true);
+        }
+        self.tree_builder.tokenizer().close();
+        Ok(())
+    }
+
+    /// `run` parses the input received so far.
+    /// When a script is found, the insertion point is set until `run` is called next, so that `write` can insert markup after the script.
+    pub fn run(&mut self) -> ParserStatus {
+        self.tree_builder.tokenizer().clear_insertion_point();
+        match self.tree_builder.run() {
+            Some(script) => {
+                self.tree_builder.tokenizer().set_insertion_point();
+                ParserStatus::Script(script)
+            }
+            None if self.tree_builder.tokenizer().is_finished() => ParserStatus::Finished,
+            None => ParserStatus::NeedsInput,
+        }
+    }
+
+    /// `write` inserts `markup` at the insertion point as `document.write` does.
+    /// It returns false if there's no insertion point, i.e. no script is being executed.
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-document-write
+    pub fn write(&mut self, markup: &str) -> bool {
+        self.tree_builder.tokenizer().insert(markup)
+    }
+
+    /// `take_document` returns the document parsed so far.
+    pub fn take_document(&mut self) -> Document {
+        let set_metadata = self.metadata_setter(DocumentReadyState::Loading);
+        let mut document = self
+            .tree_builder
+            .take_document(self.url.clone(), self.url.clone());
+        set_metadata(&mut document);
+        document
+    }
+
+    /// `restore_document` gives back the document taken with `take_document`.
+    pub fn restore_document(&mut self, document: Document) {
+        self.tree_builder.restore_document(document);
+    }
+
+    /// `finish` returns the parsed document.
+    /// The document is *interactive*; the caller makes it *complete* after loading its subresources.
+    pub fn finish(self) -> Document {
+        let url = self.url.clone();
+        let set_metadata = self.metadata_setter(DocumentReadyState::Interactive);
+        let mut document = self.tree_builder.into_document(url.clone(), url);
+        set_metadata(&mut document);
+        document
+    }
+
+    /// `metadata_setter` returns a function which sets the metadata of the resource to a document.
+    fn metadata_setter(&self, ready_state: DocumentReadyState) -> impl FnOnce(&mut Document) {
+        let encoding = self
+            .decoder
+            .as_ref()
+            .map(|decoder| decoder.encoding())
+            .unwrap_or(UTF_8);
+        // https://mimesniff.spec.whatwg.org/#mime-type-essence
+        let content_type = self
+            .content_type
+            .as_ref()
+            .and_then(|value| value.split(';').next())
+            .map(|essence| essence.trim().to_ascii_lowercase())
+            .filter(|essence| !essence.is_empty())
+            .unwrap_or_else(|| "text/html".to_string());
+        let referrer = self.referrer.clone();
+        let last_modified = self.last_modified;
+        move |document| {
+            document.character_set = encoding.name().to_string();
+            document.content_type = content_type;
+            document.referrer = referrer;
+            document.last_modified = last_modified;
+            document.ready_state = ready_state;
+        }
+    }
+
+    fn start_decoding(&mut self, last: bool) -> Result<(), HTMLParseError> {
+        let encoding = encoding::sniff(&self.undecoded, self.content_type.as_deref());
+        if encoding == REPLACEMENT {
+            // NOTE: encodings like ISO-2022-KR are mapped to "replacement" encoding, which decodes nothing meaningful.
+            return Err(HTMLParseError::UnsupportedEncodingError(encoding.name()));
+        }
+        // NOTE: a BOM overrides `encoding`, and the decoder removes it.
+        self.decoder = Some(encoding.new_decoder());
+        let data = std::mem::take(&mut self.undecoded);
+        self.decode(&data, last);
+        Ok(())
+    }
+
+    fn decode(&mut self, data: &[u8], last: bool) {
+        let decoder = self.decoder.as_mut().unwrap();
+        let mut decoded = String::with_capacity(
+            decoder
+                .max_utf8_buffer_length(data.len())
+                .unwrap_or_default(),
+        );
+        // malformed sequences are replaced with U+FFFD; see https://encoding.spec.whatwg.org/#decode
+        let (result, _, _) = decoder.decode_to_string(data, &mut decoded, last);
+        debug_assert_eq!(result, CoderResult::InputEmpty);
+        self.tree_builder.tokenizer().feed(decoded.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::{AttrMap, Element, NodeId, NodeType, Text};
+    use crate::fetch::{HTTPStatus, HeaderMap, ResponseType};
+    use crate::url::Url;
+
+    fn run_to_end(parser: &mut Parser) -> Vec<String> {
+        let mut scripts = vec![];
+        loop {
+            match parser.run() {
+                ParserStatus::Script(script) => scripts.push(script),
+                ParserStatus::NeedsInput => panic!("the input is closed"),
+                ParserStatus::Finished => return scripts,
+            }
+        }
+    }
+
+    fn body(document: &Document) -> NodeId {
+        let html = document.document_element().unwrap();
+        document.node(html).last_child().unwrap()
+    }
+
+    fn body_children(document: &Document) -> Vec<NodeId> {
+        document.children(body(document)).collect()
+    }
+
+    #[test]
+    fn test_feed_chunks() {
+        let data = "<p>\u{3042}</p>".as_bytes();
+        let mut parser = Parser::new("http://example.com".to_string(), None);
+        for chunk in data.chunks(1) {
+            parser.feed(chunk).unwrap();
+            assert_eq!(parser.run(), ParserStatus::NeedsInput);
+        }
+        parser.close().unwrap();
+        assert_eq!(run_to_end(&mut parser), Vec::<String>::new());
+        let document = parser.finish();
+        assert_eq!(
+            document.to_tree(body(&document)).children,
+            vec![Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![Text::new("\u{3042}".to_string())]
+            )]
+        );
+
+        // the encoding is determined before the whole resource is received.
+        let mut data = b"<meta charset=shift_jis>".to_vec();
+        data.resize(PRESCAN_LENGTH, b' ');
+        data.extend_from_slice(b"<p>\x82\xA0</p>");
+        let mut parser = Parser::new("http://example.com".to_string(), None);
+        parser.feed(&data[..PRESCAN_LENGTH + 4]).unwrap();
+        assert_eq!(parser.run(), ParserStatus::NeedsInput);
+        parser.feed(&data[PRESCAN_LENGTH + 4..]).unwrap();
+        parser.close().unwrap();
+        run_to_end(&mut parser);
+        let document = parser.finish();
+        assert_eq!(document.inner_text(body_children(&document)[0]), "\u{3042}");
+
+        // a BOM overrides the encoding in `Content-Type` header, and is removed.
+        let mut parser = Parser::new(
+            "http://example.com".to_string(),
+            Some("text/html; charset=shift_jis"),
+        );
+        parser.feed(b"\xEF\xBB\xBF<p>\xE3\x81\x82</p>").unwrap();
+        parser.close().unwrap();
+        run_to_end(&mut parser);
+        let document = parser.finish();
+        assert_eq!(document.inner_text(body(&document)), "\u{3042}");
+
+        let mut parser = Parser::new(
+            "http://example.com".to_string(),
+            Some("text/html; charset=iso-2022-kr"),
+        );
+        parser.feed(b"<p>").unwrap();
+        assert_eq!(
+            parser.close(),
+            Err(HTMLParseError::UnsupportedEncodingError("replacement"))
+        );
+    }
+
+    #[test]
+    fn test_pause_at_script() {
+        let mut parser = Parser::new("http://example.com".to_string(), None);
+        parser
+            .feed(b"<p>a</p><script>f()</script><p>b</p>")
+            .unwrap();
+        parser.close().unwrap();
+
+        assert_eq!(parser.run(), ParserStatus::Script("f()".to_string()));
+        // only the nodes before the script exist while the script runs.
+        let document = parser.take_document();
+        assert_eq!(body_children(&document).len(), 2);
+        assert_eq!(document.inner_text(body_children(&document)[0]), "a");
+        parser.restore_document(document);
+
+        assert_eq!(parser.run(), ParserStatus::Finished);
+        let document = parser.finish();
+        assert_eq!(body_children(&document).len(), 3);
+        assert_eq!(document.inner_text(body_children(&document)[2]), "b");
+    }
+
+    #[test]
+    fn test_modify_document_while_paused() {
+        let mut parser = Parser::new("http://example.com".to_string(), None);
+        parser
+            .feed(
+                b"<div id=a><b><script></script>x</b></div><section><script></script>y</section>z",
+            )
+            .unwrap();
+        parser.close().unwrap();
+
+        // changes by the script are kept, and the parser keeps inserting nodes into the open elements.
+        assert!(matches!(parser.run(), ParserStatus::Script(_)));
+        let mut document = parser.take_document();
+        let div = body_children(&document)[0];
+        let text = document.create_node(NodeType::Text(Text {
+            data: "text".to_string(),
+        }));
+        let first_child = document.node(div).first_child();
+        document.insert(div, text, first_child);
+        if let NodeType::Element(ref mut e) = document.node_mut(div).node_type {
+            e.attributes.insert("id".to_string(), "b".to_string());
+        }
+        parser.restore_document(document);
+
+        // the parser keeps inserting nodes into the open elements even if they are removed from the document.
+        assert!(matches!(parser.run(), ParserStatus::Script(_)));
+        let mut document = parser.take_document();
+        let children = body_children(&document);
+        assert_eq!(document.inner_text(children[0]), "textx");
+        assert_eq!(
+            document.outer_html(children[0]),
+            "<div id=\"b\">text<b><script></script>x</b></div>"
+        );
+        let section = children[1];
+        document.detach(section);
+        parser.restore_document(document);
+
+        assert_eq!(parser.run(), ParserStatus::Finished);
+        let document = parser.finish();
+        assert_eq!(document.inner_text(section), "y");
+        let children = body_children(&document);
+        assert_eq!(children.len(), 2);
+        assert_eq!(document.to_tree(children[1]), Text::new("z".to_string()));
+    }
+
+    #[test]
+    fn test_write() {
+        let mut parser = Parser::new("http://example.com".to_string(), None);
+        parser
+            .feed(b"<body><script>document.write()</script><p>c</p>")
+            .unwrap();
+        parser.close().unwrap();
+        assert!(!parser.write("<p>x</p>"));
+
+        assert!(matches!(parser.run(), ParserStatus::Script(_)));
+        assert!(parser.write("<p>a"));
+        assert!(parser.write("</p><script>second()</script><p>b</p>"));
+
+        // scripts written by another script are executed as well.
+        assert_eq!(parser.run(), ParserStatus::Script("second()".to_string()));
+        assert!(parser.write("<i>"));
+        assert_eq!(parser.run(), ParserStatus::Finished);
+        assert!(!parser.write("<p>x</p>"));
+
+        let document = parser.finish();
+        let texts: Vec<String> = body_children(&document)
+            .into_iter()
+            .map(|id| document.outer_html(id))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "<script>document.write()</script>",
+                "<p>a</p>",
+                "<script>second()</script>",
+                "<i><p>b</p><p>c</p></i>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Content-Type".to_string(),
+            "Text/HTML; charset=shift_jis".to_string(),
+        );
+        headers.insert(
+            "Last-Modified".to_string(),
+            "Wed, 21 Oct 2015 07:28:00 GMT".to_string(),
+        );
+        let response = Response {
+            url: Url::parse("http://example.com").unwrap(),
+            status: HTTPStatus::OK,
+            rtype: ResponseType::Basic,
+            headers,
+            data: b"<title>a</title><script>f()</script>".to_vec(),
+        };
+        let mut parser = Parser::from_response(&response);
+        parser.set_referrer("http://example.com/from".to_string());
+        parser.feed(&response.data).unwrap();
+        parser.close().unwrap();
+
+        assert_eq!(parser.run(), ParserStatus::Script("f()".to_string()));
+        let document = parser.take_document();
+        assert_eq!(document.ready_state, DocumentReadyState::Loading);
+        assert_eq!(document.character_set, "Shift_JIS");
+        assert_eq!(document.content_type, "text/html");
+        assert_eq!(document.referrer, "http://example.com/from");
+        assert_eq!(
+            document.last_modified,
+            parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert_eq!(document.title(), "a");
+        parser.restore_document(document);
+
+        assert_eq!(parser.run(), ParserStatus::Finished);
+        let document = parser.finish();
+        assert_eq!(document.ready_state, DocumentReadyState::Interactive);
+        assert_eq!(document.character_set, "Shift_JIS");
+    }
+}
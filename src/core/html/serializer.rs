@@ -0,0 +1,347 @@
+//! This module includes an implementation of the HTML fragment serialization algorithm.
+//! The algorithm is defined at https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments.
+//!
+//! NOTE: attributes are serialized in the order of their names, since `AttrMap` doesn't keep the order in the source.
+
+use super::VOID_ELEMENTS;
+use crate::dom::{CDATASection, Document, NodeId, NodeType, Text};
+
+/// `RAW_TEXT_ELEMENTS` is a list of elements whose text contents are serialized without escaping.
+/// `noscript` is included because puppy runs scripts.
+const RAW_TEXT_ELEMENTS: &[&str] = &[
+    "style",
+    "script",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+    "noscript",
+];
+
+/// `PRESERVE_WHITESPACE_ELEMENTS` is a list of elements whose contents are never indented in pretty-printed output,
+/// because whitespace in them is significant.
+const PRESERVE_WHITESPACE_ELEMENTS: &[&str] = &["pre", "textarea", "listing"];
+
+const INDENT: &str = "  ";
+
+/// `SerializationOptions` configures how nodes are serialized.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SerializationOptions {
+    /// `pretty` puts each child node on its own indented line.
+    /// Whitespace-only text is dropped and other text is trimmed, so the output is for humans and may not be parsed into the same tree.
+    pub pretty: bool,
+}
+
+/// `serialize` returns the markup of `node` itself, as `outerHTML` getter does.
+pub fn serialize(document: &Document, id: NodeId, options: SerializationOptions) -> String {
+    let mut output = String::new();
+    write_node(&mut output, document, id, None, options, 0);
+    output
+}
+
+/// `serialize_children` returns the markup of the children of `node`, as `innerHTML` getter does.
+/// https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-serialisation-algorithm
+pub fn serialize_children(
+    document: &Document,
+    id: NodeId,
+    options: SerializationOptions,
+) -> String {
+    let mut output = String::new();
+    write_children(&mut output, document, id, options, 0);
+    output
+}
+
+fn write_children(
+    output: &mut String,
+    document: &Document,
+    id: NodeId,
+    options: SerializationOptions,
+    depth: usize,
+) {
+    let tag_name = match document.node(id).node_type {
+        NodeType::Element(ref e) => Some(e.tag_name.as_str()),
+        _ => None,
+    };
+    // whitespace in some elements is significant, so their descendants are never pretty-printed.
+    let options = SerializationOptions {
+        pretty: options.pretty && !tag_name.map(preserves_whitespace).unwrap_or(false),
+    };
+    let children = document
+        .children(id)
+        .filter(|&child| !(options.pretty && is_whitespace_text(document, child)))
+        .collect::<Vec<_>>();
+
+    // a single text child is kept on the same line as the tags around it, as in `<p>text</p>`.
+    let indents = options.pretty
+        && !(children.len() == 1
+            && matches!(document.node(children[0]).node_type, NodeType::Text(_)));
+    for &child in children.iter() {
+        if indents {
+            output.push('\n');
+            output.push_str(INDENT.repeat(depth + 1).as_str());
+        }
+        write_node(output, document, child, tag_name, options, depth + 1);
+    }
+    if indents && !children.is_empty() {
+        output.push('\n');
+        output.push_str(INDENT.repeat(depth).as_str());
+    }
+}
+
+fn write_node(
+    output: &mut String,
+    document: &Document,
+    id: NodeId,
+    parent_tag_name: Option<&str>,
+    options: SerializationOptions,
+    depth: usize,
+) {
+    match document.node(id).node_type {
+        NodeType::Document | NodeType::DocumentFragment => {
+            write_children(output, document, id, options, depth)
+        }
+        NodeType::Element(ref e) => {
+            output.push('<');
+            output.push_str(e.tag_name.as_str());
+            let mut attributes = e.attributes.iter().collect::<Vec<_>>();
+            attributes.sort();
+            for (name, value) in attributes {
+                output.push(' ');
+                output.push_str(name.as_str());
+                output.push_str("=\"");
+                output.push_str(escape_attribute_value(value).as_str());
+                output.push('"');
+            }
+            output.push('>');
+
+            if VOID_ELEMENTS.contains(&e.tag_name.as_str()) {
+                return;
+            }
+            write_children(output, document, id, options, depth);
+            output.push_str("</");
+            output.push_str(e.tag_name.as_str());
+            output.push('>');
+        }
+        // NOTE: `CDATASection` is a kind of `Text`, so it's serialized as a text as well.
+        NodeType::Text(Text { ref data }) | NodeType::CDATASection(CDATASection { ref data }) => {
+            match parent_tag_name {
+                Some(name) if RAW_TEXT_ELEMENTS.contains(&name) => output.push_str(data.as_str()),
+                _ if options.pretty => output.push_str(escape_text(data.trim()).as_str()),
+                _ => output.push_str(escape_text(data.as_str()).as_str()),
+            }
+        }
+        NodeType::Comment(ref c) => {
+            output.push_str("<!--");
+            output.push_str(c.data.as_str());
+            output.push_str("-->");
+        }
+        NodeType::ProcessingInstruction(ref p) => {
+            output.push_str("<?");
+            output.push_str(p.target.as_str());
+            output.push(' ');
+            output.push_str(p.data.as_str());
+            output.push('>');
+        }
+        NodeType::DocumentType(ref d) => {
+            output.push_str("<!DOCTYPE ");
+            output.push_str(d.name.as_str());
+            output.push('>');
+        }
+    }
+}
+
+fn preserves_whitespace(tag_name: &str) -> bool {
+    PRESERVE_WHITESPACE_ELEMENTS.contains(&tag_name) || RAW_TEXT_ELEMENTS.contains(&tag_name)
+}
+
+fn is_whitespace_text(document: &Document, id: NodeId) -> bool {
+    match document.node(id).node_type {
+        NodeType::Text(ref t) => t
+            .data
+            .chars()
+            .all(|c| matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ')),
+        _ => false,
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+fn escape_text(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('\u{A0}', "&nbsp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+fn escape_attribute_value(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('\u{A0}', "&nbsp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::{AttrMap, Comment, DocumentType, Element, NodeTree, ProcessingInstruction};
+    use crate::html::parse_fragment;
+
+    fn body() -> Element {
+        Element {
+            tag_name: "body".to_string(),
+            attributes: AttrMap::new(),
+        }
+    }
+
+    /// `to_html` serializes `trees` one by one, and joins the results with newlines if `options.pretty` is set.
+    fn to_html(trees: Vec<NodeTree>, options: SerializationOptions) -> String {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let ids = trees
+            .into_iter()
+            .map(|tree| document.append_tree(root, tree))
+            .collect::<Vec<_>>();
+        ids.into_iter()
+            .map(|id| serialize(&document, id, options))
+            .collect::<Vec<_>>()
+            .join(if options.pretty { "\n" } else { "" })
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("type".to_string(), "text".to_string());
+        let node = Element::new(
+            "div".to_string(),
+            AttrMap::new(),
+            vec![
+                Comment::new(" comment ".to_string()),
+                Element::new("input".to_string(), attributes, vec![]),
+                Element::new("br".to_string(), AttrMap::new(), vec![]),
+                Text::new("text & <tag>".to_string()),
+            ],
+        );
+        assert_eq!(
+            to_html(vec![node], SerializationOptions::default()),
+            "<div><!-- comment --><input type=\"text\"><br>text &amp; &lt;tag&gt;</div>"
+        );
+
+        let mut attributes = AttrMap::new();
+        attributes.insert("title".to_string(), "\u{A0}\"><script>&".to_string());
+        attributes.insert("class".to_string(), "a".to_string());
+        let node = Element::new(
+            "p".to_string(),
+            attributes,
+            vec![Text::new("a\u{A0}<b>".to_string())],
+        );
+        assert_eq!(
+            to_html(vec![node], SerializationOptions::default()),
+            "<p class=\"a\" title=\"&nbsp;&quot;&gt;&lt;script&gt;&amp;\">a&nbsp;&lt;b&gt;</p>"
+        );
+
+        let nodes = vec![
+            DocumentType::new("html".to_string(), "".to_string(), "".to_string()),
+            Element::new(
+                "script".to_string(),
+                AttrMap::new(),
+                vec![Text::new("1 < 2 && true".to_string())],
+            ),
+            Element::new(
+                "noscript".to_string(),
+                AttrMap::new(),
+                vec![Text::new("<p>&amp;</p>".to_string())],
+            ),
+        ];
+        assert_eq!(
+            to_html(nodes, SerializationOptions::default()),
+            "<!DOCTYPE html><script>1 < 2 && true</script><noscript><p>&amp;</p></noscript>"
+        );
+
+        let nodes = vec![
+            ProcessingInstruction::new("xml-stylesheet".to_string(), "href=\"a.css\"".to_string()),
+            Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![CDATASection::new("<b>".to_string())],
+            ),
+        ];
+        assert_eq!(
+            to_html(nodes, SerializationOptions::default()),
+            "<?xml-stylesheet href=\"a.css\"><p>&lt;b&gt;</p>"
+        );
+    }
+
+    #[test]
+    fn test_serialize_children() {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let p = document.append_tree(
+            root,
+            Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![
+                    Text::new("a".to_string()),
+                    Element::new("br".to_string(), AttrMap::new(), vec![]),
+                ],
+            ),
+        );
+        assert_eq!(
+            serialize_children(&document, p, SerializationOptions::default()),
+            "a<br>"
+        );
+        assert_eq!(
+            serialize(&document, root, SerializationOptions::default()),
+            "<p>a<br></p>"
+        );
+    }
+
+    #[test]
+    fn test_serialize_pretty() {
+        let nodes = parse_fragment(
+            "<ul>\n<li>a</li> <li><b>b</b>c</li></ul><pre>\n x\n</pre><p>  text  </p>",
+            &body(),
+        );
+        assert_eq!(
+            to_html(nodes, SerializationOptions { pretty: true }),
+            "<ul>\n  <li>a</li>\n  <li>\n    <b>b</b>\n    c\n  </li>\n</ul>\n<pre> x\n</pre>\n<p>text</p>"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let sources = [
+            "<p class=\"a b\" title=\"&quot;'&lt;&gt;&amp;\">x &amp; y &lt;z&gt;</p>",
+            "<div><!-- comment --><br><img src=\"a.png\">text</div>",
+            "<script>if (a < b && c > d) { document.write(\"</p>\"); }</script>",
+            "<style>p > a { content: \"&amp;\"; }</style>",
+            "<textarea>&lt;p&gt;</textarea><title>&amp;</title>",
+            "<table><tbody><tr><td>1</td></tr></tbody></table>",
+            "<p>&nbsp;<b><i>nested</i></b></p>",
+        ];
+        for source in sources.iter() {
+            let nodes = parse_fragment(source, &body());
+            let serialized = to_html(
+                parse_fragment(source, &body()),
+                SerializationOptions::default(),
+            );
+            assert_eq!(&serialized, source);
+            assert_eq!(parse_fragment(serialized.as_str(), &body()), nodes);
+        }
+
+        // markup which is not well-formed is serialized as the tree the parser built.
+        let source = "<p title=a\"b>1<p>2<b>3</p>4";
+        let nodes = parse_fragment(source, &body());
+        let serialized = to_html(
+            parse_fragment(source, &body()),
+            SerializationOptions::default(),
+        );
+        assert_eq!(
+            serialized,
+            "<p title=\"a&quot;b\">1</p><p>2<b>3</b></p><b>4</b>"
+        );
+        assert_eq!(parse_fragment(serialized.as_str(), &body()), nodes);
+    }
+}
@@ -0,0 +1,1847 @@
+//! This module includes an implementation of the tokenization stage of HTML parsing.
+//!
+//! The tokenizer is a state machine defined at https://html.spec.whatwg.org/multipage/parsing.html#tokenization.
+//! It consumes characters one by one and keeps its state between calls, so that it can stop when it runs out of input and resume after more input is fed.
+
+use super::entities::{LONGEST_NAME_LENGTH, NAMED_CHARACTER_REFERENCES};
+use crate::source::{ParseDiagnostic, SourcePosition, SourceSpan};
+use log::trace;
+use std::collections::VecDeque;
+
+/// `Tag` represents a start tag or an end tag token.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tag {
+    pub tag_name: String,
+    pub attributes: Vec<(String, String)>,
+    pub self_closing: bool,
+}
+
+impl Tag {
+    pub fn new(tag_name: &str) -> Self {
+        Tag {
+            tag_name: tag_name.to_string(),
+            attributes: vec![],
+            self_closing: false,
+        }
+    }
+}
+
+/// `Doctype` represents a DOCTYPE token.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Doctype {
+    pub name: Option<String>,
+    pub public_id: Option<String>,
+    pub system_id: Option<String>,
+    pub force_quirks: bool,
+}
+
+/// `Token` is an output of the tokenizer, which is defined at https://html.spec.whatwg.org/multipage/parsing.html#tokenization.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    Doctype(Doctype),
+    StartTag(Tag),
+    EndTag(Tag),
+    Comment(String),
+    Character(char),
+    EOF,
+}
+
+/// `State` is a state of the tokenizer.
+/// Each variant corresponds to a state defined in the standard.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum State {
+    Data,
+    RCDATA,
+    RAWTEXT,
+    ScriptData,
+    PLAINTEXT,
+    TagOpen,
+    EndTagOpen,
+    TagName,
+    BeforeAttributeName,
+    AttributeName,
+    AfterAttributeName,
+    BeforeAttributeValue,
+    AttributeValueDoubleQuoted,
+    AttributeValueSingleQuoted,
+    AttributeValueUnquoted,
+    AfterAttributeValueQuoted,
+    RCDATALessThanSign,
+    RCDATAEndTagOpen,
+    RCDATAEndTagName,
+    RAWTEXTLessThanSign,
+    RAWTEXTEndTagOpen,
+    RAWTEXTEndTagName,
+    ScriptDataLessThanSign,
+    ScriptDataEndTagOpen,
+    ScriptDataEndTagName,
+    ScriptDataEscapeStart,
+    ScriptDataEscapeStartDash,
+    ScriptDataEscaped,
+    ScriptDataEscapedDash,
+    ScriptDataEscapedDashDash,
+    ScriptDataEscapedLessThanSign,
+    ScriptDataEscapedEndTagOpen,
+    ScriptDataEscapedEndTagName,
+    ScriptDataDoubleEscapeStart,
+    ScriptDataDoubleEscaped,
+    ScriptDataDoubleEscapedDash,
+    ScriptDataDoubleEscapedDashDash,
+    ScriptDataDoubleEscapedLessThanSign,
+    ScriptDataDoubleEscapeEnd,
+    SelfClosingStartTag,
+    BogusComment,
+    MarkupDeclarationOpen,
+    CommentStart,
+    CommentStartDash,
+    Comment,
+    CommentEndDash,
+    CommentEnd,
+    CommentEndBang,
+    Doctype,
+    BeforeDoctypeName,
+    DoctypeName,
+    AfterDoctypeName,
+    AfterDoctypePublicKeyword,
+    BeforeDoctypePublicIdentifier,
+    DoctypePublicIdentifierDoubleQuoted,
+    DoctypePublicIdentifierSingleQuoted,
+    AfterDoctypePublicIdentifier,
+    BetweenDoctypePublicAndSystemIdentifiers,
+    AfterDoctypeSystemKeyword,
+    BeforeDoctypeSystemIdentifier,
+    DoctypeSystemIdentifierDoubleQuoted,
+    DoctypeSystemIdentifierSingleQuoted,
+    AfterDoctypeSystemIdentifier,
+    BogusDoctype,
+}
+
+/// `Tokenizer` converts characters into a sequence of `Token`.
+pub struct Tokenizer {
+    input: Vec<char>,
+    pos: usize,
+    closed: bool,
+    last_char_was_cr: bool,
+
+    state: State,
+    current_tag: Option<(bool, Tag)>,
+    current_attribute: Option<(String, String)>,
+    current_comment: String,
+    current_doctype: Option<Doctype>,
+    last_start_tag_name: Option<String>,
+    temporary_buffer: String,
+
+    pending_tokens: VecDeque<(Token, SourceSpan)>,
+    eof_emitted: bool,
+
+    // `insertion_point` is the index of `input` where `document.write` inserts markup.
+    insertion_point: Option<usize>,
+
+    // `line_starts` holds indices of `input` where each line starts.
+    line_starts: Vec<usize>,
+    // `token_start` is the index of `input` where the next token starts.
+    token_start: usize,
+    last_token_span: SourceSpan,
+    errors: Vec<ParseDiagnostic>,
+}
+
+impl Tokenizer {
+    pub fn new() -> Self {
+        Tokenizer {
+            input: vec![],
+            pos: 0,
+            closed: false,
+            last_char_was_cr: false,
+
+            state: State::Data,
+            current_tag: None,
+            current_attribute: None,
+            current_comment: String::new(),
+            current_doctype: None,
+            last_start_tag_name: None,
+            temporary_buffer: String::new(),
+
+            pending_tokens: VecDeque::new(),
+            eof_emitted: false,
+
+            insertion_point: None,
+
+            line_starts: vec![0],
+            token_start: 0,
+            last_token_span: SourceSpan::default(),
+            errors: vec![],
+        }
+    }
+
+    /// `feed` appends `s` to the input stream.
+    /// Newlines are normalized as the standard requires: https://html.spec.whatwg.org/multipage/parsing.html#preprocessing-the-input-stream
+    pub fn feed(&mut self, s: &str) {
+        for c in s.chars() {
+            match c {
+                '\r' => {
+                    self.push_input('\n');
+                    self.last_char_was_cr = true;
+                    continue;
+                }
+                '\n' if self.last_char_was_cr => {}
+                c => self.push_input(c),
+            }
+            self.last_char_was_cr = false;
+        }
+    }
+
+    /// `set_insertion_point` puts the insertion point just after the characters consumed so far.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insertion-point
+    pub fn set_insertion_point(&mut self) {
+        self.insertion_point = Some(self.pos);
+    }
+
+    /// `clear_insertion_point` makes the insertion point undefined.
+    pub fn clear_insertion_point(&mut self) {
+        self.insertion_point = None;
+    }
+
+    /// `insert` inserts `s` into the input stream at the insertion point, and moves the insertion point to the end of the inserted characters.
+    /// It returns false if the insertion point is undefined.
+    /// This is how `document.write` works: https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#document-write-steps
+    pub fn insert(&mut self, s: &str) -> bool {
+        let at = match self.insertion_point {
+            Some(at) => at,
+            None => return false,
+        };
+        let chars: Vec<char> = s
+            .replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .chars()
+            .collect();
+        let len = chars.len();
+        self.input.splice(at..at, chars);
+        self.insertion_point = Some(at + len);
+
+        // NOTE: the inserted characters shift the lines after them, so positions are counted in the input stream after the insertion.
+        self.line_starts = std::iter::once(0)
+            .chain(
+                self.input
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &c)| c == '\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+        true
+    }
+
+    fn push_input(&mut self, c: char) {
+        self.input.push(c);
+        if c == '\n' {
+            self.line_starts.push(self.input.len());
+        }
+    }
+
+    /// `close` marks the end of the input stream.
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    /// `set_state` switches the state of the tokenizer.
+    /// The tree builder uses this to tokenize contents of elements like `<script>` and `<textarea>` appropriately.
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+    }
+
+    /// `next_token` returns the next token.
+    /// It returns `None` when more input is needed or when EOF token has already been emitted.
+    pub fn next_token(&mut self) -> Option<Token> {
+        loop {
+            if let Some((token, span)) = self.pending_tokens.pop_front() {
+                self.last_token_span = span;
+                return Some(token);
+            }
+            if self.eof_emitted || !self.step() {
+                return None;
+            }
+        }
+    }
+
+    /// `is_finished` returns true if all the tokens including EOF token have been returned.
+    pub fn is_finished(&self) -> bool {
+        self.eof_emitted && self.pending_tokens.is_empty()
+    }
+
+    /// `last_token_span` returns the location of the token `next_token` returned last.
+    pub fn last_token_span(&self) -> SourceSpan {
+        self.last_token_span
+    }
+
+    /// `take_errors` returns parse errors found so far.
+    pub fn take_errors(&mut self) -> Vec<ParseDiagnostic> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// `position_at` converts an index of `input` into a line and a column.
+    fn position_at(&self, index: usize) -> SourcePosition {
+        let line = match self.line_starts.binary_search(&index) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        SourcePosition::new(line + 1, index - self.line_starts[line] + 1)
+    }
+
+    fn error(&mut self, message: &'static str) {
+        trace!("parse error in tokenization; {}", message);
+        let position = self.position_at(self.pos.saturating_sub(1));
+        self.errors
+            .push(ParseDiagnostic::new(message.to_string(), position));
+    }
+
+    fn emit(&mut self, token: Token) {
+        if token == Token::EOF {
+            self.eof_emitted = true;
+        }
+        // a token spans from the end of the previous token to the current position.
+        let span = SourceSpan::new(
+            self.position_at(self.token_start),
+            self.position_at(self.pos),
+        );
+        self.token_start = self.pos;
+        self.pending_tokens.push_back((token, span));
+    }
+
+    fn emit_current_tag(&mut self) {
+        self.finish_attribute();
+        if let Some((is_end, tag)) = self.current_tag.take() {
+            if is_end {
+                if !tag.attributes.is_empty() {
+                    self.error("end-tag-with-attributes");
+                }
+                self.emit(Token::EndTag(tag));
+            } else {
+                self.last_start_tag_name = Some(tag.tag_name.clone());
+                self.emit(Token::StartTag(tag));
+            }
+        }
+    }
+
+    fn emit_current_comment(&mut self) {
+        let comment = std::mem::take(&mut self.current_comment);
+        self.emit(Token::Comment(comment));
+    }
+
+    fn start_doctype(&mut self) {
+        self.current_doctype = Some(Doctype {
+            name: None,
+            public_id: None,
+            system_id: None,
+            force_quirks: false,
+        });
+    }
+
+    fn current_doctype_mut(&mut self) -> &mut Doctype {
+        self.current_doctype.as_mut().unwrap()
+    }
+
+    fn emit_current_doctype(&mut self, force_quirks: bool) {
+        if let Some(mut doctype) = self.current_doctype.take() {
+            doctype.force_quirks |= force_quirks;
+            self.emit(Token::Doctype(doctype));
+        }
+    }
+
+    fn start_tag(&mut self, is_end: bool) {
+        self.current_tag = Some((is_end, Tag::new("")));
+    }
+
+    fn current_tag_mut(&mut self) -> &mut Tag {
+        &mut self.current_tag.as_mut().unwrap().1
+    }
+
+    fn start_attribute(&mut self, name: &str) {
+        self.finish_attribute();
+        self.current_attribute = Some((name.to_string(), String::new()));
+    }
+
+    /// `finish_attribute` adds the current attribute to the current tag.
+    /// When the tag already has an attribute with the same name, the new one is dropped; the first one wins.
+    fn finish_attribute(&mut self) {
+        if let Some(attribute) = self.current_attribute.take() {
+            if let Some((_, tag)) = self.current_tag.as_mut() {
+                if tag.attributes.iter().any(|(name, _)| *name == attribute.0) {
+                    self.error("duplicate-attribute");
+                } else {
+                    tag.attributes.push(attribute);
+                }
+            }
+        }
+    }
+
+    fn append_to_attribute_name(&mut self, c: char) {
+        if let Some((name, _)) = self.current_attribute.as_mut() {
+            name.push(c);
+        }
+    }
+
+    fn append_to_attribute_value(&mut self, c: char) {
+        if let Some((_, value)) = self.current_attribute.as_mut() {
+            value.push(c);
+        }
+    }
+
+    fn reconsume(&mut self, c: Option<char>, state: State) {
+        if c.is_some() {
+            self.pos -= 1;
+            self.token_start = std::cmp::min(self.token_start, self.pos);
+        }
+        self.state = state;
+    }
+
+    /// `step` consumes a character and moves the state machine forward.
+    /// It returns `false` if it needs more input to proceed.
+    fn step(&mut self) -> bool {
+        if self.state == State::MarkupDeclarationOpen {
+            return self.markup_declaration_open();
+        }
+
+        // `None` represents EOF here.
+        let c = match self.input.get(self.pos) {
+            Some(&c) => {
+                self.pos += 1;
+                Some(c)
+            }
+            None if self.closed => None,
+            None => return false,
+        };
+
+        match self.state {
+            // https://html.spec.whatwg.org/multipage/parsing.html#data-state
+            State::Data => match c {
+                Some('&') => match self.consume_character_reference(false) {
+                    Some(s) => s.chars().for_each(|c| self.emit(Token::Character(c))),
+                    None => {
+                        self.pos -= 1;
+                        return false;
+                    }
+                },
+                Some('<') => self.state = State::TagOpen,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.emit(Token::Character('\0'));
+                }
+                Some(c) => self.emit(Token::Character(c)),
+                None => self.emit(Token::EOF),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-state
+            State::RCDATA => match c {
+                Some('&') => match self.consume_character_reference(false) {
+                    Some(s) => s.chars().for_each(|c| self.emit(Token::Character(c))),
+                    None => {
+                        self.pos -= 1;
+                        return false;
+                    }
+                },
+                Some('<') => self.state = State::RCDATALessThanSign,
+                _ => self.emit_text_character(c),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#rawtext-state
+            State::RAWTEXT => match c {
+                Some('<') => self.state = State::RAWTEXTLessThanSign,
+                _ => self.emit_text_character(c),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-state
+            State::ScriptData => match c {
+                Some('<') => self.state = State::ScriptDataLessThanSign,
+                _ => self.emit_text_character(c),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#plaintext-state
+            State::PLAINTEXT => self.emit_text_character(c),
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#tag-open-state
+            State::TagOpen => match c {
+                Some('!') => self.state = State::MarkupDeclarationOpen,
+                Some('/') => self.state = State::EndTagOpen,
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(false);
+                    self.reconsume(Some(c), State::TagName);
+                }
+                Some('?') => {
+                    self.error("unexpected-question-mark-instead-of-tag-name");
+                    self.current_comment = String::new();
+                    self.reconsume(c, State::BogusComment);
+                }
+                None => {
+                    self.error("eof-before-tag-name");
+                    self.emit(Token::Character('<'));
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.error("invalid-first-character-of-tag-name");
+                    self.emit(Token::Character('<'));
+                    self.reconsume(Some(c), State::Data);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#end-tag-open-state
+            State::EndTagOpen => match c {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(true);
+                    self.reconsume(Some(c), State::TagName);
+                }
+                Some('>') => {
+                    self.error("missing-end-tag-name");
+                    self.state = State::Data;
+                }
+                None => {
+                    self.error("eof-before-tag-name");
+                    self.emit(Token::Character('<'));
+                    self.emit(Token::Character('/'));
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.error("invalid-first-character-of-tag-name");
+                    self.current_comment = String::new();
+                    self.reconsume(Some(c), State::BogusComment);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#tag-name-state
+            State::TagName => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeAttributeName
+                }
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.current_tag_mut().tag_name.push('\u{FFFD}');
+                }
+                Some(c) => self.current_tag_mut().tag_name.push(c.to_ascii_lowercase()),
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit(Token::EOF);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#before-attribute-name-state
+            State::BeforeAttributeName => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('/') | Some('>') | None => self.reconsume(c, State::AfterAttributeName),
+                Some('=') => {
+                    self.error("unexpected-equals-sign-before-attribute-name");
+                    self.start_attribute("=");
+                    self.state = State::AttributeName;
+                }
+                Some(c) => {
+                    self.start_attribute("");
+                    self.reconsume(Some(c), State::AttributeName);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#attribute-name-state
+            State::AttributeName => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') | Some('/') | Some('>')
+                | None => self.reconsume(c, State::AfterAttributeName),
+                Some('=') => self.state = State::BeforeAttributeValue,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.append_to_attribute_name('\u{FFFD}');
+                }
+                Some(c) => {
+                    if c == '"' || c == '\'' || c == '<' {
+                        self.error("unexpected-character-in-attribute-name");
+                    }
+                    self.append_to_attribute_name(c.to_ascii_lowercase());
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#after-attribute-name-state
+            State::AfterAttributeName => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('=') => self.state = State::BeforeAttributeValue,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.start_attribute("");
+                    self.reconsume(Some(c), State::AttributeName);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#before-attribute-value-state
+            State::BeforeAttributeValue => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('"') => self.state = State::AttributeValueDoubleQuoted,
+                Some('\'') => self.state = State::AttributeValueSingleQuoted,
+                Some('>') => {
+                    self.error("missing-attribute-value");
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                _ => self.reconsume(c, State::AttributeValueUnquoted),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#attribute-value-(double-quoted)-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#attribute-value-(single-quoted)-state
+            State::AttributeValueDoubleQuoted | State::AttributeValueSingleQuoted => {
+                let quote = if self.state == State::AttributeValueDoubleQuoted {
+                    '"'
+                } else {
+                    '\''
+                };
+                match c {
+                    Some(c) if c == quote => self.state = State::AfterAttributeValueQuoted,
+                    Some('&') => {
+                        if !self.append_character_reference_to_attribute_value() {
+                            return false;
+                        }
+                    }
+                    Some('\0') => {
+                        self.error("unexpected-null-character");
+                        self.append_to_attribute_value('\u{FFFD}');
+                    }
+                    Some(c) => self.append_to_attribute_value(c),
+                    None => {
+                        self.error("eof-in-tag");
+                        self.emit(Token::EOF);
+                    }
+                }
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#attribute-value-(unquoted)-state
+            State::AttributeValueUnquoted => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeAttributeName
+                }
+                Some('&') => {
+                    if !self.append_character_reference_to_attribute_value() {
+                        return false;
+                    }
+                }
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.append_to_attribute_value('\u{FFFD}');
+                }
+                Some(c) => {
+                    if c == '"' || c == '\'' || c == '<' || c == '=' || c == '`' {
+                        self.error("unexpected-character-in-unquoted-attribute-value");
+                    }
+                    self.append_to_attribute_value(c);
+                }
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit(Token::EOF);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#after-attribute-value-(quoted)-state
+            State::AfterAttributeValueQuoted => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeAttributeName
+                }
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.error("missing-whitespace-between-attributes");
+                    self.reconsume(Some(c), State::BeforeAttributeName);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-less-than-sign-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#rawtext-less-than-sign-state
+            State::RCDATALessThanSign | State::RAWTEXTLessThanSign => {
+                let (end_tag_open, text) = if self.state == State::RCDATALessThanSign {
+                    (State::RCDATAEndTagOpen, State::RCDATA)
+                } else {
+                    (State::RAWTEXTEndTagOpen, State::RAWTEXT)
+                };
+                match c {
+                    Some('/') => {
+                        self.temporary_buffer = String::new();
+                        self.state = end_tag_open;
+                    }
+                    _ => {
+                        self.emit(Token::Character('<'));
+                        self.reconsume(c, text);
+                    }
+                }
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-open-state
+            State::RCDATAEndTagOpen => {
+                self.text_end_tag_open(c, State::RCDATAEndTagName, State::RCDATA)
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-name-state
+            State::RCDATAEndTagName => self.text_end_tag_name(c, State::RCDATA),
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#rawtext-end-tag-open-state
+            State::RAWTEXTEndTagOpen => {
+                self.text_end_tag_open(c, State::RAWTEXTEndTagName, State::RAWTEXT)
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#rawtext-end-tag-name-state
+            State::RAWTEXTEndTagName => self.text_end_tag_name(c, State::RAWTEXT),
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-less-than-sign-state
+            State::ScriptDataLessThanSign => match c {
+                Some('/') => {
+                    self.temporary_buffer = String::new();
+                    self.state = State::ScriptDataEndTagOpen;
+                }
+                Some('!') => {
+                    self.state = State::ScriptDataEscapeStart;
+                    self.emit(Token::Character('<'));
+                    self.emit(Token::Character('!'));
+                }
+                _ => {
+                    self.emit(Token::Character('<'));
+                    self.reconsume(c, State::ScriptData);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-end-tag-open-state
+            State::ScriptDataEndTagOpen => {
+                self.text_end_tag_open(c, State::ScriptDataEndTagName, State::ScriptData)
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-end-tag-name-state
+            State::ScriptDataEndTagName => self.text_end_tag_name(c, State::ScriptData),
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escape-start-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escape-start-dash-state
+            State::ScriptDataEscapeStart | State::ScriptDataEscapeStartDash => match c {
+                Some('-') => {
+                    self.state = if self.state == State::ScriptDataEscapeStart {
+                        State::ScriptDataEscapeStartDash
+                    } else {
+                        State::ScriptDataEscapedDashDash
+                    };
+                    self.emit(Token::Character('-'));
+                }
+                _ => self.reconsume(c, State::ScriptData),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-dash-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-dash-dash-state
+            State::ScriptDataEscaped
+            | State::ScriptDataEscapedDash
+            | State::ScriptDataEscapedDashDash => match c {
+                Some('-') => {
+                    if self.state != State::ScriptDataEscapedDashDash {
+                        self.state = if self.state == State::ScriptDataEscaped {
+                            State::ScriptDataEscapedDash
+                        } else {
+                            State::ScriptDataEscapedDashDash
+                        };
+                    }
+                    self.emit(Token::Character('-'));
+                }
+                Some('<') => self.state = State::ScriptDataEscapedLessThanSign,
+                Some('>') if self.state == State::ScriptDataEscapedDashDash => {
+                    self.state = State::ScriptData;
+                    self.emit(Token::Character('>'));
+                }
+                None => {
+                    self.error("eof-in-script-html-comment-like-text");
+                    self.emit(Token::EOF);
+                }
+                _ => {
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_text_character(c);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-less-than-sign-state
+            State::ScriptDataEscapedLessThanSign => match c {
+                Some('/') => {
+                    self.temporary_buffer = String::new();
+                    self.state = State::ScriptDataEscapedEndTagOpen;
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.temporary_buffer = String::new();
+                    self.emit(Token::Character('<'));
+                    self.reconsume(Some(c), State::ScriptDataDoubleEscapeStart);
+                }
+                _ => {
+                    self.emit(Token::Character('<'));
+                    self.reconsume(c, State::ScriptDataEscaped);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-end-tag-open-state
+            State::ScriptDataEscapedEndTagOpen => self.text_end_tag_open(
+                c,
+                State::ScriptDataEscapedEndTagName,
+                State::ScriptDataEscaped,
+            ),
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-end-tag-name-state
+            State::ScriptDataEscapedEndTagName => {
+                self.text_end_tag_name(c, State::ScriptDataEscaped)
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escape-start-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escape-end-state
+            State::ScriptDataDoubleEscapeStart | State::ScriptDataDoubleEscapeEnd => {
+                let (inside, outside) = if self.state == State::ScriptDataDoubleEscapeStart {
+                    (State::ScriptDataDoubleEscaped, State::ScriptDataEscaped)
+                } else {
+                    (State::ScriptDataEscaped, State::ScriptDataDoubleEscaped)
+                };
+                match c {
+                    Some(c @ '\t') | Some(c @ '\n') | Some(c @ '\x0C') | Some(c @ ' ')
+                    | Some(c @ '/') | Some(c @ '>') => {
+                        self.state = if self.temporary_buffer == "script" {
+                            inside
+                        } else {
+                            outside
+                        };
+                        self.emit(Token::Character(c));
+                    }
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        self.temporary_buffer.push(c.to_ascii_lowercase());
+                        self.emit(Token::Character(c));
+                    }
+                    _ => self.reconsume(c, outside),
+                }
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-dash-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-dash-dash-state
+            State::ScriptDataDoubleEscaped
+            | State::ScriptDataDoubleEscapedDash
+            | State::ScriptDataDoubleEscapedDashDash => match c {
+                Some('-') => {
+                    if self.state != State::ScriptDataDoubleEscapedDashDash {
+                        self.state = if self.state == State::ScriptDataDoubleEscaped {
+                            State::ScriptDataDoubleEscapedDash
+                        } else {
+                            State::ScriptDataDoubleEscapedDashDash
+                        };
+                    }
+                    self.emit(Token::Character('-'));
+                }
+                Some('<') => {
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                    self.emit(Token::Character('<'));
+                }
+                Some('>') if self.state == State::ScriptDataDoubleEscapedDashDash => {
+                    self.state = State::ScriptData;
+                    self.emit(Token::Character('>'));
+                }
+                None => {
+                    self.error("eof-in-script-html-comment-like-text");
+                    self.emit(Token::EOF);
+                }
+                _ => {
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_text_character(c);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-less-than-sign-state
+            State::ScriptDataDoubleEscapedLessThanSign => match c {
+                Some('/') => {
+                    self.temporary_buffer = String::new();
+                    self.state = State::ScriptDataDoubleEscapeEnd;
+                    self.emit(Token::Character('/'));
+                }
+                _ => self.reconsume(c, State::ScriptDataDoubleEscaped),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#self-closing-start-tag-state
+            State::SelfClosingStartTag => match c {
+                Some('>') => {
+                    self.current_tag_mut().self_closing = true;
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.error("unexpected-solidus-in-tag");
+                    self.reconsume(Some(c), State::BeforeAttributeName);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#bogus-comment-state
+            State::BogusComment => match c {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.current_comment.push('\u{FFFD}');
+                }
+                Some(c) => self.current_comment.push(c),
+                None => {
+                    self.emit_current_comment();
+                    self.emit(Token::EOF);
+                }
+            },
+
+            State::MarkupDeclarationOpen => unreachable!(),
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#comment-start-state
+            State::CommentStart => match c {
+                Some('-') => self.state = State::CommentStartDash,
+                Some('>') => {
+                    self.error("abrupt-closing-of-empty-comment");
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                _ => self.reconsume(c, State::Comment),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#comment-start-dash-state
+            State::CommentStartDash => match c {
+                Some('-') => self.state = State::CommentEnd,
+                Some('>') => {
+                    self.error("abrupt-closing-of-empty-comment");
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.current_comment.push('-');
+                    self.reconsume(Some(c), State::Comment);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#comment-state
+            // NOTE: puppy omits comment less-than sign states, which only detect nested comments as parse errors.
+            State::Comment => match c {
+                Some('-') => self.state = State::CommentEndDash,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.current_comment.push('\u{FFFD}');
+                }
+                Some(c) => self.current_comment.push(c),
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit(Token::EOF);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#comment-end-dash-state
+            State::CommentEndDash => match c {
+                Some('-') => self.state = State::CommentEnd,
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.current_comment.push('-');
+                    self.reconsume(Some(c), State::Comment);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#comment-end-state
+            State::CommentEnd => match c {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                Some('!') => self.state = State::CommentEndBang,
+                Some('-') => self.current_comment.push('-'),
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.current_comment.push_str("--");
+                    self.reconsume(Some(c), State::Comment);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#comment-end-bang-state
+            State::CommentEndBang => match c {
+                Some('-') => {
+                    self.current_comment.push_str("--!");
+                    self.state = State::CommentEndDash;
+                }
+                Some('>') => {
+                    self.error("incorrectly-closed-comment");
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.current_comment.push_str("--!");
+                    self.reconsume(Some(c), State::Comment);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#doctype-state
+            State::Doctype => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeDoctypeName
+                }
+                Some('>') => self.reconsume(c, State::BeforeDoctypeName),
+                None => {
+                    self.error("eof-in-doctype");
+                    self.start_doctype();
+                    self.emit_current_doctype(true);
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.error("missing-whitespace-before-doctype-name");
+                    self.reconsume(Some(c), State::BeforeDoctypeName);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-name-state
+            State::BeforeDoctypeName => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('>') => {
+                    self.error("missing-doctype-name");
+                    self.start_doctype();
+                    self.state = State::Data;
+                    self.emit_current_doctype(true);
+                }
+                None => {
+                    self.error("eof-in-doctype");
+                    self.start_doctype();
+                    self.emit_current_doctype(true);
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    let c = if c == '\0' { '\u{FFFD}' } else { c };
+                    self.start_doctype();
+                    self.current_doctype_mut().name = Some(c.to_ascii_lowercase().to_string());
+                    self.state = State::DoctypeName;
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#doctype-name-state
+            State::DoctypeName => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::AfterDoctypeName
+                }
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype(false);
+                }
+                None => {
+                    self.error("eof-in-doctype");
+                    self.emit_current_doctype(true);
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    let c = if c == '\0' { '\u{FFFD}' } else { c };
+                    if let Some(name) = self.current_doctype_mut().name.as_mut() {
+                        name.push(c.to_ascii_lowercase());
+                    }
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-name-state
+            State::AfterDoctypeName => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype(false);
+                }
+                None => {
+                    self.error("eof-in-doctype");
+                    self.emit_current_doctype(true);
+                    self.emit(Token::EOF);
+                }
+                Some(_) => {
+                    // look at the consumed character again with the following ones.
+                    self.pos -= 1;
+                    if !self.closed && self.input.len() - self.pos < "public".len() {
+                        return false;
+                    }
+                    if self.consume_if_ascii_case_insensitive("public") {
+                        self.state = State::AfterDoctypePublicKeyword;
+                    } else if self.consume_if_ascii_case_insensitive("system") {
+                        self.state = State::AfterDoctypeSystemKeyword;
+                    } else {
+                        self.error("invalid-character-sequence-after-doctype-name");
+                        self.current_doctype_mut().force_quirks = true;
+                        self.state = State::BogusDoctype;
+                    }
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-public-keyword-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-system-keyword-state
+            State::AfterDoctypePublicKeyword | State::AfterDoctypeSystemKeyword => {
+                let is_public = self.state == State::AfterDoctypePublicKeyword;
+                match c {
+                    Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                        self.state = if is_public {
+                            State::BeforeDoctypePublicIdentifier
+                        } else {
+                            State::BeforeDoctypeSystemIdentifier
+                        }
+                    }
+                    Some('"') | Some('\'') => {
+                        self.error("missing-whitespace-after-doctype-keyword");
+                        self.start_doctype_identifier(is_public, c == Some('"'));
+                    }
+                    _ => self.doctype_identifier_missing(c),
+                }
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-public-identifier-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-system-identifier-state
+            State::BeforeDoctypePublicIdentifier | State::BeforeDoctypeSystemIdentifier => {
+                let is_public = self.state == State::BeforeDoctypePublicIdentifier;
+                match c {
+                    Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                    Some('"') | Some('\'') => {
+                        self.start_doctype_identifier(is_public, c == Some('"'))
+                    }
+                    _ => self.doctype_identifier_missing(c),
+                }
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#doctype-public-identifier-(double-quoted)-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#doctype-system-identifier-(double-quoted)-state
+            State::DoctypePublicIdentifierDoubleQuoted
+            | State::DoctypePublicIdentifierSingleQuoted
+            | State::DoctypeSystemIdentifierDoubleQuoted
+            | State::DoctypeSystemIdentifierSingleQuoted => {
+                let (is_public, quote) = match self.state {
+                    State::DoctypePublicIdentifierDoubleQuoted => (true, '"'),
+                    State::DoctypePublicIdentifierSingleQuoted => (true, '\''),
+                    State::DoctypeSystemIdentifierDoubleQuoted => (false, '"'),
+                    _ => (false, '\''),
+                };
+                match c {
+                    Some(c) if c == quote => {
+                        self.state = if is_public {
+                            State::AfterDoctypePublicIdentifier
+                        } else {
+                            State::AfterDoctypeSystemIdentifier
+                        }
+                    }
+                    Some('>') => {
+                        self.error("abrupt-doctype-identifier");
+                        self.state = State::Data;
+                        self.emit_current_doctype(true);
+                    }
+                    None => {
+                        self.error("eof-in-doctype");
+                        self.emit_current_doctype(true);
+                        self.emit(Token::EOF);
+                    }
+                    Some(c) => {
+                        let c = if c == '\0' { '\u{FFFD}' } else { c };
+                        let doctype = self.current_doctype_mut();
+                        let identifier = if is_public {
+                            doctype.public_id.as_mut()
+                        } else {
+                            doctype.system_id.as_mut()
+                        };
+                        if let Some(identifier) = identifier {
+                            identifier.push(c);
+                        }
+                    }
+                }
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-public-identifier-state
+            // https://html.spec.whatwg.org/multipage/parsing.html#between-doctype-public-and-system-identifiers-state
+            State::AfterDoctypePublicIdentifier
+            | State::BetweenDoctypePublicAndSystemIdentifiers => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BetweenDoctypePublicAndSystemIdentifiers
+                }
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype(false);
+                }
+                Some('"') | Some('\'') => {
+                    if self.state == State::AfterDoctypePublicIdentifier {
+                        self.error(
+                            "missing-whitespace-between-doctype-public-and-system-identifiers",
+                        );
+                    }
+                    self.start_doctype_identifier(false, c == Some('"'));
+                }
+                _ => self.doctype_identifier_missing(c),
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-system-identifier-state
+            State::AfterDoctypeSystemIdentifier => match c {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype(false);
+                }
+                None => {
+                    self.error("eof-in-doctype");
+                    self.emit_current_doctype(true);
+                    self.emit(Token::EOF);
+                }
+                Some(c) => {
+                    self.error("unexpected-character-after-doctype-system-identifier");
+                    self.reconsume(Some(c), State::BogusDoctype);
+                }
+            },
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#bogus-doctype-state
+            State::BogusDoctype => match c {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype(false);
+                }
+                None => {
+                    self.emit_current_doctype(false);
+                    self.emit(Token::EOF);
+                }
+                _ => {}
+            },
+        }
+
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#markup-declaration-open-state
+    fn markup_declaration_open(&mut self) -> bool {
+        let rest = &self.input[self.pos..];
+        if !self.closed && rest.len() < "DOCTYPE".len() {
+            // wait for more input unless it's obvious that none of the keywords follows.
+            let prefixes = ["--", "doctype", "[CDATA["];
+            let may_follow = prefixes.iter().any(|prefix| {
+                rest.iter()
+                    .zip(prefix.chars())
+                    .all(|(a, b)| a.eq_ignore_ascii_case(&b))
+            });
+            if may_follow {
+                return false;
+            }
+        }
+
+        self.current_comment = String::new();
+        if self.consume_if_ascii_case_insensitive("--") {
+            self.state = State::CommentStart;
+        } else if self.consume_if_ascii_case_insensitive("doctype") {
+            self.state = State::Doctype;
+        } else {
+            // NOTE: CDATA sections are only allowed in foreign content, which puppy doesn't support.
+            self.error("incorrectly-opened-comment");
+            self.state = State::BogusComment;
+        }
+        true
+    }
+
+    /// `emit_text_character` emits a character in states like RAWTEXT, replacing U+0000 NULL with U+FFFD.
+    fn emit_text_character(&mut self, c: Option<char>) {
+        match c {
+            Some('\0') => {
+                self.error("unexpected-null-character");
+                self.emit(Token::Character('\u{FFFD}'));
+            }
+            Some(c) => self.emit(Token::Character(c)),
+            None => self.emit(Token::EOF),
+        }
+    }
+
+    /// `is_appropriate_end_tag` checks whether the current end tag closes the last emitted start tag.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    fn is_appropriate_end_tag(&self) -> bool {
+        match (&self.current_tag, &self.last_start_tag_name) {
+            (Some((true, tag)), Some(name)) => tag.tag_name == *name,
+            _ => false,
+        }
+    }
+
+    /// `text_end_tag_open` implements the end tag open states of RCDATA, RAWTEXT and script data.
+    fn text_end_tag_open(&mut self, c: Option<char>, name_state: State, text_state: State) {
+        match c {
+            Some(c) if c.is_ascii_alphabetic() => {
+                self.start_tag(true);
+                self.reconsume(Some(c), name_state);
+            }
+            _ => {
+                self.emit(Token::Character('<'));
+                self.emit(Token::Character('/'));
+                self.reconsume(c, text_state);
+            }
+        }
+    }
+
+    /// `text_end_tag_name` implements the end tag name states of RCDATA, RAWTEXT and script data.
+    /// Unless the tag is an appropriate end tag, the characters consumed so far are emitted as text.
+    fn text_end_tag_name(&mut self, c: Option<char>, text_state: State) {
+        match c {
+            Some('\t') | Some('\n') | Some('\x0C') | Some(' ') if self.is_appropriate_end_tag() => {
+                self.state = State::BeforeAttributeName;
+            }
+            Some('/') if self.is_appropriate_end_tag() => {
+                self.state = State::SelfClosingStartTag;
+            }
+            Some('>') if self.is_appropriate_end_tag() => {
+                self.state = State::Data;
+                self.emit_current_tag();
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                self.current_tag_mut().tag_name.push(c.to_ascii_lowercase());
+                self.temporary_buffer.push(c);
+            }
+            _ => {
+                self.current_tag = None;
+                self.emit(Token::Character('<'));
+                self.emit(Token::Character('/'));
+                let buffer = std::mem::take(&mut self.temporary_buffer);
+                buffer.chars().for_each(|c| self.emit(Token::Character(c)));
+                self.reconsume(c, text_state);
+            }
+        }
+    }
+
+    /// `append_character_reference_to_attribute_value` handles a character reference in an attribute value.
+    /// It returns `false` if it needs more input to proceed.
+    fn append_character_reference_to_attribute_value(&mut self) -> bool {
+        match self.consume_character_reference(true) {
+            Some(s) => {
+                s.chars().for_each(|c| self.append_to_attribute_value(c));
+                true
+            }
+            None => {
+                self.pos -= 1;
+                false
+            }
+        }
+    }
+
+    /// `consume_character_reference` consumes a character reference following `&` and returns the characters it stands for.
+    /// When the input doesn't form a valid reference, it returns `&` only and leaves the rest of the input as it is.
+    /// It returns `None` if it needs more input to decide.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+    fn consume_character_reference(&mut self, in_attribute: bool) -> Option<String> {
+        match self.input.get(self.pos) {
+            Some('#') => self.consume_numeric_character_reference(),
+            Some(c) if c.is_ascii_alphanumeric() => {
+                self.consume_named_character_reference(in_attribute)
+            }
+            None if !self.closed => None,
+            _ => Some("&".to_string()),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
+    fn consume_named_character_reference(&mut self, in_attribute: bool) -> Option<String> {
+        let rest = &self.input[self.pos..];
+        let may_continue = rest
+            .iter()
+            .take(LONGEST_NAME_LENGTH)
+            .all(|c| c.is_ascii_alphanumeric());
+        if !self.closed && may_continue && rest.len() <= LONGEST_NAME_LENGTH {
+            return None;
+        }
+
+        // find the longest name matching with the input.
+        let matched = (1..=std::cmp::min(rest.len(), LONGEST_NAME_LENGTH))
+            .rev()
+            .find_map(|len| {
+                let candidate: String = rest[..len].iter().collect();
+                NAMED_CHARACTER_REFERENCES
+                    .binary_search_by(|(name, _)| name.cmp(&candidate.as_str()))
+                    .ok()
+                    .map(|i| NAMED_CHARACTER_REFERENCES[i])
+            });
+
+        let (name, value) = match matched {
+            Some(m) => m,
+            None => {
+                // NOTE: the following alphanumerics are consumed as they are, as the ambiguous ampersand state does.
+                if let Some(len) = rest.iter().position(|c| !c.is_ascii_alphanumeric()) {
+                    if rest[len] == ';' {
+                        self.error("unknown-named-character-reference");
+                    }
+                }
+                return Some("&".to_string());
+            }
+        };
+
+        let len = name.len();
+        if !name.ends_with(';') {
+            let next = rest.get(len);
+            if in_attribute && matches!(next, Some(c) if *c == '=' || c.is_ascii_alphanumeric()) {
+                // for historical reasons, the reference is kept as it is in this case.
+                self.pos += len;
+                return Some(format!("&{}", name));
+            }
+            self.error("missing-semicolon-after-character-reference");
+        }
+        self.pos += len;
+        Some(value.to_string())
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-state
+    fn consume_numeric_character_reference(&mut self) -> Option<String> {
+        let rest = &self.input[self.pos + 1..];
+        let is_hex = matches!(rest.first(), Some('x') | Some('X'));
+        let prefix_len = if is_hex { 2 } else { 1 };
+        let digits = &self.input[self.pos + prefix_len..];
+        let radix = if is_hex { 16 } else { 10 };
+
+        let digits_len = digits.iter().take_while(|c| c.is_digit(radix)).count();
+        if !self.closed && (rest.is_empty() || digits_len == digits.len()) {
+            return None;
+        }
+        if digits_len == 0 {
+            self.error("absence-of-digits-in-numeric-character-reference");
+            return Some("&".to_string());
+        }
+
+        let code = digits[..digits_len].iter().fold(0u32, |acc, c| {
+            acc.saturating_mul(radix)
+                .saturating_add(c.to_digit(radix).unwrap())
+        });
+        self.pos += prefix_len + digits_len;
+        if self.input.get(self.pos) == Some(&';') {
+            self.pos += 1;
+        } else {
+            self.error("missing-semicolon-after-character-reference");
+        }
+
+        // https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+        let c = match code {
+            0 => {
+                self.error("null-character-reference");
+                '\u{FFFD}'
+            }
+            0xD800..=0xDFFF => {
+                self.error("surrogate-character-reference");
+                '\u{FFFD}'
+            }
+            0x80..=0x9F => {
+                self.error("control-character-reference");
+                C1_REPLACEMENTS
+                    .iter()
+                    .find(|(from, _)| *from == code)
+                    .map(|(_, to)| *to)
+                    .unwrap_or_else(|| std::char::from_u32(code).unwrap())
+            }
+            code => match std::char::from_u32(code) {
+                Some(c) => {
+                    if c == '\r' || (c.is_control() && !c.is_ascii_whitespace()) {
+                        self.error("control-character-reference");
+                    }
+                    c
+                }
+                None => {
+                    self.error("character-reference-outside-unicode-range");
+                    '\u{FFFD}'
+                }
+            },
+        };
+        Some(c.to_string())
+    }
+
+    fn consume_if_ascii_case_insensitive(&mut self, expected: &str) -> bool {
+        let len = expected.chars().count();
+        let matched = self.input.len() >= self.pos + len
+            && self.input[self.pos..self.pos + len]
+                .iter()
+                .zip(expected.chars())
+                .all(|(a, b)| a.eq_ignore_ascii_case(&b));
+        if matched {
+            self.pos += len;
+        }
+        matched
+    }
+
+    fn start_doctype_identifier(&mut self, is_public: bool, double_quoted: bool) {
+        let doctype = self.current_doctype_mut();
+        if is_public {
+            doctype.public_id = Some(String::new());
+        } else {
+            doctype.system_id = Some(String::new());
+        }
+        self.state = match (is_public, double_quoted) {
+            (true, true) => State::DoctypePublicIdentifierDoubleQuoted,
+            (true, false) => State::DoctypePublicIdentifierSingleQuoted,
+            (false, true) => State::DoctypeSystemIdentifierDoubleQuoted,
+            (false, false) => State::DoctypeSystemIdentifierSingleQuoted,
+        };
+    }
+
+    fn doctype_identifier_missing(&mut self, c: Option<char>) {
+        match c {
+            Some('>') => {
+                self.error("missing-doctype-identifier");
+                self.state = State::Data;
+                self.emit_current_doctype(true);
+            }
+            None => {
+                self.error("eof-in-doctype");
+                self.emit_current_doctype(true);
+                self.emit(Token::EOF);
+            }
+            Some(c) => {
+                self.error("missing-quote-before-doctype-identifier");
+                self.current_doctype_mut().force_quirks = true;
+                self.reconsume(Some(c), State::BogusDoctype);
+            }
+        }
+    }
+}
+
+/// `C1_REPLACEMENTS` maps numeric character references in the C1 controls range to the characters legacy contents mean.
+const C1_REPLACEMENTS: &[(u32, char)] = &[
+    (0x80, '\u{20AC}'),
+    (0x82, '\u{201A}'),
+    (0x83, '\u{0192}'),
+    (0x84, '\u{201E}'),
+    (0x85, '\u{2026}'),
+    (0x86, '\u{2020}'),
+    (0x87, '\u{2021}'),
+    (0x88, '\u{02C6}'),
+    (0x89, '\u{2030}'),
+    (0x8A, '\u{0160}'),
+    (0x8B, '\u{2039}'),
+    (0x8C, '\u{0152}'),
+    (0x8E, '\u{017D}'),
+    (0x91, '\u{2018}'),
+    (0x92, '\u{2019}'),
+    (0x93, '\u{201C}'),
+    (0x94, '\u{201D}'),
+    (0x95, '\u{2022}'),
+    (0x96, '\u{2013}'),
+    (0x97, '\u{2014}'),
+    (0x98, '\u{02DC}'),
+    (0x99, '\u{2122}'),
+    (0x9A, '\u{0161}'),
+    (0x9B, '\u{203A}'),
+    (0x9C, '\u{0153}'),
+    (0x9E, '\u{017E}'),
+    (0x9F, '\u{0178}'),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokenize(s: &str) -> Vec<Token> {
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.feed(s);
+        tokenizer.close();
+
+        let mut tokens = vec![];
+        while let Some(token) = tokenizer.next_token() {
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    fn start_tag(tag_name: &str, attributes: Vec<(&str, &str)>) -> Token {
+        Token::StartTag(Tag {
+            tag_name: tag_name.to_string(),
+            attributes: attributes
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            self_closing: false,
+        })
+    }
+
+    fn end_tag(tag_name: &str) -> Token {
+        Token::EndTag(Tag::new(tag_name))
+    }
+
+    #[test]
+    fn test_tokenize_tags() {
+        assert_eq!(
+            tokenize("<p>hi</P>"),
+            vec![
+                start_tag("p", vec![]),
+                Token::Character('h'),
+                Token::Character('i'),
+                end_tag("p"),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            tokenize("<br/>"),
+            vec![
+                Token::StartTag(Tag {
+                    tag_name: "br".to_string(),
+                    attributes: vec![],
+                    self_closing: true,
+                }),
+                Token::EOF
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_attributes() {
+        assert_eq!(
+            tokenize("<p id=\"test\" CLASS = \"sample\">"),
+            vec![
+                start_tag("p", vec![("id", "test"), ("class", "sample")]),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            tokenize("<p id=a ID='b' id=\"c\">"),
+            vec![start_tag("p", vec![("id", "a")]), Token::EOF]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_invalid_markup() {
+        assert_eq!(
+            tokenize("a < b"),
+            vec![
+                Token::Character('a'),
+                Token::Character(' '),
+                Token::Character('<'),
+                Token::Character(' '),
+                Token::Character('b'),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            tokenize("<?xml?>"),
+            vec![Token::Comment("?xml?".to_string()), Token::EOF]
+        );
+        assert_eq!(tokenize("<p"), vec![Token::EOF]);
+    }
+
+    #[test]
+    fn test_tokenize_comments() {
+        assert_eq!(
+            tokenize("<!-- a -- b -->"),
+            vec![Token::Comment(" a -- b ".to_string()), Token::EOF]
+        );
+        assert_eq!(
+            tokenize("<!---->x"),
+            vec![
+                Token::Comment("".to_string()),
+                Token::Character('x'),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            tokenize("<!-- unclosed"),
+            vec![Token::Comment(" unclosed".to_string()), Token::EOF]
+        );
+        assert_eq!(
+            tokenize("<!foo>"),
+            vec![Token::Comment("foo".to_string()), Token::EOF]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_doctype() {
+        assert_eq!(
+            tokenize("<!DOCTYPE html>"),
+            vec![
+                Token::Doctype(Doctype {
+                    name: Some("html".to_string()),
+                    public_id: None,
+                    system_id: None,
+                    force_quirks: false,
+                }),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            tokenize("<!doctype HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\" 'http://www.w3.org/TR/html4/strict.dtd'>"),
+            vec![
+                Token::Doctype(Doctype {
+                    name: Some("html".to_string()),
+                    public_id: Some("-//W3C//DTD HTML 4.01//EN".to_string()),
+                    system_id: Some("http://www.w3.org/TR/html4/strict.dtd".to_string()),
+                    force_quirks: false,
+                }),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            tokenize("<!DOCTYPE>"),
+            vec![
+                Token::Doctype(Doctype {
+                    name: None,
+                    public_id: None,
+                    system_id: None,
+                    force_quirks: true,
+                }),
+                Token::EOF
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_incrementally() {
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.feed("<di");
+        assert_eq!(tokenizer.next_token(), None);
+        tokenizer.feed("v>\r\n");
+        tokenizer.close();
+        assert_eq!(tokenizer.next_token(), Some(start_tag("div", vec![])));
+        assert_eq!(tokenizer.next_token(), Some(Token::Character('\n')));
+        assert_eq!(tokenizer.next_token(), Some(Token::EOF));
+        assert_eq!(tokenizer.next_token(), None);
+
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.feed("<!DOC");
+        assert_eq!(tokenizer.next_token(), None);
+        tokenizer.feed("TYPE html>");
+        tokenizer.close();
+        assert!(matches!(tokenizer.next_token(), Some(Token::Doctype(_))));
+
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.feed("&am");
+        assert_eq!(tokenizer.next_token(), None);
+        tokenizer.feed("p;");
+        tokenizer.close();
+        assert_eq!(tokenizer.next_token(), Some(Token::Character('&')));
+        assert_eq!(tokenizer.next_token(), Some(Token::EOF));
+    }
+
+    #[test]
+    fn test_insert_at_insertion_point() {
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.feed("<p>b");
+        assert_eq!(tokenizer.next_token(), Some(start_tag("p", vec![])));
+        assert!(!tokenizer.insert("x"));
+
+        tokenizer.set_insertion_point();
+        assert!(tokenizer.insert("<i>"));
+        assert!(tokenizer.insert("a\r\n"));
+        tokenizer.clear_insertion_point();
+        assert!(!tokenizer.insert("y"));
+        tokenizer.close();
+
+        let mut tokens = vec![];
+        while let Some(token) = tokenizer.next_token() {
+            tokens.push(token);
+        }
+        assert_eq!(
+            tokens,
+            vec![
+                start_tag("i", vec![]),
+                Token::Character('a'),
+                Token::Character('\n'),
+                Token::Character('b'),
+                Token::EOF
+            ]
+        );
+        assert!(tokenizer.is_finished());
+    }
+
+    fn text(s: &str) -> Vec<Token> {
+        s.chars()
+            .map(Token::Character)
+            .chain(std::iter::once(Token::EOF))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_text_states() {
+        fn tokenize_in(state: State, s: &str) -> Vec<Token> {
+            let mut tokenizer = Tokenizer::new();
+            tokenizer.feed(&format!("<x>{}", s));
+            tokenizer.close();
+            let mut tokens = vec![tokenizer.next_token().unwrap()];
+            tokenizer.set_state(state);
+            while let Some(token) = tokenizer.next_token() {
+                tokens.push(token);
+            }
+            tokens
+        }
+        fn expected(data: &str, end: Vec<Token>) -> Vec<Token> {
+            std::iter::once(start_tag("x", vec![]))
+                .chain(data.chars().map(Token::Character))
+                .chain(end)
+                .collect()
+        }
+
+        assert_eq!(
+            tokenize_in(State::RAWTEXT, "a<b</y>&amp;</X>"),
+            expected("a<b</y>&amp;", vec![end_tag("x"), Token::EOF])
+        );
+        assert_eq!(
+            tokenize_in(State::RCDATA, "a<b</y>&amp;</x>"),
+            expected("a<b</y>&", vec![end_tag("x"), Token::EOF])
+        );
+        assert_eq!(
+            tokenize_in(State::ScriptData, "<!-- <script> </x> </script> --></x>"),
+            expected(
+                "<!-- <script> </x> </script> -->",
+                vec![end_tag("x"), Token::EOF]
+            )
+        );
+        assert_eq!(
+            tokenize_in(State::PLAINTEXT, "</x>"),
+            expected("</x>", vec![Token::EOF])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_character_references() {
+        assert_eq!(tokenize("&lt;&amp;&gt;"), text("<&>"));
+        assert_eq!(tokenize("&#60;&#x3C;&#X3c;"), text("<<<"));
+        assert_eq!(tokenize("&notin; &notit;"), text("\u{2209} \u{AC}it;"));
+        assert_eq!(tokenize("&copy 2021"), text("\u{A9} 2021"));
+        assert_eq!(tokenize("&unknown; & &# &;"), text("&unknown; & &# &;"));
+        assert_eq!(
+            tokenize("&#0;&#x110000;&#xD800;"),
+            text("\u{FFFD}\u{FFFD}\u{FFFD}")
+        );
+        assert_eq!(tokenize("&#x80;&#150"), text("\u{20AC}\u{2013}"));
+        assert_eq!(tokenize("&amp"), text("&"));
+
+        assert_eq!(
+            tokenize("<a title=\"&quot;x&quot;\" href=?a=1&copy=2&amp;b=&copy;>"),
+            vec![
+                start_tag(
+                    "a",
+                    vec![("title", "\"x\""), ("href", "?a=1&copy=2&b=\u{A9}")]
+                ),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            tokenize("<a title='&copy'>"),
+            vec![start_tag("a", vec![("title", "\u{A9}")]), Token::EOF]
+        );
+    }
+}
@@ -0,0 +1,2156 @@
+//! This module includes an implementation of the tree construction stage of HTML parsing.
+//!
+//! The algorithm is defined at https://html.spec.whatwg.org/multipage/parsing.html#tree-construction.
+//! Here are some notes on what puppy omits from the standard:
+//! - `template`, `frameset` and foreign elements (SVG and MathML) are handled as ordinary elements.
+//! - the document is always parsed in no-quirks mode.
+
+use super::tokenizer::{Doctype, State, Tag, Token, Tokenizer};
+use super::VOID_ELEMENTS;
+use crate::dom::{
+    AttrMap, Comment, Document, DocumentType, Element, NodeId, NodeTree, NodeType, Text,
+};
+use crate::source::{ParseDiagnostic, SourceSpan};
+use log::trace;
+
+/// `InsertionMode` is a mode of the tree builder defined at https://html.spec.whatwg.org/multipage/parsing.html#the-insertion-mode.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum InsertionMode {
+    Initial,
+    BeforeHtml,
+    BeforeHead,
+    InHead,
+    AfterHead,
+    InBody,
+    Text,
+    InTable,
+    InTableText,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    InSelect,
+    InSelectInTable,
+    AfterBody,
+    AfterAfterBody,
+}
+
+/// `Step` describes what to do after a token is processed by the rules of an insertion mode.
+enum Step {
+    Done,
+    Reprocess(Token),
+}
+
+/// `Scope` is a kind of *element in scope* defined at https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-the-specific-scope.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Scope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+    Select,
+}
+
+/// `FormattingEntry` is an entry of *the list of active formatting elements*.
+#[derive(Debug, Clone)]
+enum FormattingEntry {
+    Marker,
+    Element(NodeId, Tag),
+}
+
+const SPECIAL_ELEMENTS: &[&str] = &[
+    "address",
+    "applet",
+    "area",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "bgsound",
+    "blockquote",
+    "body",
+    "br",
+    "button",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "embed",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "iframe",
+    "img",
+    "input",
+    "keygen",
+    "li",
+    "link",
+    "listing",
+    "main",
+    "marquee",
+    "menu",
+    "meta",
+    "nav",
+    "noembed",
+    "noframes",
+    "noscript",
+    "object",
+    "ol",
+    "p",
+    "param",
+    "plaintext",
+    "pre",
+    "script",
+    "search",
+    "section",
+    "select",
+    "source",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "textarea",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+    "wbr",
+    "xmp",
+];
+
+const HEADINGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+const FORMATTING_ELEMENTS: &[&str] = &[
+    "a", "b", "big", "code", "em", "font", "i", "nobr", "s", "small", "strike", "strong", "tt", "u",
+];
+
+const IMPLIED_END_TAGS: &[&str] = &[
+    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
+];
+
+const TABLE_SECTIONS: &[&str] = &["tbody", "tfoot", "thead"];
+
+fn is_whitespace(c: char) -> bool {
+    c == '\t' || c == '\n' || c == '\x0C' || c == '\r' || c == ' '
+}
+
+/// `TreeBuilder` consumes tokens from `Tokenizer` and constructs a node tree.
+pub struct TreeBuilder {
+    tokenizer: Tokenizer,
+
+    document: Document,
+    mode: InsertionMode,
+    original_mode: InsertionMode,
+    open_elements: Vec<NodeId>,
+    active_formatting_elements: Vec<FormattingEntry>,
+    head_element: Option<NodeId>,
+    form_element: Option<NodeId>,
+    context_element: Option<NodeId>,
+
+    foster_parenting: bool,
+    ignore_next_lf: bool,
+    pending_table_characters: Vec<char>,
+
+    // `current_span` and `current_start_tag_name` describe the token being processed.
+    // They are used to record where each node comes from.
+    current_span: SourceSpan,
+    current_start_tag_name: Option<String>,
+    errors: Vec<ParseDiagnostic>,
+
+    // `pending_script` is the text of a `<script>` element whose end tag has just been processed.
+    pending_script: Option<String>,
+}
+
+impl TreeBuilder {
+    /// `new` creates a tree builder to parse a whole document.
+    pub fn new(tokenizer: Tokenizer) -> Self {
+        TreeBuilder {
+            tokenizer,
+
+            document: Document::new("".to_string(), "".to_string()),
+            mode: InsertionMode::Initial,
+            original_mode: InsertionMode::Initial,
+            open_elements: vec![],
+            active_formatting_elements: vec![],
+            head_element: None,
+            form_element: None,
+            context_element: None,
+
+            foster_parenting: false,
+            ignore_next_lf: false,
+            pending_table_characters: vec![],
+
+            current_span: SourceSpan::default(),
+            current_start_tag_name: None,
+            errors: vec![],
+
+            pending_script: None,
+        }
+    }
+
+    /// `for_fragment` creates a tree builder to parse a fragment in the context of `context` element.
+    /// See https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-parsing-algorithm for the details.
+    pub fn for_fragment(tokenizer: Tokenizer, context: &Element) -> Self {
+        let mut builder = Self::new(tokenizer);
+
+        // NOTE: puppy runs scripts, so `<noscript>` is always parsed as raw text.
+        match context.tag_name.as_str() {
+            "title" | "textarea" => builder.tokenizer.set_state(State::RCDATA),
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" | "noscript" => {
+                builder.tokenizer.set_state(State::RAWTEXT)
+            }
+            "script" => builder.tokenizer.set_state(State::ScriptData),
+            "plaintext" => builder.tokenizer.set_state(State::PLAINTEXT),
+            _ => {}
+        }
+
+        let mut tag = Tag::new(context.tag_name.as_str());
+        tag.attributes = context
+            .attributes
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        let context = builder.create_element(&tag);
+        builder.context_element = Some(context);
+
+        let root = builder.create_element(&Tag::new("html"));
+        builder.append(builder.document.root(), root);
+        builder.open_elements.push(root);
+
+        builder.reset_insertion_mode();
+        builder
+    }
+
+    /// `tokenizer` returns the tokenizer the tree builder consumes tokens from.
+    /// The caller can feed more input to it and resume parsing with `run`.
+    pub fn tokenizer(&mut self) -> &mut Tokenizer {
+        &mut self.tokenizer
+    }
+
+    /// `run` processes the tokens the tokenizer emits until more input is needed or a script is ready to be executed.
+    /// In the latter case, it returns the text of the script, and the caller should execute it before calling `run` again.
+    /// Scripts in fragments are never returned, because they are not executed as the standard says.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#scriptEndTag
+    pub fn run(&mut self) -> Option<String> {
+        while let Some(token) = self.tokenizer.next_token() {
+            self.current_span = self.tokenizer.last_token_span();
+            self.process(token);
+            if let Some(script) = self.pending_script.take() {
+                return Some(script);
+            }
+        }
+        None
+    }
+
+    /// `into_document` returns a `Document` with the constructed tree.
+    pub fn into_document(mut self, url: String, document_uri: String) -> Document {
+        let mut document = self.take_document(url, document_uri);
+        document.parse_errors = self.tokenizer.take_errors();
+        document.parse_errors.append(&mut self.errors);
+        document.parse_errors.sort_by_key(|e| e.position);
+        document
+    }
+
+    /// `take_document` moves the document constructed so far out of the tree builder, so that scripts can access it while parsing is paused.
+    /// The document must be given back with `restore_document` before parsing is resumed.
+    pub fn take_document(&mut self, url: String, document_uri: String) -> Document {
+        let mut document = std::mem::replace(
+            &mut self.document,
+            Document::new("".to_string(), "".to_string()),
+        );
+        document.url = url;
+        document.document_uri = document_uri;
+        document
+    }
+
+    /// `restore_document` gives back `document`, which has been taken with `take_document` and may have been modified by scripts.
+    ///
+    /// NOTE: `NodeId`s stay valid while scripts run, so the parser keeps inserting nodes into the open elements even if scripts remove them from the tree, as the standard says.
+    pub fn restore_document(&mut self, document: Document) {
+        self.document = document;
+    }
+
+    /// `into_fragment` returns the nodes parsed by a tree builder created with `for_fragment`.
+    pub fn into_fragment(self) -> Vec<NodeTree> {
+        let root = self
+            .document
+            .node(self.document.root())
+            .first_child()
+            .unwrap();
+        self.document
+            .children(root)
+            .map(|id| self.document.to_tree(id))
+            .collect()
+    }
+
+    fn error(&mut self, message: &'static str) {
+        trace!("parse error in tree construction; {}", message);
+        self.errors.push(ParseDiagnostic::new(
+            message.to_string(),
+            self.current_span.start,
+        ));
+    }
+}
+
+/// Implementations for node operations
+impl TreeBuilder {
+    fn create_element(&mut self, tag: &Tag) -> NodeId {
+        let attributes: AttrMap = tag.attributes.iter().cloned().collect();
+        let id = self.create_node(NodeType::Element(Element {
+            tag_name: tag.tag_name.clone(),
+            attributes,
+        }));
+        // elements implied by the parser, such as `<html>` inserted before `<p>`, have no location in the source.
+        if self.current_start_tag_name.as_ref() != Some(&tag.tag_name) {
+            self.document.node_mut(id).source_span = None;
+        }
+        id
+    }
+
+    fn create_node(&mut self, node_type: NodeType) -> NodeId {
+        let id = self.document.create_node(node_type);
+        self.document.node_mut(id).source_span = Some(self.current_span);
+        id
+    }
+
+    fn append(&mut self, parent: NodeId, child: NodeId) {
+        self.document.append(parent, child);
+    }
+
+    fn insert_at(&mut self, (parent, reference): (NodeId, Option<NodeId>), child: NodeId) {
+        self.document.insert(parent, child, reference);
+    }
+
+    fn is_element(&self, id: NodeId) -> bool {
+        matches!(self.document.node(id).node_type, NodeType::Element(_))
+    }
+
+    fn tag_name(&self, id: NodeId) -> &str {
+        match self.document.node(id).node_type {
+            NodeType::Element(ref e) => e.tag_name.as_str(),
+            _ => "",
+        }
+    }
+
+    fn is_one_of(&self, id: NodeId, names: &[&str]) -> bool {
+        names.contains(&self.tag_name(id))
+    }
+
+    fn current_node(&self) -> NodeId {
+        self.open_elements
+            .last()
+            .cloned()
+            .unwrap_or_else(|| self.document.root())
+    }
+
+    fn current_node_is(&self, names: &[&str]) -> bool {
+        self.is_one_of(self.current_node(), names)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-place-for-inserting-a-node
+    fn appropriate_place(&self, override_target: Option<NodeId>) -> (NodeId, Option<NodeId>) {
+        let target = override_target.unwrap_or_else(|| self.current_node());
+        if self.foster_parenting
+            && self.is_one_of(target, &["table", "tbody", "tfoot", "thead", "tr"])
+        {
+            let last_table = self
+                .open_elements
+                .iter()
+                .rposition(|&id| self.tag_name(id) == "table");
+            match last_table {
+                Some(index) => {
+                    let table = self.open_elements[index];
+                    match self.document.node(table).parent() {
+                        Some(parent) => (parent, Some(table)),
+                        None => (self.open_elements[index - 1], None),
+                    }
+                }
+                None => (self.open_elements[0], None),
+            }
+        } else {
+            (target, None)
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-an-html-element
+    fn insert_element(&mut self, tag: &Tag) -> NodeId {
+        let place = self.appropriate_place(None);
+        let element = self.create_element(tag);
+        self.insert_at(place, element);
+        self.open_elements.push(element);
+        element
+    }
+
+    /// `insert_void_element` inserts an element which is popped from the stack of open elements immediately.
+    fn insert_void_element(&mut self, tag: &Tag) {
+        self.insert_element(tag);
+        self.open_elements.pop();
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-character
+    fn insert_character(&mut self, c: char) {
+        let (parent, reference) = self.appropriate_place(None);
+        if parent == self.document.root() {
+            return;
+        }
+
+        let previous = match reference {
+            Some(reference) => self.document.node(reference).previous_sibling(),
+            None => self.document.node(parent).last_child(),
+        };
+        if let Some(previous) = previous {
+            let end = self.current_span.end;
+            let previous = self.document.node_mut(previous);
+            if let NodeType::Text(ref mut t) = previous.node_type {
+                t.data.push(c);
+                if let Some(ref mut span) = previous.source_span {
+                    span.end = end;
+                }
+                return;
+            }
+        }
+
+        let text = self.create_node(NodeType::Text(Text {
+            data: c.to_string(),
+        }));
+        self.insert_at((parent, reference), text);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-comment
+    fn insert_comment(&mut self, data: String, parent: Option<NodeId>) {
+        let place = match parent {
+            Some(parent) => (parent, None),
+            None => self.appropriate_place(None),
+        };
+        let comment = self.create_node(NodeType::Comment(Comment { data }));
+        self.insert_at(place, comment);
+    }
+
+    fn insert_doctype(&mut self, doctype: Doctype) {
+        let node = self.create_node(NodeType::DocumentType(DocumentType {
+            name: doctype.name.unwrap_or_default(),
+            public_id: doctype.public_id.unwrap_or_default(),
+            system_id: doctype.system_id.unwrap_or_default(),
+        }));
+        self.append(self.document.root(), node);
+    }
+
+    /// `add_missing_attributes` copies attributes in `tag` to the element if the element doesn't have them yet.
+    fn add_missing_attributes(&mut self, id: NodeId, tag: &Tag) {
+        if let NodeType::Element(ref mut e) = self.document.node_mut(id).node_type {
+            for (name, value) in &tag.attributes {
+                if !e.attributes.contains_key(name) {
+                    e.attributes.insert(name.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    /// `parse_generic_text_element` inserts an element whose contents are tokenized in `state`, and switches to the "text" insertion mode.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generic-raw-text-element-parsing-algorithm
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generic-rcdata-element-parsing-algorithm
+    fn parse_generic_text_element(&mut self, tag: &Tag, state: State) {
+        self.insert_element(tag);
+        self.tokenizer.set_state(state);
+        self.original_mode = self.mode;
+        self.mode = InsertionMode::Text;
+    }
+}
+
+/// Implementations for the stack of open elements
+impl TreeBuilder {
+    fn scope_boundaries(scope: Scope) -> &'static [&'static str] {
+        match scope {
+            Scope::Default => &[
+                "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template",
+            ],
+            Scope::ListItem => &[
+                "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template",
+                "ol", "ul",
+            ],
+            Scope::Button => &[
+                "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template",
+                "button",
+            ],
+            Scope::Table => &["html", "table", "template"],
+            Scope::Select => &[],
+        }
+    }
+
+    fn is_scope_boundary(&self, id: NodeId, scope: Scope) -> bool {
+        match scope {
+            Scope::Select => !self.is_one_of(id, &["optgroup", "option"]),
+            _ => self.is_one_of(id, Self::scope_boundaries(scope)),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-the-specific-scope
+    fn in_scope(&self, names: &[&str], scope: Scope) -> bool {
+        for &id in self.open_elements.iter().rev() {
+            if self.is_one_of(id, names) {
+                return true;
+            }
+            if self.is_scope_boundary(id, scope) {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn node_in_scope(&self, target: NodeId, scope: Scope) -> bool {
+        for &id in self.open_elements.iter().rev() {
+            if id == target {
+                return true;
+            }
+            if self.is_scope_boundary(id, scope) {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn pop_until(&mut self, names: &[&str]) {
+        while let Some(id) = self.open_elements.pop() {
+            if self.is_one_of(id, names) {
+                break;
+            }
+        }
+    }
+
+    fn pop_until_node(&mut self, target: NodeId) {
+        while let Some(id) = self.open_elements.pop() {
+            if id == target {
+                break;
+            }
+        }
+    }
+
+    fn remove_from_stack(&mut self, target: NodeId) {
+        self.open_elements.retain(|&id| id != target);
+    }
+
+    fn clear_stack_back_to(&mut self, names: &[&str]) {
+        while !self.current_node_is(names) && self.open_elements.len() > 1 {
+            self.open_elements.pop();
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
+    fn generate_implied_end_tags(&mut self, except: Option<&str>) {
+        while self.current_node_is(IMPLIED_END_TAGS)
+            && Some(self.tag_name(self.current_node())) != except
+        {
+            self.open_elements.pop();
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-a-p-element
+    fn close_p_element(&mut self) {
+        self.generate_implied_end_tags(Some("p"));
+        if !self.current_node_is(&["p"]) {
+            self.error("unexpected-end-tag");
+        }
+        self.pop_until(&["p"]);
+    }
+
+    fn close_p_element_in_button_scope(&mut self) {
+        if self.in_scope(&["p"], Scope::Button) {
+            self.close_p_element();
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately
+    fn reset_insertion_mode(&mut self) {
+        for (i, &id) in self.open_elements.iter().enumerate().rev() {
+            let last = i == 0;
+            let node = match self.context_element {
+                Some(context) if last => context,
+                _ => id,
+            };
+            self.mode = match self.tag_name(node) {
+                "select" => {
+                    let in_table = self.open_elements[..i]
+                        .iter()
+                        .rev()
+                        .take_while(|&&ancestor| self.tag_name(ancestor) != "template")
+                        .any(|&ancestor| self.tag_name(ancestor) == "table");
+                    if in_table {
+                        InsertionMode::InSelectInTable
+                    } else {
+                        InsertionMode::InSelect
+                    }
+                }
+                "td" | "th" if !last => InsertionMode::InCell,
+                "tr" => InsertionMode::InRow,
+                "tbody" | "thead" | "tfoot" => InsertionMode::InTableBody,
+                "caption" => InsertionMode::InCaption,
+                "colgroup" => InsertionMode::InColumnGroup,
+                "table" => InsertionMode::InTable,
+                "head" if !last => InsertionMode::InHead,
+                "body" => InsertionMode::InBody,
+                "html" => {
+                    if self.head_element.is_none() {
+                        InsertionMode::BeforeHead
+                    } else {
+                        InsertionMode::AfterHead
+                    }
+                }
+                _ if last => InsertionMode::InBody,
+                _ => continue,
+            };
+            return;
+        }
+        self.mode = InsertionMode::InBody;
+    }
+}
+
+/// Implementations for the list of active formatting elements
+impl TreeBuilder {
+    fn formatting_position(&self, target: NodeId) -> Option<usize> {
+        self.active_formatting_elements
+            .iter()
+            .position(|entry| match entry {
+                FormattingEntry::Element(id, _) => *id == target,
+                FormattingEntry::Marker => false,
+            })
+    }
+
+    /// `push_formatting_element` pushes the element to the list with the Noah's Ark clause.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#push-onto-the-list-of-active-formatting-elements
+    fn push_formatting_element(&mut self, id: NodeId, tag: &Tag) {
+        let mut same_elements = vec![];
+        for (i, entry) in self.active_formatting_elements.iter().enumerate().rev() {
+            match entry {
+                FormattingEntry::Marker => break,
+                FormattingEntry::Element(_, t) => {
+                    let mut a = t.attributes.clone();
+                    let mut b = tag.attributes.clone();
+                    a.sort();
+                    b.sort();
+                    if t.tag_name == tag.tag_name && a == b {
+                        same_elements.push(i);
+                    }
+                }
+            }
+        }
+        if same_elements.len() >= 3 {
+            self.active_formatting_elements
+                .remove(*same_elements.last().unwrap());
+        }
+        self.active_formatting_elements
+            .push(FormattingEntry::Element(id, tag.clone()));
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reconstruct-the-active-formatting-elements
+    fn reconstruct_active_formatting_elements(&mut self) {
+        let is_open = |builder: &Self, entry: &FormattingEntry| match entry {
+            FormattingEntry::Marker => true,
+            FormattingEntry::Element(id, _) => builder.open_elements.contains(id),
+        };
+
+        let mut i = match self.active_formatting_elements.last() {
+            None => return,
+            Some(entry) if is_open(self, entry) => return,
+            Some(_) => self.active_formatting_elements.len() - 1,
+        };
+        while i > 0 && !is_open(self, &self.active_formatting_elements[i - 1]) {
+            i -= 1;
+        }
+
+        for j in i..self.active_formatting_elements.len() {
+            if let FormattingEntry::Element(_, tag) = self.active_formatting_elements[j].clone() {
+                let element = self.insert_element(&tag);
+                self.active_formatting_elements[j] = FormattingEntry::Element(element, tag);
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-list-of-active-formatting-elements-up-to-the-last-marker
+    fn clear_active_formatting_elements_to_last_marker(&mut self) {
+        while let Some(entry) = self.active_formatting_elements.pop() {
+            if let FormattingEntry::Marker = entry {
+                break;
+            }
+        }
+    }
+
+    /// `adoption_agency` runs the adoption agency algorithm, which fixes misnested formatting elements like `<b><i></b></i>`.
+    /// It returns `false` if the end tag should be processed as "any other end tag".
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+    fn adoption_agency(&mut self, subject: &str) -> bool {
+        let current = self.current_node();
+        if self.tag_name(current) == subject && self.formatting_position(current).is_none() {
+            self.open_elements.pop();
+            return true;
+        }
+
+        for _ in 0..8 {
+            // find the formatting element
+            let found = self
+                .active_formatting_elements
+                .iter()
+                .enumerate()
+                .rev()
+                .take_while(|(_, entry)| !matches!(entry, FormattingEntry::Marker))
+                .find_map(|(i, entry)| match entry {
+                    FormattingEntry::Element(id, tag) if tag.tag_name == subject => {
+                        Some((i, *id, tag.clone()))
+                    }
+                    _ => None,
+                });
+            let (mut bookmark, formatting_element, formatting_tag) = match found {
+                Some(found) => found,
+                None => return false,
+            };
+
+            let stack_index = match self
+                .open_elements
+                .iter()
+                .position(|&id| id == formatting_element)
+            {
+                Some(index) => index,
+                None => {
+                    self.error("adoption-agency-1.2");
+                    self.active_formatting_elements.remove(bookmark);
+                    return true;
+                }
+            };
+            if !self.node_in_scope(formatting_element, Scope::Default) {
+                self.error("adoption-agency-4.4");
+                return true;
+            }
+            if formatting_element != self.current_node() {
+                self.error("adoption-agency-1.3");
+            }
+
+            // find the furthest block
+            let furthest_block = self.open_elements[stack_index + 1..]
+                .iter()
+                .find(|&&id| self.is_one_of(id, SPECIAL_ELEMENTS))
+                .cloned();
+            let furthest_block = match furthest_block {
+                Some(furthest_block) => furthest_block,
+                None => {
+                    self.pop_until_node(formatting_element);
+                    self.active_formatting_elements.remove(bookmark);
+                    return true;
+                }
+            };
+
+            let common_ancestor = self.open_elements[stack_index - 1];
+
+            // the inner loop
+            let mut node_index = self
+                .open_elements
+                .iter()
+                .position(|&id| id == furthest_block)
+                .unwrap();
+            let mut last_node = furthest_block;
+            let mut inner_loop_counter = 0;
+            loop {
+                inner_loop_counter += 1;
+                node_index -= 1;
+                let node = self.open_elements[node_index];
+                if node == formatting_element {
+                    break;
+                }
+
+                let mut formatting_index = self.formatting_position(node);
+                if inner_loop_counter > 3 {
+                    if let Some(index) = formatting_index {
+                        self.active_formatting_elements.remove(index);
+                        if index < bookmark {
+                            bookmark -= 1;
+                        }
+                        formatting_index = None;
+                    }
+                }
+                let formatting_index = match formatting_index {
+                    Some(index) => index,
+                    None => {
+                        self.open_elements.remove(node_index);
+                        continue;
+                    }
+                };
+
+                let tag = match &self.active_formatting_elements[formatting_index] {
+                    FormattingEntry::Element(_, tag) => tag.clone(),
+                    FormattingEntry::Marker => unreachable!(),
+                };
+                let new_element = self.create_element(&tag);
+                self.active_formatting_elements[formatting_index] =
+                    FormattingEntry::Element(new_element, tag);
+                self.open_elements[node_index] = new_element;
+
+                if last_node == furthest_block {
+                    bookmark = formatting_index + 1;
+                }
+                self.append(new_element, last_node);
+                last_node = new_element;
+            }
+
+            let place = self.appropriate_place(Some(common_ancestor));
+            self.insert_at(place, last_node);
+
+            let new_element = self.create_element(&formatting_tag);
+            let children = self.document.children(furthest_block).collect::<Vec<_>>();
+            for child in children {
+                self.append(new_element, child);
+            }
+            self.append(furthest_block, new_element);
+
+            let old_index = self.formatting_position(formatting_element).unwrap();
+            self.active_formatting_elements.remove(old_index);
+            if old_index < bookmark {
+                bookmark -= 1;
+            }
+            let bookmark = bookmark.min(self.active_formatting_elements.len());
+            self.active_formatting_elements.insert(
+                bookmark,
+                FormattingEntry::Element(new_element, formatting_tag),
+            );
+
+            self.remove_from_stack(formatting_element);
+            let furthest_block_index = self
+                .open_elements
+                .iter()
+                .position(|&id| id == furthest_block)
+                .unwrap();
+            self.open_elements
+                .insert(furthest_block_index + 1, new_element);
+        }
+        true
+    }
+}
+
+/// Implementations for insertion modes
+impl TreeBuilder {
+    fn process(&mut self, token: Token) {
+        if self.ignore_next_lf {
+            self.ignore_next_lf = false;
+            if token == Token::Character('\n') {
+                return;
+            }
+        }
+
+        if let Token::StartTag(ref tag) = token {
+            if tag.self_closing && !VOID_ELEMENTS.contains(&tag.tag_name.as_str()) {
+                self.error("non-void-html-element-start-tag-with-trailing-solidus");
+            }
+        }
+
+        let mut token = token;
+        loop {
+            self.current_start_tag_name = match token {
+                Token::StartTag(ref tag) => Some(tag.tag_name.clone()),
+                _ => None,
+            };
+            match self.process_in(self.mode, token) {
+                Step::Reprocess(next) => token = next,
+                Step::Done => break,
+            }
+        }
+    }
+
+    fn process_in(&mut self, mode: InsertionMode, token: Token) -> Step {
+        match mode {
+            InsertionMode::Initial => self.initial(token),
+            InsertionMode::BeforeHtml => self.before_html(token),
+            InsertionMode::BeforeHead => self.before_head(token),
+            InsertionMode::InHead => self.in_head(token),
+            InsertionMode::AfterHead => self.after_head(token),
+            InsertionMode::InBody => self.in_body(token),
+            InsertionMode::Text => self.text(token),
+            InsertionMode::InTable => self.in_table(token),
+            InsertionMode::InTableText => self.in_table_text(token),
+            InsertionMode::InCaption => self.in_caption(token),
+            InsertionMode::InColumnGroup => self.in_column_group(token),
+            InsertionMode::InTableBody => self.in_table_body(token),
+            InsertionMode::InRow => self.in_row(token),
+            InsertionMode::InCell => self.in_cell(token),
+            InsertionMode::InSelect => self.in_select(token),
+            InsertionMode::InSelectInTable => self.in_select_in_table(token),
+            InsertionMode::AfterBody => self.after_body(token),
+            InsertionMode::AfterAfterBody => self.after_after_body(token),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+    fn initial(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(c) if is_whitespace(c) => Step::Done,
+            Token::Comment(data) => {
+                self.insert_comment(data, Some(self.document.root()));
+                Step::Done
+            }
+            Token::Doctype(doctype) => {
+                self.insert_doctype(doctype);
+                self.mode = InsertionMode::BeforeHtml;
+                Step::Done
+            }
+            token => {
+                self.error("missing-doctype");
+                self.mode = InsertionMode::BeforeHtml;
+                Step::Reprocess(token)
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-before-html-insertion-mode
+    fn before_html(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(c) if is_whitespace(c) => Step::Done,
+            Token::Doctype(_) => {
+                self.error("unexpected-doctype");
+                Step::Done
+            }
+            Token::Comment(data) => {
+                self.insert_comment(data, Some(self.document.root()));
+                Step::Done
+            }
+            Token::StartTag(ref tag) if tag.tag_name == "html" => {
+                let element = self.create_element(tag);
+                self.append(self.document.root(), element);
+                self.open_elements.push(element);
+                self.mode = InsertionMode::BeforeHead;
+                Step::Done
+            }
+            Token::EndTag(ref tag)
+                if !["head", "body", "html", "br"].contains(&tag.tag_name.as_str()) =>
+            {
+                self.error("unexpected-end-tag");
+                Step::Done
+            }
+            token => {
+                let element = self.create_element(&Tag::new("html"));
+                self.append(self.document.root(), element);
+                self.open_elements.push(element);
+                self.mode = InsertionMode::BeforeHead;
+                Step::Reprocess(token)
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-before-head-insertion-mode
+    fn before_head(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(c) if is_whitespace(c) => Step::Done,
+            Token::Doctype(_) => {
+                self.error("unexpected-doctype");
+                Step::Done
+            }
+            Token::Comment(data) => {
+                self.insert_comment(data, None);
+                Step::Done
+            }
+            Token::StartTag(ref tag) if tag.tag_name == "html" => self.in_body(token),
+            Token::StartTag(ref tag) if tag.tag_name == "head" => {
+                self.head_element = Some(self.insert_element(tag));
+                self.mode = InsertionMode::InHead;
+                Step::Done
+            }
+            Token::EndTag(ref tag)
+                if !["head", "body", "html", "br"].contains(&tag.tag_name.as_str()) =>
+            {
+                self.error("unexpected-end-tag");
+                Step::Done
+            }
+            token => {
+                self.head_element = Some(self.insert_element(&Tag::new("head")));
+                self.mode = InsertionMode::InHead;
+                Step::Reprocess(token)
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inhead
+    fn in_head(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(c) if is_whitespace(c) => {
+                self.insert_character(c);
+                Step::Done
+            }
+            Token::Doctype(_) => {
+                self.error("unexpected-doctype");
+                Step::Done
+            }
+            Token::Comment(data) => {
+                self.insert_comment(data, None);
+                Step::Done
+            }
+            Token::StartTag(ref tag) => match tag.tag_name.as_str() {
+                "html" => self.in_body(token),
+                "base" | "basefont" | "bgsound" | "link" | "meta" => {
+                    self.insert_void_element(tag);
+                    Step::Done
+                }
+                "title" => {
+                    self.parse_generic_text_element(tag, State::RCDATA);
+                    Step::Done
+                }
+                // NOTE: puppy runs scripts, so `<noscript>` is always parsed as raw text.
+                "noscript" | "noframes" | "style" => {
+                    self.parse_generic_text_element(tag, State::RAWTEXT);
+                    Step::Done
+                }
+                "script" => {
+                    self.parse_generic_text_element(tag, State::ScriptData);
+                    Step::Done
+                }
+                "head" => {
+                    self.error("unexpected-start-tag");
+                    Step::Done
+                }
+                _ => self.anything_else_in_head(token),
+            },
+            Token::EndTag(ref tag) => match tag.tag_name.as_str() {
+                "head" => {
+                    self.open_elements.pop();
+                    self.mode = InsertionMode::AfterHead;
+                    Step::Done
+                }
+                "body" | "html" | "br" => self.anything_else_in_head(token),
+                _ => {
+                    self.error("unexpected-end-tag");
+                    Step::Done
+                }
+            },
+            token => self.anything_else_in_head(token),
+        }
+    }
+
+    fn anything_else_in_head(&mut self, token: Token) -> Step {
+        self.open_elements.pop();
+        self.mode = InsertionMode::AfterHead;
+        Step::Reprocess(token)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-after-head-insertion-mode
+    fn after_head(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(c) if is_whitespace(c) => {
+                self.insert_character(c);
+                Step::Done
+            }
+            Token::Doctype(_) => {
+                self.error("unexpected-doctype");
+                Step::Done
+            }
+            Token::Comment(data) => {
+                self.insert_comment(data, None);
+                Step::Done
+            }
+            Token::StartTag(ref tag) => match tag.tag_name.as_str() {
+                "html" => self.in_body(token),
+                "body" => {
+                    self.insert_element(tag);
+                    self.mode = InsertionMode::InBody;
+                    Step::Done
+                }
+                "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script"
+                | "style" | "title" => {
+                    self.error("unexpected-start-tag");
+                    let head = self.head_element.unwrap();
+                    self.open_elements.push(head);
+                    let step = self.in_head(token);
+                    self.remove_from_stack(head);
+                    step
+                }
+                "head" => {
+                    self.error("unexpected-start-tag");
+                    Step::Done
+                }
+                _ => self.anything_else_after_head(token),
+            },
+            Token::EndTag(ref tag) if !["body", "html", "br"].contains(&tag.tag_name.as_str()) => {
+                self.error("unexpected-end-tag");
+                Step::Done
+            }
+            token => self.anything_else_after_head(token),
+        }
+    }
+
+    fn anything_else_after_head(&mut self, token: Token) -> Step {
+        self.insert_element(&Tag::new("body"));
+        self.mode = InsertionMode::InBody;
+        Step::Reprocess(token)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    fn in_body(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character('\0') => {
+                self.error("unexpected-null-character");
+            }
+            Token::Character(c) => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_character(c);
+            }
+            Token::Comment(data) => self.insert_comment(data, None),
+            Token::Doctype(_) => self.error("unexpected-doctype"),
+            Token::EOF => {}
+            Token::StartTag(tag) => return self.start_tag_in_body(tag),
+            Token::EndTag(tag) => return self.end_tag_in_body(tag),
+        }
+        Step::Done
+    }
+
+    fn start_tag_in_body(&mut self, tag: Tag) -> Step {
+        match tag.tag_name.as_str() {
+            "html" => {
+                self.error("unexpected-start-tag");
+                if let Some(&html) = self.open_elements.first() {
+                    self.add_missing_attributes(html, &tag);
+                }
+            }
+            "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script" | "style"
+            | "title" => return self.in_head(Token::StartTag(tag)),
+            "body" => {
+                self.error("unexpected-start-tag");
+                if self.open_elements.len() > 1 && self.tag_name(self.open_elements[1]) == "body" {
+                    let body = self.open_elements[1];
+                    self.add_missing_attributes(body, &tag);
+                }
+            }
+            "address" | "article" | "aside" | "blockquote" | "center" | "details" | "dialog"
+            | "dir" | "div" | "dl" | "fieldset" | "figcaption" | "figure" | "footer" | "header"
+            | "hgroup" | "main" | "menu" | "nav" | "ol" | "p" | "search" | "section"
+            | "summary" | "ul" => {
+                self.close_p_element_in_button_scope();
+                self.insert_element(&tag);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.close_p_element_in_button_scope();
+                if self.current_node_is(HEADINGS) {
+                    self.error("unexpected-start-tag");
+                    self.open_elements.pop();
+                }
+                self.insert_element(&tag);
+            }
+            "pre" | "listing" => {
+                self.close_p_element_in_button_scope();
+                self.insert_element(&tag);
+                self.ignore_next_lf = true;
+            }
+            "form" => {
+                if self.form_element.is_some() {
+                    self.error("unexpected-start-tag");
+                } else {
+                    self.close_p_element_in_button_scope();
+                    self.form_element = Some(self.insert_element(&tag));
+                }
+            }
+            "li" | "dd" | "dt" => {
+                let names: &[&str] = if tag.tag_name == "li" {
+                    &["li"]
+                } else {
+                    &["dd", "dt"]
+                };
+                for &node in self.open_elements.clone().iter().rev() {
+                    if self.is_one_of(node, names) {
+                        let name = self.tag_name(node).to_string();
+                        self.generate_implied_end_tags(Some(name.as_str()));
+                        if self.tag_name(self.current_node()) != name {
+                            self.error("unexpected-start-tag");
+                        }
+                        self.pop_until(&[name.as_str()]);
+                        break;
+                    }
+                    if self.is_one_of(node, SPECIAL_ELEMENTS)
+                        && !self.is_one_of(node, &["address", "div", "p"])
+                    {
+                        break;
+                    }
+                }
+                self.close_p_element_in_button_scope();
+                self.insert_element(&tag);
+            }
+            "plaintext" => {
+                self.close_p_element_in_button_scope();
+                self.insert_element(&tag);
+                self.tokenizer.set_state(State::PLAINTEXT);
+            }
+            "button" => {
+                if self.in_scope(&["button"], Scope::Default) {
+                    self.error("unexpected-start-tag");
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&["button"]);
+                }
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&tag);
+            }
+            "a" => {
+                let existing = self
+                    .active_formatting_elements
+                    .iter()
+                    .rev()
+                    .take_while(|entry| !matches!(entry, FormattingEntry::Marker))
+                    .find_map(|entry| match entry {
+                        FormattingEntry::Element(id, t) if t.tag_name == "a" => Some(*id),
+                        _ => None,
+                    });
+                if let Some(existing) = existing {
+                    self.error("unexpected-start-tag");
+                    self.adoption_agency("a");
+                    if let Some(index) = self.formatting_position(existing) {
+                        self.active_formatting_elements.remove(index);
+                    }
+                    self.remove_from_stack(existing);
+                }
+                self.reconstruct_active_formatting_elements();
+                let element = self.insert_element(&tag);
+                self.push_formatting_element(element, &tag);
+            }
+            "b" | "big" | "code" | "em" | "font" | "i" | "s" | "small" | "strike" | "strong"
+            | "tt" | "u" => {
+                self.reconstruct_active_formatting_elements();
+                let element = self.insert_element(&tag);
+                self.push_formatting_element(element, &tag);
+            }
+            "nobr" => {
+                self.reconstruct_active_formatting_elements();
+                if self.in_scope(&["nobr"], Scope::Default) {
+                    self.error("unexpected-start-tag");
+                    self.adoption_agency("nobr");
+                    self.reconstruct_active_formatting_elements();
+                }
+                let element = self.insert_element(&tag);
+                self.push_formatting_element(element, &tag);
+            }
+            "applet" | "marquee" | "object" => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&tag);
+                self.active_formatting_elements
+                    .push(FormattingEntry::Marker);
+            }
+            "table" => {
+                self.close_p_element_in_button_scope();
+                self.insert_element(&tag);
+                self.mode = InsertionMode::InTable;
+            }
+            "area" | "br" | "embed" | "img" | "keygen" | "wbr" | "input" => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_void_element(&tag);
+            }
+            "param" | "source" | "track" => {
+                self.insert_void_element(&tag);
+            }
+            "hr" => {
+                self.close_p_element_in_button_scope();
+                self.insert_void_element(&tag);
+            }
+            "image" => {
+                self.error("unexpected-start-tag");
+                let mut tag = tag;
+                tag.tag_name = "img".to_string();
+                return Step::Reprocess(Token::StartTag(tag));
+            }
+            "textarea" => {
+                self.parse_generic_text_element(&tag, State::RCDATA);
+                self.ignore_next_lf = true;
+            }
+            "xmp" => {
+                self.close_p_element_in_button_scope();
+                self.reconstruct_active_formatting_elements();
+                self.parse_generic_text_element(&tag, State::RAWTEXT);
+            }
+            "iframe" | "noembed" | "noscript" => {
+                self.parse_generic_text_element(&tag, State::RAWTEXT);
+            }
+            "select" => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&tag);
+                self.mode = match self.mode {
+                    InsertionMode::InTable
+                    | InsertionMode::InCaption
+                    | InsertionMode::InTableBody
+                    | InsertionMode::InRow
+                    | InsertionMode::InCell => InsertionMode::InSelectInTable,
+                    _ => InsertionMode::InSelect,
+                };
+            }
+            "optgroup" | "option" => {
+                if self.current_node_is(&["option"]) {
+                    self.open_elements.pop();
+                }
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&tag);
+            }
+            "rb" | "rtc" => {
+                if self.in_scope(&["ruby"], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                }
+                self.insert_element(&tag);
+            }
+            "rp" | "rt" => {
+                if self.in_scope(&["ruby"], Scope::Default) {
+                    self.generate_implied_end_tags(Some("rtc"));
+                }
+                self.insert_element(&tag);
+            }
+            "caption" | "col" | "colgroup" | "frame" | "head" | "tbody" | "td" | "tfoot" | "th"
+            | "thead" | "tr" => {
+                self.error("unexpected-start-tag");
+            }
+            _ => {
+                self.reconstruct_active_formatting_elements();
+                if tag.self_closing && (tag.tag_name == "svg" || tag.tag_name == "math") {
+                    self.insert_void_element(&tag);
+                } else {
+                    self.insert_element(&tag);
+                }
+            }
+        }
+        Step::Done
+    }
+
+    fn end_tag_in_body(&mut self, tag: Tag) -> Step {
+        match tag.tag_name.as_str() {
+            "body" => {
+                if self.in_scope(&["body"], Scope::Default) {
+                    self.mode = InsertionMode::AfterBody;
+                } else {
+                    self.error("unexpected-end-tag");
+                }
+            }
+            "html" => {
+                if self.in_scope(&["body"], Scope::Default) {
+                    self.mode = InsertionMode::AfterBody;
+                    return Step::Reprocess(Token::EndTag(tag));
+                }
+                self.error("unexpected-end-tag");
+            }
+            "address" | "article" | "aside" | "blockquote" | "button" | "center" | "details"
+            | "dialog" | "dir" | "div" | "dl" | "fieldset" | "figcaption" | "figure" | "footer"
+            | "header" | "hgroup" | "listing" | "main" | "menu" | "nav" | "ol" | "pre"
+            | "search" | "section" | "summary" | "ul" => {
+                let name = tag.tag_name.as_str();
+                if self.in_scope(&[name], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[name]);
+                } else {
+                    self.error("unexpected-end-tag");
+                }
+            }
+            "form" => {
+                let node = self.form_element.take();
+                match node {
+                    Some(node) if self.node_in_scope(node, Scope::Default) => {
+                        self.generate_implied_end_tags(None);
+                        self.remove_from_stack(node);
+                    }
+                    _ => self.error("unexpected-end-tag"),
+                }
+            }
+            "p" => {
+                if !self.in_scope(&["p"], Scope::Button) {
+                    self.error("unexpected-end-tag");
+                    self.insert_element(&Tag::new("p"));
+                }
+                self.close_p_element();
+            }
+            "li" => {
+                if self.in_scope(&["li"], Scope::ListItem) {
+                    self.generate_implied_end_tags(Some("li"));
+                    self.pop_until(&["li"]);
+                } else {
+                    self.error("unexpected-end-tag");
+                }
+            }
+            "dd" | "dt" => {
+                let name = tag.tag_name.as_str();
+                if self.in_scope(&[name], Scope::Default) {
+                    self.generate_implied_end_tags(Some(name));
+                    self.pop_until(&[name]);
+                } else {
+                    self.error("unexpected-end-tag");
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                if self.in_scope(HEADINGS, Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(HEADINGS);
+                } else {
+                    self.error("unexpected-end-tag");
+                }
+            }
+            name if FORMATTING_ELEMENTS.contains(&name) => {
+                let name = name.to_string();
+                if !self.adoption_agency(name.as_str()) {
+                    self.any_other_end_tag_in_body(&tag);
+                }
+            }
+            "applet" | "marquee" | "object" => {
+                let name = tag.tag_name.as_str();
+                if self.in_scope(&[name], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[name]);
+                    self.clear_active_formatting_elements_to_last_marker();
+                } else {
+                    self.error("unexpected-end-tag");
+                }
+            }
+            "br" => {
+                self.error("unexpected-end-tag");
+                return Step::Reprocess(Token::StartTag(Tag::new("br")));
+            }
+            _ => self.any_other_end_tag_in_body(&tag),
+        }
+        Step::Done
+    }
+
+    fn any_other_end_tag_in_body(&mut self, tag: &Tag) {
+        for &node in self.open_elements.clone().iter().rev() {
+            if self.tag_name(node) == tag.tag_name {
+                self.generate_implied_end_tags(Some(tag.tag_name.as_str()));
+                self.pop_until_node(node);
+                return;
+            }
+            if self.is_one_of(node, SPECIAL_ELEMENTS) {
+                self.error("unexpected-end-tag");
+                return;
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incdata
+    fn text(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(c) => {
+                self.insert_character(c);
+                Step::Done
+            }
+            Token::EOF => {
+                self.error("eof-in-element-that-can-contain-only-text");
+                self.open_elements.pop();
+                self.mode = self.original_mode;
+                Step::Reprocess(token)
+            }
+            Token::EndTag(_) => {
+                let current_node = self.current_node();
+                if self.tag_name(current_node) == "script" && self.context_element.is_none() {
+                    let script = self
+                        .document
+                        .children(current_node)
+                        .filter_map(|id| match self.document.node(id).node_type {
+                            NodeType::Text(ref t) => Some(t.data.as_str()),
+                            _ => None,
+                        })
+                        .collect();
+                    self.pending_script = Some(script);
+                }
+                self.open_elements.pop();
+                self.mode = self.original_mode;
+                Step::Done
+            }
+            _ => Step::Done,
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intable
+    fn in_table(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(_)
+                if self
+                    .current_node_is(&["table", "tbody", "template", "tfoot", "thead", "tr"]) =>
+            {
+                self.pending_table_characters = vec![];
+                self.original_mode = self.mode;
+                self.mode = InsertionMode::InTableText;
+                Step::Reprocess(token)
+            }
+            Token::Doctype(_) => {
+                self.error("unexpected-doctype");
+                Step::Done
+            }
+            Token::Comment(data) => {
+                self.insert_comment(data, None);
+                Step::Done
+            }
+            Token::StartTag(ref tag) => match tag.tag_name.as_str() {
+                "caption" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.active_formatting_elements
+                        .push(FormattingEntry::Marker);
+                    self.insert_element(tag);
+                    self.mode = InsertionMode::InCaption;
+                    Step::Done
+                }
+                "colgroup" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_element(tag);
+                    self.mode = InsertionMode::InColumnGroup;
+                    Step::Done
+                }
+                "col" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_element(&Tag::new("colgroup"));
+                    self.mode = InsertionMode::InColumnGroup;
+                    Step::Reprocess(token)
+                }
+                "tbody" | "tfoot" | "thead" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_element(tag);
+                    self.mode = InsertionMode::InTableBody;
+                    Step::Done
+                }
+                "td" | "th" | "tr" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_element(&Tag::new("tbody"));
+                    self.mode = InsertionMode::InTableBody;
+                    Step::Reprocess(token)
+                }
+                "table" => {
+                    self.error("unexpected-start-tag");
+                    if !self.in_scope(&["table"], Scope::Table) {
+                        return Step::Done;
+                    }
+                    self.pop_until(&["table"]);
+                    self.reset_insertion_mode();
+                    Step::Reprocess(token)
+                }
+                "style" | "script" => self.in_head(token),
+                "input"
+                    if tag
+                        .attributes
+                        .iter()
+                        .any(|(k, v)| k == "type" && v.eq_ignore_ascii_case("hidden")) =>
+                {
+                    self.error("unexpected-start-tag");
+                    self.insert_void_element(tag);
+                    Step::Done
+                }
+                "form" => {
+                    self.error("unexpected-start-tag");
+                    if self.form_element.is_none() {
+                        self.form_element = Some(self.insert_element(tag));
+                        self.open_elements.pop();
+                    }
+                    Step::Done
+                }
+                _ => self.anything_else_in_table(token),
+            },
+            Token::EndTag(ref tag) => match tag.tag_name.as_str() {
+                "table" => {
+                    if self.in_scope(&["table"], Scope::Table) {
+                        self.pop_until(&["table"]);
+                        self.reset_insertion_mode();
+                    } else {
+                        self.error("unexpected-end-tag");
+                    }
+                    Step::Done
+                }
+                "body" | "caption" | "col" | "colgroup" | "html" | "tbody" | "td" | "tfoot"
+                | "th" | "thead" | "tr" => {
+                    self.error("unexpected-end-tag");
+                    Step::Done
+                }
+                _ => self.anything_else_in_table(token),
+            },
+            Token::EOF => self.in_body(token),
+            token => self.anything_else_in_table(token),
+        }
+    }
+
+    fn anything_else_in_table(&mut self, token: Token) -> Step {
+        self.error("foster-parenting");
+        self.foster_parenting = true;
+        let step = self.in_body(token);
+        self.foster_parenting = false;
+        step
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intabletext
+    fn in_table_text(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character('\0') => {
+                self.error("unexpected-null-character");
+                Step::Done
+            }
+            Token::Character(c) => {
+                self.pending_table_characters.push(c);
+                Step::Done
+            }
+            token => {
+                let characters = std::mem::take(&mut self.pending_table_characters);
+                if characters.iter().any(|&c| !is_whitespace(c)) {
+                    for c in characters {
+                        self.anything_else_in_table(Token::Character(c));
+                    }
+                } else {
+                    for c in characters {
+                        self.insert_character(c);
+                    }
+                }
+                self.mode = self.original_mode;
+                Step::Reprocess(token)
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incaption
+    fn in_caption(&mut self, token: Token) -> Step {
+        let closes_caption = match token {
+            Token::EndTag(ref tag) if tag.tag_name == "caption" => Some(false),
+            Token::EndTag(ref tag) if tag.tag_name == "table" => Some(true),
+            Token::StartTag(ref tag)
+                if [
+                    "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                ]
+                .contains(&tag.tag_name.as_str()) =>
+            {
+                Some(true)
+            }
+            Token::EndTag(ref tag)
+                if [
+                    "body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead", "tr",
+                ]
+                .contains(&tag.tag_name.as_str()) =>
+            {
+                self.error("unexpected-end-tag");
+                return Step::Done;
+            }
+            _ => None,
+        };
+
+        match closes_caption {
+            Some(reprocess) => {
+                if !self.in_scope(&["caption"], Scope::Table) {
+                    self.error("unexpected-end-tag");
+                    return Step::Done;
+                }
+                self.generate_implied_end_tags(None);
+                self.pop_until(&["caption"]);
+                self.clear_active_formatting_elements_to_last_marker();
+                self.mode = InsertionMode::InTable;
+                if reprocess {
+                    Step::Reprocess(token)
+                } else {
+                    Step::Done
+                }
+            }
+            None => self.in_body(token),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incolgroup
+    fn in_column_group(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(c) if is_whitespace(c) => {
+                self.insert_character(c);
+                Step::Done
+            }
+            Token::Doctype(_) => {
+                self.error("unexpected-doctype");
+                Step::Done
+            }
+            Token::Comment(data) => {
+                self.insert_comment(data, None);
+                Step::Done
+            }
+            Token::StartTag(ref tag) if tag.tag_name == "html" => self.in_body(token),
+            Token::StartTag(ref tag) if tag.tag_name == "col" => {
+                self.insert_void_element(tag);
+                Step::Done
+            }
+            Token::EndTag(ref tag) if tag.tag_name == "colgroup" => {
+                if self.current_node_is(&["colgroup"]) {
+                    self.open_elements.pop();
+                    self.mode = InsertionMode::InTable;
+                } else {
+                    self.error("unexpected-end-tag");
+                }
+                Step::Done
+            }
+            Token::EndTag(ref tag) if tag.tag_name == "col" => {
+                self.error("unexpected-end-tag");
+                Step::Done
+            }
+            Token::EOF => self.in_body(token),
+            token => {
+                if !self.current_node_is(&["colgroup"]) {
+                    self.error("unexpected-token");
+                    return Step::Done;
+                }
+                self.open_elements.pop();
+                self.mode = InsertionMode::InTable;
+                Step::Reprocess(token)
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intbody
+    fn in_table_body(&mut self, token: Token) -> Step {
+        const CONTEXT: &[&str] = &["tbody", "tfoot", "thead", "template", "html"];
+        match token {
+            Token::StartTag(ref tag) if tag.tag_name == "tr" => {
+                self.clear_stack_back_to(CONTEXT);
+                self.insert_element(tag);
+                self.mode = InsertionMode::InRow;
+                Step::Done
+            }
+            Token::StartTag(ref tag) if tag.tag_name == "th" || tag.tag_name == "td" => {
+                self.error("unexpected-start-tag");
+                self.clear_stack_back_to(CONTEXT);
+                self.insert_element(&Tag::new("tr"));
+                self.mode = InsertionMode::InRow;
+                Step::Reprocess(token)
+            }
+            Token::EndTag(ref tag) if TABLE_SECTIONS.contains(&tag.tag_name.as_str()) => {
+                if !self.in_scope(&[tag.tag_name.as_str()], Scope::Table) {
+                    self.error("unexpected-end-tag");
+                    return Step::Done;
+                }
+                self.clear_stack_back_to(CONTEXT);
+                self.open_elements.pop();
+                self.mode = InsertionMode::InTable;
+                Step::Done
+            }
+            Token::StartTag(ref tag)
+                if ["caption", "col", "colgroup", "tbody", "tfoot", "thead"]
+                    .contains(&tag.tag_name.as_str()) =>
+            {
+                self.close_table_section(token)
+            }
+            Token::EndTag(ref tag) if tag.tag_name == "table" => self.close_table_section(token),
+            Token::EndTag(ref tag)
+                if [
+                    "body", "caption", "col", "colgroup", "html", "td", "th", "tr",
+                ]
+                .contains(&tag.tag_name.as_str()) =>
+            {
+                self.error("unexpected-end-tag");
+                Step::Done
+            }
+            token => self.in_table(token),
+        }
+    }
+
+    fn close_table_section(&mut self, token: Token) -> Step {
+        if !self.in_scope(TABLE_SECTIONS, Scope::Table) {
+            self.error("unexpected-token");
+            return Step::Done;
+        }
+        self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+        self.open_elements.pop();
+        self.mode = InsertionMode::InTable;
+        Step::Reprocess(token)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intr
+    fn in_row(&mut self, token: Token) -> Step {
+        const CONTEXT: &[&str] = &["tr", "template", "html"];
+        match token {
+            Token::StartTag(ref tag) if tag.tag_name == "th" || tag.tag_name == "td" => {
+                self.clear_stack_back_to(CONTEXT);
+                self.insert_element(tag);
+                self.mode = InsertionMode::InCell;
+                self.active_formatting_elements
+                    .push(FormattingEntry::Marker);
+                Step::Done
+            }
+            Token::EndTag(ref tag) if tag.tag_name == "tr" => {
+                if !self.in_scope(&["tr"], Scope::Table) {
+                    self.error("unexpected-end-tag");
+                    return Step::Done;
+                }
+                self.clear_stack_back_to(CONTEXT);
+                self.open_elements.pop();
+                self.mode = InsertionMode::InTableBody;
+                Step::Done
+            }
+            Token::StartTag(ref tag)
+                if [
+                    "caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr",
+                ]
+                .contains(&tag.tag_name.as_str()) =>
+            {
+                self.close_row(token)
+            }
+            Token::EndTag(ref tag) if tag.tag_name == "table" => self.close_row(token),
+            Token::EndTag(ref tag) if TABLE_SECTIONS.contains(&tag.tag_name.as_str()) => {
+                if !self.in_scope(&[tag.tag_name.as_str()], Scope::Table) {
+                    self.error("unexpected-end-tag");
+                    return Step::Done;
+                }
+                self.close_row(token)
+            }
+            Token::EndTag(ref tag)
+                if ["body", "caption", "col", "colgroup", "html", "td", "th"]
+                    .contains(&tag.tag_name.as_str()) =>
+            {
+                self.error("unexpected-end-tag");
+                Step::Done
+            }
+            token => self.in_table(token),
+        }
+    }
+
+    fn close_row(&mut self, token: Token) -> Step {
+        if !self.in_scope(&["tr"], Scope::Table) {
+            self.error("unexpected-token");
+            return Step::Done;
+        }
+        self.clear_stack_back_to(&["tr", "template", "html"]);
+        self.open_elements.pop();
+        self.mode = InsertionMode::InTableBody;
+        Step::Reprocess(token)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intd
+    fn in_cell(&mut self, token: Token) -> Step {
+        match token {
+            Token::EndTag(ref tag) if tag.tag_name == "td" || tag.tag_name == "th" => {
+                let name = tag.tag_name.as_str();
+                if !self.in_scope(&[name], Scope::Table) {
+                    self.error("unexpected-end-tag");
+                    return Step::Done;
+                }
+                self.generate_implied_end_tags(None);
+                self.pop_until(&[name]);
+                self.clear_active_formatting_elements_to_last_marker();
+                self.mode = InsertionMode::InRow;
+                Step::Done
+            }
+            Token::StartTag(ref tag)
+                if [
+                    "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                ]
+                .contains(&tag.tag_name.as_str()) =>
+            {
+                if !self.in_scope(&["td", "th"], Scope::Table) {
+                    self.error("unexpected-start-tag");
+                    return Step::Done;
+                }
+                self.close_cell();
+                Step::Reprocess(token)
+            }
+            Token::EndTag(ref tag)
+                if ["body", "caption", "col", "colgroup", "html"]
+                    .contains(&tag.tag_name.as_str()) =>
+            {
+                self.error("unexpected-end-tag");
+                Step::Done
+            }
+            Token::EndTag(ref tag)
+                if ["table", "tbody", "tfoot", "thead", "tr"].contains(&tag.tag_name.as_str()) =>
+            {
+                if !self.in_scope(&[tag.tag_name.as_str()], Scope::Table) {
+                    self.error("unexpected-end-tag");
+                    return Step::Done;
+                }
+                self.close_cell();
+                Step::Reprocess(token)
+            }
+            token => self.in_body(token),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-the-cell
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["td", "th"]);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.mode = InsertionMode::InRow;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inselect
+    fn in_select(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character('\0') => {
+                self.error("unexpected-null-character");
+                Step::Done
+            }
+            Token::Character(c) => {
+                self.insert_character(c);
+                Step::Done
+            }
+            Token::Doctype(_) => {
+                self.error("unexpected-doctype");
+                Step::Done
+            }
+            Token::Comment(data) => {
+                self.insert_comment(data, None);
+                Step::Done
+            }
+            Token::StartTag(ref tag) => match tag.tag_name.as_str() {
+                "html" => self.in_body(token),
+                "option" => {
+                    if self.current_node_is(&["option"]) {
+                        self.open_elements.pop();
+                    }
+                    self.insert_element(tag);
+                    Step::Done
+                }
+                "optgroup" | "hr" => {
+                    if self.current_node_is(&["option"]) {
+                        self.open_elements.pop();
+                    }
+                    if self.current_node_is(&["optgroup"]) {
+                        self.open_elements.pop();
+                    }
+                    if tag.tag_name == "hr" {
+                        self.insert_void_element(tag);
+                    } else {
+                        self.insert_element(tag);
+                    }
+                    Step::Done
+                }
+                "select" => {
+                    self.error("unexpected-start-tag");
+                    if self.in_scope(&["select"], Scope::Select) {
+                        self.pop_until(&["select"]);
+                        self.reset_insertion_mode();
+                    }
+                    Step::Done
+                }
+                "input" | "keygen" | "textarea" => {
+                    self.error("unexpected-start-tag");
+                    if !self.in_scope(&["select"], Scope::Select) {
+                        return Step::Done;
+                    }
+                    self.pop_until(&["select"]);
+                    self.reset_insertion_mode();
+                    Step::Reprocess(token)
+                }
+                "script" => self.in_head(token),
+                _ => {
+                    self.error("unexpected-start-tag");
+                    Step::Done
+                }
+            },
+            Token::EndTag(ref tag) => match tag.tag_name.as_str() {
+                "optgroup" => {
+                    let len = self.open_elements.len();
+                    if self.current_node_is(&["option"])
+                        && len > 1
+                        && self.tag_name(self.open_elements[len - 2]) == "optgroup"
+                    {
+                        self.open_elements.pop();
+                    }
+                    if self.current_node_is(&["optgroup"]) {
+                        self.open_elements.pop();
+                    } else {
+                        self.error("unexpected-end-tag");
+                    }
+                    Step::Done
+                }
+                "option" => {
+                    if self.current_node_is(&["option"]) {
+                        self.open_elements.pop();
+                    } else {
+                        self.error("unexpected-end-tag");
+                    }
+                    Step::Done
+                }
+                "select" => {
+                    if self.in_scope(&["select"], Scope::Select) {
+                        self.pop_until(&["select"]);
+                        self.reset_insertion_mode();
+                    } else {
+                        self.error("unexpected-end-tag");
+                    }
+                    Step::Done
+                }
+                _ => {
+                    self.error("unexpected-end-tag");
+                    Step::Done
+                }
+            },
+            Token::EOF => self.in_body(token),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inselectintable
+    fn in_select_in_table(&mut self, token: Token) -> Step {
+        const TABLE_ELEMENTS: &[&str] = &[
+            "caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th",
+        ];
+        match token {
+            Token::StartTag(ref tag) if TABLE_ELEMENTS.contains(&tag.tag_name.as_str()) => {
+                self.error("unexpected-start-tag");
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+                Step::Reprocess(token)
+            }
+            Token::EndTag(ref tag) if TABLE_ELEMENTS.contains(&tag.tag_name.as_str()) => {
+                self.error("unexpected-end-tag");
+                if !self.in_scope(&[tag.tag_name.as_str()], Scope::Table) {
+                    return Step::Done;
+                }
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+                Step::Reprocess(token)
+            }
+            token => self.in_select(token),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-afterbody
+    fn after_body(&mut self, token: Token) -> Step {
+        match token {
+            Token::Character(c) if is_whitespace(c) => self.in_body(token),
+            Token::Comment(data) => {
+                let html = self.open_elements[0];
+                self.insert_comment(data, Some(html));
+                Step::Done
+            }
+            Token::Doctype(_) => {
+                self.error("unexpected-doctype");
+                Step::Done
+            }
+            Token::StartTag(ref tag) if tag.tag_name == "html" => self.in_body(token),
+            Token::EndTag(ref tag) if tag.tag_name == "html" => {
+                if self.context_element.is_some() {
+                    self.error("unexpected-end-tag");
+                } else {
+                    self.mode = InsertionMode::AfterAfterBody;
+                }
+                Step::Done
+            }
+            Token::EOF => Step::Done,
+            token => {
+                self.error("unexpected-token-after-body");
+                self.mode = InsertionMode::InBody;
+                Step::Reprocess(token)
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-after-after-body-insertion-mode
+    fn after_after_body(&mut self, token: Token) -> Step {
+        match token {
+            Token::Comment(data) => {
+                self.insert_comment(data, Some(self.document.root()));
+                Step::Done
+            }
+            Token::Doctype(_) => self.in_body(token),
+            Token::Character(c) if is_whitespace(c) => self.in_body(token),
+            Token::StartTag(ref tag) if tag.tag_name == "html" => self.in_body(token),
+            Token::EOF => Step::Done,
+            token => {
+                self.error("unexpected-token-after-body");
+                self.mode = InsertionMode::InBody;
+                Step::Reprocess(token)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `parse` parses `html` as a whole document, and returns the markup of the children of `body` element.
+    fn parse(html: &str) -> String {
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.feed(html);
+        tokenizer.close();
+
+        let mut tree_builder = TreeBuilder::new(tokenizer);
+        tree_builder.run();
+        let document = tree_builder.into_document("".to_string(), "".to_string());
+        let body = document.get_elements_by_tag_name(document.root(), "body")[0];
+        document.inner_html(body)
+    }
+
+    #[test]
+    fn test_adoption_agency() {
+        assert_eq!(parse("<b>1<p>2</b>3</p>"), "<b>1</b><p><b>2</b>3</p>");
+        assert_eq!(parse("<a>1<b>2</a>3</b>"), "<a>1<b>2</b></a><b>3</b>");
+        assert_eq!(
+            parse("<p><b><i>1</p>2"),
+            "<p><b><i>1</i></b></p><b><i>2</i></b>"
+        );
+        // a formatting element which is not in scope is ignored
+        assert_eq!(parse("<b>1</i>2</b>"), "<b>12</b>");
+    }
+
+    #[test]
+    fn test_foster_parenting() {
+        assert_eq!(
+            parse("<table>a<tr><td>b</td></tr>c</table>"),
+            "ac<table><tbody><tr><td>b</td></tr></tbody></table>"
+        );
+        assert_eq!(
+            parse("<table><div>x</div><tr></tr></table>"),
+            "<div>x</div><table><tbody><tr></tr></tbody></table>"
+        );
+        // whitespaces stay in the table
+        assert_eq!(
+            parse("<table> <tr> </tr></table>"),
+            "<table> <tbody><tr> </tr></tbody></table>"
+        );
+    }
+
+    #[test]
+    fn test_in_table() {
+        assert_eq!(
+            parse("<table><td>1<td>2</table>3"),
+            "<table><tbody><tr><td>1</td><td>2</td></tr></tbody></table>3"
+        );
+        assert_eq!(
+            parse("<table><caption>c<tr><td>1</table>"),
+            "<table><caption>c</caption><tbody><tr><td>1</td></tr></tbody></table>"
+        );
+        assert_eq!(
+            parse("<table><col><thead><th>h<tbody><tr><td>d</table>"),
+            "<table><colgroup><col></colgroup><thead><tr><th>h</th></tr></thead><tbody><tr><td>d</td></tr></tbody></table>"
+        );
+        // a table in a cell is nested, while a table in a table closes it
+        assert_eq!(
+            parse("<table><tr><td><table></table></td></tr></table>"),
+            "<table><tbody><tr><td><table></table></td></tr></tbody></table>"
+        );
+        assert_eq!(parse("<table><table>"), "<table></table><table></table>");
+    }
+
+    #[test]
+    fn test_in_select() {
+        assert_eq!(
+            parse("<select><option>1<option>2<p>x</select>"),
+            "<select><option>1</option><option>2x</option></select>"
+        );
+        assert_eq!(
+            parse("<select><optgroup><option>1<optgroup><option>2</select>"),
+            "<select><optgroup><option>1</option></optgroup><optgroup><option>2</option></optgroup></select>"
+        );
+        // `<select>` in a select element closes it
+        assert_eq!(
+            parse("<select><option>1<select>2"),
+            "<select><option>1</option></select>2"
+        );
+        // a cell closes the select element in it
+        assert_eq!(
+            parse("<table><tr><td><select><option>1<td>2</table>"),
+            "<table><tbody><tr><td><select><option>1</option></select></td><td>2</td></tr></tbody></table>"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! This module includes some types to point at locations in source texts, such as HTML documents and CSS stylesheets.
+
+use std::fmt;
+
+/// `SourcePosition` represents a position in a source text.
+/// Both `line` and `column` start from 1, and `column` counts characters, not bytes.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourcePosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        SourcePosition { line, column }
+    }
+}
+
+impl Default for SourcePosition {
+    fn default() -> Self {
+        SourcePosition::new(1, 1)
+    }
+}
+
+impl fmt::Display for SourcePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// `SourceSpan` represents a range in a source text.
+/// `start` is inclusive and `end` is exclusive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct SourceSpan {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+}
+
+impl SourceSpan {
+    pub fn new(start: SourcePosition, end: SourcePosition) -> Self {
+        SourceSpan { start, end }
+    }
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// `ParseDiagnostic` describes a non-fatal error found while parsing a source text.
+/// Parsers recover from these errors, but they are useful to find out why a page is rendered unexpectedly.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseDiagnostic {
+    pub message: String,
+    pub position: SourcePosition,
+}
+
+impl ParseDiagnostic {
+    pub fn new(message: String, position: SourcePosition) -> Self {
+        ParseDiagnostic { message, position }
+    }
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
@@ -1,13 +1,24 @@
 //! This module includes some implementations on node styles.
 
 use super::{
-    css::{self, CSSValue, Stylesheet},
-    dom::{Document, Node, NodeType},
+    css::{
+        self, media::Device, CSSValue, Declaration, Rule, RuleCondition, Specificity, Stylesheet,
+    },
+    dom::{Document, NodeId, NodeType, StyleSource},
+    fetch::{fetch, Request},
+    url::Url,
 };
+use log::{debug, info};
 use std::collections::HashMap;
 
+pub mod property;
+pub use self::property::*;
+
 pub type PropertyMap = HashMap<String, CSSValue>;
 
+/// `LinkedStylesheets` holds the stylesheets fetched for `link` elements and `@import` rules, keyed by their URLs.
+pub type LinkedStylesheets = HashMap<Url, Stylesheet>;
+
 #[derive(Debug, PartialEq)]
 pub enum Display {
     Inline,
@@ -36,41 +47,231 @@ pub struct StyledNode<'a> {
 /// - Safari: https://trac.webkit.org/browser/trunk/Source/WebCore/css/html.css
 /// - Firefox: https://searchfox.org/mozilla-central/source/layout/style/res/html.css
 const DEFAULT_STYLESHEET: &str = r#"
-script, style {
+head, script, style {
     display: none;
 }
-p, div {
+html, body, p, div {
     display: block;
 }
 "#;
 
+/// `CascadeOrigin` is the origin of a stylesheet, which decides the precedence of its declarations in the cascade.
+/// https://drafts.csswg.org/css-cascade-4/#cascading-origins
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CascadeOrigin {
+    /// `UserAgent` is the origin of `DEFAULT_STYLESHEET`.
+    UserAgent,
+    /// `User` is the origin of the stylesheet given by the user of puppy.
+    User,
+    /// `Author` is the origin of the stylesheets in documents.
+    Author,
+}
+
+impl CascadeOrigin {
+    /// `precedence` returns the rank of declarations from the origin in the cascade; the higher wins.
+    /// Important declarations reverse the order of origins.
+    /// https://drafts.csswg.org/css-cascade-4/#cascade-origin
+    fn precedence(self, important: bool) -> usize {
+        match (self, important) {
+            (CascadeOrigin::UserAgent, false) => 0,
+            (CascadeOrigin::User, false) => 1,
+            (CascadeOrigin::Author, false) => 2,
+            (CascadeOrigin::Author, true) => 3,
+            (CascadeOrigin::User, true) => 4,
+            (CascadeOrigin::UserAgent, true) => 5,
+        }
+    }
+}
+
+/// `load_linked_stylesheets` fetches the stylesheets linked from the document and the ones imported by them, which are not in `stylesheets` yet.
+/// A stylesheet which can't be fetched or is responded with a non-ok status is stored as an empty one, so that it isn't requested again.
+/// https://html.spec.whatwg.org/multipage/links.html#link-type-stylesheet
+pub fn load_linked_stylesheets(document: &Document, stylesheets: &mut LinkedStylesheets) {
+    let mut urls = vec![];
+    for source in document.get_style_sources() {
+        match source {
+            StyleSource::Inline(text) => {
+                if let Some(base_url) = document.base_url() {
+                    urls.extend(imported_urls(&css::parse(text), &base_url));
+                }
+            }
+            StyleSource::External(url) => urls.push(url),
+        }
+    }
+
+    while let Some(url) = urls.pop() {
+        if stylesheets.contains_key(&url) {
+            continue;
+        }
+        let stylesheet = match fetch(Request::new(url.to_string())) {
+            // https://html.spec.whatwg.org/multipage/semantics.html#fetching-and-processing-a-resource-from-a-link-element
+            Ok(response) if !response.status.is_ok() => {
+                info!(
+                    "failed to fetch the stylesheet at {}; the status is {}",
+                    url, response.status
+                );
+                Stylesheet::new(vec![])
+            }
+            Ok(response) => css::parse(css::decode(&response.data, response.content_type())),
+            Err(e) => {
+                info!("failed to fetch the stylesheet at {}; {}", url, e);
+                Stylesheet::new(vec![])
+            }
+        };
+        for parse_error in &stylesheet.parse_errors {
+            debug!("parse error in {} at {}", url, parse_error);
+        }
+        urls.extend(imported_urls(&stylesheet, &url));
+        stylesheets.insert(url, stylesheet);
+    }
+}
+
+/// `imported_urls` returns the URLs of the stylesheets imported by `stylesheet`, resolved against `base_url`.
+fn imported_urls(stylesheet: &Stylesheet, base_url: &Url) -> Vec<Url> {
+    stylesheet
+        .imports
+        .iter()
+        .filter_map(|import| base_url.join(import.url.as_str()).ok())
+        .collect()
+}
+
 /// `to_styled_document` transforms the given Document instance into `StyledDocument`, a node tree with corresponding CSS properties.
-pub fn to_styled_document<'a>(document: &'a Document) -> StyledDocument<'a> {
-    let styles = format!(
-        "{}\n{}",
-        DEFAULT_STYLESHEET.to_string(),
-        document.get_style_inners().join("\n")
-    );
-    let stylesheet = css::parse(styles).unwrap_or(Stylesheet::new(vec![]));
-    let document_element = to_styled_node(&document.document_element, &stylesheet);
+/// `user_stylesheet` is applied to the document as a stylesheet of `CascadeOrigin::User`.
+/// The stylesheets of the document are applied in tree order; `linked_stylesheets` should have been filled by `load_linked_stylesheets`, and links to the other stylesheets are ignored.
+/// Rules are applied only if their conditions, such as media queries, are true for `device`.
+pub fn to_styled_document<'a>(
+    document: &'a Document,
+    user_stylesheet: &Stylesheet,
+    linked_stylesheets: &LinkedStylesheets,
+    device: &Device,
+) -> StyledDocument<'a> {
+    let default_stylesheet = css::parse(DEFAULT_STYLESHEET.to_string());
+    let sources = document.get_style_sources();
+    let inline_stylesheets = sources
+        .iter()
+        .filter_map(|source| match source {
+            StyleSource::Inline(text) => Some(css::parse(text.clone())),
+            StyleSource::External(_) => None,
+        })
+        .collect::<Vec<_>>();
+    for parse_error in inline_stylesheets
+        .iter()
+        .flat_map(|s| s.parse_errors.iter())
+    {
+        debug!("parse error in stylesheet at {}", parse_error);
+    }
+
+    // NOTE: `@import` in the user agent stylesheet and the user stylesheet is ignored, since they have no URL to resolve it against.
+    let mut collector = RuleCollector {
+        rules: vec![],
+        linked_stylesheets,
+        device,
+        importing: vec![],
+    };
+    collector.collect(CascadeOrigin::UserAgent, &default_stylesheet, None);
+    collector.collect(CascadeOrigin::User, user_stylesheet, None);
+    let base_url = document.base_url();
+    let mut inline_stylesheets = inline_stylesheets.iter();
+    for source in &sources {
+        match source {
+            StyleSource::Inline(_) => {
+                if let Some(stylesheet) = inline_stylesheets.next() {
+                    collector.collect(CascadeOrigin::Author, stylesheet, base_url.as_ref());
+                }
+            }
+            StyleSource::External(url) => collector.collect_linked(CascadeOrigin::Author, url),
+        }
+    }
+    // NOTE: a document without any element, e.g. one emptied by scripts, is styled from the document node itself.
+    let document_element = document
+        .document_element()
+        .unwrap_or_else(|| document.root());
+    let document_element = to_styled_node(document, document_element, &collector.rules, None, None);
 
     StyledDocument {
         document_element: document_element,
     }
 }
 
-fn to_styled_node<'a>(node: &'a Box<Node>, stylesheet: &Stylesheet) -> StyledNode<'a> {
-    // prepare basic information of StyledNode
-    let mut props = PropertyMap::new();
-    let children = to_styled_nodes(&node.children, stylesheet);
+/// `RuleCollector` collects the rules applied to a document in the order of appearance.
+struct RuleCollector<'s> {
+    rules: Vec<(CascadeOrigin, &'s Rule)>,
+    linked_stylesheets: &'s LinkedStylesheets,
+    device: &'s Device,
+    /// `importing` holds the URLs of the stylesheets being collected, which can't be imported again.
+    importing: Vec<Url>,
+}
 
-    // match CSS rules
-    for matched_rule in stylesheet.rules.iter().filter(|r| r.matches(node)) {
-        for declaration in &matched_rule.declarations {
-            props.insert(declaration.name.clone(), declaration.value.clone());
+impl<'s> RuleCollector<'s> {
+    /// `collect` appends the rules of `stylesheet` whose conditions are true, preceded by the ones of the stylesheets it imports.
+    /// Imports are resolved against `base_url`, and are ignored if it's `None`.
+    /// https://drafts.csswg.org/css-cascade-4/#import-processing
+    fn collect(
+        &mut self,
+        origin: CascadeOrigin,
+        stylesheet: &'s Stylesheet,
+        base_url: Option<&Url>,
+    ) {
+        if let Some(base_url) = base_url {
+            for import in &stylesheet.imports {
+                if !self.is_applicable(&import.conditions) {
+                    continue;
+                }
+                if let Ok(url) = base_url.join(import.url.as_str()) {
+                    self.collect_linked(origin, &url);
+                }
+            }
+        }
+        for rule in &stylesheet.rules {
+            if self.is_applicable(rule.conditions.iter().map(|c| c.as_ref())) {
+                self.rules.push((origin, rule));
+            }
         }
     }
 
+    /// `collect_linked` collects the rules of the stylesheet loaded from `url`.
+    /// Cyclic imports and the stylesheets which aren't loaded yet are ignored.
+    fn collect_linked(&mut self, origin: CascadeOrigin, url: &Url) {
+        let linked_stylesheets = self.linked_stylesheets;
+        let stylesheet = match linked_stylesheets.get(url) {
+            Some(stylesheet) if !self.importing.contains(url) => stylesheet,
+            _ => return,
+        };
+        self.importing.push(url.clone());
+        self.collect(origin, stylesheet, Some(url));
+        self.importing.pop();
+    }
+
+    /// `is_applicable` returns true if all the conditions are true.
+    fn is_applicable<'c, I>(&self, conditions: I) -> bool
+    where
+        I: IntoIterator<Item = &'c RuleCondition>,
+    {
+        conditions.into_iter().all(|condition| match condition {
+            RuleCondition::Media(media) => media.matches(self.device),
+            RuleCondition::Supports(supports) => supports.matches(&is_supported),
+        })
+    }
+}
+
+/// `to_styled_node` styles the node and its descendants.
+/// `parent` and `root` are the computed values of the parent node and the root node, which are `None` for the root node itself.
+fn to_styled_node<'a>(
+    document: &'a Document,
+    id: NodeId,
+    rules: &[(CascadeOrigin, &Rule)],
+    parent: Option<&PropertyMap>,
+    root: Option<&PropertyMap>,
+) -> StyledNode<'a> {
+    // prepare basic information of StyledNode
+    let node = document.node(id);
+    let props = to_computed_properties(cascade(document, id, rules), parent, root);
+    let children = document
+        .children(id)
+        .filter(|&child| is_rendered(&document.node(child).node_type))
+        .map(|child| to_styled_node(document, child, rules, Some(&props), root.or(Some(&props))))
+        .collect();
+
     // all set :-)
     StyledNode {
         node_type: &node.node_type,
@@ -79,56 +280,566 @@ fn to_styled_node<'a>(node: &'a Box<Node>, stylesheet: &Stylesheet) -> StyledNod
     }
 }
 
-fn to_styled_nodes<'a>(nodes: &'a Vec<Box<Node>>, stylesheet: &Stylesheet) -> Vec<StyledNode<'a>> {
-    nodes
-        .iter()
-        .map(|x| to_styled_node(x, stylesheet))
-        .collect()
+/// `cascade` returns the winning value of each property declared for the node.
+/// `rules` are the rules applied to the document in the order of appearance, with their origins.
+/// Only elements are matched with the rules; the other nodes only inherit the values of their parents.
+/// https://drafts.csswg.org/css-cascade-4/#cascading
+fn cascade(document: &Document, id: NodeId, rules: &[(CascadeOrigin, &Rule)]) -> PropertyMap {
+    let mut props = PropertyMap::new();
+    let element = match document.node(id).node_type {
+        NodeType::Element(ref e) => e,
+        _ => return props,
+    };
+
+    // match CSS rules
+    let mut declarations = vec![];
+    for (origin, rule) in rules {
+        if let Some(specificity) = rule.matched_specificity(document, id) {
+            for declaration in rule.declarations.iter().filter(|d| is_valid(d)) {
+                let precedence = origin.precedence(declaration.important);
+                declarations.push((precedence, false, specificity, declaration));
+            }
+        }
+    }
+
+    // declarations in `style` attribute are author declarations which win over any rule with the same importance
+    // https://drafts.csswg.org/css-cascade-4/#style-attr
+    let style_attribute = element
+        .attributes
+        .get("style")
+        .map(|style| css::parse_declarations(style.as_str()))
+        .unwrap_or_default();
+    for declaration in style_attribute.iter().filter(|d| is_valid(d)) {
+        let precedence = CascadeOrigin::Author.precedence(declaration.important);
+        declarations.push((precedence, true, Specificity::default(), declaration));
+    }
+
+    // apply declarations in the cascade order, so that the winning one is applied last
+    // NOTE: the sort is stable; declarations which tie are applied in the order of appearance.
+    // https://drafts.csswg.org/css-cascade-4/#cascade-sort
+    declarations.sort_by_key(|&(precedence, element_attached, specificity, _)| {
+        (precedence, element_attached, specificity)
+    });
+    for (_, _, _, declaration) in declarations {
+        props.insert(declaration.name.clone(), declaration.value.clone());
+    }
+    props
+}
+
+/// `is_valid` returns true if the value of the declaration is valid for its property.
+/// Declarations of the properties puppy doesn't know are regarded as valid.
+fn is_valid(declaration: &Declaration) -> bool {
+    property(declaration.name.as_str())
+        .map(|p| p.is_valid(&declaration.value))
+        .unwrap_or(true)
+}
+
+/// `is_supported` returns true if puppy implements the property of the declaration and its value is valid, which `@supports` tests.
+/// Custom properties are always supported.
+/// https://drafts.csswg.org/css-conditional-3/#support-definition
+fn is_supported(declaration: &Declaration) -> bool {
+    declaration.name.starts_with("--")
+        || property(declaration.name.as_str())
+            .map(|p| p.is_valid(&declaration.value))
+            .unwrap_or(false)
+}
+
+/// `is_rendered` returns true if the node can be rendered, i.e. it can have a box.
+/// Comments, processing instructions and doctypes are never rendered.
+///
+/// NOTE: `CDATASection` only appears in XML documents, which puppy doesn't render.
+fn is_rendered(node_type: &NodeType) -> bool {
+    matches!(
+        node_type,
+        NodeType::Document | NodeType::DocumentFragment | NodeType::Element(_) | NodeType::Text(_)
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::{
-        css::{Declaration, Rule, SimpleSelector, Stylesheet},
-        dom::{AttrMap, Element},
+        css::{
+            media::{ColorScheme, Device},
+            Selector, SimpleSelector, Unit,
+        },
+        dom::{AttrMap, Comment, Element, ProcessingInstruction, Text},
     };
 
+    /// `rules_of` lists the rules of the stylesheets in order, as `to_styled_document` does.
+    fn rules_of<'s>(
+        stylesheets: &[(CascadeOrigin, &'s Stylesheet)],
+    ) -> Vec<(CascadeOrigin, &'s Rule)> {
+        stylesheets
+            .iter()
+            .flat_map(|(origin, stylesheet)| stylesheet.rules.iter().map(move |r| (*origin, r)))
+            .collect()
+    }
+
     #[test]
     fn test_properties() {
-        let e = &Element::new("p".to_string(), AttrMap::new(), vec![]);
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let e = document.append_tree(root, Element::new("p".to_string(), AttrMap::new(), vec![]));
         let styled_e: StyledNode<'_> = to_styled_node(
+            &document,
             e,
-            &Stylesheet::new(vec![Rule {
-                selectors: vec![SimpleSelector::UniversalSelector],
-                declarations: vec![Declaration {
-                    name: "display".to_string(),
-                    value: CSSValue::Keyword("block".to_string()),
-                }],
-            }]),
-        );
-        assert_eq!(
-            styled_e,
-            StyledNode {
-                node_type: &e.node_type,
-                properties: [(
-                    "display".to_string(),
-                    CSSValue::Keyword("block".to_string())
-                )]
+            &[(
+                CascadeOrigin::Author,
+                &Rule {
+                    selectors: vec![Selector::from(SimpleSelector::UniversalSelector)],
+                    declarations: vec![Declaration {
+                        name: "display".to_string(),
+                        value: CSSValue::Keyword("block".to_string()),
+                        important: false,
+                        source_span: None,
+                    }],
+                    conditions: vec![],
+                    source_span: None,
+                },
+            )],
+            None,
+            None,
+        );
+        assert_eq!(styled_e.node_type, &document.node(e).node_type);
+        assert_eq!(
+            styled_e.properties.get("display"),
+            Some(&CSSValue::Keyword("block".to_string()))
+        );
+        assert_eq!(
+            styled_e.properties.get("color"),
+            Some(&CSSValue::Keyword("canvastext".to_string()))
+        );
+        assert_eq!(styled_e.children, vec![]);
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let body = document.append_tree(
+            root,
+            Element::new(
+                "body".to_string(),
+                AttrMap::new(),
+                vec![Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("text".to_string())],
+                )],
+            ),
+        );
+        let stylesheet = css::parse(
+            "body { color: red; display: block } p { display: blocky } * { display: flex }"
+                .to_string(),
+        );
+        let styled = to_styled_node(
+            &document,
+            body,
+            &rules_of(&[(CascadeOrigin::Author, &stylesheet)]),
+            None,
+            None,
+        );
+        let p = &styled.children[0];
+        let text = &p.children[0];
+        let red = CSSValue::Keyword("red".to_string());
+        assert_eq!(p.properties.get("color"), Some(&red));
+        assert_eq!(text.properties.get("color"), Some(&red));
+        // the invalid value is ignored, and `display` is never inherited
+        assert_eq!(p.display(), Display::Block);
+        assert_eq!(
+            p.properties.get("display"),
+            Some(&CSSValue::Keyword("flex".to_string()))
+        );
+        assert_eq!(text.display(), Display::Inline);
+    }
+
+    #[test]
+    fn test_cascade() {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "x".to_string());
+        attributes.insert("class".to_string(), "y".to_string());
+        let e = document.append_tree(root, Element::new("p".to_string(), attributes, vec![]));
+
+        let cascade = |stylesheets: &[(CascadeOrigin, &str)]| {
+            let stylesheets = stylesheets
+                .iter()
+                .map(|(origin, s)| (*origin, css::parse(s.to_string())))
+                .collect::<Vec<_>>();
+            let stylesheets = stylesheets
                 .iter()
-                .cloned()
-                .collect(),
-                children: vec![],
+                .map(|(origin, s)| (*origin, s))
+                .collect::<Vec<_>>();
+            match to_styled_node(&document, e, &rules_of(&stylesheets), None, None)
+                .properties
+                .get("a")
+            {
+                Some(CSSValue::Keyword(k)) => k.clone(),
+                _ => "".to_string(),
             }
+        };
+        let author = CascadeOrigin::Author;
+        let user = CascadeOrigin::User;
+        let user_agent = CascadeOrigin::UserAgent;
+
+        // specificity wins over the order of appearance
+        assert_eq!(cascade(&[(author, "#x { a: id } p { a: type }")]), "id");
+        assert_eq!(
+            cascade(&[(author, "p.y { a: class } p { a: type }")]),
+            "class"
+        );
+        // the latter wins if specificities are the same
+        assert_eq!(
+            cascade(&[(author, ".y { a: b } p.y { a: c } p.y { a: d }")]),
+            "d"
+        );
+        // the most specific selector in a rule is used
+        assert_eq!(cascade(&[(author, "p, #x { a: b } .y { a: c }")]), "b");
+        // `!important` wins over specificity
+        assert_eq!(
+            cascade(&[(author, "p { a: b !important } #x { a: c }")]),
+            "b"
+        );
+        // origins
+        assert_eq!(
+            cascade(&[
+                (user_agent, "#x { a: b }"),
+                (user, "p { a: c }"),
+                (author, "* { a: d }")
+            ]),
+            "d"
+        );
+        assert_eq!(
+            cascade(&[(user_agent, "p { a: b }"), (user, "p { a: c }")]),
+            "c"
+        );
+        // important declarations reverse the order of origins
+        assert_eq!(
+            cascade(&[
+                (user, "p { a: b !important }"),
+                (author, "p { a: c !important }")
+            ]),
+            "b"
+        );
+        assert_eq!(
+            cascade(&[
+                (user_agent, "p { a: b !important }"),
+                (user, "#x { a: c !important }")
+            ]),
+            "b"
+        );
+        assert_eq!(
+            cascade(&[(user, "p { a: b !important }"), (author, "#x { a: c }")]),
+            "b"
+        );
+
+        // invalid declarations are dropped before the cascade
+        let stylesheet = css::parse(
+            "p { color: red; font-weight: 700 } p { color: bogus; font-weight: heavy }".to_string(),
+        );
+        let props = to_styled_node(
+            &document,
+            e,
+            &rules_of(&[(author, &stylesheet)]),
+            None,
+            None,
+        )
+        .properties;
+        assert_eq!(
+            props.get("color"),
+            Some(&CSSValue::Keyword("red".to_string()))
+        );
+        assert_eq!(props.get("font-weight"), Some(&CSSValue::Number(700.0)));
+
+        let stylesheet = css::parse(
+            "p { font-size: 20px } p { font-size: 0 } #x { font-size: 30px } #x { font-size: -1px }"
+                .to_string(),
+        );
+        let props = to_styled_node(
+            &document,
+            e,
+            &rules_of(&[(author, &stylesheet)]),
+            None,
+            None,
+        )
+        .properties;
+        assert_eq!(
+            props.get("font-size"),
+            Some(&CSSValue::Length((30.0, Unit::Px)))
+        );
+        let stylesheet = css::parse("p { font-size: 20px } p { font-size: 0 }".to_string());
+        let props = to_styled_node(
+            &document,
+            e,
+            &rules_of(&[(author, &stylesheet)]),
+            None,
+            None,
+        )
+        .properties;
+        assert_eq!(
+            props.get("font-size"),
+            Some(&CSSValue::Length((0.0, Unit::Px)))
+        );
+    }
+
+    #[test]
+    fn test_style_attribute() {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "x".to_string());
+        attributes.insert(
+            "style".to_string(),
+            "a: b; c: d; e: f !important; g: h; i: 1foo".to_string(),
+        );
+        let e = document.append_tree(root, Element::new("p".to_string(), attributes, vec![]));
+        let stylesheet = css::parse(
+            "#x { a: x; c: x !important; e: x !important; i: x } p { g: x }".to_string(),
+        );
+        let user_stylesheet = css::parse("p { g: y !important }".to_string());
+        let props = to_styled_node(
+            &document,
+            e,
+            &rules_of(&[
+                (CascadeOrigin::User, &user_stylesheet),
+                (CascadeOrigin::Author, &stylesheet),
+            ]),
+            None,
+            None,
+        )
+        .properties;
+        let get = |name: &str| match props.get(name) {
+            Some(CSSValue::Keyword(k)) => k.as_str(),
+            _ => "",
+        };
+        // the style attribute wins over any selector
+        assert_eq!(get("a"), "b");
+        // but `!important` in stylesheets wins over normal declarations in the style attribute
+        assert_eq!(get("c"), "x");
+        assert_eq!(get("e"), "f");
+        // important user declarations win over author ones
+        assert_eq!(get("g"), "y");
+        // invalid declarations in the style attribute are dropped
+        assert_eq!(get("i"), "x");
+    }
+
+    #[test]
+    fn test_author_stylesheets() {
+        let mut document = Document::new(
+            "http://example.com/a.html".to_string(),
+            "http://example.com/a.html".to_string(),
+        );
+        let root = document.root();
+        let link = |href: &str| {
+            let mut attributes = AttrMap::new();
+            attributes.insert("rel".to_string(), "stylesheet".to_string());
+            attributes.insert("href".to_string(), href.to_string());
+            Element::new("link".to_string(), attributes, vec![])
+        };
+        let style = |text: &str| {
+            Element::new(
+                "style".to_string(),
+                AttrMap::new(),
+                vec![Text::new(text.to_string())],
+            )
+        };
+        document.append_tree(
+            root,
+            Element::new(
+                "html".to_string(),
+                AttrMap::new(),
+                vec![
+                    link("x.css"),
+                    style("html { a: inline; b: inline } html {"),
+                    link("/y.css"),
+                    link("z.css"),
+                    style("html { d: inline }"),
+                ],
+            ),
+        );
+        let mut linked_stylesheets = LinkedStylesheets::new();
+        linked_stylesheets.insert(
+            Url::parse("http://example.com/x.css").unwrap(),
+            css::parse("html { a: x; c: x; d: x }".to_string()),
+        );
+        linked_stylesheets.insert(
+            Url::parse("http://example.com/y.css").unwrap(),
+            css::parse("html { b: y }".to_string()),
+        );
+
+        let styled = to_styled_document(
+            &document,
+            &Stylesheet::new(vec![]),
+            &linked_stylesheets,
+            &Device::default(),
+        );
+        let get = |name: &str| match styled.document_element.properties.get(name) {
+            Some(CSSValue::Keyword(k)) => k.as_str(),
+            _ => "",
+        };
+        // stylesheets are applied in tree order, and an unclosed rule doesn't affect the next stylesheet
+        assert_eq!(get("a"), "inline");
+        assert_eq!(get("b"), "y");
+        assert_eq!(get("c"), "x");
+        assert_eq!(get("d"), "inline");
+    }
+
+    #[test]
+    fn test_conditional_rules() {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        document.append_tree(
+            root,
+            Element::new(
+                "html".to_string(),
+                AttrMap::new(),
+                vec![Element::new(
+                    "style".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new(
+                        r#"
+                        html { a: x; b: x; c: x; d: x }
+                        @media (max-width: 80ch) { html { a: narrow } }
+                        @media (min-width: 81ch) { html { a: wide } }
+                        @media screen { html { b: screen } }
+                        @media tty and (prefers-color-scheme: dark) {
+                            @supports (display: flex) and (not (display: flexbox)) { html { c: dark } }
+                        }
+                        @supports (color: red) or (unknown: x) { html { d: supported } }
+                        @supports (display: 1foo) { html { d: unsupported } }
+                        "#
+                        .to_string(),
+                    )],
+                )],
+            ),
+        );
+        let linked_stylesheets = LinkedStylesheets::new();
+        let props = |device: Device| {
+            let styled = to_styled_document(
+                &document,
+                &Stylesheet::new(vec![]),
+                &linked_stylesheets,
+                &device,
+            );
+            ["a", "b", "c", "d"]
+                .iter()
+                .map(|name| match styled.document_element.properties.get(*name) {
+                    Some(CSSValue::Keyword(k)) => k.clone(),
+                    _ => "".to_string(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            props(Device::default()),
+            vec!["narrow", "x", "x", "supported"]
+        );
+        assert_eq!(
+            props(Device {
+                width: 120,
+                height: 40,
+                color_scheme: ColorScheme::Dark,
+            }),
+            vec!["wide", "x", "dark", "supported"]
+        );
+    }
+
+    #[test]
+    fn test_imports() {
+        let mut document = Document::new(
+            "http://example.com/a.html".to_string(),
+            "http://example.com/a.html".to_string(),
+        );
+        let root = document.root();
+        let mut attributes = AttrMap::new();
+        attributes.insert("rel".to_string(), "stylesheet".to_string());
+        attributes.insert("href".to_string(), "css/x.css".to_string());
+        document.append_tree(
+            root,
+            Element::new(
+                "html".to_string(),
+                AttrMap::new(),
+                vec![
+                    Element::new(
+                        "style".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new(
+                            "@import 'css/y.css'; html { a: inline; c: inline }".to_string(),
+                        )],
+                    ),
+                    Element::new("link".to_string(), attributes, vec![]),
+                ],
+            ),
+        );
+        let mut linked_stylesheets = LinkedStylesheets::new();
+        let mut insert = |url: &str, text: &str| {
+            linked_stylesheets.insert(Url::parse(url).unwrap(), css::parse(text.to_string()));
+        };
+        // y.css is imported twice, and z.css imports x.css again
+        insert(
+            "http://example.com/css/x.css",
+            "@import url(y.css); @import 'z.css' print; @import 'z.css' (width); html { b: x }",
+        );
+        insert("http://example.com/css/y.css", "html { a: y; b: y; d: y }");
+        insert(
+            "http://example.com/css/z.css",
+            "@import 'x.css'; html { d: z }",
+        );
+
+        let styled = to_styled_document(
+            &document,
+            &Stylesheet::new(vec![]),
+            &linked_stylesheets,
+            &Device::default(),
+        );
+        let get = |name: &str| match styled.document_element.properties.get(name) {
+            Some(CSSValue::Keyword(k)) => k.as_str(),
+            _ => "",
+        };
+        // imported rules precede the rules of the importing stylesheet, and y.css is applied again for x.css
+        assert_eq!(get("a"), "y");
+        assert_eq!(get("b"), "x");
+        assert_eq!(get("c"), "inline");
+        // the import for `print` is ignored, and the cyclic import of x.css is ignored
+        assert_eq!(get("d"), "z");
+    }
+
+    #[test]
+    fn test_skip_non_rendered_nodes() {
+        let mut document = Document::new("".to_string(), "".to_string());
+        let root = document.root();
+        let p = document.append_tree(
+            root,
+            Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![
+                    Comment::new("comment".to_string()),
+                    Text::new("text".to_string()),
+                    ProcessingInstruction::new("target".to_string(), "data".to_string()),
+                ],
+            ),
+        );
+        let styled = to_styled_node(&document, p, &[], None, None);
+        assert_eq!(styled.children.len(), 1);
+        assert_eq!(
+            styled.children[0].node_type,
+            &NodeType::Text(Text {
+                data: "text".to_string()
+            })
         );
     }
 }
 
 impl<'a> StyledNode<'a> {
+    /// `display` returns how the node is laid out, from its outer display type.
+    /// https://drafts.csswg.org/css-display-3/#outer-role
     pub fn display(&self) -> Display {
         match self.properties.get("display") {
-            Some(CSSValue::Keyword(s)) => match s.as_str() {
-                "block" => Display::Block,
+            Some(CSSValue::Keyword(s)) => match s.to_ascii_lowercase().as_str() {
+                "block" | "list-item" | "flex" | "grid" | "table" | "flow-root" => Display::Block,
                 "none" => Display::None,
                 _ => Display::Inline,
             },
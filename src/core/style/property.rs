@@ -0,0 +1,514 @@
+//! This module defines the CSS properties puppy knows, and resolves their computed values.
+//! The resolution is defined at [CSS Cascading and Inheritance Level 4](https://drafts.csswg.org/css-cascade-4/#value-stages).
+
+use super::PropertyMap;
+use crate::core::css::{CSSValue, Color, Unit};
+
+/// `Property` is a definition of a CSS property.
+#[derive(Debug, PartialEq)]
+pub struct Property {
+    pub name: &'static str,
+    /// `inherited` is true if the property takes the value of the parent when no value is specified.
+    /// https://drafts.csswg.org/css-cascade-4/#inherited-property
+    pub inherited: bool,
+    /// `initial` is the keyword which is used when no value is specified for a non-inherited property.
+    /// https://drafts.csswg.org/css-cascade-4/#initial-value
+    pub initial: &'static str,
+    /// `keywords` lists the keywords valid for the property, or is empty if any value is valid.
+    /// NOTE: the keywords of `color` are the named colors, which are checked in `is_valid`.
+    pub keywords: &'static [&'static str],
+}
+
+/// `PROPERTIES` is the registry of the properties puppy knows.
+/// Declarations of the other properties are cascaded, but are never inherited and have no initial value.
+pub static PROPERTIES: &[Property] = &[
+    Property {
+        name: "display",
+        inherited: false,
+        initial: "inline",
+        keywords: &[
+            "block",
+            "inline",
+            "inline-block",
+            "list-item",
+            "flex",
+            "inline-flex",
+            "grid",
+            "inline-grid",
+            "table",
+            "flow-root",
+            "contents",
+            "none",
+        ],
+    },
+    Property {
+        name: "color",
+        inherited: true,
+        initial: "canvastext",
+        keywords: &[],
+    },
+    Property {
+        name: "font-size",
+        inherited: true,
+        initial: "medium",
+        keywords: &[
+            "xx-small", "x-small", "small", "medium", "large", "x-large", "xx-large",
+        ],
+    },
+    Property {
+        name: "font-style",
+        inherited: true,
+        initial: "normal",
+        keywords: &["normal", "italic", "oblique"],
+    },
+    Property {
+        name: "font-weight",
+        inherited: true,
+        initial: "normal",
+        keywords: &["normal", "bold", "bolder", "lighter"],
+    },
+    Property {
+        name: "text-decoration",
+        inherited: false,
+        initial: "none",
+        keywords: &["none", "underline", "overline", "line-through"],
+    },
+    Property {
+        name: "visibility",
+        inherited: true,
+        initial: "visible",
+        keywords: &["visible", "hidden", "collapse"],
+    },
+    Property {
+        name: "white-space",
+        inherited: true,
+        initial: "normal",
+        keywords: &["normal", "pre", "nowrap", "pre-wrap", "pre-line"],
+    },
+];
+
+/// `DEFAULT_FONT_SIZE` is the computed value of `font-size: medium` in pixels.
+const DEFAULT_FONT_SIZE: f64 = 16.0;
+
+/// `property` looks up the definition of the property named `name`.
+pub fn property(name: &str) -> Option<&'static Property> {
+    PROPERTIES.iter().find(|p| p.name == name)
+}
+
+impl Property {
+    /// `initial_value` returns the initial value of the property.
+    pub fn initial_value(&self) -> CSSValue {
+        CSSValue::Keyword(self.initial.to_string())
+    }
+
+    /// `is_valid` returns true if `value` is a valid value for the property.
+    /// Invalid declarations are ignored in the cascade.
+    /// https://drafts.csswg.org/css-syntax-3/#css-parse-something-according-to-a-css-grammar
+    pub fn is_valid(&self, value: &CSSValue) -> bool {
+        match value {
+            CSSValue::Keyword(k) if is_css_wide_keyword(k) => true,
+            // https://drafts.csswg.org/css-color-4/#typedef-color
+            CSSValue::Keyword(k) if self.name == "color" => {
+                Color::from_name(k).is_some()
+                    || ["currentcolor", "canvastext"]
+                        .iter()
+                        .any(|c| c.eq_ignore_ascii_case(k))
+            }
+            CSSValue::Keyword(k) => {
+                self.keywords.is_empty() || self.keywords.contains(&k.to_ascii_lowercase().as_str())
+            }
+            // NOTE: negative values are invalid for all the properties puppy knows to take lengths.
+            CSSValue::Length((size, _)) | CSSValue::Percentage(size) => {
+                self.takes_length() && *size >= 0.0
+            }
+            CSSValue::Color(_) => self.name == "color",
+            // a unitless zero is a length. https://drafts.csswg.org/css-values-3/#lengths
+            CSSValue::Number(0.0) if self.takes_length() => true,
+            // https://drafts.csswg.org/css-fonts-4/#font-weight-absolute-values
+            CSSValue::Number(n) => self.name == "font-weight" && (1.0..=1000.0).contains(n),
+            _ => false,
+        }
+    }
+
+    /// `takes_length` returns true if lengths and percentages are valid for the property.
+    fn takes_length(&self) -> bool {
+        self.name == "font-size"
+    }
+}
+
+/// `is_css_wide_keyword` returns true if `keyword` is valid for all the properties.
+/// https://drafts.csswg.org/css-values-3/#common-keywords
+fn is_css_wide_keyword(keyword: &str) -> bool {
+    ["inherit", "initial", "unset"]
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(keyword))
+}
+
+/// `to_computed_properties` resolves the computed values of the properties from `cascaded`, the winning declarations in the cascade.
+/// `parent` is the computed values of the parent node, and `root` is the ones of the root node; they are `None` for the root node.
+/// - The registered properties without any cascaded value take their parent's value or their initial value.
+/// - `inherit`, `initial` and `unset` are resolved. https://drafts.csswg.org/css-cascade-4/#defaulting-keywords
+/// - Relative lengths are resolved into absolute ones. https://drafts.csswg.org/css-values-3/#relative-lengths
+pub fn to_computed_properties(
+    mut cascaded: PropertyMap,
+    parent: Option<&PropertyMap>,
+    root: Option<&PropertyMap>,
+) -> PropertyMap {
+    let parent_value = |name: &str| parent.and_then(|parent| parent.get(name)).cloned();
+    let mut props = PropertyMap::new();
+    for property in PROPERTIES {
+        let value = match cascaded.remove(property.name) {
+            Some(CSSValue::Keyword(ref k)) if k.eq_ignore_ascii_case("inherit") => {
+                parent_value(property.name)
+            }
+            Some(CSSValue::Keyword(ref k)) if k.eq_ignore_ascii_case("initial") => None,
+            Some(CSSValue::Keyword(ref k)) if k.eq_ignore_ascii_case("unset") => {
+                parent_value(property.name).filter(|_| property.inherited)
+            }
+            Some(value) => Some(value),
+            None => parent_value(property.name).filter(|_| property.inherited),
+        };
+        props.insert(
+            property.name.to_string(),
+            value.unwrap_or_else(|| property.initial_value()),
+        );
+    }
+    // NOTE: the properties puppy doesn't know are handled as non-inherited ones without initial values.
+    for (name, value) in cascaded {
+        let value = match value {
+            CSSValue::Keyword(ref k) if k.eq_ignore_ascii_case("inherit") => parent_value(&name),
+            CSSValue::Keyword(ref k) if is_css_wide_keyword(k) => None,
+            value => Some(value),
+        };
+        if let Some(value) = value {
+            props.insert(name, value);
+        }
+    }
+
+    // `bolder` and `lighter` are relative to the weight of the parent.
+    if let Some(value) = props.get_mut("font-weight") {
+        if let Some(weight) = to_relative_font_weight(value, computed_font_weight(parent)) {
+            *value = CSSValue::Number(weight);
+        }
+    }
+
+    // `em` is relative to the font size of the parent in `font-size`, and to the font size of the element itself otherwise.
+    let parent_font_size = computed_font_size(parent);
+    let root_font_size = computed_font_size(root);
+    let font_size = match props.get("font-size") {
+        Some(value) => to_font_size(value, parent_font_size, root_font_size),
+        None => parent_font_size,
+    };
+    for (name, value) in props.iter_mut() {
+        if name == "font-size" {
+            *value = CSSValue::Length((font_size, Unit::Px));
+        } else if let CSSValue::Length(length) = value {
+            *length = to_absolute_length(*length, font_size, root_font_size);
+        }
+    }
+    props
+}
+
+/// `computed_font_size` returns the computed value of `font-size` in `props`, or the initial one if `props` is `None`.
+fn computed_font_size(props: Option<&PropertyMap>) -> f64 {
+    match props.and_then(|props| props.get("font-size")) {
+        Some(CSSValue::Length((size, Unit::Px))) => *size,
+        _ => DEFAULT_FONT_SIZE,
+    }
+}
+
+/// `computed_font_weight` returns the computed value of `font-weight` in `props` as a number, or the initial one if `props` is `None`.
+fn computed_font_weight(props: Option<&PropertyMap>) -> f64 {
+    match props.and_then(|props| props.get("font-weight")) {
+        Some(CSSValue::Number(weight)) => *weight,
+        Some(CSSValue::Keyword(k)) if k.eq_ignore_ascii_case("bold") => 700.0,
+        _ => 400.0,
+    }
+}
+
+/// `to_relative_font_weight` resolves `bolder` and `lighter` against the weight of the parent, and returns `None` for the other values.
+/// https://drafts.csswg.org/css-fonts-4/#relative-weights
+fn to_relative_font_weight(value: &CSSValue, parent_weight: f64) -> Option<f64> {
+    let keyword = match value {
+        CSSValue::Keyword(k) => k.to_ascii_lowercase(),
+        _ => return None,
+    };
+    match keyword.as_str() {
+        "bolder" => Some(if parent_weight < 350.0 {
+            400.0
+        } else if parent_weight < 550.0 {
+            700.0
+        } else if parent_weight < 900.0 {
+            900.0
+        } else {
+            parent_weight
+        }),
+        "lighter" => Some(if parent_weight < 100.0 {
+            parent_weight
+        } else if parent_weight < 550.0 {
+            100.0
+        } else if parent_weight < 750.0 {
+            400.0
+        } else {
+            700.0
+        }),
+        _ => None,
+    }
+}
+
+/// `to_absolute_length` resolves a font-relative length into pixels.
+/// https://drafts.csswg.org/css-values-3/#font-relative-lengths
+///
+/// NOTE: viewport-percentage lengths are kept as they are since the viewport is unknown while styling.
+fn to_absolute_length(length: (f64, Unit), font_size: f64, root_font_size: f64) -> (f64, Unit) {
+    match length {
+        (size, Unit::Em) => (size * font_size, Unit::Px),
+        (size, Unit::Rem) => (size * root_font_size, Unit::Px),
+        // NOTE: puppy doesn't know the width of "0" of fonts, and uses the fallback of 0.5em.
+        (size, Unit::Ch) => (size * font_size / 2.0, Unit::Px),
+        length => length,
+    }
+}
+
+/// `to_font_size` resolves the value of `font-size` into pixels.
+/// https://drafts.csswg.org/css-fonts-4/#font-size-prop
+fn to_font_size(value: &CSSValue, parent_font_size: f64, root_font_size: f64) -> f64 {
+    match value {
+        CSSValue::Length((size, Unit::Px)) => *size,
+        CSSValue::Length(length) => {
+            match to_absolute_length(*length, parent_font_size, root_font_size) {
+                (size, Unit::Px) => size,
+                _ => parent_font_size,
+            }
+        }
+        CSSValue::Percentage(p) => parent_font_size * p / 100.0,
+        CSSValue::Number(0.0) => 0.0,
+        // https://drafts.csswg.org/css-fonts-4/#absolute-size-mapping
+        CSSValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+            "xx-small" => 9.0,
+            "x-small" => 10.0,
+            "small" => 13.0,
+            "large" => 18.0,
+            "x-large" => 24.0,
+            "xx-large" => 32.0,
+            _ => DEFAULT_FONT_SIZE,
+        },
+        _ => parent_font_size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::css::Color;
+
+    fn keyword(k: &str) -> CSSValue {
+        CSSValue::Keyword(k.to_string())
+    }
+
+    fn property_map(props: &[(&str, CSSValue)]) -> PropertyMap {
+        props
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let display = property("display").unwrap();
+        assert!(display.is_valid(&keyword("block")));
+        assert!(display.is_valid(&keyword("INHERIT")));
+        assert!(!display.is_valid(&keyword("blocky")));
+        assert!(!display.is_valid(&CSSValue::Length((1.0, Unit::Em))));
+        assert!(property("color").unwrap().is_valid(&keyword("red")));
+        assert!(property("color")
+            .unwrap()
+            .is_valid(&keyword("currentColor")));
+        assert!(!property("color").unwrap().is_valid(&keyword("bogus")));
+        let font_weight = property("font-weight").unwrap();
+        assert!(font_weight.is_valid(&keyword("bolder")));
+        assert!(font_weight.is_valid(&CSSValue::Number(700.0)));
+        assert!(font_weight.is_valid(&CSSValue::Number(1000.0)));
+        assert!(!font_weight.is_valid(&CSSValue::Number(0.0)));
+        assert!(!font_weight.is_valid(&CSSValue::Number(1001.0)));
+        assert!(!display.is_valid(&CSSValue::Number(700.0)));
+        let font_size = property("font-size").unwrap();
+        assert!(font_size.is_valid(&CSSValue::Number(0.0)));
+        assert!(!font_size.is_valid(&CSSValue::Number(1.0)));
+        assert!(!font_size.is_valid(&CSSValue::Length((-1.0, Unit::Px))));
+        assert!(!font_size.is_valid(&CSSValue::Percentage(-50.0)));
+        assert!(!display.is_valid(&CSSValue::Number(0.0)));
+        assert!(property("font-size")
+            .unwrap()
+            .is_valid(&CSSValue::Length((2.0, Unit::Em))));
+        assert!(property("color")
+            .unwrap()
+            .is_valid(&CSSValue::Color(Color::rgb(0, 0, 0))));
+        assert!(!property("display")
+            .unwrap()
+            .is_valid(&CSSValue::Color(Color::rgb(0, 0, 0))));
+        assert_eq!(property("unknown"), None);
+    }
+
+    #[test]
+    fn test_to_computed_properties() {
+        let root = to_computed_properties(
+            property_map(&[("color", keyword("red")), ("display", keyword("block"))]),
+            None,
+            None,
+        );
+        assert_eq!(root.get("color"), Some(&keyword("red")));
+        assert_eq!(root.get("display"), Some(&keyword("block")));
+        assert_eq!(root.get("visibility"), Some(&keyword("visible")));
+        assert_eq!(
+            root.get("font-size"),
+            Some(&CSSValue::Length((16.0, Unit::Px)))
+        );
+
+        // inherited properties are inherited, and non-inherited ones are not
+        let child = to_computed_properties(PropertyMap::new(), Some(&root), Some(&root));
+        assert_eq!(child.get("color"), Some(&keyword("red")));
+        assert_eq!(child.get("display"), Some(&keyword("inline")));
+
+        // defaulting keywords
+        let child = to_computed_properties(
+            property_map(&[
+                ("color", keyword("initial")),
+                ("display", keyword("inherit")),
+                ("visibility", keyword("unset")),
+                ("margin", keyword("inherit")),
+                ("padding", keyword("unset")),
+            ]),
+            Some(&property_map(&[
+                ("visibility", keyword("hidden")),
+                ("margin", CSSValue::Length((32.0, Unit::Px))),
+            ])),
+            None,
+        );
+        assert_eq!(child.get("color"), Some(&keyword("canvastext")));
+        assert_eq!(child.get("display"), Some(&keyword("inline")));
+        assert_eq!(child.get("visibility"), Some(&keyword("hidden")));
+        assert_eq!(
+            child.get("margin"),
+            Some(&CSSValue::Length((32.0, Unit::Px)))
+        );
+        assert_eq!(child.get("padding"), None);
+    }
+
+    #[test]
+    fn test_relative_font_weight() {
+        let weight_of = |value: CSSValue, parent: Option<&PropertyMap>| {
+            to_computed_properties(property_map(&[("font-weight", value)]), parent, parent)
+                .remove("font-weight")
+                .unwrap()
+        };
+        assert_eq!(weight_of(keyword("bolder"), None), CSSValue::Number(700.0));
+        assert_eq!(weight_of(keyword("lighter"), None), CSSValue::Number(100.0));
+        assert_eq!(weight_of(keyword("bold"), None), keyword("bold"));
+
+        let parent = property_map(&[("font-weight", keyword("bold"))]);
+        assert_eq!(
+            weight_of(keyword("bolder"), Some(&parent)),
+            CSSValue::Number(900.0)
+        );
+        assert_eq!(
+            weight_of(keyword("lighter"), Some(&parent)),
+            CSSValue::Number(400.0)
+        );
+
+        let parent = property_map(&[("font-weight", CSSValue::Number(950.0))]);
+        assert_eq!(
+            weight_of(keyword("bolder"), Some(&parent)),
+            CSSValue::Number(950.0)
+        );
+        assert_eq!(
+            weight_of(keyword("lighter"), Some(&parent)),
+            CSSValue::Number(700.0)
+        );
+        let parent = property_map(&[("font-weight", CSSValue::Number(50.0))]);
+        assert_eq!(
+            weight_of(keyword("lighter"), Some(&parent)),
+            CSSValue::Number(50.0)
+        );
+    }
+
+    #[test]
+    fn test_relative_lengths() {
+        let parent = to_computed_properties(
+            property_map(&[("font-size", CSSValue::Length((2.0, Unit::Em)))]),
+            None,
+            None,
+        );
+        assert_eq!(
+            parent.get("font-size"),
+            Some(&CSSValue::Length((32.0, Unit::Px)))
+        );
+
+        let child = to_computed_properties(
+            property_map(&[
+                ("font-size", CSSValue::Length((2.0, Unit::Em))),
+                ("margin", CSSValue::Length((1.0, Unit::Em))),
+                ("padding", CSSValue::Length((1.0, Unit::Rem))),
+                ("width", CSSValue::Length((2.0, Unit::Ch))),
+                ("height", CSSValue::Length((50.0, Unit::Vh))),
+            ]),
+            Some(&parent),
+            Some(&parent),
+        );
+        assert_eq!(
+            child.get("font-size"),
+            Some(&CSSValue::Length((64.0, Unit::Px)))
+        );
+        assert_eq!(
+            child.get("margin"),
+            Some(&CSSValue::Length((64.0, Unit::Px)))
+        );
+        assert_eq!(
+            child.get("padding"),
+            Some(&CSSValue::Length((32.0, Unit::Px)))
+        );
+        assert_eq!(
+            child.get("width"),
+            Some(&CSSValue::Length((64.0, Unit::Px)))
+        );
+        assert_eq!(
+            child.get("height"),
+            Some(&CSSValue::Length((50.0, Unit::Vh)))
+        );
+
+        let child = to_computed_properties(
+            property_map(&[("font-size", keyword("large"))]),
+            Some(&parent),
+            Some(&parent),
+        );
+        assert_eq!(
+            child.get("font-size"),
+            Some(&CSSValue::Length((18.0, Unit::Px)))
+        );
+        let grandchild = to_computed_properties(PropertyMap::new(), Some(&child), Some(&parent));
+        assert_eq!(
+            grandchild.get("font-size"),
+            Some(&CSSValue::Length((18.0, Unit::Px)))
+        );
+
+        let grandchild = to_computed_properties(
+            property_map(&[("font-size", CSSValue::Number(0.0))]),
+            Some(&child),
+            Some(&parent),
+        );
+        assert_eq!(
+            grandchild.get("font-size"),
+            Some(&CSSValue::Length((0.0, Unit::Px)))
+        );
+
+        let grandchild = to_computed_properties(
+            property_map(&[("font-size", CSSValue::Percentage(50.0))]),
+            Some(&child),
+            Some(&parent),
+        );
+        assert_eq!(
+            grandchild.get("font-size"),
+            Some(&CSSValue::Length((9.0, Unit::Px)))
+        );
+    }
+}
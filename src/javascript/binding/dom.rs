@@ -2,33 +2,268 @@
 //!
 //! This module includes implementations of a subset of DOM API (https://dom.spec.whatwg.org/).
 
-use std::ffi::c_void;
+use std::{cell::RefCell, convert::TryFrom, rc::Rc};
 
-use super::{set_accessor_to, set_constant_to, set_function_to, set_property_to};
+use super::{
+    create_object_under, set_accessor_to, set_constant_to, set_function_to, set_property_to,
+};
 use crate::{
-    core::dom::{Node, NodeType},
+    core::dom::{
+        dataset_attribute_name, dataset_property_name, is_valid_name, parse_ordered_set, AttrMap,
+        CharacterData, Comment, DOMException, Document, Element, FilterResult, MutationObserverId,
+        MutationObserverInit, MutationRecord, NodeFilter, NodeId, NodeIterator, NodeType,
+        ProcessingInstruction, Text, TreeWalker, WhatToShow,
+    },
     javascript::{api::request_rerender, JavaScriptRuntime},
 };
 use log::error;
 use rusty_v8 as v8;
 
-/// This function creates a new `Node` object.
+/// This function creates a new object of `kind` linked to a node, such as a `Node` object.
+/// The ID of the node should be set with `set_node_internal_ref`.
 ///
 /// Here are major standards on this object:
 /// - https://dom.spec.whatwg.org/#interface-node
-///
-/// TODO (enhancement): fix memory leak caused by Box::leak()
-fn create_v8_node<'s>(scope: &mut v8::HandleScope<'s>) -> v8::Local<'s, v8::Object> {
-    let template = v8::ObjectTemplate::new(scope);
-
-    // set properties
-    // TODO (enhancement): add appendChild etc.
-
+fn create_v8_node<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    kind: WrapperKind,
+) -> v8::Local<'s, v8::Object> {
     // extend internal field capacity to store node ID (which is used to identify the actual node in PageView)
-    template.set_internal_field_count(1);
+    create_wrapper(scope, kind, NODE_FIELD + 1)
+}
 
-    // create new node instance
-    template.new_instance(scope).unwrap()
+/// This function adds methods to mutate the tree into the given `Node` object.
+///
+/// Here are major standards on these methods:
+/// - https://dom.spec.whatwg.org/#dom-node-appendchild
+/// - https://dom.spec.whatwg.org/#dom-node-insertbefore
+/// - https://dom.spec.whatwg.org/#dom-node-removechild
+/// - https://dom.spec.whatwg.org/#dom-node-replacechild
+/// - https://dom.spec.whatwg.org/#dom-node-clonenode
+/// - https://dom.spec.whatwg.org/#dom-node-normalize
+///
+/// NOTE: a new JS object is created whenever a node is returned, so `node.appendChild(child) === child` is false in puppy.
+fn set_mutation_methods_to<'s>(scope: &mut v8::HandleScope<'s>, node: v8::Local<v8::Object>) {
+    set_function_to(
+        scope,
+        node,
+        "appendChild",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, parent) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let node = match node_argument(scope, &document, args.get(0)) {
+                Some(node) => node,
+                None => return,
+            };
+            let result = document.borrow_mut().append_child(parent, node);
+            set_mutation_result(scope, &document, result, &mut retval, "appendChild");
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "insertBefore",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, parent) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let node = match node_argument(scope, &document, args.get(0)) {
+                Some(node) => node,
+                None => return,
+            };
+            let child = if args.get(1).is_null_or_undefined() {
+                None
+            } else {
+                match node_argument(scope, &document, args.get(1)) {
+                    Some(child) => Some(child),
+                    None => return,
+                }
+            };
+            let result = document.borrow_mut().insert_before(parent, node, child);
+            set_mutation_result(scope, &document, result, &mut retval, "insertBefore");
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "removeChild",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, parent) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let child = match node_argument(scope, &document, args.get(0)) {
+                Some(child) => child,
+                None => return,
+            };
+            let result = document.borrow_mut().remove_child(parent, child);
+            set_mutation_result(scope, &document, result, &mut retval, "removeChild");
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "replaceChild",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, parent) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let node = match node_argument(scope, &document, args.get(0)) {
+                Some(node) => node,
+                None => return,
+            };
+            let child = match node_argument(scope, &document, args.get(1)) {
+                Some(child) => child,
+                None => return,
+            };
+            let result = document.borrow_mut().replace_child(parent, node, child);
+            set_mutation_result(scope, &document, result, &mut retval, "replaceChild");
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "cloneNode",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let deep = args.get(0).boolean_value(scope);
+            let result = document.borrow_mut().clone_node(id, deep);
+            match result {
+                Ok(clone) => {
+                    let clone = wrap_node(scope, &document, clone);
+                    retval.set(clone.into());
+                }
+                Err(e) => throw_dom_exception(scope, e),
+            }
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "normalize",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         _retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            document.borrow_mut().normalize(id);
+            notify_mutation(scope, "normalize");
+        },
+    );
+}
+
+/// This function adds methods to find elements into the given `Document` or `Element` object.
+///
+/// Here are major standards on these methods:
+/// - https://dom.spec.whatwg.org/#dom-parentnode-queryselector
+/// - https://dom.spec.whatwg.org/#dom-parentnode-queryselectorall
+/// - https://dom.spec.whatwg.org/#dom-document-getelementsbytagname
+/// - https://dom.spec.whatwg.org/#dom-document-getelementsbyclassname
+///
+/// NOTE: the standard returns live collections from `getElementsBy*`, but puppy returns arrays of the elements found at the call.
+fn set_query_methods_to<'s>(scope: &mut v8::HandleScope<'s>, node: v8::Local<v8::Object>) {
+    set_function_to(
+        scope,
+        node,
+        "querySelector",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let selectors = args.get(0).to_rust_string_lossy(scope);
+            let result = document.borrow().query_selector(id, selectors.as_str());
+            match result {
+                Ok(Some(element)) => {
+                    let element = wrap_node(scope, &document, element);
+                    retval.set(element.into());
+                }
+                Ok(None) => retval.set(v8::null(scope).into()),
+                Err(_) => throw_invalid_selector(scope),
+            }
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "querySelectorAll",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let selectors = args.get(0).to_rust_string_lossy(scope);
+            let result = document.borrow().query_selector_all(id, selectors.as_str());
+            match result {
+                Ok(elements) => {
+                    let elements = to_v8_array(scope, &document, elements);
+                    retval.set(elements.into());
+                }
+                Err(_) => throw_invalid_selector(scope),
+            }
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "getElementsByTagName",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let name = args.get(0).to_rust_string_lossy(scope);
+            let elements = document
+                .borrow()
+                .get_elements_by_tag_name(id, name.as_str());
+            let elements = to_v8_array(scope, &document, elements);
+            retval.set(elements.into());
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "getElementsByClassName",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let class_names = args.get(0).to_rust_string_lossy(scope);
+            let elements = document
+                .borrow()
+                .get_elements_by_class_name(id, class_names.as_str());
+            let elements = to_v8_array(scope, &document, elements);
+            retval.set(elements.into());
+        },
+    );
 }
 
 /// This function creates a new `Element` object.
@@ -38,10 +273,9 @@ fn create_v8_node<'s>(scope: &mut v8::HandleScope<'s>) -> v8::Local<'s, v8::Obje
 fn to_v8_element<'s>(
     scope: &mut v8::HandleScope<'s>,
     tag_name: &str,
-    attributes: Vec<(String, String)>,
-    node_rust: NodeRefTarget,
+    id: NodeId,
 ) -> v8::Local<'s, v8::Object> {
-    let node = to_v8_node(scope, node_rust);
+    let node = to_v8_node(scope, id);
 
     // set properties
     {
@@ -49,18 +283,9 @@ fn to_v8_element<'s>(
         let tag_name = v8::String::new(scope, tag_name).unwrap();
         set_constant_to(scope, node, "tagName", tag_name.into());
     }
-    {
-        // set attributes as properties
-        for (key, value) in attributes {
-            let value = v8::String::new(scope, value.as_str()).unwrap();
-            set_constant_to(scope, node, key.as_str(), value.into());
-        }
-    }
+    set_reflected_attributes_to(scope, node);
     {
         // add `innerHTML` property
-        // TODO (security): the setter might cause dangling pointer from v8 to rust's heap.
-        // This is because objects returned by `document.all` have pointers to rust's heap their own internal fields,
-        // and they will be alive after setting values to (any node).`innerHTML` and some node are deleted from the heap.
         set_accessor_to(
             scope,
             node,
@@ -70,9 +295,13 @@ fn to_v8_element<'s>(
                   args: v8::PropertyCallbackArguments,
                   mut rv: v8::ReturnValue| {
                 let this = args.this();
-                let node = to_linked_rust_node(scope, this);
+                let (document, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
 
-                let ret = v8::String::new(scope, node.inner_html().as_str()).unwrap();
+                let html = document.borrow().inner_html(id);
+                let ret = v8::String::new(scope, html.as_str()).unwrap();
                 rv.set(ret.into());
             },
             move |scope: &mut v8::HandleScope,
@@ -80,190 +309,2929 @@ fn to_v8_element<'s>(
                   value: v8::Local<v8::Value>,
                   args: v8::PropertyCallbackArguments| {
                 let this = args.this();
-                let node = to_linked_rust_node(scope, this);
-                if let Err(e) = node.set_inner_html(value.to_rust_string_lossy(scope)) {
-                    error!("failed to set innerHTML; {}", e);
+                let (document, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+
+                let html = value.to_rust_string_lossy(scope);
+                let result = document.borrow_mut().set_inner_html(id, html);
+                if let Err(e) = result {
+                    throw_error(scope, format!("failed to set innerHTML; {}", e).as_str());
+                    return;
                 }
-                request_rerender(scope, "setter of innerHTML");
+                notify_mutation(scope, "setter of innerHTML");
             },
         );
     }
-
-    node
-}
-
-/// This function creates a new `Document` object.
-///
-/// Here are major standards on this object:
-/// - https://dom.spec.whatwg.org/#interface-document
-/// - https://html.spec.whatwg.org/multipage/dom.html#the-document-object
-fn create_document_object<'s>(scope: &mut v8::HandleScope<'s>) -> v8::Local<'s, v8::Object> {
-    let document = create_v8_node(scope);
-
-    // set properties
     {
-        // add `all` property (too old though!)
-        // standard: https://dom.spec.whatwg.org/#dom-document-createelement
+        // add `outerHTML` property
         set_accessor_to(
             scope,
-            document,
-            "all",
-            |scope: &mut v8::HandleScope,
-             _key: v8::Local<v8::Name>,
-             _args: v8::PropertyCallbackArguments,
-             mut rv: v8::ReturnValue| {
-                // get puppy's document object
-                let document = match JavaScriptRuntime::document(scope) {
-                    Some(_document) => _document,
-                    None => {
-                        error!("failed to get document reference; document is None");
-                        return;
-                    }
+            node,
+            "outerHTML",
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  args: v8::PropertyCallbackArguments,
+                  mut rv: v8::ReturnValue| {
+                let this = args.this();
+                let (document, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
                 };
-                let mut document = document.borrow_mut();
-
-                // get all nodes
-                let document_element = &mut document.document_element;
 
-                let mut f = |n: &mut Box<Node>| -> Option<v8::Local<v8::Value>> {
-                    let (tag_name, attributes) = match n.node_type {
-                        NodeType::Element(ref e) => (e.tag_name.clone(), e.attributes()),
-                        _ => return None,
-                    };
-                    Some(to_v8_element(scope, tag_name.as_str(), attributes, n).into())
+                let html = document.borrow().outer_html(id);
+                let ret = v8::String::new(scope, html.as_str()).unwrap();
+                rv.set(ret.into());
+            },
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  value: v8::Local<v8::Value>,
+                  args: v8::PropertyCallbackArguments| {
+                let this = args.this();
+                let (document, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
                 };
 
-                let all: Vec<v8::Local<v8::Value>> = map_mut(document_element, &mut f)
-                    .into_iter()
-                    .filter_map(|n| n)
-                    .collect();
-                let all = v8::Array::new_with_elements(scope, all.as_slice());
-
-                // all set!
-                rv.set(all.into());
+                let html = value.to_rust_string_lossy(scope);
+                let result = document.borrow_mut().set_outer_html(id, html);
+                if let Err(e) = result {
+                    throw_dom_exception(scope, e);
+                    return;
+                }
+                notify_mutation(scope, "setter of outerHTML");
             },
-            |_scope: &mut v8::HandleScope,
-             _key: v8::Local<v8::Name>,
-             _value: v8::Local<v8::Value>,
-             _args: v8::PropertyCallbackArguments| {},
         );
     }
     {
-        // `getElementById` property
+        // add `matches` and `closest` method
+        // standard: https://dom.spec.whatwg.org/#dom-element-matches
         set_function_to(
             scope,
-            document,
-            "getElementById",
+            node,
+            "matches",
             |scope: &mut v8::HandleScope,
              args: v8::FunctionCallbackArguments,
              mut retval: v8::ReturnValue| {
-                let id = args
-                    .get(0)
-                    .to_string(scope)
-                    .unwrap()
-                    .to_rust_string_lossy(scope);
-                // get puppy's document object
-                let document = match JavaScriptRuntime::document(scope) {
-                    Some(_document) => _document,
-                    None => {
-                        error!("failed to get document reference; document is None");
-                        return;
-                    }
+                let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
                 };
-                let mut document = document.borrow_mut();
-
-                // get all nodes
-                let document_element = &mut document.document_element;
-
-                let mut f = |n: &mut Box<Node>| -> Option<v8::Local<v8::Value>> {
-                    let (tag_name, attributes) = match n.node_type {
-                        NodeType::Element(ref e) => {
-                            if e.id().map(|eid| eid.to_string() == id).unwrap_or(false) {
-                                (e.tag_name.clone(), e.attributes())
-                            } else {
-                                return None;
-                            }
-                        }
-                        _ => return None,
-                    };
-                    Some(to_v8_element(scope, tag_name.as_str(), attributes, n).into())
+                let selectors = args.get(0).to_rust_string_lossy(scope);
+                let result = document.borrow().matches(id, selectors.as_str());
+                match result {
+                    Ok(matched) => retval.set(v8::Boolean::new(scope, matched).into()),
+                    Err(_) => throw_invalid_selector(scope),
+                }
+            },
+        );
+        set_function_to(
+            scope,
+            node,
+            "closest",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
                 };
-
-                let element: v8::Local<v8::Value> = map_mut(document_element, &mut f)
-                    .into_iter()
-                    .find_map(|n| n)
-                    .unwrap_or(v8::undefined(scope).into());
-
-                // all set!
-                retval.set(element.into());
+                let selectors = args.get(0).to_rust_string_lossy(scope);
+                let result = document.borrow().closest(id, selectors.as_str());
+                match result {
+                    Ok(Some(element)) => {
+                        let element = wrap_node(scope, &document, element);
+                        retval.set(element.into());
+                    }
+                    Ok(None) => retval.set(v8::null(scope).into()),
+                    Err(_) => throw_invalid_selector(scope),
+                }
             },
         );
     }
+    set_attribute_methods_to(scope, node);
+    set_query_methods_to(scope, node);
 
-    document
+    node
 }
 
-/// This function sets `document` object into `global`.
-pub fn initialize_dom<'s>(
-    scope: &mut v8::ContextScope<'s, v8::EscapableHandleScope>,
-    global: v8::Local<v8::Object>,
-) {
-    let document = create_document_object(scope);
-    set_property_to(scope, global, "document", document.into());
+/// This function sets the methods to read and modify attributes into the given element object.
+///
+/// Here are major standards on these methods:
+/// - https://dom.spec.whatwg.org/#dom-element-getattribute
+/// - https://dom.spec.whatwg.org/#dom-element-setattribute
+/// - https://dom.spec.whatwg.org/#dom-element-toggleattribute
+fn set_attribute_methods_to<'s>(scope: &mut v8::HandleScope<'s>, node: v8::Local<v8::Object>) {
+    set_function_to(
+        scope,
+        node,
+        "getAttribute",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let name = attribute_name_argument(scope, args.get(0));
+            let value = document.borrow().get_attribute(id, name.as_str());
+            retval.set(to_v8_nullable_string(scope, value));
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "getAttributeNames",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let names = match document.borrow().node(id).node_type {
+                NodeType::Element(ref e) => e.attribute_names(),
+                _ => vec![],
+            };
+            let names = names
+                .iter()
+                .map(|name| v8::String::new(scope, name.as_str()).unwrap().into())
+                .collect::<Vec<v8::Local<v8::Value>>>();
+            retval.set(v8::Array::new_with_elements(scope, names.as_slice()).into());
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "hasAttribute",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let name = attribute_name_argument(scope, args.get(0));
+            let has = document.borrow().get_attribute(id, name.as_str()).is_some();
+            retval.set(v8::Boolean::new(scope, has).into());
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "setAttribute",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         _retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let name = attribute_name_argument(scope, args.get(0));
+            let value = args.get(1).to_rust_string_lossy(scope);
+            let result = document
+                .borrow_mut()
+                .set_attribute(id, name.as_str(), value);
+            set_attribute_result(scope, result, "setAttribute");
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "removeAttribute",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         _retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let name = attribute_name_argument(scope, args.get(0));
+            let result = document.borrow_mut().remove_attribute(id, name.as_str());
+            set_attribute_result(scope, result, "removeAttribute");
+        },
+    );
+    set_function_to(
+        scope,
+        node,
+        "toggleAttribute",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                Some(v) => v,
+                None => return,
+            };
+            let name = attribute_name_argument(scope, args.get(0));
+            let force = if args.get(1).is_undefined() {
+                None
+            } else {
+                Some(args.get(1).boolean_value(scope))
+            };
+            let result = document
+                .borrow_mut()
+                .toggle_attribute(id, name.as_str(), force);
+            match result {
+                Ok(exists) => {
+                    retval.set(v8::Boolean::new(scope, exists).into());
+                    notify_mutation(scope, "toggleAttribute");
+                }
+                Err(e) => throw_dom_exception(scope, e),
+            }
+        },
+    );
 }
 
-// utilities
-// =========
-
-type NodeRefTarget<'a> = &'a mut Box<Node>;
+/// This function sets the properties reflecting attributes into the given element object.
+/// These properties read the attributes of the element whenever they are accessed, so they never return stale values.
+///
+/// Here are major standards on these properties:
+/// - https://dom.spec.whatwg.org/#dom-element-id
+/// - https://dom.spec.whatwg.org/#dom-element-classname
+/// - https://dom.spec.whatwg.org/#dom-element-classlist
+/// - https://dom.spec.whatwg.org/#dom-element-attributes
+/// - https://html.spec.whatwg.org/multipage/dom.html#dom-dataset
+fn set_reflected_attributes_to<'s>(scope: &mut v8::HandleScope<'s>, node: v8::Local<v8::Object>) {
+    {
+        // add `id` property
+        set_accessor_to(
+            scope,
+            node,
+            "id",
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  args: v8::PropertyCallbackArguments,
+                  mut rv: v8::ReturnValue| {
+                let value = reflected_attribute(scope, args.this(), WrapperKind::Node, "id")
+                    .unwrap_or_else(|| v8::String::empty(scope).into());
+                rv.set(value);
+            },
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  value: v8::Local<v8::Value>,
+                  args: v8::PropertyCallbackArguments| {
+                set_reflected_attribute(scope, args.this(), WrapperKind::Node, "id", value);
+            },
+        );
+    }
+    {
+        // add `className` property
+        set_accessor_to(
+            scope,
+            node,
+            "className",
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  args: v8::PropertyCallbackArguments,
+                  mut rv: v8::ReturnValue| {
+                let value = reflected_attribute(scope, args.this(), WrapperKind::Node, "class")
+                    .unwrap_or_else(|| v8::String::empty(scope).into());
+                rv.set(value);
+            },
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  value: v8::Local<v8::Value>,
+                  args: v8::PropertyCallbackArguments| {
+                set_reflected_attribute(scope, args.this(), WrapperKind::Node, "class", value);
+            },
+        );
+    }
+    {
+        // add `classList` property
+        set_accessor_to(
+            scope,
+            node,
+            "classList",
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  args: v8::PropertyCallbackArguments,
+                  mut rv: v8::ReturnValue| {
+                let this = args.this();
+                let (_, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let class_list = create_v8_dom_token_list(scope, id);
+                rv.set(class_list.into());
+            },
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  value: v8::Local<v8::Value>,
+                  args: v8::PropertyCallbackArguments| {
+                // NOTE: `classList` is declared with `[PutForwards=value]`.
+                set_reflected_attribute(scope, args.this(), WrapperKind::Node, "class", value);
+            },
+        );
+    }
+    {
+        // add `attributes` property
+        set_accessor_to(
+            scope,
+            node,
+            "attributes",
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  args: v8::PropertyCallbackArguments,
+                  mut rv: v8::ReturnValue| {
+                let this = args.this();
+                let (document, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let attributes = create_v8_named_node_map(scope, &document, id);
+                rv.set(attributes.into());
+            },
+            move |_scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  _value: v8::Local<v8::Value>,
+                  _args: v8::PropertyCallbackArguments| {},
+        );
+    }
+    {
+        // add `dataset` property
+        set_accessor_to(
+            scope,
+            node,
+            "dataset",
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  args: v8::PropertyCallbackArguments,
+                  mut rv: v8::ReturnValue| {
+                let this = args.this();
+                let (_, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                if let Some(dataset) = create_v8_dataset(scope, id) {
+                    rv.set(dataset);
+                }
+            },
+            move |_scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  _value: v8::Local<v8::Value>,
+                  _args: v8::PropertyCallbackArguments| {},
+        );
+    }
+}
 
-fn set_node_internal_ref<'s>(
+/// This function creates a new `NamedNodeMap` object holding the attributes of the element.
+/// NOTE: the attributes are indexed and named when this object is created, while `length`, `item` and `getNamedItem` see the current attributes.
+///
+/// Here are major standards on this object:
+/// - https://dom.spec.whatwg.org/#interface-namednodemap
+fn create_v8_named_node_map<'s>(
     scope: &mut v8::HandleScope<'s>,
-    node_rust: NodeRefTarget,
-    node_v8: v8::Local<v8::Object>,
-) {
-    let boxed_ref = Box::new(node_rust);
-    let addr = Box::leak(boxed_ref) as *mut NodeRefTarget as *mut c_void;
-    let v8_ext = v8::External::new(scope, addr);
-    let target_node_ref_v8: v8::Local<v8::Value> = v8_ext.into();
-    node_v8.set_internal_field(0, target_node_ref_v8);
+    document: &Rc<RefCell<Document>>,
+    id: NodeId,
+) -> v8::Local<'s, v8::Object> {
+    let map = create_v8_node(scope, WrapperKind::NamedNodeMap);
+    set_node_internal_ref(scope, id, map);
+
+    let names = match document.borrow().node(id).node_type {
+        NodeType::Element(ref e) => e.attribute_names(),
+        _ => vec![],
+    };
+    set_accessor_to(
+        scope,
+        map,
+        "length",
+        move |scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              args: v8::PropertyCallbackArguments,
+              mut rv: v8::ReturnValue| {
+            let this = args.this();
+            let (document, id) = match linked_node(scope, this, WrapperKind::NamedNodeMap) {
+                Some(v) => v,
+                None => return,
+            };
+            let length = match document.borrow().node(id).node_type {
+                NodeType::Element(ref e) => e.attributes.len(),
+                _ => 0,
+            };
+            rv.set(v8::Number::new(scope, length as f64).into());
+        },
+        move |_scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              _value: v8::Local<v8::Value>,
+              _args: v8::PropertyCallbackArguments| {},
+    );
+    set_function_to(
+        scope,
+        map,
+        "item",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::NamedNodeMap) {
+                Some(v) => v,
+                None => return,
+            };
+            let index = unsigned_long_argument(scope, &args, 0);
+            let name = match document.borrow().node(id).node_type {
+                NodeType::Element(ref e) => e.attribute_names().into_iter().nth(index),
+                _ => None,
+            };
+            match name {
+                Some(name) => retval.set(create_v8_attr(scope, id, name.as_str()).into()),
+                None => retval.set(v8::null(scope).into()),
+            }
+        },
+    );
+    set_function_to(
+        scope,
+        map,
+        "getNamedItem",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_node(scope, &args, WrapperKind::NamedNodeMap) {
+                Some(v) => v,
+                None => return,
+            };
+            let name = attribute_name_argument(scope, args.get(0));
+            if document.borrow().get_attribute(id, name.as_str()).is_some() {
+                retval.set(create_v8_attr(scope, id, name.as_str()).into());
+            } else {
+                retval.set(v8::null(scope).into());
+            }
+        },
+    );
+
+    for (i, name) in names.iter().enumerate() {
+        let attr = create_v8_attr(scope, id, name.as_str());
+        map.set_index(scope, i as u32, attr.into());
+        // NOTE: attributes never shadow the members of `NamedNodeMap`.
+        if !matches!(name.as_str(), "length" | "item" | "getNamedItem") {
+            set_constant_to(scope, map, name.as_str(), attr.into());
+        }
+    }
+
+    map
 }
 
-fn to_linked_rust_node<'s>(
+/// This function creates a new `Attr` object, which refers to the attribute of the element with the name.
+///
+/// Here are major standards on this object:
+/// - https://dom.spec.whatwg.org/#interface-attr
+fn create_v8_attr<'s>(
     scope: &mut v8::HandleScope<'s>,
-    node_v8: v8::Local<v8::Object>,
-) -> &'s mut NodeRefTarget<'s> {
-    let node_v8 = node_v8.get_internal_field(scope, 0).unwrap();
-    let node = unsafe { v8::Local::<v8::External>::cast(node_v8) };
-    let node = node.value() as *mut NodeRefTarget;
-    unsafe { &mut *node }
+    id: NodeId,
+    name: &str,
+) -> v8::Local<'s, v8::Object> {
+    let attr = create_wrapper(scope, WrapperKind::Attr, AttrField::Count as usize);
+    let owner = v8::Number::new(scope, id.index() as f64);
+    attr.set_internal_field(AttrField::OwnerElement as usize, owner.into());
+    let name = v8::String::new(scope, name).unwrap();
+    attr.set_internal_field(AttrField::Name as usize, name.into());
+
+    set_constant_to(scope, attr, "name", name.into());
+    set_constant_to(scope, attr, "localName", name.into());
+    // NOTE: puppy doesn't support namespaces of attributes.
+    let null = v8::null(scope);
+    set_constant_to(scope, attr, "namespaceURI", null.into());
+    set_constant_to(scope, attr, "prefix", null.into());
+
+    set_accessor_to(
+        scope,
+        attr,
+        "value",
+        move |scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              args: v8::PropertyCallbackArguments,
+              mut rv: v8::ReturnValue| {
+            let this = args.this();
+            let name = match attr_name(scope, this) {
+                Some(name) => name,
+                None => return,
+            };
+            // NOTE: the value of a removed attribute is kept in the spec, but it's empty here.
+            let value = reflected_attribute(scope, this, WrapperKind::Attr, name.as_str());
+            if let Some(value) = value {
+                rv.set(value);
+            }
+        },
+        move |scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              value: v8::Local<v8::Value>,
+              args: v8::PropertyCallbackArguments| {
+            let this = args.this();
+            let name = match attr_name(scope, this) {
+                Some(name) => name,
+                None => return,
+            };
+            set_reflected_attribute(scope, this, WrapperKind::Attr, name.as_str(), value);
+        },
+    );
+    set_accessor_to(
+        scope,
+        attr,
+        "ownerElement",
+        move |scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              args: v8::PropertyCallbackArguments,
+              mut rv: v8::ReturnValue| {
+            let this = args.this();
+            let (document, id) = match linked_node(scope, this, WrapperKind::Attr) {
+                Some(v) => v,
+                None => return,
+            };
+            let element = wrap_node(scope, &document, id);
+            rv.set(element.into());
+        },
+        move |_scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              _value: v8::Local<v8::Value>,
+              _args: v8::PropertyCallbackArguments| {},
+    );
+
+    attr
 }
 
-fn to_v8_node<'s>(
+/// This function creates a new `DOMTokenList` object for the classes of the element.
+///
+/// Here are major standards on this object:
+/// - https://dom.spec.whatwg.org/#interface-domtokenlist
+fn create_v8_dom_token_list<'s>(
     scope: &mut v8::HandleScope<'s>,
-    node_rust: NodeRefTarget,
+    id: NodeId,
 ) -> v8::Local<'s, v8::Object> {
-    // create new node instance
-    let node_v8 = create_v8_node(scope);
+    let list = create_v8_node(scope, WrapperKind::DOMTokenList);
+    set_node_internal_ref(scope, id, list);
 
-    // set a reference to Node into the internal field
-    set_node_internal_ref(scope, node_rust, node_v8);
+    set_accessor_to(
+        scope,
+        list,
+        "length",
+        move |scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              args: v8::PropertyCallbackArguments,
+              mut rv: v8::ReturnValue| {
+            if let Some(tokens) = class_list(scope, args.this()) {
+                rv.set(v8::Number::new(scope, tokens.len() as f64).into());
+            }
+        },
+        move |_scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              _value: v8::Local<v8::Value>,
+              _args: v8::PropertyCallbackArguments| {},
+    );
+    set_accessor_to(
+        scope,
+        list,
+        "value",
+        move |scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              args: v8::PropertyCallbackArguments,
+              mut rv: v8::ReturnValue| {
+            let value = reflected_attribute(scope, args.this(), WrapperKind::DOMTokenList, "class")
+                .unwrap_or_else(|| v8::String::empty(scope).into());
+            rv.set(value);
+        },
+        move |scope: &mut v8::HandleScope,
+              _key: v8::Local<v8::Name>,
+              value: v8::Local<v8::Value>,
+              args: v8::PropertyCallbackArguments| {
+            set_reflected_attribute(
+                scope,
+                args.this(),
+                WrapperKind::DOMTokenList,
+                "class",
+                value,
+            );
+        },
+    );
+    set_function_to(
+        scope,
+        list,
+        "item",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let index = unsigned_long_argument(scope, &args, 0);
+            let token = class_list(scope, args.this()).and_then(|t| t.into_iter().nth(index));
+            retval.set(to_v8_nullable_string(scope, token));
+        },
+    );
+    set_function_to(
+        scope,
+        list,
+        "contains",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let token = args.get(0).to_rust_string_lossy(scope);
+            let tokens = class_list(scope, args.this()).unwrap_or_default();
+            let contained = tokens.iter().any(|t| *t == token);
+            retval.set(v8::Boolean::new(scope, contained).into());
+        },
+    );
+    set_function_to(
+        scope,
+        list,
+        "add",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         _retval: v8::ReturnValue| {
+            let tokens = match token_arguments(scope, &args) {
+                Some(tokens) => tokens,
+                None => return,
+            };
+            update_class_list(scope, args.this(), "add", |list| {
+                for token in tokens {
+                    if !list.contains(&token) {
+                        list.push(token);
+                    }
+                }
+            });
+        },
+    );
+    set_function_to(
+        scope,
+        list,
+        "remove",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         _retval: v8::ReturnValue| {
+            let tokens = match token_arguments(scope, &args) {
+                Some(tokens) => tokens,
+                None => return,
+            };
+            update_class_list(scope, args.this(), "remove", |list| {
+                list.retain(|t| !tokens.contains(t))
+            });
+        },
+    );
+    set_function_to(
+        scope,
+        list,
+        "toggle",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let token = args.get(0).to_rust_string_lossy(scope);
+            if let Err(e) = validate_token(token.as_str()) {
+                throw_dom_exception(scope, e);
+                return;
+            }
+            let force = if args.get(1).is_undefined() {
+                None
+            } else {
+                Some(args.get(1).boolean_value(scope))
+            };
+            let tokens = class_list(scope, args.this()).unwrap_or_default();
+            let exists = tokens.contains(&token);
+            let result = match (exists, force) {
+                (true, None) | (true, Some(false)) => {
+                    update_class_list(scope, args.this(), "toggle", |list| {
+                        list.retain(|t| *t != token)
+                    });
+                    false
+                }
+                (false, None) | (false, Some(true)) => {
+                    update_class_list(scope, args.this(), "toggle", |list| list.push(token));
+                    true
+                }
+                (exists, Some(_)) => exists,
+            };
+            retval.set(v8::Boolean::new(scope, result).into());
+        },
+    );
+    set_function_to(
+        scope,
+        list,
+        "replace",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let tokens = match token_arguments(scope, &args) {
+                Some(tokens) if tokens.len() >= 2 => tokens,
+                Some(_) => {
+                    throw_error(scope, "replace requires 2 arguments");
+                    return;
+                }
+                None => return,
+            };
+            let (token, new_token) = (tokens[0].clone(), tokens[1].clone());
+            let list = class_list(scope, args.this()).unwrap_or_default();
+            if !list.contains(&token) {
+                retval.set(v8::Boolean::new(scope, false).into());
+                return;
+            }
+            update_class_list(scope, args.this(), "replace", |list| {
+                let replaced = list
+                    .iter()
+                    .map(|t| if *t == token { &new_token } else { t })
+                    .cloned()
+                    .collect::<Vec<_>>();
+                *list = parse_ordered_set(replaced.join(" ").as_str());
+            });
+            retval.set(v8::Boolean::new(scope, true).into());
+        },
+    );
 
-    // all set :-)
-    node_v8
+    list
 }
 
-fn map_mut<T, F>(node: NodeRefTarget, f: &mut F) -> Vec<T>
-where
-    F: FnMut(&mut Box<Node>) -> T,
-{
-    let mut v: Vec<T> = vec![];
+/// This function creates a new `DOMStringMap` object for the `data-*` attributes of the element.
+/// The object is a `Proxy`, whose traps read and modify the attributes of the element.
+///
+/// Here are major standards on this object:
+/// - https://html.spec.whatwg.org/multipage/dom.html#domstringmap
+fn create_v8_dataset<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    id: NodeId,
+) -> Option<v8::Local<'s, v8::Value>> {
+    let target = create_v8_node(scope, WrapperKind::Dataset);
+    set_node_internal_ref(scope, id, target);
 
-    for child in &mut node.children {
-        v.push(f(child));
-        v.extend(map_mut(child, f));
-    }
+    let handler = v8::Object::new(scope);
+    set_function_to(
+        scope,
+        handler,
+        "get",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            if let Some(name) = dataset_attribute_argument(scope, &args) {
+                let target = args.get(0).to_object(scope).unwrap();
+                if let Some(value) =
+                    reflected_attribute(scope, target, WrapperKind::Dataset, name.as_str())
+                {
+                    retval.set(value);
+                }
+            }
+        },
+    );
+    set_function_to(
+        scope,
+        handler,
+        "has",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let exists = match dataset_attribute_argument(scope, &args) {
+                Some(name) => {
+                    let target = args.get(0).to_object(scope).unwrap();
+                    reflected_attribute(scope, target, WrapperKind::Dataset, name.as_str())
+                        .is_some()
+                }
+                None => false,
+            };
+            retval.set(v8::Boolean::new(scope, exists).into());
+        },
+    );
+    set_function_to(
+        scope,
+        handler,
+        "set",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let property = args.get(1).to_rust_string_lossy(scope);
+            let name = match dataset_attribute_name(property.as_str()) {
+                Some(name) => name,
+                None => {
+                    throw_dom_exception(
+                        scope,
+                        DOMException::SyntaxError(
+                            "the name contains '-' followed by a lowercase letter",
+                        ),
+                    );
+                    return;
+                }
+            };
+            let target = args.get(0).to_object(scope).unwrap();
+            let succeeded = set_reflected_attribute(
+                scope,
+                target,
+                WrapperKind::Dataset,
+                name.as_str(),
+                args.get(2),
+            );
+            retval.set(v8::Boolean::new(scope, succeeded).into());
+        },
+    );
+    set_function_to(
+        scope,
+        handler,
+        "deleteProperty",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            if let Some(name) = dataset_attribute_argument(scope, &args) {
+                let target = args.get(0).to_object(scope).unwrap();
+                let (document, id) = match linked_node(scope, target, WrapperKind::Dataset) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let result = document.borrow_mut().remove_attribute(id, name.as_str());
+                set_attribute_result(scope, result, "deleter of dataset");
+            }
+            retval.set(v8::Boolean::new(scope, true).into());
+        },
+    );
+    set_function_to(
+        scope,
+        handler,
+        "ownKeys",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let target = args.get(0).to_object(scope).unwrap();
+            let (document, id) = match linked_node(scope, target, WrapperKind::Dataset) {
+                Some(v) => v,
+                None => return,
+            };
+            let names = match document.borrow().node(id).node_type {
+                NodeType::Element(ref e) => e.attribute_names(),
+                _ => vec![],
+            };
+            let properties = names
+                .iter()
+                .filter_map(|name| dataset_property_name(name.as_str()))
+                .map(|property| v8::String::new(scope, property.as_str()).unwrap().into())
+                .collect::<Vec<v8::Local<v8::Value>>>();
+            retval.set(v8::Array::new_with_elements(scope, properties.as_slice()).into());
+        },
+    );
+    set_function_to(
+        scope,
+        handler,
+        "getOwnPropertyDescriptor",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let name = match dataset_attribute_argument(scope, &args) {
+                Some(name) => name,
+                None => return,
+            };
+            let target = args.get(0).to_object(scope).unwrap();
+            if let Some(value) =
+                reflected_attribute(scope, target, WrapperKind::Dataset, name.as_str())
+            {
+                let descriptor = v8::Object::new(scope);
+                set_property_to(scope, descriptor, "value", value);
+                let t = v8::Boolean::new(scope, true);
+                set_property_to(scope, descriptor, "writable", t.into());
+                set_property_to(scope, descriptor, "enumerable", t.into());
+                set_property_to(scope, descriptor, "configurable", t.into());
+                retval.set(descriptor.into());
+            }
+        },
+    );
+
+    v8::Proxy::new(scope, target, handler).map(|proxy| proxy.into())
+}
+
+/// This function creates a new `CharacterData` object, which is a `Text` object if `is_text` is true.
+///
+/// Here are major standards on this object:
+/// - https://dom.spec.whatwg.org/#interface-characterdata
+/// - https://dom.spec.whatwg.org/#interface-text
+fn to_v8_character_data<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    id: NodeId,
+    is_text: bool,
+) -> v8::Local<'s, v8::Object> {
+    let node = to_v8_node(scope, id);
 
-    v.push(f(node));
-    v
+    // set properties
+    {
+        // add `data` property
+        set_accessor_to(
+            scope,
+            node,
+            "data",
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  args: v8::PropertyCallbackArguments,
+                  mut rv: v8::ReturnValue| {
+                let this = args.this();
+                let (document, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+
+                let data = match document.borrow().node(id).node_type.character_data() {
+                    Some(c) => c.data().to_string(),
+                    None => return,
+                };
+                let ret = v8::String::new(scope, data.as_str()).unwrap();
+                rv.set(ret.into());
+            },
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  value: v8::Local<v8::Value>,
+                  args: v8::PropertyCallbackArguments| {
+                let this = args.this();
+                let (document, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+
+                let data = value.to_rust_string_lossy(scope);
+                let result = document.borrow_mut().modify_character_data(id, |c| {
+                    c.set_data(data);
+                    Ok(())
+                });
+                if result.is_ok() {
+                    notify_mutation(scope, "setter of data");
+                }
+            },
+        );
+    }
+    {
+        // add `length` property
+        set_accessor_to(
+            scope,
+            node,
+            "length",
+            move |scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  args: v8::PropertyCallbackArguments,
+                  mut rv: v8::ReturnValue| {
+                let this = args.this();
+                let (document, id) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+
+                let length = match document.borrow().node(id).node_type.character_data() {
+                    Some(c) => c.length(),
+                    None => return,
+                };
+                rv.set(v8::Number::new(scope, length as f64).into());
+            },
+            move |_scope: &mut v8::HandleScope,
+                  _key: v8::Local<v8::Name>,
+                  _value: v8::Local<v8::Value>,
+                  _args: v8::PropertyCallbackArguments| {},
+        );
+    }
+    {
+        // add methods to read and modify the data
+        // standard: https://dom.spec.whatwg.org/#dom-characterdata-substringdata
+        set_function_to(
+            scope,
+            node,
+            "substringData",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let offset = unsigned_long_argument(scope, &args, 0);
+                let count = unsigned_long_argument(scope, &args, 1);
+                let result = match document.borrow().node(id).node_type.character_data() {
+                    Some(c) => c.substring_data(offset, count),
+                    None => return,
+                };
+                match result {
+                    Ok(data) => {
+                        let data = v8::String::new(scope, data.as_str()).unwrap();
+                        retval.set(data.into());
+                    }
+                    Err(e) => throw_dom_exception(scope, e),
+                }
+            },
+        );
+        set_function_to(
+            scope,
+            node,
+            "appendData",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             _retval: v8::ReturnValue| {
+                let data = args.get(0).to_rust_string_lossy(scope);
+                modify_character_data(scope, &args, "appendData", |c| {
+                    c.append_data(data.as_str());
+                    Ok(())
+                });
+            },
+        );
+        set_function_to(
+            scope,
+            node,
+            "insertData",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             _retval: v8::ReturnValue| {
+                let offset = unsigned_long_argument(scope, &args, 0);
+                let data = args.get(1).to_rust_string_lossy(scope);
+                modify_character_data(scope, &args, "insertData", |c| {
+                    c.insert_data(offset, data.as_str())
+                });
+            },
+        );
+        set_function_to(
+            scope,
+            node,
+            "deleteData",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             _retval: v8::ReturnValue| {
+                let offset = unsigned_long_argument(scope, &args, 0);
+                let count = unsigned_long_argument(scope, &args, 1);
+                modify_character_data(scope, &args, "deleteData", |c| c.delete_data(offset, count));
+            },
+        );
+        set_function_to(
+            scope,
+            node,
+            "replaceData",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             _retval: v8::ReturnValue| {
+                let offset = unsigned_long_argument(scope, &args, 0);
+                let count = unsigned_long_argument(scope, &args, 1);
+                let data = args.get(2).to_rust_string_lossy(scope);
+                modify_character_data(scope, &args, "replaceData", |c| {
+                    c.replace_data(offset, count, data.as_str())
+                });
+            },
+        );
+    }
+    if is_text {
+        // add `splitText` method
+        // standard: https://dom.spec.whatwg.org/#dom-text-splittext
+        set_function_to(
+            scope,
+            node,
+            "splitText",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let (document, id) = match this_node(scope, &args, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let offset = unsigned_long_argument(scope, &args, 0);
+                let result = document.borrow_mut().split_text(id, offset);
+                set_mutation_result(scope, &document, result, &mut retval, "splitText");
+            },
+        );
+    }
+
+    node
+}
+
+/// This function creates a new `TreeWalker` object or `NodeIterator` object, which has `root`, `whatToShow` and `filter` properties.
+/// The state of the traversal is stored in internal fields; see `TraversalField`.
+///
+/// Here are major standards on these objects:
+/// - https://dom.spec.whatwg.org/#interface-treewalker
+/// - https://dom.spec.whatwg.org/#interface-nodeiterator
+fn create_traversal_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    document: &Rc<RefCell<Document>>,
+    root: NodeId,
+    what_to_show: WhatToShow,
+    filter: v8::Local<v8::Value>,
+) -> v8::Local<'s, v8::Object> {
+    let traversal = create_wrapper(
+        scope,
+        WrapperKind::Traversal,
+        TraversalField::Count as usize,
+    );
+    set_traversal_field(scope, traversal, TraversalField::Root, root.index() as f64);
+    set_traversal_field(
+        scope,
+        traversal,
+        TraversalField::Current,
+        root.index() as f64,
+    );
+    let pointer_before_reference = v8::Boolean::new(scope, true);
+    traversal.set_internal_field(
+        TraversalField::PointerBeforeReference as usize,
+        pointer_before_reference.into(),
+    );
+    let active = v8::Boolean::new(scope, false);
+    traversal.set_internal_field(TraversalField::Active as usize, active.into());
+
+    let root = wrap_node(scope, document, root);
+    set_constant_to(scope, traversal, "root", root.into());
+    let what_to_show = v8::Number::new(scope, what_to_show.0 as f64);
+    set_constant_to(scope, traversal, "whatToShow", what_to_show.into());
+    set_constant_to(scope, traversal, "filter", filter);
+
+    traversal
+}
+
+/// This function adds methods and properties of `TreeWalker` into the given traversal object.
+fn set_tree_walker_methods_to<'s>(scope: &mut v8::HandleScope<'s>, walker: v8::Local<v8::Object>) {
+    set_accessor_to(
+        scope,
+        walker,
+        "currentNode",
+        |scope: &mut v8::HandleScope,
+         _key: v8::Local<v8::Name>,
+         args: v8::PropertyCallbackArguments,
+         mut rv: v8::ReturnValue| {
+            let this = args.this();
+            let (document, _) = match linked_node(scope, this, WrapperKind::Traversal) {
+                Some(v) => v,
+                None => return,
+            };
+            let current = match traversal_node(scope, this, TraversalField::Current) {
+                Some(current) => current,
+                None => return,
+            };
+            let node = wrap_node(scope, &document, current);
+            rv.set(node.into());
+        },
+        |scope: &mut v8::HandleScope,
+         _key: v8::Local<v8::Name>,
+         value: v8::Local<v8::Value>,
+         args: v8::PropertyCallbackArguments| {
+            let this = args.this();
+            let (document, _) = match linked_node(scope, this, WrapperKind::Traversal) {
+                Some(v) => v,
+                None => return,
+            };
+            if let Some(node) = node_argument(scope, &document, value) {
+                set_traversal_field(scope, this, TraversalField::Current, node.index() as f64);
+            }
+        },
+    );
+    set_function_to(
+        scope,
+        walker,
+        "parentNode",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            walk_tree(scope, &args, &mut retval, TreeWalker::parent_node);
+        },
+    );
+    set_function_to(
+        scope,
+        walker,
+        "firstChild",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            walk_tree(scope, &args, &mut retval, TreeWalker::first_child);
+        },
+    );
+    set_function_to(
+        scope,
+        walker,
+        "lastChild",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            walk_tree(scope, &args, &mut retval, TreeWalker::last_child);
+        },
+    );
+    set_function_to(
+        scope,
+        walker,
+        "previousSibling",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            walk_tree(scope, &args, &mut retval, TreeWalker::previous_sibling);
+        },
+    );
+    set_function_to(
+        scope,
+        walker,
+        "nextSibling",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            walk_tree(scope, &args, &mut retval, TreeWalker::next_sibling);
+        },
+    );
+    set_function_to(
+        scope,
+        walker,
+        "previousNode",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            walk_tree(scope, &args, &mut retval, TreeWalker::previous_node);
+        },
+    );
+    set_function_to(
+        scope,
+        walker,
+        "nextNode",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            walk_tree(scope, &args, &mut retval, TreeWalker::next_node);
+        },
+    );
+}
+
+/// This function adds methods and properties of `NodeIterator` into the given traversal object.
+fn set_node_iterator_methods_to<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    iterator: v8::Local<v8::Object>,
+) {
+    set_accessor_to(
+        scope,
+        iterator,
+        "referenceNode",
+        |scope: &mut v8::HandleScope,
+         _key: v8::Local<v8::Name>,
+         args: v8::PropertyCallbackArguments,
+         mut rv: v8::ReturnValue| {
+            let this = args.this();
+            let (document, _) = match linked_node(scope, this, WrapperKind::Traversal) {
+                Some(v) => v,
+                None => return,
+            };
+            let reference = match traversal_node(scope, this, TraversalField::Current) {
+                Some(reference) => reference,
+                None => return,
+            };
+            let node = wrap_node(scope, &document, reference);
+            rv.set(node.into());
+        },
+        |_scope: &mut v8::HandleScope,
+         _key: v8::Local<v8::Name>,
+         _value: v8::Local<v8::Value>,
+         _args: v8::PropertyCallbackArguments| {},
+    );
+    set_accessor_to(
+        scope,
+        iterator,
+        "pointerBeforeReferenceNode",
+        |scope: &mut v8::HandleScope,
+         _key: v8::Local<v8::Name>,
+         args: v8::PropertyCallbackArguments,
+         mut rv: v8::ReturnValue| {
+            let pointer_before_reference =
+                traversal_field(scope, args.this(), TraversalField::PointerBeforeReference);
+            if let Some(pointer_before_reference) = pointer_before_reference {
+                rv.set(pointer_before_reference);
+            }
+        },
+        |_scope: &mut v8::HandleScope,
+         _key: v8::Local<v8::Name>,
+         _value: v8::Local<v8::Value>,
+         _args: v8::PropertyCallbackArguments| {},
+    );
+    set_function_to(
+        scope,
+        iterator,
+        "previousNode",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            iterate_nodes(scope, &args, &mut retval, NodeIterator::previous_node);
+        },
+    );
+    set_function_to(
+        scope,
+        iterator,
+        "nextNode",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            iterate_nodes(scope, &args, &mut retval, NodeIterator::next_node);
+        },
+    );
+    // `detach` does nothing as the standard says.
+    set_function_to(
+        scope,
+        iterator,
+        "detach",
+        |_scope: &mut v8::HandleScope,
+         _args: v8::FunctionCallbackArguments,
+         _retval: v8::ReturnValue| {},
+    );
+}
+
+/// This function sets `NodeFilter` object, which has constants for node filters, into `global`.
+/// standard: https://dom.spec.whatwg.org/#interface-nodefilter
+fn initialize_node_filter<'s>(scope: &mut v8::HandleScope<'s>, global: v8::Local<v8::Object>) {
+    let node_filter = create_object_under(scope, global, "NodeFilter");
+    let constants = [
+        ("FILTER_ACCEPT", 1),
+        ("FILTER_REJECT", 2),
+        ("FILTER_SKIP", 3),
+        ("SHOW_ALL", WhatToShow::ALL.0),
+        ("SHOW_ELEMENT", WhatToShow::ELEMENT.0),
+        ("SHOW_ATTRIBUTE", WhatToShow::ATTRIBUTE.0),
+        ("SHOW_TEXT", WhatToShow::TEXT.0),
+        ("SHOW_CDATA_SECTION", WhatToShow::CDATA_SECTION.0),
+        (
+            "SHOW_PROCESSING_INSTRUCTION",
+            WhatToShow::PROCESSING_INSTRUCTION.0,
+        ),
+        ("SHOW_COMMENT", WhatToShow::COMMENT.0),
+        ("SHOW_DOCUMENT", WhatToShow::DOCUMENT.0),
+        ("SHOW_DOCUMENT_TYPE", WhatToShow::DOCUMENT_TYPE.0),
+        ("SHOW_DOCUMENT_FRAGMENT", WhatToShow::DOCUMENT_FRAGMENT.0),
+    ];
+    for (name, value) in constants.iter() {
+        let value = v8::Number::new(scope, *value as f64);
+        set_constant_to(scope, node_filter, name, value.into());
+    }
+}
+
+/// This function sets `MutationObserver` constructor into `global`.
+///
+/// Here are major standards on this object:
+/// - https://dom.spec.whatwg.org/#interface-mutationobserver
+/// - https://dom.spec.whatwg.org/#interface-mutationrecord
+fn initialize_mutation_observer<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    global: v8::Local<v8::Object>,
+) {
+    // NOTE: `new MutationObserver(callback)` returns the object this function returns.
+    set_function_to(
+        scope,
+        global,
+        "MutationObserver",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let callback = match v8::Local::<v8::Function>::try_from(args.get(0)) {
+                Ok(callback) => callback,
+                Err(_) => {
+                    let message = v8::String::new(scope, "the callback is not a function").unwrap();
+                    let exception = v8::Exception::type_error(scope, message);
+                    scope.throw_exception(exception);
+                    return;
+                }
+            };
+            let document = match JavaScriptRuntime::document(scope) {
+                Some(document) => document,
+                None => {
+                    error!("failed to get document reference; document is None");
+                    return;
+                }
+            };
+            let id = document.borrow_mut().create_mutation_observer();
+            let observer = create_v8_mutation_observer(scope, id);
+
+            let state = JavaScriptRuntime::state(scope);
+            let observer_global = v8::Global::new(scope, observer);
+            let callback = v8::Global::new(scope, callback);
+            state
+                .borrow_mut()
+                .mutation_observers
+                .push((id, observer_global, callback));
+            retval.set(observer.into());
+        },
+    );
+}
+
+fn create_v8_mutation_observer<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    id: MutationObserverId,
+) -> v8::Local<'s, v8::Object> {
+    let observer = create_wrapper(scope, WrapperKind::MutationObserver, OBSERVER_FIELD + 1);
+    let id_v8 = v8::Number::new(scope, id.index() as f64);
+    observer.set_internal_field(OBSERVER_FIELD, id_v8.into());
+
+    set_function_to(
+        scope,
+        observer,
+        "observe",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         _retval: v8::ReturnValue| {
+            let (document, id) = match this_mutation_observer(scope, &args) {
+                Some(v) => v,
+                None => return,
+            };
+            let target = match node_argument(scope, &document, args.get(0)) {
+                Some(target) => target,
+                None => return,
+            };
+            let options = to_mutation_observer_init(scope, args.get(1));
+            let result = document.borrow_mut().observe(id, target, options);
+            if let Err(e) = result {
+                let message = v8::String::new(scope, e.to_string().as_str()).unwrap();
+                let exception = v8::Exception::type_error(scope, message);
+                scope.throw_exception(exception);
+            }
+        },
+    );
+    set_function_to(
+        scope,
+        observer,
+        "disconnect",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         _retval: v8::ReturnValue| {
+            if let Some((document, id)) = this_mutation_observer(scope, &args) {
+                document.borrow_mut().disconnect(id);
+            }
+        },
+    );
+    set_function_to(
+        scope,
+        observer,
+        "takeRecords",
+        |scope: &mut v8::HandleScope,
+         args: v8::FunctionCallbackArguments,
+         mut retval: v8::ReturnValue| {
+            let (document, id) = match this_mutation_observer(scope, &args) {
+                Some(v) => v,
+                None => return,
+            };
+            let records = document.borrow_mut().take_records(id);
+            let records = to_v8_mutation_records(scope, &document, records);
+            retval.set(records.into());
+        },
+    );
+
+    observer
+}
+
+/// This function creates a new `Document` object.
+///
+/// Here are major standards on this object:
+/// - https://dom.spec.whatwg.org/#interface-document
+/// - https://html.spec.whatwg.org/multipage/dom.html#the-document-object
+fn create_document_object<'s>(scope: &mut v8::HandleScope<'s>) -> v8::Local<'s, v8::Object> {
+    // NOTE: the document node is always the first node in `Document`; see `Document::root`.
+    let document = to_v8_node(scope, NodeId::from_index(0));
+    set_query_methods_to(scope, document);
+    set_document_metadata_to(scope, document);
+
+    // set properties
+    {
+        // add `all` property (too old though!)
+        // standard: https://dom.spec.whatwg.org/#dom-document-createelement
+        set_accessor_to(
+            scope,
+            document,
+            "all",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                // get puppy's document object
+                let document = match JavaScriptRuntime::document(scope) {
+                    Some(_document) => _document,
+                    None => {
+                        error!("failed to get document reference; document is None");
+                        return;
+                    }
+                };
+
+                // get all elements
+                let elements = {
+                    let document = document.borrow();
+                    document.get_elements_by_tag_name(document.root(), "*")
+                };
+                let all = to_v8_array(scope, &document, elements);
+
+                // all set!
+                rv.set(all.into());
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+    }
+    {
+        // `getElementById` property
+        set_function_to(
+            scope,
+            document,
+            "getElementById",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let id = args
+                    .get(0)
+                    .to_string(scope)
+                    .unwrap()
+                    .to_rust_string_lossy(scope);
+                // get puppy's document object
+                let document = match JavaScriptRuntime::document(scope) {
+                    Some(_document) => _document,
+                    None => {
+                        error!("failed to get document reference; document is None");
+                        return;
+                    }
+                };
+
+                // find the first element with the ID in tree order
+                let found = {
+                    let document = document.borrow();
+                    document.descendants(document.root()).find_map(|node_id| {
+                        match document.node(node_id).node_type {
+                            NodeType::Element(ref e)
+                                if e.id().map(|eid| eid.to_string() == id).unwrap_or(false) =>
+                            {
+                                Some((node_id, e.tag_name.clone()))
+                            }
+                            _ => None,
+                        }
+                    })
+                };
+                let element: v8::Local<v8::Value> = match found {
+                    Some((node_id, tag_name)) => {
+                        to_v8_element(scope, tag_name.as_str(), node_id).into()
+                    }
+                    None => v8::undefined(scope).into(),
+                };
+
+                // all set!
+                retval.set(element.into());
+            },
+        );
+    }
+    {
+        // `createElement`, `createTextNode` and other factory methods
+        // standard: https://dom.spec.whatwg.org/#dom-document-createelement
+        set_function_to(
+            scope,
+            document,
+            "createElement",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let this = args.this();
+                let (document, _) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let tag_name = args.get(0).to_rust_string_lossy(scope);
+                if !is_valid_name(&tag_name) {
+                    throw_dom_exception(
+                        scope,
+                        DOMException::InvalidCharacterError("the tag name is not a valid name"),
+                    );
+                    return;
+                }
+                // NOTE: puppy handles HTML documents only, so the tag name is always ASCII-lowercased.
+                let tag_name = tag_name.to_ascii_lowercase();
+                let id = document
+                    .borrow_mut()
+                    .create_node(NodeType::Element(Element {
+                        tag_name,
+                        attributes: AttrMap::new(),
+                    }));
+                let element = wrap_node(scope, &document, id);
+                retval.set(element.into());
+            },
+        );
+        set_function_to(
+            scope,
+            document,
+            "createTextNode",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let this = args.this();
+                let (document, _) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let data = args.get(0).to_rust_string_lossy(scope);
+                let id = document
+                    .borrow_mut()
+                    .create_node(NodeType::Text(Text { data }));
+                let text = wrap_node(scope, &document, id);
+                retval.set(text.into());
+            },
+        );
+        set_function_to(
+            scope,
+            document,
+            "createComment",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let this = args.this();
+                let (document, _) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let data = args.get(0).to_rust_string_lossy(scope);
+                let id = document
+                    .borrow_mut()
+                    .create_node(NodeType::Comment(Comment { data }));
+                let comment = wrap_node(scope, &document, id);
+                retval.set(comment.into());
+            },
+        );
+        set_function_to(
+            scope,
+            document,
+            "createDocumentFragment",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let this = args.this();
+                let (document, _) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let id = document
+                    .borrow_mut()
+                    .create_node(NodeType::DocumentFragment);
+                let fragment = wrap_node(scope, &document, id);
+                retval.set(fragment.into());
+            },
+        );
+        set_function_to(
+            scope,
+            document,
+            "createProcessingInstruction",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let this = args.this();
+                let (document, _) = match linked_node(scope, this, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let target = args.get(0).to_rust_string_lossy(scope);
+                let data = args.get(1).to_rust_string_lossy(scope);
+                if !is_valid_name(&target) {
+                    throw_dom_exception(
+                        scope,
+                        DOMException::InvalidCharacterError("the target is not a valid name"),
+                    );
+                    return;
+                }
+                if data.contains("?>") {
+                    throw_dom_exception(
+                        scope,
+                        DOMException::InvalidCharacterError("the data contains \"?>\""),
+                    );
+                    return;
+                }
+                let id = document
+                    .borrow_mut()
+                    .create_node(NodeType::ProcessingInstruction(ProcessingInstruction {
+                        target,
+                        data,
+                    }));
+                let instruction = wrap_node(scope, &document, id);
+                retval.set(instruction.into());
+            },
+        );
+    }
+    {
+        // `createTreeWalker` and `createNodeIterator` property
+        // standard: https://dom.spec.whatwg.org/#dom-document-createtreewalker
+        set_function_to(
+            scope,
+            document,
+            "createTreeWalker",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let (document, _) = match this_node(scope, &args, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let root = match node_argument(scope, &document, args.get(0)) {
+                    Some(root) => root,
+                    None => return,
+                };
+                let what_to_show = what_to_show_argument(scope, &args, 1);
+                let walker =
+                    create_traversal_object(scope, &document, root, what_to_show, args.get(2));
+                set_tree_walker_methods_to(scope, walker);
+                retval.set(walker.into());
+            },
+        );
+        set_function_to(
+            scope,
+            document,
+            "createNodeIterator",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             mut retval: v8::ReturnValue| {
+                let (document, _) = match this_node(scope, &args, WrapperKind::Node) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let root = match node_argument(scope, &document, args.get(0)) {
+                    Some(root) => root,
+                    None => return,
+                };
+                let what_to_show = what_to_show_argument(scope, &args, 1);
+                let iterator =
+                    create_traversal_object(scope, &document, root, what_to_show, args.get(2));
+                set_node_iterator_methods_to(scope, iterator);
+                retval.set(iterator.into());
+            },
+        );
+    }
+    {
+        // `write` and `writeln` property
+        // standard: https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-document-write
+        //
+        // NOTE: the written markup is parsed after the running script finishes, not immediately as the standard says.
+        // Also, the standard replaces the whole document if `write` is called after the document is parsed, but puppy ignores such calls.
+        set_function_to(
+            scope,
+            document,
+            "write",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             _retval: v8::ReturnValue| {
+                let markup: String = (0..args.length())
+                    .map(|i| args.get(i).to_rust_string_lossy(scope))
+                    .collect();
+                if !JavaScriptRuntime::write_markup(scope, markup.as_str()) {
+                    error!("document.write is ignored; no insertion point exists");
+                }
+            },
+        );
+        set_function_to(
+            scope,
+            document,
+            "writeln",
+            |scope: &mut v8::HandleScope,
+             args: v8::FunctionCallbackArguments,
+             _retval: v8::ReturnValue| {
+                let mut markup: String = (0..args.length())
+                    .map(|i| args.get(i).to_rust_string_lossy(scope))
+                    .collect();
+                markup.push('\n');
+                if !JavaScriptRuntime::write_markup(scope, markup.as_str()) {
+                    error!("document.writeln is ignored; no insertion point exists");
+                }
+            },
+        );
+    }
+
+    document
+}
+
+/// This function sets the properties on the metadata of the document into the given `Document` object.
+///
+/// Here are major standards on these properties:
+/// - https://html.spec.whatwg.org/multipage/dom.html#the-document-object
+/// - https://dom.spec.whatwg.org/#interface-document
+fn set_document_metadata_to<'s>(scope: &mut v8::HandleScope<'s>, document: v8::Local<v8::Object>) {
+    {
+        // add `title` property
+        set_accessor_to(
+            scope,
+            document,
+            "title",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                if let Some(title) = document_string(scope, |d| d.title()) {
+                    rv.set(title);
+                }
+            },
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {
+                let document = match JavaScriptRuntime::document(scope) {
+                    Some(document) => document,
+                    None => {
+                        error!("failed to get document reference; document is None");
+                        return;
+                    }
+                };
+                let title = value.to_rust_string_lossy(scope);
+                let result = document.borrow_mut().set_title(title);
+                match result {
+                    Ok(()) => notify_mutation(scope, "setter of title"),
+                    Err(e) => throw_dom_exception(scope, e),
+                }
+            },
+        );
+    }
+    {
+        // add `head` and `body` properties
+        set_accessor_to(
+            scope,
+            document,
+            "head",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                if let Some(head) = document_element_with(scope, |d| d.head()) {
+                    rv.set(head);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+        // TODO (enhancement): support the setter of `body`.
+        set_accessor_to(
+            scope,
+            document,
+            "body",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                if let Some(body) = document_element_with(scope, |d| d.body()) {
+                    rv.set(body);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+    }
+    {
+        // add `URL`, `documentURI`, and `baseURI` properties
+        set_accessor_to(
+            scope,
+            document,
+            "URL",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                if let Some(url) = document_string(scope, |d| d.url.clone()) {
+                    rv.set(url);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+        set_accessor_to(
+            scope,
+            document,
+            "documentURI",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                if let Some(uri) = document_string(scope, |d| d.document_uri.clone()) {
+                    rv.set(uri);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+        set_accessor_to(
+            scope,
+            document,
+            "baseURI",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                let base_url = document_string(scope, |d| {
+                    d.base_url().map(|u| u.to_string()).unwrap_or_default()
+                });
+                if let Some(base_url) = base_url {
+                    rv.set(base_url);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+    }
+    {
+        // add `characterSet` property with its legacy aliases, `charset` and `inputEncoding`
+        for name in &["characterSet", "charset", "inputEncoding"] {
+            set_accessor_to(
+                scope,
+                document,
+                *name,
+                |scope: &mut v8::HandleScope,
+                 _key: v8::Local<v8::Name>,
+                 _args: v8::PropertyCallbackArguments,
+                 mut rv: v8::ReturnValue| {
+                    if let Some(charset) = document_string(scope, |d| d.character_set.clone()) {
+                        rv.set(charset);
+                    }
+                },
+                |_scope: &mut v8::HandleScope,
+                 _key: v8::Local<v8::Name>,
+                 _value: v8::Local<v8::Value>,
+                 _args: v8::PropertyCallbackArguments| {},
+            );
+        }
+    }
+    {
+        // add `contentType`, `referrer`, `lastModified`, and `readyState` properties
+        set_accessor_to(
+            scope,
+            document,
+            "contentType",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                if let Some(content_type) = document_string(scope, |d| d.content_type.clone()) {
+                    rv.set(content_type);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+        set_accessor_to(
+            scope,
+            document,
+            "referrer",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                if let Some(referrer) = document_string(scope, |d| d.referrer.clone()) {
+                    rv.set(referrer);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+        set_accessor_to(
+            scope,
+            document,
+            "lastModified",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                if let Some(last_modified) = document_string(scope, |d| d.last_modified_string()) {
+                    rv.set(last_modified);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+        set_accessor_to(
+            scope,
+            document,
+            "readyState",
+            |scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _args: v8::PropertyCallbackArguments,
+             mut rv: v8::ReturnValue| {
+                let ready_state = document_string(scope, |d| d.ready_state.as_str().to_string());
+                if let Some(ready_state) = ready_state {
+                    rv.set(ready_state);
+                }
+            },
+            |_scope: &mut v8::HandleScope,
+             _key: v8::Local<v8::Name>,
+             _value: v8::Local<v8::Value>,
+             _args: v8::PropertyCallbackArguments| {},
+        );
+    }
+}
+
+/// This function sets `document` object into `global`.
+pub fn initialize_dom<'s>(
+    scope: &mut v8::ContextScope<'s, v8::EscapableHandleScope>,
+    global: v8::Local<v8::Object>,
+) {
+    let document = create_document_object(scope);
+    set_property_to(scope, global, "document", document.into());
+    initialize_node_filter(scope, global);
+    initialize_mutation_observer(scope, global);
+}
+
+// utilities
+// =========
+
+/// `WrapperKind` is the interface of a JS object linked to the Rust world, which is stored in `WRAPPER_KIND_FIELD` of the object.
+/// It's checked whenever an object is used, so that e.g. an `Attr` object is never used as its owner element,
+/// and a method called with a foreign `this` throws `TypeError`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum WrapperKind {
+    Node = 1,
+    NamedNodeMap,
+    Attr,
+    DOMTokenList,
+    Dataset,
+    /// `Traversal` is a `TreeWalker` or `NodeIterator` object.
+    Traversal,
+    MutationObserver,
+}
+
+const WRAPPER_KINDS: &[WrapperKind] = &[
+    WrapperKind::Node,
+    WrapperKind::NamedNodeMap,
+    WrapperKind::Attr,
+    WrapperKind::DOMTokenList,
+    WrapperKind::Dataset,
+    WrapperKind::Traversal,
+    WrapperKind::MutationObserver,
+];
+
+/// `WRAPPER_KIND_FIELD` is the index of the internal field holding `WrapperKind` of every object linked to the Rust world.
+const WRAPPER_KIND_FIELD: usize = 0;
+
+/// `NODE_FIELD` is the index of the internal field holding the ID of the node an object is linked to.
+/// Objects other than nodes hold the node they belong to, e.g. the element of `NamedNodeMap` or the root of `TreeWalker`.
+const NODE_FIELD: usize = 1;
+
+/// `OBSERVER_FIELD` is the index of the internal field holding the ID of `MutationObserver` objects.
+const OBSERVER_FIELD: usize = 1;
+
+/// `create_wrapper` creates an object of `kind` which has `field_count` internal fields, including `WRAPPER_KIND_FIELD`.
+fn create_wrapper<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    kind: WrapperKind,
+    field_count: usize,
+) -> v8::Local<'s, v8::Object> {
+    let template = v8::ObjectTemplate::new(scope);
+    template.set_internal_field_count(field_count);
+    let object = template.new_instance(scope).unwrap();
+    let kind_v8 = v8::Number::new(scope, kind as u32 as f64);
+    object.set_internal_field(WRAPPER_KIND_FIELD, kind_v8.into());
+    object
+}
+
+/// `wrapper_kind` returns the kind of `object`, or `None` if it's not linked to the Rust world, e.g. a plain object.
+fn wrapper_kind<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    object: v8::Local<v8::Object>,
+) -> Option<WrapperKind> {
+    if object.internal_field_count() <= WRAPPER_KIND_FIELD {
+        return None;
+    }
+    let kind = object.get_internal_field(scope, WRAPPER_KIND_FIELD)?;
+    if !kind.is_number() {
+        return None;
+    }
+    let kind = kind.integer_value(scope)?;
+    WRAPPER_KINDS.iter().copied().find(|k| *k as i64 == kind)
+}
+
+fn set_node_internal_ref<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    id: NodeId,
+    node_v8: v8::Local<v8::Object>,
+) {
+    // NOTE: `NodeId` is an index in the document, so it can be stored as a number without any pointer to rust's heap.
+    let id_v8 = v8::Number::new(scope, id.index() as f64);
+    node_v8.set_internal_field(NODE_FIELD, id_v8.into());
+}
+
+/// `to_linked_rust_node` returns the ID of the node linked to `object`, or `None` if `object` is not an object of `kind`.
+fn to_linked_rust_node<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    object: v8::Local<v8::Object>,
+    kind: WrapperKind,
+) -> Option<NodeId> {
+    if wrapper_kind(scope, object) != Some(kind) {
+        return None;
+    }
+    let id_v8 = object.get_internal_field(scope, NODE_FIELD)?;
+    let index = id_v8.integer_value(scope)?;
+    Some(NodeId::from_index(index as usize))
+}
+
+/// `linked_node` returns the document and the ID of the node linked to `object`, which should be an object of `kind`.
+/// It throws `TypeError` and returns `None` if `object` is of another kind, e.g. a method is called with a foreign `this`.
+/// It also throws an exception and returns `None` if the node is not found.
+fn linked_node<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    object: v8::Local<v8::Object>,
+    kind: WrapperKind,
+) -> Option<(Rc<RefCell<Document>>, NodeId)> {
+    let document = match JavaScriptRuntime::document(scope) {
+        Some(document) => document,
+        None => {
+            error!("failed to get document reference; document is None");
+            return None;
+        }
+    };
+    let id = match to_linked_rust_node(scope, object, kind) {
+        Some(id) => id,
+        None => {
+            throw_illegal_invocation(scope);
+            return None;
+        }
+    };
+    if document.borrow().get(id).is_none() {
+        throw_error(scope, "the node does not belong to the document");
+        return None;
+    }
+    Some((document, id))
+}
+
+/// `this_node` returns the document and the ID of the node `this` of a method call is linked to.
+/// `this` should be an object of `kind`; see `linked_node`.
+fn this_node<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    kind: WrapperKind,
+) -> Option<(Rc<RefCell<Document>>, NodeId)> {
+    let this = args.this();
+    linked_node(scope, this, kind)
+}
+
+/// `node_argument` returns the ID of the node linked to `value` given as an argument.
+/// It throws `TypeError` and returns `None` if `value` is not a node in `document`, including objects of the other kinds like `Attr`.
+fn node_argument<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    document: &Rc<RefCell<Document>>,
+    value: v8::Local<v8::Value>,
+) -> Option<NodeId> {
+    let id = if value.is_object() {
+        value
+            .to_object(scope)
+            .and_then(|object| to_linked_rust_node(scope, object, WrapperKind::Node))
+    } else {
+        None
+    };
+    match id {
+        Some(id) if document.borrow().get(id).is_some() => Some(id),
+        _ => {
+            throw_type_error(scope, "the argument is not a node");
+            None
+        }
+    }
+}
+
+/// `set_mutation_result` returns the node of `result` to scripts and notifies the mutation, or throws the error of `result`.
+fn set_mutation_result<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    document: &Rc<RefCell<Document>>,
+    result: Result<NodeId, DOMException>,
+    retval: &mut v8::ReturnValue,
+    caller: &'static str,
+) {
+    match result {
+        Ok(id) => {
+            let node = wrap_node(scope, document, id);
+            retval.set(node.into());
+            notify_mutation(scope, caller);
+        }
+        Err(e) => throw_dom_exception(scope, e),
+    }
+}
+
+/// `wrap_node` creates a JS object linked to the node, whose interface depends on the type of the node.
+fn wrap_node<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    document: &Rc<RefCell<Document>>,
+    id: NodeId,
+) -> v8::Local<'s, v8::Object> {
+    let (element, character_data) = {
+        let document = document.borrow();
+        let node_type = &document.node(id).node_type;
+        let element = match node_type {
+            NodeType::Element(ref e) => Some(e.tag_name.clone()),
+            _ => None,
+        };
+        (element, node_type.character_data().is_some())
+    };
+    match element {
+        Some(tag_name) => to_v8_element(scope, tag_name.as_str(), id),
+        None if character_data => {
+            let is_text = document.borrow().node(id).node_type.is_text();
+            to_v8_character_data(scope, id, is_text)
+        }
+        None => to_v8_node(scope, id),
+    }
+}
+
+/// `modify_character_data` applies `f` to the data of the node `this` is linked to, and notifies the mutation.
+/// It throws the error returned by `f` instead if any.
+fn modify_character_data<'s, F>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    caller: &'static str,
+    f: F,
+) where
+    F: FnOnce(&mut dyn CharacterData) -> Result<(), DOMException>,
+{
+    let (document, id) = match this_node(scope, args, WrapperKind::Node) {
+        Some(v) => v,
+        None => return,
+    };
+    let result = document.borrow_mut().modify_character_data(id, f);
+    match result {
+        Ok(()) => notify_mutation(scope, caller),
+        Err(e) => throw_dom_exception(scope, e),
+    }
+}
+
+/// `unsigned_long_argument` converts the `i`-th argument into `unsigned long` of Web IDL.
+/// standard: https://webidl.spec.whatwg.org/#es-unsigned-long
+fn unsigned_long_argument<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    i: i32,
+) -> usize {
+    // NOTE: the conversion wraps around modulo 2^32 like `ToUint32`.
+    args.get(i).integer_value(scope).unwrap_or(0) as u32 as usize
+}
+
+/// `to_v8_array` creates an array of JS objects linked to the nodes.
+fn to_v8_array<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    document: &Rc<RefCell<Document>>,
+    ids: Vec<NodeId>,
+) -> v8::Local<'s, v8::Array> {
+    let nodes: Vec<v8::Local<v8::Value>> = ids
+        .into_iter()
+        .map(|id| wrap_node(scope, document, id).into())
+        .collect();
+    v8::Array::new_with_elements(scope, nodes.as_slice())
+}
+
+/// `notify_mutation` requests re-rendering after scripts modify the document,
+/// and queues a microtask to notify mutation observers if they have records.
+fn notify_mutation<'s>(scope: &mut v8::HandleScope<'s>, caller: &'static str) {
+    request_rerender(scope, caller);
+    queue_mutation_observer_microtask(scope);
+}
+
+/// https://dom.spec.whatwg.org/#queue-a-mutation-observer-compound-microtask
+fn queue_mutation_observer_microtask<'s>(scope: &mut v8::HandleScope<'s>) {
+    let has_records = match JavaScriptRuntime::document(scope) {
+        Some(document) => document.borrow().has_mutation_records(),
+        None => false,
+    };
+    let state = JavaScriptRuntime::state(scope);
+    if !has_records || state.borrow().mutation_observer_microtask_queued {
+        return;
+    }
+    state.borrow_mut().mutation_observer_microtask_queued = true;
+    let microtask = v8::Function::new(scope, notify_mutation_observers).unwrap();
+    scope.enqueue_microtask(microtask);
+}
+
+/// `notify_mutation_observers` calls the callback of each `MutationObserver` with its records.
+/// An exception thrown by a callback is reported, and doesn't prevent the other callbacks.
+/// https://dom.spec.whatwg.org/#notify-mutation-observers
+fn notify_mutation_observers(
+    scope: &mut v8::HandleScope,
+    _args: v8::FunctionCallbackArguments,
+    _retval: v8::ReturnValue,
+) {
+    let state = JavaScriptRuntime::state(scope);
+    state.borrow_mut().mutation_observer_microtask_queued = false;
+    let document = match JavaScriptRuntime::document(scope) {
+        Some(document) => document,
+        None => return,
+    };
+    let observers = state
+        .borrow()
+        .mutation_observers
+        .iter()
+        .map(|(id, observer, callback)| (*id, observer.clone(), callback.clone()))
+        .collect::<Vec<_>>();
+    for (id, observer, callback) in observers {
+        let records = document.borrow_mut().take_records(id);
+        if records.is_empty() {
+            continue;
+        }
+        let records = to_v8_mutation_records(scope, &document, records);
+        let observer = v8::Local::new(scope, observer);
+        let callback = v8::Local::new(scope, callback);
+
+        let tc_scope = &mut v8::TryCatch::new(scope);
+        let args = [records.into(), observer.into()];
+        if callback.call(tc_scope, observer.into(), &args).is_none() {
+            let exception = tc_scope
+                .exception()
+                .map(|e| e.to_rust_string_lossy(tc_scope))
+                .unwrap_or_default();
+            error!(
+                "an exception was thrown from MutationObserver; {}",
+                exception
+            );
+        }
+    }
+}
+
+/// `this_mutation_observer` returns the document and the ID of the `MutationObserver` object `this` of a method call.
+fn this_mutation_observer<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+) -> Option<(Rc<RefCell<Document>>, MutationObserverId)> {
+    let document = match JavaScriptRuntime::document(scope) {
+        Some(document) => document,
+        None => {
+            error!("failed to get document reference; document is None");
+            return None;
+        }
+    };
+    let this = args.this();
+    if wrapper_kind(scope, this) != Some(WrapperKind::MutationObserver) {
+        throw_illegal_invocation(scope);
+        return None;
+    }
+    let id_v8 = this.get_internal_field(scope, OBSERVER_FIELD)?;
+    let index = id_v8.integer_value(scope)?;
+    let state = JavaScriptRuntime::state(scope);
+    let id = state
+        .borrow()
+        .mutation_observers
+        .iter()
+        .map(|(id, _, _)| *id)
+        .find(|id| id.index() == index as usize)?;
+    Some((document, id))
+}
+
+/// `to_mutation_observer_init` converts `MutationObserverInit` dictionary given by scripts.
+fn to_mutation_observer_init<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<v8::Value>,
+) -> MutationObserverInit {
+    let object = match value.to_object(scope) {
+        Some(object) => object,
+        None => return MutationObserverInit::default(),
+    };
+    let mut member = |name: &str| -> Option<bool> {
+        let key = v8::String::new(scope, name).unwrap();
+        object
+            .get(scope, key.into())
+            .filter(|value| !value.is_undefined())
+            .map(|value| value.boolean_value(scope))
+    };
+    let child_list = member("childList");
+    let attributes = member("attributes");
+    let character_data = member("characterData");
+    let subtree = member("subtree");
+    let attribute_old_value = member("attributeOldValue");
+    let character_data_old_value = member("characterDataOldValue");
+
+    let key = v8::String::new(scope, "attributeFilter").unwrap();
+    let attribute_filter = object
+        .get(scope, key.into())
+        .and_then(|value| v8::Local::<v8::Array>::try_from(value).ok())
+        .map(|array| {
+            (0..array.length())
+                .filter_map(|i| array.get_index(scope, i))
+                .map(|name| name.to_rust_string_lossy(scope))
+                .collect()
+        });
+    MutationObserverInit {
+        child_list: child_list.unwrap_or(false),
+        attributes,
+        character_data,
+        subtree: subtree.unwrap_or(false),
+        attribute_old_value,
+        character_data_old_value,
+        attribute_filter,
+    }
+}
+
+/// `to_v8_mutation_records` creates an array of `MutationRecord` objects.
+fn to_v8_mutation_records<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    document: &Rc<RefCell<Document>>,
+    records: Vec<MutationRecord>,
+) -> v8::Local<'s, v8::Array> {
+    let records: Vec<v8::Local<v8::Value>> = records
+        .into_iter()
+        .map(|record| {
+            let object = v8::Object::new(scope);
+            let record_type = v8::String::new(scope, record.record_type.as_str()).unwrap();
+            set_constant_to(scope, object, "type", record_type.into());
+            let target = wrap_node(scope, document, record.target);
+            set_constant_to(scope, object, "target", target.into());
+            let added_nodes = to_v8_array(scope, document, record.added_nodes);
+            set_constant_to(scope, object, "addedNodes", added_nodes.into());
+            let removed_nodes = to_v8_array(scope, document, record.removed_nodes);
+            set_constant_to(scope, object, "removedNodes", removed_nodes.into());
+            let siblings = [
+                ("previousSibling", record.previous_sibling),
+                ("nextSibling", record.next_sibling),
+            ];
+            for (name, sibling) in siblings.iter() {
+                let sibling: v8::Local<v8::Value> = match sibling {
+                    Some(sibling) => wrap_node(scope, document, *sibling).into(),
+                    None => v8::null(scope).into(),
+                };
+                set_constant_to(scope, object, name, sibling);
+            }
+            let values = [
+                ("attributeName", record.attribute_name),
+                ("oldValue", record.old_value),
+            ];
+            for (name, value) in values.iter() {
+                let value: v8::Local<v8::Value> = match value {
+                    Some(value) => v8::String::new(scope, value.as_str()).unwrap().into(),
+                    None => v8::null(scope).into(),
+                };
+                set_constant_to(scope, object, name, value);
+            }
+            // NOTE: puppy doesn't support namespaces of attributes.
+            let namespace = v8::null(scope);
+            set_constant_to(scope, object, "attributeNamespace", namespace.into());
+            object.into()
+        })
+        .collect();
+    v8::Array::new_with_elements(scope, records.as_slice())
+}
+
+/// `document_string` returns a string computed by `f` from the document.
+fn document_string<'s, F>(scope: &mut v8::HandleScope<'s>, f: F) -> Option<v8::Local<'s, v8::Value>>
+where
+    F: FnOnce(&Document) -> String,
+{
+    let document = match JavaScriptRuntime::document(scope) {
+        Some(document) => document,
+        None => {
+            error!("failed to get document reference; document is None");
+            return None;
+        }
+    };
+    let value = f(&document.borrow());
+    Some(v8::String::new(scope, value.as_str()).unwrap().into())
+}
+
+/// `document_element_with` returns the element found by `f` in the document, or `null` if it's not found.
+fn document_element_with<'s, F>(
+    scope: &mut v8::HandleScope<'s>,
+    f: F,
+) -> Option<v8::Local<'s, v8::Value>>
+where
+    F: FnOnce(&Document) -> Option<NodeId>,
+{
+    let document = match JavaScriptRuntime::document(scope) {
+        Some(document) => document,
+        None => {
+            error!("failed to get document reference; document is None");
+            return None;
+        }
+    };
+    let found = f(&document.borrow());
+    match found {
+        Some(id) => Some(wrap_node(scope, &document, id).into()),
+        None => Some(v8::null(scope).into()),
+    }
+}
+
+/// `AttrField` is an index of the internal fields of `Attr` objects, following `WRAPPER_KIND_FIELD`.
+enum AttrField {
+    /// `OwnerElement` is the ID of the element having the attribute, which is read as the node linked to the object.
+    OwnerElement = NODE_FIELD as isize,
+    Name,
+    Count,
+}
+
+/// `attr_name` returns the name of the attribute the `Attr` object refers to.
+/// It throws `TypeError` and returns `None` if `attr` is not an `Attr` object.
+fn attr_name<'s>(scope: &mut v8::HandleScope<'s>, attr: v8::Local<v8::Object>) -> Option<String> {
+    if wrapper_kind(scope, attr) != Some(WrapperKind::Attr) {
+        throw_illegal_invocation(scope);
+        return None;
+    }
+    attr.get_internal_field(scope, AttrField::Name as usize)
+        .map(|name| name.to_rust_string_lossy(scope))
+}
+
+/// `attribute_name_argument` converts `value` to the name of an attribute.
+/// Names are lowercased since puppy handles all the elements as HTML elements.
+fn attribute_name_argument<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<v8::Value>,
+) -> String {
+    value.to_rust_string_lossy(scope).to_ascii_lowercase()
+}
+
+/// `reflected_attribute` returns the value of the attribute of the element linked to `node_v8`, which should be an object of `kind`.
+/// It returns `None` if the attribute doesn't exist or the element is not found.
+fn reflected_attribute<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    node_v8: v8::Local<v8::Object>,
+    kind: WrapperKind,
+    name: &str,
+) -> Option<v8::Local<'s, v8::Value>> {
+    let (document, id) = linked_node(scope, node_v8, kind)?;
+    let value = document.borrow().get_attribute(id, name)?;
+    Some(v8::String::new(scope, value.as_str()).unwrap().into())
+}
+
+/// `set_reflected_attribute` sets `value` to the attribute of the element linked to `node_v8`, which should be an object of `kind`.
+/// It returns whether the attribute is set.
+fn set_reflected_attribute<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    node_v8: v8::Local<v8::Object>,
+    kind: WrapperKind,
+    name: &str,
+    value: v8::Local<v8::Value>,
+) -> bool {
+    let (document, id) = match linked_node(scope, node_v8, kind) {
+        Some(v) => v,
+        None => return false,
+    };
+    let value = value.to_rust_string_lossy(scope);
+    let result = document.borrow_mut().set_attribute(id, name, value);
+    set_attribute_result(scope, result, "setter of a reflected attribute")
+}
+
+/// `set_attribute_result` notifies the mutation, or throws the error of `result`.
+/// It returns whether `result` is `Ok`.
+fn set_attribute_result<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    result: Result<(), DOMException>,
+    caller: &'static str,
+) -> bool {
+    match result {
+        Ok(()) => {
+            notify_mutation(scope, caller);
+            true
+        }
+        Err(e) => {
+            throw_dom_exception(scope, e);
+            false
+        }
+    }
+}
+
+/// `class_list` returns the classes of the element linked to `node_v8`.
+fn class_list<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    node_v8: v8::Local<v8::Object>,
+) -> Option<Vec<String>> {
+    let (document, id) = linked_node(scope, node_v8, WrapperKind::DOMTokenList)?;
+    let document = document.borrow();
+    match document.node(id).node_type {
+        NodeType::Element(ref e) => Some(e.class_list()),
+        _ => None,
+    }
+}
+
+/// `update_class_list` applies `f` to the classes of the element linked to `node_v8`, and sets them to its `class` attribute.
+/// https://dom.spec.whatwg.org/#concept-dtl-update
+fn update_class_list<'s, F>(
+    scope: &mut v8::HandleScope<'s>,
+    node_v8: v8::Local<v8::Object>,
+    caller: &'static str,
+    f: F,
+) where
+    F: FnOnce(&mut Vec<String>),
+{
+    let (document, id) = match linked_node(scope, node_v8, WrapperKind::DOMTokenList) {
+        Some(v) => v,
+        None => return,
+    };
+    let mut tokens = match class_list(scope, node_v8) {
+        Some(tokens) => tokens,
+        None => return,
+    };
+    f(&mut tokens);
+    if tokens.is_empty() && document.borrow().get_attribute(id, "class").is_none() {
+        return;
+    }
+    let result = document
+        .borrow_mut()
+        .set_attribute(id, "class", tokens.join(" "));
+    set_attribute_result(scope, result, caller);
+}
+
+/// `validate_token` checks whether `token` can be added to `DOMTokenList`.
+fn validate_token(token: &str) -> Result<(), DOMException> {
+    if token.is_empty() {
+        Err(DOMException::SyntaxError("the token is empty"))
+    } else if token.chars().any(|c| c.is_ascii_whitespace()) {
+        Err(DOMException::InvalidCharacterError(
+            "the token contains ASCII whitespace",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// `token_arguments` returns all the arguments as tokens of `DOMTokenList`.
+/// It throws an exception and returns `None` if some of them are invalid.
+fn token_arguments<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+) -> Option<Vec<String>> {
+    let tokens = (0..args.length())
+        .map(|i| args.get(i).to_rust_string_lossy(scope))
+        .collect::<Vec<_>>();
+    for token in &tokens {
+        if let Err(e) = validate_token(token.as_str()) {
+            throw_dom_exception(scope, e);
+            return None;
+        }
+    }
+    Some(tokens)
+}
+
+/// `dataset_attribute_argument` returns the name of the `data-*` attribute for the property given to a trap of `dataset`.
+/// It returns `None` for symbols and names which don't correspond to any `data-*` attribute.
+fn dataset_attribute_argument<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+) -> Option<String> {
+    let property = args.get(1);
+    if !property.is_string() {
+        return None;
+    }
+    let property = property.to_rust_string_lossy(scope);
+    dataset_attribute_name(property.as_str())
+}
+
+/// `to_v8_nullable_string` converts `value` to a string, or `null` if it's `None`.
+fn to_v8_nullable_string<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    value: Option<String>,
+) -> v8::Local<'s, v8::Value> {
+    match value {
+        Some(value) => v8::String::new(scope, value.as_str()).unwrap().into(),
+        None => v8::null(scope).into(),
+    }
+}
+
+/// `TraversalField` is an index of internal fields of `TreeWalker` and `NodeIterator` objects, following `WRAPPER_KIND_FIELD`.
+enum TraversalField {
+    /// `Root` is the ID of the root, which is read as the node linked to the object.
+    Root = NODE_FIELD as isize,
+    /// `Current` is the ID of `currentNode` of `TreeWalker`, or `referenceNode` of `NodeIterator`.
+    Current,
+    PointerBeforeReference,
+    /// `Active` is true while the filter is running.
+    /// https://dom.spec.whatwg.org/#concept-traversal-active
+    Active,
+    Count,
+}
+
+fn set_traversal_field<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    traversal: v8::Local<v8::Object>,
+    field: TraversalField,
+    value: f64,
+) {
+    let value = v8::Number::new(scope, value);
+    traversal.set_internal_field(field as usize, value.into());
+}
+
+/// `traversal_field` returns the internal field of the traversal object.
+/// It throws `TypeError` and returns `None` if `traversal` is not a `TreeWalker` or `NodeIterator` object.
+fn traversal_field<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    traversal: v8::Local<v8::Object>,
+    field: TraversalField,
+) -> Option<v8::Local<'s, v8::Value>> {
+    if wrapper_kind(scope, traversal) != Some(WrapperKind::Traversal) {
+        throw_illegal_invocation(scope);
+        return None;
+    }
+    traversal.get_internal_field(scope, field as usize)
+}
+
+fn traversal_node<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    traversal: v8::Local<v8::Object>,
+    field: TraversalField,
+) -> Option<NodeId> {
+    let id_v8 = traversal_field(scope, traversal, field)?;
+    let index = id_v8.integer_value(scope)?;
+    Some(NodeId::from_index(index as usize))
+}
+
+/// `what_to_show_argument` converts the `i`-th argument into `WhatToShow`, which is `SHOW_ALL` if the argument is omitted.
+fn what_to_show_argument<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    i: i32,
+) -> WhatToShow {
+    if args.get(i).is_undefined() {
+        WhatToShow::ALL
+    } else {
+        WhatToShow(unsigned_long_argument(scope, args, i) as u32)
+    }
+}
+
+/// `begin_traversal` sets the active flag of the traversal object `this`, and returns the document, `whatToShow` and `filter` of it.
+/// It throws `InvalidStateError` and returns `None` if the flag is already set, i.e. it's called from the filter of the same object.
+fn begin_traversal<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+) -> Option<(Rc<RefCell<Document>>, WhatToShow, v8::Local<'s, v8::Value>)> {
+    let (document, _) = this_node(scope, args, WrapperKind::Traversal)?;
+    let this = args.this();
+    let active = traversal_field(scope, this, TraversalField::Active)?;
+    if active.boolean_value(scope) {
+        throw_dom_exception(
+            scope,
+            DOMException::InvalidStateError("the filter is already running"),
+        );
+        return None;
+    }
+    let key = v8::String::new(scope, "whatToShow").unwrap();
+    let what_to_show = this
+        .get(scope, key.into())
+        .and_then(|value| value.integer_value(scope))
+        .unwrap_or(0);
+    let key = v8::String::new(scope, "filter").unwrap();
+    let filter = this.get(scope, key.into())?;
+
+    let active = v8::Boolean::new(scope, true);
+    this.set_internal_field(TraversalField::Active as usize, active.into());
+    Some((document, WhatToShow(what_to_show as u32), filter))
+}
+
+/// `end_traversal` unsets the active flag of the traversal object `this`, and returns the node found by the traversal to scripts.
+/// If the filter threw an exception, it's rethrown as it is.
+fn end_traversal<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    document: &Rc<RefCell<Document>>,
+    result: Result<Option<NodeId>, ()>,
+    retval: &mut v8::ReturnValue,
+) {
+    let active = v8::Boolean::new(scope, false);
+    args.this()
+        .set_internal_field(TraversalField::Active as usize, active.into());
+    match result {
+        Ok(Some(id)) => {
+            let node = wrap_node(scope, document, id);
+            retval.set(node.into());
+        }
+        Ok(None) => retval.set(v8::null(scope).into()),
+        Err(()) => {}
+    }
+}
+
+/// `walk_tree` runs `step` for the `TreeWalker` object `this` of a method call.
+fn walk_tree<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    retval: &mut v8::ReturnValue,
+    step: fn(
+        &mut TreeWalker,
+        &RefCell<Document>,
+        &mut NodeFilter<'_, ()>,
+    ) -> Result<Option<NodeId>, ()>,
+) {
+    let (document, what_to_show, filter) = match begin_traversal(scope, args) {
+        Some(v) => v,
+        None => return,
+    };
+    let this = args.this();
+    let (root, current_node) = match (
+        traversal_node(scope, this, TraversalField::Root),
+        traversal_node(scope, this, TraversalField::Current),
+    ) {
+        (Some(root), Some(current_node)) => (root, current_node),
+        _ => return end_traversal(scope, args, &document, Err(()), retval),
+    };
+    let mut walker = TreeWalker {
+        root,
+        what_to_show,
+        current_node,
+    };
+    let result = step(&mut walker, &document, &mut |id: NodeId| {
+        run_node_filter(scope, &document, filter, id)
+    });
+    let current = walker.current_node.index() as f64;
+    set_traversal_field(scope, this, TraversalField::Current, current);
+    end_traversal(scope, args, &document, result, retval);
+}
+
+/// `iterate_nodes` runs `step` for the `NodeIterator` object `this` of a method call.
+fn iterate_nodes<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    retval: &mut v8::ReturnValue,
+    step: fn(
+        &mut NodeIterator,
+        &RefCell<Document>,
+        &mut NodeFilter<'_, ()>,
+    ) -> Result<Option<NodeId>, ()>,
+) {
+    let (document, what_to_show, filter) = match begin_traversal(scope, args) {
+        Some(v) => v,
+        None => return,
+    };
+    let this = args.this();
+    let (root, reference_node, pointer_before_reference) = match (
+        traversal_node(scope, this, TraversalField::Root),
+        traversal_node(scope, this, TraversalField::Current),
+        traversal_field(scope, this, TraversalField::PointerBeforeReference),
+    ) {
+        (Some(root), Some(reference_node), Some(pointer_before_reference)) => (
+            root,
+            reference_node,
+            pointer_before_reference.boolean_value(scope),
+        ),
+        _ => return end_traversal(scope, args, &document, Err(()), retval),
+    };
+    let mut iterator = NodeIterator {
+        root,
+        what_to_show,
+        reference_node,
+        pointer_before_reference_node: pointer_before_reference,
+    };
+    let result = step(&mut iterator, &document, &mut |id: NodeId| {
+        run_node_filter(scope, &document, filter, id)
+    });
+    let reference = iterator.reference_node.index() as f64;
+    set_traversal_field(scope, this, TraversalField::Current, reference);
+    let pointer_before_reference = v8::Boolean::new(scope, iterator.pointer_before_reference_node);
+    this.set_internal_field(
+        TraversalField::PointerBeforeReference as usize,
+        pointer_before_reference.into(),
+    );
+    end_traversal(scope, args, &document, result, retval);
+}
+
+/// `run_node_filter` calls `filter` given by scripts, which is either a function or an object with `acceptNode` method.
+/// It returns `Err` if an exception is thrown.
+/// https://dom.spec.whatwg.org/#concept-node-filter
+///
+/// NOTE: any value other than `FILTER_ACCEPT` and `FILTER_REJECT` is regarded as `FILTER_SKIP`.
+fn run_node_filter<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    document: &Rc<RefCell<Document>>,
+    filter: v8::Local<v8::Value>,
+    id: NodeId,
+) -> Result<FilterResult, ()> {
+    if filter.is_null_or_undefined() {
+        return Ok(FilterResult::Accept);
+    }
+    let (function, receiver): (v8::Local<v8::Function>, v8::Local<v8::Value>) =
+        match v8::Local::<v8::Function>::try_from(filter) {
+            Ok(function) => (function, v8::undefined(scope).into()),
+            Err(_) => {
+                let object = filter.to_object(scope).ok_or(())?;
+                let key = v8::String::new(scope, "acceptNode").unwrap();
+                let accept_node = object.get(scope, key.into()).ok_or(())?;
+                match v8::Local::<v8::Function>::try_from(accept_node) {
+                    Ok(function) => (function, filter),
+                    Err(_) => {
+                        let message =
+                            v8::String::new(scope, "acceptNode is not a function").unwrap();
+                        let exception = v8::Exception::type_error(scope, message);
+                        scope.throw_exception(exception);
+                        return Err(());
+                    }
+                }
+            }
+        };
+    let node = wrap_node(scope, document, id);
+    let result = function.call(scope, receiver, &[node.into()]).ok_or(())?;
+    match result.integer_value(scope).ok_or(())? {
+        1 => Ok(FilterResult::Accept),
+        2 => Ok(FilterResult::Reject),
+        _ => Ok(FilterResult::Skip),
+    }
+}
+
+fn throw_error<'s>(scope: &mut v8::HandleScope<'s>, message: &str) {
+    let message = v8::String::new(scope, message).unwrap();
+    let exception = v8::Exception::error(scope, message);
+    scope.throw_exception(exception);
+}
+
+fn throw_type_error<'s>(scope: &mut v8::HandleScope<'s>, message: &str) {
+    let message = v8::String::new(scope, message).unwrap();
+    let exception = v8::Exception::type_error(scope, message);
+    scope.throw_exception(exception);
+}
+
+/// `throw_illegal_invocation` throws `TypeError` for a method or an accessor called with `this` of another interface.
+fn throw_illegal_invocation<'s>(scope: &mut v8::HandleScope<'s>) {
+    throw_type_error(scope, "Illegal invocation");
+}
+
+fn throw_invalid_selector<'s>(scope: &mut v8::HandleScope<'s>) {
+    throw_dom_exception(scope, DOMException::SyntaxError("the selector is invalid"));
+}
+
+/// `throw_dom_exception` throws `e` as an error whose `name` is the name of the exception.
+fn throw_dom_exception<'s>(scope: &mut v8::HandleScope<'s>, e: DOMException) {
+    let message = v8::String::new(scope, e.to_string().as_str()).unwrap();
+    let exception = v8::Exception::error(scope, message);
+    if let Some(object) = exception.to_object(scope) {
+        let name = v8::String::new(scope, e.name()).unwrap();
+        set_property_to(scope, object, "name", name.into());
+    }
+    scope.throw_exception(exception);
+}
+
+fn to_v8_node<'s>(scope: &mut v8::HandleScope<'s>, id: NodeId) -> v8::Local<'s, v8::Object> {
+    // create new node instance
+    let node_v8 = create_v8_node(scope, WrapperKind::Node);
+
+    // set the ID of the node into the internal field
+    set_node_internal_ref(scope, id, node_v8);
+
+    // set methods
+    set_mutation_methods_to(scope, node_v8);
+
+    // all set :-)
+    node_v8
 }
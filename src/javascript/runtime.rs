@@ -1,6 +1,11 @@
 //! This module defines a JavaScript runtime with V8.
 
-use crate::{core::dom::Document, javascript::binding, tui::PageViewAPIHandler, window::Window};
+use crate::{
+    core::dom::{Document, MutationObserverId},
+    javascript::binding,
+    tui::PageViewAPIHandler,
+    window::Window,
+};
 use rusty_v8 as v8;
 use std::{cell::RefCell, rc::Rc, sync::Once};
 use thiserror::Error;
@@ -14,6 +19,18 @@ pub struct JavaScriptRuntimeState {
     pub window: Option<Rc<RefCell<Window>>>,
     pub document: Option<Rc<RefCell<Document>>>,
     pub pv_api_handler: Option<Rc<PageViewAPIHandler>>,
+    /// `written_markup` holds markup written by `document.write` while a script found by the HTML parser runs.
+    /// It's `None` if no such script is running.
+    pub written_markup: Option<String>,
+    /// `mutation_observers` holds `MutationObserver` objects created by scripts with their callbacks.
+    pub mutation_observers: Vec<(
+        MutationObserverId,
+        v8::Global<v8::Object>,
+        v8::Global<v8::Function>,
+    )>,
+    /// `mutation_observer_microtask_queued` is the *mutation observer microtask queued* flag.
+    /// https://dom.spec.whatwg.org/#mutation-observer-microtask-queued
+    pub mutation_observer_microtask_queued: bool,
 }
 
 /// `JavaScriptRuntime` defines a JS runtime with v8.
@@ -59,6 +76,9 @@ impl JavaScriptRuntime {
             window: None,
             document: None,
             pv_api_handler: None,
+            written_markup: None,
+            mutation_observers: vec![],
+            mutation_observer_microtask_queued: false,
         })));
 
         JavaScriptRuntime {
@@ -188,6 +208,37 @@ impl JavaScriptRuntime {
     }
 }
 
+/// Implementations for `document.write` called from scripts found by the HTML parser.
+#[allow(dead_code)]
+impl JavaScriptRuntime {
+    /// `write_markup` appends `markup` to the markup written by the running script.
+    /// It returns false if the running script was not found by the HTML parser, where no insertion point exists.
+    pub fn write_markup(isolate: &v8::Isolate, markup: &str) -> bool {
+        let state = Self::state(isolate);
+        let mut state = state.borrow_mut();
+        match state.written_markup {
+            Some(ref mut written) => {
+                written.push_str(markup);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `execute_with_insertion_point` runs a script found by the HTML parser,
+    /// and returns the result with markup the script wrote by `document.write`.
+    pub fn execute_with_insertion_point(
+        &mut self,
+        filename: &str,
+        source: &str,
+    ) -> (Result<String, JavaScriptRuntimeError>, String) {
+        self.get_state().borrow_mut().written_markup = Some(String::new());
+        let result = self.execute(filename, source);
+        let written = self.get_state().borrow_mut().written_markup.take();
+        (result, written.unwrap_or_default())
+    }
+}
+
 /// Implementations for proxying Rust-world PageView API and the JS runtime.
 #[allow(dead_code)]
 impl JavaScriptRuntime {
@@ -286,15 +337,20 @@ mod tests {
         Rc<RefCell<Window>>,
         Rc<PageViewAPIHandler>,
     ) {
-        let document = Rc::new(RefCell::new(Document::new(
+        let mut document = Document::new(
             "http://example.com".to_string(),
             "http://example.com".to_string(),
+        );
+        let root = document.root();
+        document.append_tree(
+            root,
             Element::new(
                 "p".to_string(),
                 AttrMap::new(),
                 vec![Text::new("hi".to_string())],
             ),
-        )));
+        );
+        let document = Rc::new(RefCell::new(document));
         runtime.set_document(document.clone());
 
         let window = Rc::new(RefCell::new(Window {
@@ -362,6 +418,273 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_dom_mutation() {
+        let mut runtime = JavaScriptRuntime::new();
+        let (document, _, _) = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "const p = document.all[0]; const b = document.createElement('B'); p.appendChild(b); b.appendChild(document.createTextNode('!')); p.outerHTML",
+        );
+        assert_eq!(r.unwrap(), "<p>hi<b>!</b></p>");
+        let r = runtime.execute("", "try { b.appendChild(p) } catch (e) { e.name }");
+        assert_eq!(r.unwrap(), "HierarchyRequestError");
+        let r = runtime.execute("", "p.removeChild(b); p.outerHTML");
+        assert_eq!(r.unwrap(), "<p>hi</p>");
+        let r = runtime.execute("", "try { p.removeChild(b) } catch (e) { e.name }");
+        assert_eq!(r.unwrap(), "NotFoundError");
+
+        let document = document.borrow();
+        assert_eq!(document.inner_html(document.root()), "<p>hi</p>");
+    }
+
+    #[test]
+    fn test_outer_html() {
+        let mut runtime = JavaScriptRuntime::new();
+        let (document, _, _) = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "const p = document.all[0]; const i = p.appendChild(document.createElement('i')); i.outerHTML = '<b>1</b>2'; i.outerHTML = '<s></s>'; [p.innerHTML, i.outerHTML].join()",
+        );
+        assert_eq!(r.unwrap(), "hi<b>1</b>2,<i></i>");
+        let r = runtime.execute("", "try { p.outerHTML = '' } catch (e) { e.name }");
+        assert_eq!(r.unwrap(), "NoModificationAllowedError");
+
+        let document = document.borrow();
+        assert_eq!(document.inner_html(document.root()), "<p>hi<b>1</b>2</p>");
+    }
+
+    #[test]
+    fn test_create_element() {
+        let mut runtime = JavaScriptRuntime::new();
+        let _ = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "[document.createElement('DIV').tagName, document.createElement('\\u0130').tagName, document.createElement('my-Element').tagName].join()",
+        );
+        assert_eq!(r.unwrap(), "div,\u{130},my-element");
+        let r = runtime.execute(
+            "",
+            "const errors = []; for (const name of ['img src=x onerror=alert(1)', '', '1p', '<p>']) { try { document.createElement(name) } catch (e) { errors.push(e.name) } }; errors.join()",
+        );
+        assert_eq!(
+            r.unwrap(),
+            "InvalidCharacterError,InvalidCharacterError,InvalidCharacterError,InvalidCharacterError"
+        );
+    }
+
+    #[test]
+    fn test_document_write() {
+        let mut runtime = JavaScriptRuntime::new();
+        let (document, _, _) = setup_runtime(&mut runtime);
+
+        // markup written by a script found by the HTML parser is returned to the parser
+        let (r, written) = runtime.execute_with_insertion_point(
+            "",
+            "document.write('<p>x</p>'); document.writeln('a', 'b'); 1",
+        );
+        assert_eq!(r.unwrap(), "1");
+        assert_eq!(written, "<p>x</p>ab\n");
+
+        // markup written outside of such scripts is ignored, and never passed to the next script
+        let r = runtime.execute("", "document.write('<p>y</p>'); 2");
+        assert_eq!(r.unwrap(), "2");
+        let (r, written) = runtime.execute_with_insertion_point("", "3");
+        assert_eq!(r.unwrap(), "3");
+        assert_eq!(written, "");
+
+        let document = document.borrow();
+        assert_eq!(document.inner_html(document.root()), "<p>hi</p>");
+    }
+
+    #[test]
+    fn test_character_data() {
+        let mut runtime = JavaScriptRuntime::new();
+        let (document, _, _) = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "const p = document.all[0]; const text = document.createTextNode('hello'); p.appendChild(text); text.appendData(' world'); text.deleteData(0, 1); [text.data, text.length, text.substringData(0, 4)].join()",
+        );
+        assert_eq!(r.unwrap(), "ello world,10,ello");
+        let r = runtime.execute("", "try { text.insertData(11, '!') } catch (e) { e.name }");
+        assert_eq!(r.unwrap(), "IndexSizeError");
+        let r = runtime.execute(
+            "",
+            "const f = document.createDocumentFragment(); f.appendChild(document.createComment('c')); text.splitText(4); p.appendChild(f); p.outerHTML",
+        );
+        assert_eq!(r.unwrap(), "<p>hiello world<!--c--></p>");
+
+        let document = document.borrow();
+        let p = document.document_element().unwrap();
+        assert_eq!(document.children(p).count(), 4);
+    }
+
+    #[test]
+    fn test_processing_instruction() {
+        let mut runtime = JavaScriptRuntime::new();
+        let _ = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "const pi = document.createProcessingInstruction('xml-stylesheet', 'href=\"a.css\"'); [pi.target, pi.data].join()",
+        );
+        assert_eq!(r.unwrap(), "xml-stylesheet,href=\"a.css\"");
+        let r = runtime.execute(
+            "",
+            "const errors = []; for (const [target, data] of [['a b', ''], ['', ''], ['?x', ''], ['x', '?>']]) { try { document.createProcessingInstruction(target, data) } catch (e) { errors.push(e.name) } }; errors.join()",
+        );
+        assert_eq!(
+            r.unwrap(),
+            "InvalidCharacterError,InvalidCharacterError,InvalidCharacterError,InvalidCharacterError"
+        );
+    }
+
+    #[test]
+    fn test_dom_traversal() {
+        let mut runtime = JavaScriptRuntime::new();
+        let _ = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "const p = document.all[0]; p.appendChild(document.createElement('b')).appendChild(document.createTextNode('!')); const w = document.createTreeWalker(document, NodeFilter.SHOW_TEXT); const texts = []; while (w.nextNode()) texts.push(w.currentNode.data); texts.join()",
+        );
+        assert_eq!(r.unwrap(), "hi,!");
+        let r = runtime.execute(
+            "",
+            "const i = document.createNodeIterator(p, NodeFilter.SHOW_ALL, { acceptNode: (n) => n.tagName === 'b' ? NodeFilter.FILTER_REJECT : NodeFilter.FILTER_ACCEPT }); [i.nextNode().tagName, i.nextNode().data, i.nextNode().data].join()",
+        );
+        assert_eq!(r.unwrap(), "p,hi,!");
+        let r = runtime.execute(
+            "",
+            "const w2 = document.createTreeWalker(p, NodeFilter.SHOW_ALL, () => { w2.nextNode() }); try { w2.nextNode() } catch (e) { e.name }",
+        );
+        assert_eq!(r.unwrap(), "InvalidStateError");
+    }
+
+    #[test]
+    fn test_wrapper_kinds() {
+        let mut runtime = JavaScriptRuntime::new();
+        let (document, _, _) = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "const p = document.all[0]; p.setAttribute('title', 't'); const errors = []; for (const x of [p.attributes.title, p.attributes, p.classList, document.createTreeWalker(p), {}]) { try { p.appendChild(x) } catch (e) { errors.push(e.name) } }; errors.join()",
+        );
+        assert_eq!(
+            r.unwrap(),
+            "TypeError,TypeError,TypeError,TypeError,TypeError"
+        );
+        let r = runtime.execute(
+            "",
+            "const messages = []; try { p.appendChild.call({}, p) } catch (e) { messages.push(e.message) }; const w = document.createTreeWalker(p); try { w.nextNode.call(p) } catch (e) { messages.push(e.message) }; messages.join()",
+        );
+        assert_eq!(r.unwrap(), "Illegal invocation,Illegal invocation");
+
+        let document = document.borrow();
+        let p = document.document_element().unwrap();
+        assert_eq!(document.children(p).count(), 1);
+    }
+
+    #[test]
+    fn test_mutation_observer() {
+        let mut runtime = JavaScriptRuntime::new();
+        let _ = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "const results = []; const p = document.all[0]; const o = new MutationObserver((records, observer) => { results.push(observer === o, ...records.map((r) => r.type + ':' + r.addedNodes.length + ':' + r.oldValue)) }); o.observe(p, { childList: true, characterData: true, characterDataOldValue: true, subtree: true }); p.appendChild(document.createElement('b')); p.firstChild.data = 'hey'; results.length",
+        );
+        assert_eq!(r.unwrap(), "0");
+        let r = runtime.execute("", "results.join()");
+        assert_eq!(r.unwrap(), "true,childList:1:null,characterData:0:hi");
+        let r = runtime.execute(
+            "",
+            "p.removeChild(p.lastChild); const records = o.takeRecords(); o.disconnect(); p.appendChild(document.createElement('i')); [records.length, records[0].removedNodes[0].tagName, o.takeRecords().length].join()",
+        );
+        assert_eq!(r.unwrap(), "1,b,0");
+        let r = runtime.execute("", "try { o.observe(p, {}) } catch (e) { e.name }");
+        assert_eq!(r.unwrap(), "TypeError");
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut runtime = JavaScriptRuntime::new();
+        let (document, _, _) = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "const p = document.all[0]; p.id = 'x'; p.setAttribute('Title', 't'); p.toggleAttribute('hidden'); [p.id, p.getAttribute('title'), p.hasAttribute('hidden'), p.getAttributeNames().join(' '), p.attributes.length, p.attributes.title.value].join()",
+        );
+        assert_eq!(r.unwrap(), "x,t,true,hidden id title,3,t");
+        let r = runtime.execute(
+            "",
+            "p.classList.add('a', 'b'); p.classList.toggle('a'); p.classList.replace('b', 'c'); p.dataset.fooBar = '1'; [p.className, p.classList.contains('c'), p.classList.length, p.getAttribute('data-foo-bar'), Object.keys(p.dataset).join()].join()",
+        );
+        assert_eq!(r.unwrap(), "c,true,1,1,fooBar");
+        let r = runtime.execute(
+            "",
+            "delete p.dataset.fooBar; p.removeAttribute('hidden'); p.attributes.title.value = 'u'; [p.dataset.fooBar, p.hasAttribute('hidden'), p.title].join()",
+        );
+        assert_eq!(r.unwrap(), ",false,");
+        let r = runtime.execute(
+            "",
+            "const errors = []; try { p.setAttribute('a b', '') } catch (e) { errors.push(e.name) }; try { p.classList.add('') } catch (e) { errors.push(e.name) }; try { p.dataset['a-b'] = '' } catch (e) { errors.push(e.name) }; errors.join()",
+        );
+        assert_eq!(r.unwrap(), "InvalidCharacterError,SyntaxError,SyntaxError");
+
+        let document = document.borrow();
+        let p = document.document_element().unwrap();
+        assert_eq!(document.get_attribute(p, "title"), Some("u".to_string()));
+        assert_eq!(document.get_attribute(p, "class"), Some("c".to_string()));
+    }
+
+    #[test]
+    fn test_document_metadata() {
+        let mut runtime = JavaScriptRuntime::new();
+        let (document, _, _) = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "document.title = 'ignored'; [document.title, document.head, document.body, document.characterSet, document.contentType, document.readyState, document.URL, document.baseURI, document.referrer].join()",
+        );
+        assert_eq!(
+            r.unwrap(),
+            ",,,UTF-8,text/html,complete,http://example.com,http://example.com/,"
+        );
+        let r = runtime.execute(
+            "",
+            "/^\\d{2}\\/\\d{2}\\/\\d{4} \\d{2}:\\d{2}:\\d{2}$/.test(document.lastModified)",
+        );
+        assert_eq!(r.unwrap(), "true");
+
+        document.borrow_mut().referrer = "http://example.com/from".to_string();
+        let r = runtime.execute("", "document.referrer");
+        assert_eq!(r.unwrap(), "http://example.com/from");
+    }
+
+    #[test]
+    fn test_dom_query() {
+        let mut runtime = JavaScriptRuntime::new();
+        let _ = setup_runtime(&mut runtime);
+
+        let r = runtime.execute(
+            "",
+            "document.querySelector('p').appendChild(document.createElement('b')); document.querySelectorAll('p, b').length",
+        );
+        assert_eq!(r.unwrap(), "2");
+        let r = runtime.execute(
+            "",
+            "const b = document.getElementsByTagName('B')[0]; [b.matches('b'), b.closest('p').tagName, document.querySelector('i')].join()",
+        );
+        assert_eq!(r.unwrap(), "true,p,");
+        let r = runtime.execute("", "try { b.querySelector('{') } catch (e) { e.name }");
+        assert_eq!(r.unwrap(), "SyntaxError");
+    }
+
     #[test]
     fn test_api_handler() {
         let mut runtime = JavaScriptRuntime::new();
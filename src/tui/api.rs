@@ -5,7 +5,11 @@ use std::{error::Error, rc::Rc};
 use cursive::CbSink;
 use log::{error, info};
 
-use super::{components::alert, views::with_current_page_view};
+use super::{
+    components::alert,
+    views::{with_current_browser_view, with_current_page_view},
+    BrowserView,
+};
 
 /// `PageViewAPIHandler` is an interface which a JavaScript runtime can use.
 pub struct PageViewAPIHandler {
@@ -34,11 +38,13 @@ impl PageViewAPIHandler {
             .send(Box::new(move |s: &mut cursive::Cursive| {
                 with_current_page_view(s, |v| {
                     info!("re-rendering started");
-                    match v.render_document() {
+                    match v.rerender_if_mutated() {
                         Ok(_) => info!("re-rendering finished"),
                         Err(e) => error!("re-rendering failed; {}", e),
                     }
                 });
+                // scripts may have changed the title of the document
+                with_current_browser_view(s, |b: &mut BrowserView| b.update_title());
             }))?;
         Ok(())
     }
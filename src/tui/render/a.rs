@@ -14,9 +14,6 @@ pub fn render(lbox: &LayoutBox, element: &Element) -> Box<dyn View> {
         .unwrap_or(&"".to_string())
         .to_string();
     Box::new(Link::new(lbox.inner_text(), move |s| {
-        with_current_browser_view(s, |b: &mut BrowserView| {
-            b.resolve_url(link_href.clone())
-                .map(|url| b.navigate_to(url))
-        });
+        with_current_browser_view(s, |b: &mut BrowserView| b.follow_link(link_href.clone()));
     }))
 }
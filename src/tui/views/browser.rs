@@ -2,16 +2,17 @@
 
 use cursive::{
     traits::Finder,
-    view::{Nameable, Resizable, ViewWrapper},
+    view::{Nameable, Resizable, View, ViewWrapper},
     views::{LinearLayout, NamedView, Panel, ScrollView},
-    CbSink, Cursive, With,
+    CbSink, Cursive, Vec2, With,
 };
 use log::error;
 use std::{error::Error, rc::Rc};
 
 use crate::{
+    css::{media::Device, Stylesheet},
     fetch::{fetch, Request},
-    html, url,
+    url,
 };
 
 use super::{NavigationView, PageView};
@@ -25,12 +26,18 @@ pub static PAGE_VIEW_CONTAINER_NAME: &str = "browser-view-page-container";
 pub struct BrowserView {
     view: LinearLayout,
     ui_cb_sink: Rc<CbSink>,
+    /// `user_stylesheet` is applied to all pages shown in the view.
+    user_stylesheet: Rc<Stylesheet>,
+    /// `device` is given to all pages shown in the view, and follows the size of the view.
+    device: Device,
 }
 
 impl BrowserView {
-    pub fn named(ui_cb_sink: Rc<CbSink>) -> NamedView<Self> {
+    pub fn named(ui_cb_sink: Rc<CbSink>, user_stylesheet: Rc<Stylesheet>) -> NamedView<Self> {
         (BrowserView {
             ui_cb_sink: ui_cb_sink.clone(),
+            user_stylesheet,
+            device: Device::default(),
             view: LinearLayout::vertical(),
         })
         .with(|view| {
@@ -54,9 +61,13 @@ impl BrowserView {
         self.view.add_child(
             Panel::new(
                 ScrollView::new(
-                    PageView::new(self.ui_cb_sink.clone())
-                        .with_name(PAGE_VIEW_NAME)
-                        .full_screen(),
+                    PageView::new(
+                        self.ui_cb_sink.clone(),
+                        self.user_stylesheet.clone(),
+                        self.device,
+                    )
+                    .with_name(PAGE_VIEW_NAME)
+                    .full_screen(),
                 )
                 .full_screen(),
             )
@@ -73,13 +84,30 @@ impl BrowserView {
             .call_on_name(PAGE_VIEW_NAME, |s: &mut PageView| f(s))
     }
 
+    /// `update_title` shows the title of the current page in the navbar.
+    pub fn update_title(&mut self) {
+        let title = self.with_page_view_mut(|v| v.title()).flatten();
+        self.view
+            .call_on_name(NAVBAR_VIEW_NAME, |view: &mut NavigationView| {
+                view.set_title(title.unwrap_or_default())
+            });
+    }
+
     pub fn current_url(&mut self) -> Result<String, Box<dyn Error>> {
         self.view
             .call_on_name(NAVBAR_VIEW_NAME, |view: &mut NavigationView| view.get_url())
             .ok_or("failed to find navbar")?
     }
 
+    /// `resolve_url` resolves the URL against the base URL of the current page.
     pub fn resolve_url(&mut self, possibly_relative_url: String) -> Result<String, Box<dyn Error>> {
+        let resolved = self
+            .with_page_view_mut(|v| v.resolve_url(possibly_relative_url.as_str()))
+            .flatten();
+        if let Some(url) = resolved {
+            return Ok(url);
+        }
+
         if possibly_relative_url.starts_with("http://")
             || possibly_relative_url.starts_with("https://")
             || possibly_relative_url.starts_with("file://")
@@ -93,8 +121,18 @@ impl BrowserView {
         }
     }
 
+    /// `follow_link` navigates to the URL of a link in the current page, which is the referrer of the next page.
+    pub fn follow_link(&mut self, possibly_relative_url: String) -> Result<(), Box<dyn Error>> {
+        let absolute_url = self.resolve_url(possibly_relative_url)?;
+        let referrer = self.current_url()?;
+        if let Err(e) = self.navigate_to_intl(absolute_url, referrer) {
+            error!("failed to navigate; {}", e);
+        }
+        Ok(())
+    }
+
     pub fn navigate_to(&mut self, absolute_url: String) {
-        match self.navigate_to_intl(absolute_url) {
+        match self.navigate_to_intl(absolute_url, "".to_string()) {
             Err(e) => {
                 error!("failed to navigate; {}", e);
             }
@@ -102,7 +140,11 @@ impl BrowserView {
         };
     }
 
-    fn navigate_to_intl(&mut self, absolute_url: String) -> Result<(), Box<dyn Error>> {
+    fn navigate_to_intl(
+        &mut self,
+        absolute_url: String,
+        referrer: String,
+    ) -> Result<(), Box<dyn Error>> {
         // change navigation content
         self.view
             .call_on_name(NAVBAR_VIEW_NAME, |view: &mut NavigationView| {
@@ -122,20 +164,22 @@ impl BrowserView {
         // add a new PageView instance
         self.add_named_page_container();
 
-        // fetch & parse document
+        // fetch document
         let response = fetch(Request::new(absolute_url.clone()))?;
-        let document = html::parse(response)?;
 
-        // set the document to PageView
+        // parse & show the document in PageView
         self.view
             .call_on_name(PAGE_VIEW_NAME, |view: &mut PageView| {
-                view.init_page(document)
+                view.init_page(response, referrer)
             })
             .ok_or(format!(
                 "failed to render {}; no element container found",
                 absolute_url
             ))??;
 
+        // show the title of the page
+        self.update_title();
+
         Ok(())
     }
 }
@@ -170,4 +214,20 @@ impl ViewWrapper for BrowserView {
     {
         Ok(self.view)
     }
+
+    fn wrap_layout(&mut self, size: Vec2) {
+        // NOTE: the viewport media queries see is approximated by the whole view, i.e. the terminal.
+        let device = Device {
+            width: size.x,
+            height: size.y,
+            ..self.device
+        };
+        if device != self.device {
+            self.device = device;
+            if let Some(Err(e)) = self.with_page_view_mut(|v| v.set_device(device)) {
+                error!("failed to render the page for the new size; {}", e);
+            }
+        }
+        self.view.layout(size);
+    }
 }
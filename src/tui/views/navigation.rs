@@ -4,7 +4,7 @@ use crate::tui::components::TextInputView;
 use cursive::{
     traits::Finder,
     view::{Nameable, Resizable, ViewWrapper},
-    views::{Button, LinearLayout, Panel, ResizedView},
+    views::{Button, LinearLayout, Panel, ResizedView, TextView},
     Cursive, With,
 };
 use log::error;
@@ -12,6 +12,7 @@ use std::{error::Error, rc::Rc};
 
 pub static NAVIGATION_INPUT_NAME: &str = "navbar-input";
 pub static NAVIGATION_BUTTON_NAME: &str = "navbar-button";
+pub static NAVIGATION_TITLE_NAME: &str = "navbar-title";
 
 pub struct NavigationView {
     view: LinearLayout,
@@ -20,25 +21,27 @@ pub struct NavigationView {
 impl NavigationView {
     pub fn new(default_value: String) -> NavigationView {
         NavigationView {
-            view: LinearLayout::vertical().child(
-                ResizedView::with_full_width(
-                    LinearLayout::horizontal()
-                        .child(Panel::new(ResizedView::with_fixed_height(
-                            1,
-                            TextInputView::new()
-                                .content(default_value)
-                                .with_name(NAVIGATION_INPUT_NAME)
-                                .full_width(),
-                        )))
-                        .child(Panel::new(
-                            Button::new("Go", |_s: &mut Cursive| {})
-                                .with_name(NAVIGATION_BUTTON_NAME)
-                                .fixed_width(5)
-                                .fixed_height(1),
-                        )),
-                )
-                .fixed_height(3),
-            ),
+            view: LinearLayout::vertical()
+                .child(TextView::new("").with_name(NAVIGATION_TITLE_NAME))
+                .child(
+                    ResizedView::with_full_width(
+                        LinearLayout::horizontal()
+                            .child(Panel::new(ResizedView::with_fixed_height(
+                                1,
+                                TextInputView::new()
+                                    .content(default_value)
+                                    .with_name(NAVIGATION_INPUT_NAME)
+                                    .full_width(),
+                            )))
+                            .child(Panel::new(
+                                Button::new("Go", |_s: &mut Cursive| {})
+                                    .with_name(NAVIGATION_BUTTON_NAME)
+                                    .fixed_width(5)
+                                    .fixed_height(1),
+                            )),
+                    )
+                    .fixed_height(3),
+                ),
         }
     }
 
@@ -101,6 +104,19 @@ impl NavigationView {
         };
     }
 
+    /// `set_title` shows `title` of the current page above the input bar.
+    pub fn set_title(&mut self, title: String) {
+        if self
+            .view
+            .call_on_name(NAVIGATION_TITLE_NAME, |view: &mut TextView| {
+                view.set_content(title);
+            })
+            .is_none()
+        {
+            error!("failed to set title to navbar");
+        };
+    }
+
     pub fn get_url(&mut self) -> Result<String, Box<dyn Error>> {
         let result = self
             .view
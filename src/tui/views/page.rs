@@ -5,9 +5,15 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::{
     core::{
-        dom::Document,
+        css::{media::Device, Stylesheet},
+        dom::{Document, DocumentReadyState, MutationObserverId, MutationObserverInit},
+        fetch::Response,
+        html::{
+            parser::{Parser, ParserStatus},
+            HTMLParseError,
+        },
         layout::{to_layout_document, LayoutDocument},
-        style::{to_styled_document, StyledDocument},
+        style::{load_linked_stylesheets, to_styled_document, LinkedStylesheets, StyledDocument},
     },
     javascript::{JavaScriptRuntime, JavaScriptRuntimeError},
     tui::{
@@ -16,15 +22,22 @@ use crate::{
     },
     window::Window,
 };
-use log::{error, info};
+use log::{debug, error, info};
 use thiserror::Error;
 
 use super::PAGE_VIEW_NAME;
+
+/// `CHUNK_SIZE` is the number of bytes given to the HTML parser at once.
+const CHUNK_SIZE: usize = 4096;
+
 #[derive(Error, Debug, PartialEq)]
 pub enum PageError {
     #[error("failed to render; no document exists")]
     NoDocumentError,
 
+    #[error("failed to parse the document: {0}")]
+    HTMLParseError(HTMLParseError),
+
     #[error("failed to render; javascript execution failed: {0:?}")]
     JavaScriptError(JavaScriptRuntimeError),
 }
@@ -33,6 +46,14 @@ pub struct PageView {
     // on document shown in the page
     window: Option<Rc<RefCell<Window>>>,
     document: Option<Rc<RefCell<Document>>>,
+    /// `mutation_observer` observes the whole document to find whether it needs to be re-rendered.
+    mutation_observer: Option<MutationObserverId>,
+    /// `user_stylesheet` is applied to the document as a stylesheet given by the user.
+    user_stylesheet: Rc<Stylesheet>,
+    /// `linked_stylesheets` holds the stylesheets fetched for `link` elements and `@import` rules in the document.
+    linked_stylesheets: LinkedStylesheets,
+    /// `device` describes the terminal the page is shown in, which media queries are evaluated against.
+    device: Device,
 
     // on UI
     view: ElementContainer,
@@ -42,10 +63,14 @@ pub struct PageView {
 }
 
 impl PageView {
-    pub fn new(ui_cb_sink: Rc<CbSink>) -> Self {
+    pub fn new(ui_cb_sink: Rc<CbSink>, user_stylesheet: Rc<Stylesheet>, device: Device) -> Self {
         (Self {
             window: None,
             document: None,
+            mutation_observer: None,
+            user_stylesheet,
+            linked_stylesheets: LinkedStylesheets::new(),
+            device,
 
             view: ElementContainer::vertical(),
 
@@ -57,32 +82,72 @@ impl PageView {
         })
     }
 
-    /// `init_page` shows the given document to the PageView. 
-    pub fn init_page(&mut self, document: Document) -> Result<(), PageError> {
+    /// `init_page` parses the given response and shows the document to the PageView.
+    /// Inline scripts are executed while parsing, as soon as their end tags are parsed.
+    /// `referrer` is the URL of the page which navigated to this page, or the empty string if there's no such page.
+    pub fn init_page(&mut self, response: Response, referrer: String) -> Result<(), PageError> {
         // prepare `Window` object for the new page
         let window = Rc::new(RefCell::new(Window {
             name: "".to_string(),
         }));
 
-        let document = Rc::new(RefCell::new(document));
+        let url = response.url.to_string();
+        let document = Rc::new(RefCell::new(empty_document(url.as_str())));
 
         // set basic props of this page
         self.window = Some(window.clone());
         self.document = Some(document.clone());
+        self.linked_stylesheets = LinkedStylesheets::new();
 
         // set reference to Window object of this page for JavaScript runtime
         self.js_runtime.set_window(window.clone());
         self.js_runtime.set_document(document.clone());
 
+        // parse document with running JavaScript
+        // NOTE: `fetch` receives the whole resource at once for now, so the resource is split into chunks here.
+        let mut parser = Parser::from_response(&response);
+        parser.set_referrer(referrer);
+        for chunk in response.data.chunks(CHUNK_SIZE) {
+            parser.feed(chunk).map_err(PageError::HTMLParseError)?;
+            self.run_parser(&mut parser)?;
+        }
+        parser.close().map_err(PageError::HTMLParseError)?;
+        self.run_parser(&mut parser)?;
+
+        let parsed = parser.finish();
+        for parse_error in &parsed.parse_errors {
+            debug!("parse error in {} at {}", url, parse_error);
+        }
+        *document.borrow_mut() = parsed;
+
+        // observe the document to re-render it only when scripts modify it
+        self.mutation_observer = Some(observe_document(&mut document.borrow_mut()));
+
+        // NOTE: linked stylesheets are loaded again only when scripts add ones. See `rerender_if_mutated`.
+        load_linked_stylesheets(&document.borrow(), &mut self.linked_stylesheets);
+
         // layout document to self.view
         self.render_document()?;
 
-        // run JavaScript
-        self.execute_inline_scripts()?;
+        // NOTE: stylesheets are the only subresources puppy loads, and they are loaded before rendering.
+        // Therefore the document is loaded as soon as it's rendered.
+        document.borrow_mut().ready_state = DocumentReadyState::Complete;
 
         Ok(())
     }
 
+    /// `title` returns the title of the document shown in the page.
+    pub fn title(&self) -> Option<String> {
+        self.document.as_ref().map(|d| d.borrow().title())
+    }
+
+    /// `resolve_url` resolves `url` against the base URL of the document shown in the page.
+    pub fn resolve_url(&self, url: &str) -> Option<String> {
+        let document = self.document.as_ref()?;
+        let resolved = document.borrow().resolve_url(url)?;
+        Some(resolved.to_string())
+    }
+
     /// `render_document` renders `self.document` to `self.view`.
     pub fn render_document(&mut self) -> Result<(), PageError> {
         // assert self.document is set
@@ -93,40 +158,115 @@ impl PageView {
         let document = &*document.borrow_mut();
 
         // render document
-        let styled: StyledDocument = to_styled_document(document);
+        let styled: StyledDocument = to_styled_document(
+            document,
+            &self.user_stylesheet,
+            &self.linked_stylesheets,
+            &self.device,
+        );
         let layout: LayoutDocument = to_layout_document(styled);
         self.view = to_element_container(&layout.top_box);
 
         Ok(())
     }
 
-    /// `execute_inline_scripts` runs all the inline scripts.
+    /// `set_device` changes the device the page is shown in, and renders the document again if it's changed.
+    pub fn set_device(&mut self, device: Device) -> Result<(), PageError> {
+        if self.device == device {
+            return Ok(());
+        }
+        self.device = device;
+        match self.document {
+            Some(_) => self.render_document(),
+            None => Ok(()),
+        }
+    }
+
+    /// `rerender_if_mutated` renders `self.document` again only if it has been modified since the last rendering.
+    /// Stylesheets linked from the document are loaded before rendering if the modification may have added ones.
+    pub fn rerender_if_mutated(&mut self) -> Result<(), PageError> {
+        let document = match &self.document {
+            Some(d) => d,
+            None => return Err(PageError::NoDocumentError),
+        };
+        let (mutated, restyled) = match self.mutation_observer {
+            Some(observer) => {
+                let mut document = document.borrow_mut();
+                let records = document.take_records(observer);
+                let restyled = records.iter().any(|r| document.affects_style_sources(r));
+                (!records.is_empty(), restyled)
+            }
+            None => (true, true),
+        };
+        if restyled {
+            load_linked_stylesheets(&document.borrow(), &mut self.linked_stylesheets);
+        }
+        if mutated {
+            self.render_document()?;
+        }
+        Ok(())
+    }
+
+    /// `run_parser` lets `parser` parse the input given so far, and runs the inline scripts it finds.
+    /// Each script sees the document parsed up to the script, and markup written by `document.write` is parsed just after the script.
+    /// See https://html.spec.whatwg.org/multipage/parsing.html#scriptEndTag for the details.
     ///
-    /// TODO (enhancement): note on "re-entrant" of HTML tree construction
-    fn execute_inline_scripts(&mut self) -> Result<(), PageError> {
-        let scripts = {
-            // extract inline scripts first to release borrowing of self.document.
-            // This should be done before JS access DOM API.
-            let document = match &self.document {
-                Some(w) => w,
-                None => return Err(PageError::NoDocumentError),
-            };
-            let document = document.borrow_mut();
-            document.get_script_inners()
+    /// An exception thrown from a script is logged, and the parser resumes as if the script ended normally.
+    ///
+    /// The same `Document` is moved back and forth between the parser and `self.document`,
+    /// so node objects kept by scripts (e.g. in global variables) keep pointing at the same nodes after parsing is resumed.
+    fn run_parser(&mut self, parser: &mut Parser) -> Result<(), PageError> {
+        let document = match &self.document {
+            Some(d) => d.clone(),
+            None => return Err(PageError::NoDocumentError),
         };
 
-        for script in scripts {
-            match self.js_runtime.execute("(inline)", script.as_str()) {
+        while let ParserStatus::Script(script) = parser.run() {
+            *document.borrow_mut() = parser.take_document();
+            let (result, written) = self
+                .js_runtime
+                .execute_with_insertion_point("(inline)", script.as_str());
+
+            let url = document.borrow().url.clone();
+            parser.restore_document(document.replace(empty_document(url.as_str())));
+            parser.write(written.as_str());
+
+            match result {
                 Ok(s) => {
                     info!("javascript execution succeeded; {}", s);
                 }
-                Err(e) => return Err(PageError::JavaScriptError(e)),
+                Err(e) => {
+                    // NOTE: an exception in a script doesn't stop parsing the rest of the document.
+                    error!("an exception was thrown from an inline script; {:?}", e);
+                }
             };
         }
         Ok(())
     }
 }
 
+/// `observe_document` registers a mutation observer which observes any change in `document`.
+fn observe_document(document: &mut Document) -> MutationObserverId {
+    let observer = document.create_mutation_observer();
+    let options = MutationObserverInit {
+        child_list: true,
+        attributes: Some(true),
+        character_data: Some(true),
+        subtree: true,
+        ..Default::default()
+    };
+    let root = document.root();
+    // NOTE: these options are always valid.
+    let _ = document.observe(observer, root, options);
+    observer
+}
+
+/// `empty_document` returns a document which has no child.
+/// It stands for the document while the HTML parser holds the tree.
+fn empty_document(url: &str) -> Document {
+    Document::new(url.to_string(), url.to_string())
+}
+
 impl ViewWrapper for PageView {
     type V = LinearLayout;
 
@@ -159,3 +299,42 @@ where
 {
     s.screen_mut().call_on_name(PAGE_VIEW_NAME, f)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fetch::{HTTPStatus, HeaderMap, ResponseType},
+        url::Url,
+    };
+
+    #[test]
+    fn test_init_page_with_throwing_script() {
+        let (cb_sink, _) = crossbeam_channel::unbounded();
+        let mut view = PageView::new(
+            Rc::new(cb_sink),
+            Rc::new(Stylesheet::new(vec![])),
+            Device::default(),
+        );
+        let response = Response {
+            url: Url::parse("http://example.com").unwrap(),
+            status: HTTPStatus::OK,
+            rtype: ResponseType::Basic,
+            headers: HeaderMap::new(),
+            data: b"<title>a</title><p>before</p><script>throw new Error('oops')</script><p>after</p><script>document.title = 'b'</script>".to_vec(),
+        };
+        assert_eq!(view.init_page(response, "".to_string()), Ok(()));
+
+        // the markup and the script after the throwing script are still processed
+        let document = view.document.clone().unwrap();
+        let document = document.borrow();
+        let body = document.body().unwrap();
+        assert_eq!(
+            document.inner_html(body),
+            "<p>before</p><script>throw new Error('oops')</script><p>after</p><script>document.title = 'b'</script>"
+        );
+        assert_eq!(view.title(), Some("b".to_string()));
+        // the body is rendered
+        assert_eq!(view.view.len(), 1);
+    }
+}